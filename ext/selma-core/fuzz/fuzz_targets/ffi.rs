@@ -0,0 +1,72 @@
+#![no_main]
+
+use std::ffi::{CStr, CString};
+
+use libfuzzer_sys::fuzz_target;
+use selma_core::{ffi::*, profiles::Profile, sanitizer::Sanitizer, typographer::Typographer};
+
+// Splits the input on NULs into a profile, element and attribute, which need
+// not be UTF-8, and checks that the C ABI agrees with the Rust API. Inputs
+// which don't split into exactly three are skipped, since none of the parts
+// can hold a NUL of its own.
+fuzz_target!(|data: &[u8]| {
+    let parts: Vec<CString> = data
+        .split(|byte| *byte == 0)
+        .map(|part| CString::new(part).unwrap())
+        .collect();
+    let [profile, element, attribute] = parts.as_slice() else {
+        return;
+    };
+
+    let allowed = unsafe {
+        selma_profile_allows_attribute(profile.as_ptr(), element.as_ptr(), attribute.as_ptr())
+    };
+    let expected = match (profile.to_str(), element.to_str(), attribute.to_str()) {
+        (Ok(profile), Ok(element), Ok(attribute)) => Profile::find(profile)
+            .is_some_and(|profile| profile.allows_attribute(element, attribute)),
+        _ => false,
+    };
+    assert_eq!(allowed, expected);
+
+    unsafe {
+        let smartened = selma_smarten(attribute.as_ptr());
+        match attribute.to_str() {
+            Ok(text) => {
+                assert_eq!(
+                    CStr::from_ptr(smartened).to_str().unwrap(),
+                    Typographer::new().smarten(text)
+                );
+            }
+            Err(_) => assert!(smartened.is_null()),
+        }
+        selma_string_free(smartened);
+    }
+
+    // the attribute doubles as a document, for the profile's sanitizer
+    unsafe {
+        let sanitizer = selma_sanitizer_new(profile.as_ptr());
+        let found = profile.to_str().ok().and_then(Profile::find);
+        assert_eq!(sanitizer.is_null(), found.is_none());
+
+        if let Some(found) = found {
+            let sanitized = selma_sanitize(sanitizer, attribute.as_ptr());
+            match attribute.to_str() {
+                Ok(html) => {
+                    // null when it can't be sanitized, as when the Rust API errors
+                    let sanitized =
+                        (!sanitized.is_null()).then(|| CStr::from_ptr(sanitized).to_str().unwrap());
+                    assert_eq!(
+                        sanitized,
+                        Sanitizer::from_profile(found)
+                            .sanitize(html)
+                            .ok()
+                            .as_deref()
+                    );
+                }
+                Err(_) => assert!(sanitized.is_null()),
+            }
+            selma_string_free(sanitized);
+        }
+        selma_sanitizer_free(sanitizer);
+    }
+});
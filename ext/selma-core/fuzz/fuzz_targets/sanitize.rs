@@ -0,0 +1,90 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use lol_html::{element, HtmlRewriter, Settings};
+use selma_core::{profiles::Profile, sanitizer::Sanitizer};
+
+/// The protocols `profile` (or one it extends) allows in `attribute` on
+/// `element`, if it restricts them at all.
+fn allowed_protocols(profile: &Profile, element: &str, attribute: &str) -> Option<Vec<String>> {
+    let protocols = profile
+        .protocols
+        .iter()
+        .find(|(allowed_element, allowed_attribute, _)| {
+            allowed_element.eq_ignore_ascii_case(element)
+                && allowed_attribute.eq_ignore_ascii_case(attribute)
+        });
+
+    match protocols {
+        Some((_, _, protocols)) => Some(
+            protocols
+                .iter()
+                .flat_map(|protocol| match protocol.strip_prefix(':') {
+                    Some(name) => Sanitizer::relative_protocols(name),
+                    None => vec![protocol.to_string()],
+                })
+                .collect(),
+        ),
+        None => profile
+            .extends
+            .and_then(|base| allowed_protocols(base, element, attribute)),
+    }
+}
+
+// Sanitizes the input with each built-in profile, and parses what's left
+// again: every element and attribute in it has to be one the profile allows,
+// and every URL one with a protocol it allows. Sanitizing it once more has to
+// leave it as it is.
+fuzz_target!(|html: &str| {
+    for name in ["BASIC", "RESTRICTED", "RELAXED", "GITHUB"] {
+        let profile = Profile::find(name).unwrap();
+        let sanitizer = Sanitizer::from_profile(profile);
+        // markup lol_html can't be sure how to parse is an error, not a risk
+        let Ok(sanitized) = sanitizer.sanitize(html) else {
+            continue;
+        };
+        assert_eq!(
+            sanitizer.sanitize(&sanitized).ok().as_deref(),
+            Some(sanitized.as_str()),
+            "{name} changed its own output"
+        );
+
+        let mut rewriter = HtmlRewriter::new(
+            Settings {
+                element_content_handlers: vec![element!("*", |el| {
+                    let tag_name = el.tag_name();
+                    assert!(
+                        profile.allows_element(&tag_name),
+                        "{name} kept <{tag_name}> in {sanitized:?}"
+                    );
+
+                    for attribute in el.attributes() {
+                        let attr_name = attribute.name();
+                        assert!(
+                            profile.allows_attribute(&tag_name, &attr_name),
+                            "{name} kept {attr_name} on <{tag_name}> in {sanitized:?}"
+                        );
+
+                        if let Some(protocols) = allowed_protocols(profile, &tag_name, &attr_name) {
+                            // read the way the sanitizer reads it, after any leading spaces
+                            let value = attribute.value();
+                            let value = escapist::unescape_html(value.trim_start().as_bytes());
+                            let value = String::from_utf8_lossy(&value);
+                            assert!(
+                                value.is_empty() || Sanitizer::has_allowed_protocol(&protocols, &value),
+                                "{name} kept {attr_name}={value:?} on <{tag_name}> in {sanitized:?}"
+                            );
+                        }
+                    }
+
+                    Ok(())
+                })],
+                ..Settings::default()
+            },
+            |_: &[u8]| {},
+        );
+        if rewriter.write(sanitized.as_bytes()).is_ok() {
+            let _ = rewriter.end();
+        }
+    }
+});
@@ -0,0 +1,17 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use selma_core::typographer::Typographer;
+
+// The typographer runs on text which has already been sanitized, so it must
+// never introduce anything that could be read as markup.
+fuzz_target!(|text: &str| {
+    let smartened = Typographer::new().smarten(text);
+
+    for markup in ['<', '>', '&', '"', '\''] {
+        assert!(
+            smartened.matches(markup).count() <= text.matches(markup).count(),
+            "{markup:?} was added to {text:?}: {smartened:?}"
+        );
+    }
+});
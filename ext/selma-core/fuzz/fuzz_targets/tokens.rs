@@ -0,0 +1,28 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use selma_core::{
+    profiles::Profile,
+    sanitizer::Sanitizer,
+    tokens::check::{lol_html, tokens},
+};
+
+// The extension's `minify:`, `pretty:`, `xhtml:` and `canonical:` options
+// split the rewriter's output up with `Tokens`, rather than lol_html, so the
+// two have to agree on where every tag, comment and text starts and ends: on
+// the input, and on what each profile leaves of it. Run it on the `sanitize`
+// target's corpus (`fuzz run tokens fuzz/corpus/sanitize`) to start from there.
+fuzz_target!(|html: &str| {
+    let mut documents = vec![html.to_string()];
+    for name in ["BASIC", "RESTRICTED", "RELAXED", "GITHUB"] {
+        let sanitizer = Sanitizer::from_profile(Profile::find(name).unwrap());
+        documents.extend(sanitizer.sanitize(html).ok());
+    }
+
+    for document in documents {
+        // markup lol_html can't be sure how to parse is an error, not a risk
+        if let Some(events) = lol_html(&document) {
+            assert_eq!(tokens(&document), events, "{document:?}");
+        }
+    }
+});
@@ -0,0 +1,51 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use selma_core::sanitizer::Sanitizer;
+
+/// The scheme a browser would read `url` as having: it ignores leading and
+/// trailing C0 controls and spaces, and tabs and newlines anywhere.
+fn scheme(url: &str) -> Option<String> {
+    let url: String = url
+        .trim_matches(|c: char| c <= ' ')
+        .chars()
+        .filter(|c| !matches!(c, '\t' | '\n' | '\r'))
+        .collect();
+    let (scheme, _) = url.split_once(':')?;
+
+    let mut chars = scheme.chars();
+    let valid = chars.next().is_some_and(|c| c.is_ascii_alphabetic())
+        && chars.all(|c| c.is_ascii_alphanumeric() || matches!(c, '+' | '-' | '.'));
+    valid.then(|| scheme.to_ascii_lowercase())
+}
+
+// Checks that a URL is only ever allowed for a protocol the sanitizer was
+// told to allow, and that escaping one leaves nothing which could end the
+// attribute it's written into.
+fuzz_target!(|url: &str| {
+    assert!(!Sanitizer::has_allowed_protocol(&[], url));
+
+    let mut protocols = Sanitizer::relative_protocols("relative");
+    protocols.push("https".to_string());
+    if Sanitizer::has_allowed_protocol(&protocols, url) {
+        let scheme = scheme(url);
+        assert!(
+            scheme.is_none() || scheme.as_deref() == Some("https"),
+            "{url:?} was allowed with a scheme of {scheme:?}"
+        );
+    }
+
+    let escaped = Sanitizer::escape_url(url);
+    assert!(
+        !escaped.contains(|c: char| {
+            matches!(c, '"' | '\'' | '<' | '>') || c.is_ascii_whitespace() || c.is_control()
+        }),
+        "{url:?} was escaped to {escaped:?}"
+    );
+    for (pos, _) in escaped.match_indices('&') {
+        assert!(
+            escaped[pos..].starts_with("&amp;") || escaped[pos..].starts_with("&#x27;"),
+            "{url:?} was escaped to {escaped:?}"
+        );
+    }
+});
@@ -0,0 +1,34 @@
+#ifndef SELMA_CORE_H
+#define SELMA_CORE_H
+
+#include <stdbool.h>
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+/* Strings are NUL-terminated UTF-8. Strings returned by these functions must be
+ * freed with selma_string_free. */
+
+bool selma_profile_allows_element(const char *profile, const char *element);
+bool selma_profile_allows_attribute(const char *profile, const char *element, const char *attribute);
+
+/* An opaque sanitizer config, from selma_sanitizer_new, which has to be freed
+ * with selma_sanitizer_free. */
+typedef struct SelmaSanitizer SelmaSanitizer;
+
+SelmaSanitizer *selma_sanitizer_new(const char *profile);
+void selma_sanitizer_allow_element(SelmaSanitizer *sanitizer, const char *element);
+void selma_sanitizer_allow_attribute(SelmaSanitizer *sanitizer, const char *element, const char *attribute);
+void selma_sanitizer_allow_protocol(SelmaSanitizer *sanitizer, const char *element, const char *attribute, const char *protocol);
+char *selma_sanitize(const SelmaSanitizer *sanitizer, const char *html);
+void selma_sanitizer_free(SelmaSanitizer *sanitizer);
+
+char *selma_smarten(const char *text);
+void selma_string_free(char *string);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif
@@ -0,0 +1,124 @@
+// Loads selma-core's WebAssembly build (from `rake wasm`), wrapping the C ABI
+// in functions that take and return JavaScript strings:
+//
+//   import { load } from "./selma_core.js";
+//
+//   const selma = await load(fetch("selma_core.wasm"));
+//   selma.allowsElement("RELAXED", "table"); // true
+//   selma.smarten('"Hello" -- world');       // “Hello” – world
+//   selma.sanitize("<b>Hi</b><script>x()</script>", "BASIC"); // <b>Hi</b>
+
+// the keys of a config which `newSanitizer` can apply
+const CONFIG_KEYS = ["profile", "elements", "attributes", "protocols"];
+
+const encoder = new TextEncoder();
+const decoder = new TextDecoder();
+
+export async function load(source) {
+  const response = await source;
+  const { instance } =
+    response instanceof Response
+      ? await WebAssembly.instantiateStreaming(response)
+      : await WebAssembly.instantiate(response);
+  const exports = instance.exports;
+
+  const bytes = () => new Uint8Array(exports.memory.buffer);
+
+  // Calls `fn` with each string copied into the module's memory.
+  const withStrings = (strings, fn) => {
+    const buffers = strings.map((string) => {
+      const encoded = encoder.encode(string);
+      const len = encoded.length + 1;
+      const ptr = exports.selma_alloc(len);
+      bytes().set(encoded, ptr);
+      bytes()[ptr + encoded.length] = 0;
+      return [ptr, len];
+    });
+
+    try {
+      return fn(...buffers.map(([ptr]) => ptr));
+    } finally {
+      buffers.forEach(([ptr, len]) => exports.selma_dealloc(ptr, len));
+    }
+  };
+
+  const takeString = (ptr) => {
+    if (ptr === 0) return null;
+
+    const memory = bytes();
+    const end = memory.indexOf(0, ptr);
+    const string = decoder.decode(memory.subarray(ptr, end));
+    exports.selma_string_free(ptr);
+    return string;
+  };
+
+  // A sanitizer for `config`: the name of a built-in profile, or an object
+  // shaped like a `Selma::Sanitizer` config hash, whose `elements`,
+  // `attributes` and `protocols` are allowed on top of its `profile` (if any).
+  // Any other key is refused, rather than left out: the document would be
+  // sanitized differently than the gem would sanitize it.
+  const newSanitizer = (config) => {
+    const options = typeof config === "string" ? { profile: config } : config;
+    const unsupported = Object.keys(options).filter((key) => !CONFIG_KEYS.includes(key));
+    if (unsupported.length > 0) {
+      throw new Error(`unsupported config keys: ${unsupported.join(", ")}`);
+    }
+
+    const { profile, elements = [], attributes = {}, protocols = {} } = options;
+    const sanitizer =
+      profile === undefined
+        ? exports.selma_sanitizer_new(0)
+        : withStrings([profile], exports.selma_sanitizer_new);
+    if (sanitizer === 0) throw new Error(`unknown profile: ${profile}`);
+
+    try {
+      elements.forEach((element) =>
+        withStrings([element], (...ptrs) => exports.selma_sanitizer_allow_element(sanitizer, ...ptrs)),
+      );
+      Object.entries(attributes).forEach(([element, names]) =>
+        names.forEach((name) =>
+          withStrings([element, name], (...ptrs) =>
+            exports.selma_sanitizer_allow_attribute(sanitizer, ...ptrs),
+          ),
+        ),
+      );
+      Object.entries(protocols).forEach(([element, byAttribute]) =>
+        Object.entries(byAttribute).forEach(([attribute, names]) =>
+          names.forEach((protocol) =>
+            withStrings([element, attribute, protocol], (...ptrs) =>
+              exports.selma_sanitizer_allow_protocol(sanitizer, ...ptrs),
+            ),
+          ),
+        ),
+      );
+    } catch (error) {
+      exports.selma_sanitizer_free(sanitizer);
+      throw error;
+    }
+
+    return sanitizer;
+  };
+
+  const sanitize = (html, config = {}) => {
+    const sanitizer = newSanitizer(config);
+
+    try {
+      const sanitized = withStrings([html], (ptr) =>
+        takeString(exports.selma_sanitize(sanitizer, ptr)),
+      );
+      if (sanitized === null) throw new Error("failed to sanitize HTML");
+      return sanitized;
+    } finally {
+      exports.selma_sanitizer_free(sanitizer);
+    }
+  };
+
+  return {
+    allowsElement: (profile, element) =>
+      withStrings([profile, element], exports.selma_profile_allows_element) !== 0,
+    allowsAttribute: (profile, element, attribute) =>
+      withStrings([profile, element, attribute], exports.selma_profile_allows_attribute) !== 0,
+    smarten: (text) => withStrings([text], (ptr) => takeString(exports.selma_smarten(ptr))),
+    sanitize,
+  };
+}
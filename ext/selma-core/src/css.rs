@@ -0,0 +1,178 @@
+//! Sanitizing the CSS in `style` attributes: a declaration is kept only if its
+//! property is allowed, and its value is made of nothing but words, numbers,
+//! colors, strings and a few harmless functions (so no `url(...)`,
+//! `expression(...)`, escapes or comments, which is where CSS gets dangerous).
+
+use std::collections::HashSet;
+
+/// The functions a value can call: colors, and nothing which loads anything.
+const FUNCTIONS: &[&str] = &["hsl", "hsla", "rgb", "rgba"];
+
+/// Which CSS properties a `style` attribute can set.
+#[derive(Clone, Debug, Default)]
+pub struct CssPolicy {
+    /// the properties (in lowercase) which declarations are kept for
+    pub properties: HashSet<String>,
+}
+
+impl CssPolicy {
+    pub fn new(properties: impl IntoIterator<Item = impl AsRef<str>>) -> Self {
+        Self {
+            properties: properties
+                .into_iter()
+                .map(|property| property.as_ref().to_ascii_lowercase())
+                .collect(),
+        }
+    }
+
+    /// The declarations in `style` which can be kept, written out as
+    /// `property: value`, separated by `; `, or `None` if there are none.
+    pub fn sanitize(&self, style: &str) -> Option<String> {
+        let declarations: Vec<String> = Self::declarations(style)
+            .into_iter()
+            .filter_map(|declaration| {
+                let (property, value) = declaration.split_once(':')?;
+                let property = property.trim().to_ascii_lowercase();
+                let value = value.trim();
+
+                (self.properties.contains(&property) && Self::is_safe_value(value))
+                    .then(|| format!("{property}: {value}"))
+            })
+            .collect();
+
+        (!declarations.is_empty()).then(|| declarations.join("; "))
+    }
+
+    /// `style` split at each `;` which isn't in a string or parentheses. A
+    /// declaration in an unterminated string or parentheses is dropped, along
+    /// with everything after it.
+    fn declarations(style: &str) -> Vec<&str> {
+        let mut declarations = vec![];
+        let mut start = 0;
+        let mut depth: usize = 0;
+        let mut quote = None;
+
+        for (index, c) in style.char_indices() {
+            match (quote, c) {
+                (Some(q), c) if c == q => quote = None,
+                (Some(_), _) => {}
+                (None, '"' | '\'') => quote = Some(c),
+                (None, '(') => depth += 1,
+                (None, ')') => depth = depth.saturating_sub(1),
+                (None, ';') if depth == 0 => {
+                    declarations.push(&style[start..index]);
+                    start = index + 1;
+                }
+                _ => {}
+            }
+        }
+        if quote.is_none() && depth == 0 {
+            declarations.push(&style[start..]);
+        }
+
+        declarations
+    }
+
+    /// Whether a declaration's value is only words, numbers, colors, strings,
+    /// and calls to `FUNCTIONS`.
+    fn is_safe_value(value: &str) -> bool {
+        if value.is_empty() {
+            return false;
+        }
+
+        let mut word = String::new();
+        let mut quote = None;
+        for c in value.chars() {
+            if let Some(q) = quote {
+                // no escapes, nor anything which could end the attribute, or
+                // the string, early
+                match c {
+                    c if c == q => quote = None,
+                    '\\' | '<' | '>' | '\n' | '\r' | '\x0c' => return false,
+                    c if c.is_control() => return false,
+                    _ => {}
+                }
+                continue;
+            }
+
+            match c {
+                '"' | '\'' => {
+                    quote = Some(c);
+                    word.clear();
+                }
+                '(' => {
+                    if !FUNCTIONS.contains(&word.to_ascii_lowercase().as_str()) {
+                        return false;
+                    }
+                    word.clear();
+                }
+                c if c.is_ascii_alphanumeric() || c == '-' || c == '_' => word.push(c),
+                ')' | ',' | '.' | '%' | '#' | '+' | '!' | ' ' | '\t' => word.clear(),
+                // between values, like `font: 12px/1.5` (a comment needs a `*`)
+                '/' => word.clear(),
+                _ => return false,
+            }
+        }
+
+        quote.is_none()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn policy() -> CssPolicy {
+        CssPolicy::new(["color", "font", "font-family", "Background-Color", "margin"])
+    }
+
+    #[test]
+    fn it_keeps_the_allowed_declarations() {
+        assert_eq!(
+            policy().sanitize("color:red; position: absolute;margin: 0 auto;"),
+            Some("color: red; margin: 0 auto".to_string())
+        );
+        assert_eq!(
+            policy().sanitize("BACKGROUND-COLOR: #FFF !important"),
+            Some("background-color: #FFF !important".to_string())
+        );
+        assert_eq!(
+            policy().sanitize("color: red; behavior: url(x.htc)"),
+            Some("color: red".to_string())
+        );
+        assert_eq!(policy().sanitize("position: fixed"), None);
+        assert_eq!(policy().sanitize(""), None);
+    }
+
+    #[test]
+    fn it_keeps_strings_and_color_functions() {
+        assert_eq!(
+            policy().sanitize(r#"font-family: "Helvetica Neue", 'Arial; Bold', sans-serif"#),
+            Some(r#"font-family: "Helvetica Neue", 'Arial; Bold', sans-serif"#.to_string())
+        );
+        assert_eq!(
+            policy().sanitize("color: rgba(0, 0, 0, 0.5); font: 12px/1.5 serif"),
+            Some("color: rgba(0, 0, 0, 0.5); font: 12px/1.5 serif".to_string())
+        );
+    }
+
+    #[test]
+    fn it_drops_values_which_load_or_run_anything() {
+        for style in [
+            "color: url(https://example.com/x.png)",
+            "color: URL(x)",
+            "color: expression(alert(1))",
+            "color: var(--x)",
+            "color: image-set('x.png' 1x)",
+            r"color: u\rl(x)",
+            r#"font-family: "a\"b""#,
+            "color: red/**/",
+            "color: @import",
+            "color: <script>",
+            "color: 'unterminated",
+            "color: rgb(0, 0, 0",
+        ] {
+            assert_eq!(policy().sanitize(style), None, "{style}");
+        }
+    }
+}
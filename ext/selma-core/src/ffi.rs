@@ -0,0 +1,366 @@
+//! A C ABI over `selma_core`, for runtimes other than Ruby. Strings go in as
+//! NUL-terminated UTF-8, and any string that comes back has to be given to
+//! `selma_string_free` once it's no longer needed. See
+//! `include/selma_core.h`.
+
+use std::ffi::{c_char, CStr, CString};
+
+use crate::{profiles::Profile, sanitizer::Sanitizer, typographer::Typographer};
+
+/// # Safety
+///
+/// `ptr` must be null, or point to a NUL-terminated string.
+unsafe fn read_str<'a>(ptr: *const c_char) -> Option<&'a str> {
+    if ptr.is_null() {
+        return None;
+    }
+
+    CStr::from_ptr(ptr).to_str().ok()
+}
+
+/// Whether the built-in `profile` (like `"RELAXED"`) allows `element`. Unknown
+/// profiles allow nothing.
+///
+/// # Safety
+///
+/// Both arguments must be null, or point to NUL-terminated strings.
+#[no_mangle]
+pub unsafe extern "C" fn selma_profile_allows_element(
+    profile: *const c_char,
+    element: *const c_char,
+) -> bool {
+    match (read_str(profile).and_then(Profile::find), read_str(element)) {
+        (Some(profile), Some(element)) => profile.allows_element(element),
+        _ => false,
+    }
+}
+
+/// Whether the built-in `profile` allows `attribute` on `element`. Unknown
+/// profiles allow nothing.
+///
+/// # Safety
+///
+/// Every argument must be null, or point to a NUL-terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn selma_profile_allows_attribute(
+    profile: *const c_char,
+    element: *const c_char,
+    attribute: *const c_char,
+) -> bool {
+    match (
+        read_str(profile).and_then(Profile::find),
+        read_str(element),
+        read_str(attribute),
+    ) {
+        (Some(profile), Some(element), Some(attribute)) => {
+            profile.allows_attribute(element, attribute)
+        }
+        _ => false,
+    }
+}
+
+/// Smartens the punctuation of `text`, as `typographer: true` does, returning
+/// a new string (or null, if `text` is null or isn't UTF-8).
+///
+/// # Safety
+///
+/// `text` must be null, or point to a NUL-terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn selma_smarten(text: *const c_char) -> *mut c_char {
+    match read_str(text).map(|text| CString::new(Typographer::new().smarten(text))) {
+        Some(Ok(smartened)) => smartened.into_raw(),
+        _ => std::ptr::null_mut(),
+    }
+}
+
+/// A new sanitizer, which allows what the built-in `profile` does, or (if
+/// `profile` is null) nothing at all, until it's told otherwise. Unknown
+/// profiles give null. It has to be given to `selma_sanitizer_free` once it's
+/// no longer needed.
+///
+/// # Safety
+///
+/// `profile` must be null, or point to a NUL-terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn selma_sanitizer_new(profile: *const c_char) -> *mut Sanitizer {
+    let sanitizer = if profile.is_null() {
+        Sanitizer::new()
+    } else {
+        match read_str(profile).and_then(Profile::find) {
+            Some(profile) => Sanitizer::from_profile(profile),
+            None => return std::ptr::null_mut(),
+        }
+    };
+
+    Box::into_raw(Box::new(sanitizer))
+}
+
+/// Allows `element`.
+///
+/// # Safety
+///
+/// `sanitizer` must be null, or have come from `selma_sanitizer_new`, and
+/// `element` must be null, or point to a NUL-terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn selma_sanitizer_allow_element(
+    sanitizer: *mut Sanitizer,
+    element: *const c_char,
+) {
+    if let (Some(sanitizer), Some(element)) = (sanitizer.as_mut(), read_str(element)) {
+        sanitizer.set_flag(element, Sanitizer::ALLOW, true);
+    }
+}
+
+/// Allows `attribute` on `element`, or on every element, as `"all"`.
+///
+/// # Safety
+///
+/// `sanitizer` must be null, or have come from `selma_sanitizer_new`, and the
+/// other arguments must be null, or point to NUL-terminated strings.
+#[no_mangle]
+pub unsafe extern "C" fn selma_sanitizer_allow_attribute(
+    sanitizer: *mut Sanitizer,
+    element: *const c_char,
+    attribute: *const c_char,
+) {
+    if let (Some(sanitizer), Some(element), Some(attribute)) =
+        (sanitizer.as_mut(), read_str(element), read_str(attribute))
+    {
+        sanitizer.allow_attribute(element, attribute, true);
+    }
+}
+
+/// Allows `protocol` in `attribute` on `element` (or `"all"` elements). Kinds
+/// of relative URL are named the way the gem's symbols are, like
+/// `":relative"` or `":fragment"`.
+///
+/// # Safety
+///
+/// `sanitizer` must be null, or have come from `selma_sanitizer_new`, and the
+/// other arguments must be null, or point to NUL-terminated strings.
+#[no_mangle]
+pub unsafe extern "C" fn selma_sanitizer_allow_protocol(
+    sanitizer: *mut Sanitizer,
+    element: *const c_char,
+    attribute: *const c_char,
+    protocol: *const c_char,
+) {
+    if let (Some(sanitizer), Some(element), Some(attribute), Some(protocol)) = (
+        sanitizer.as_mut(),
+        read_str(element),
+        read_str(attribute),
+        read_str(protocol),
+    ) {
+        let protocols = match protocol.strip_prefix(':') {
+            Some(name) => Sanitizer::relative_protocols(name),
+            None => vec![protocol.to_string()],
+        };
+        sanitizer.add_allowed_protocols(element, attribute, protocols);
+    }
+}
+
+/// Sanitizes `html` with `sanitizer`, returning a new string (or null, if
+/// either is null, `html` isn't UTF-8, or it can't be sanitized).
+///
+/// # Safety
+///
+/// `sanitizer` must be null, or have come from `selma_sanitizer_new`, and
+/// `html` must be null, or point to a NUL-terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn selma_sanitize(
+    sanitizer: *const Sanitizer,
+    html: *const c_char,
+) -> *mut c_char {
+    let sanitized = match (sanitizer.as_ref(), read_str(html)) {
+        (Some(sanitizer), Some(html)) => sanitizer.sanitize(html),
+        _ => return std::ptr::null_mut(),
+    };
+
+    match sanitized.map(CString::new) {
+        Ok(Ok(sanitized)) => sanitized.into_raw(),
+        _ => std::ptr::null_mut(),
+    }
+}
+
+/// Frees a sanitizer from `selma_sanitizer_new`.
+///
+/// # Safety
+///
+/// `sanitizer` must be null, or have come from `selma_sanitizer_new`, and must
+/// not be used (or freed) again.
+#[no_mangle]
+pub unsafe extern "C" fn selma_sanitizer_free(sanitizer: *mut Sanitizer) {
+    if !sanitizer.is_null() {
+        drop(Box::from_raw(sanitizer));
+    }
+}
+
+/// Frees a string returned by one of these functions.
+///
+/// # Safety
+///
+/// `string` must be null, or have come from one of these functions, and must
+/// not be used (or freed) again.
+#[no_mangle]
+pub unsafe extern "C" fn selma_string_free(string: *mut c_char) {
+    if !string.is_null() {
+        drop(CString::from_raw(string));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::ptr;
+
+    use super::*;
+
+    fn c(string: &str) -> CString {
+        CString::new(string).unwrap()
+    }
+
+    /// Sanitizes `html` with `sanitizer`, taking back the string that comes back.
+    unsafe fn sanitize(sanitizer: *const Sanitizer, html: &str) -> Option<String> {
+        let sanitized = selma_sanitize(sanitizer, c(html).as_ptr());
+        if sanitized.is_null() {
+            return None;
+        }
+
+        let string = CStr::from_ptr(sanitized).to_str().unwrap().to_string();
+        selma_string_free(sanitized);
+        Some(string)
+    }
+
+    #[test]
+    fn it_answers_for_the_built_in_profiles() {
+        unsafe {
+            assert!(selma_profile_allows_element(
+                c("RELAXED").as_ptr(),
+                c("table").as_ptr()
+            ));
+            assert!(!selma_profile_allows_element(
+                c("BASIC").as_ptr(),
+                c("table").as_ptr()
+            ));
+            assert!(selma_profile_allows_attribute(
+                c("basic").as_ptr(),
+                c("a").as_ptr(),
+                c("href").as_ptr()
+            ));
+            assert!(!selma_profile_allows_element(
+                c("NOPE").as_ptr(),
+                c("p").as_ptr()
+            ));
+            assert!(!selma_profile_allows_element(ptr::null(), c("p").as_ptr()));
+            assert!(!selma_profile_allows_attribute(
+                c("RELAXED").as_ptr(),
+                ptr::null(),
+                c("href").as_ptr()
+            ));
+        }
+    }
+
+    #[test]
+    fn it_smartens_text() {
+        unsafe {
+            let smartened = selma_smarten(c("\"Hello\" -- world").as_ptr());
+            assert_eq!(
+                CStr::from_ptr(smartened).to_str(),
+                Ok("\u{201c}Hello\u{201d} \u{2013} world")
+            );
+            selma_string_free(smartened);
+
+            assert!(selma_smarten(ptr::null()).is_null());
+        }
+    }
+
+    #[test]
+    fn it_sanitizes_with_a_profile() {
+        unsafe {
+            let sanitizer = selma_sanitizer_new(c("BASIC").as_ptr());
+            assert!(!sanitizer.is_null());
+
+            let html = r#"<b onclick="x()">Hi</b><script>alert(1)</script>"#;
+            assert_eq!(
+                sanitize(sanitizer, html),
+                Sanitizer::from_profile(Profile::find("BASIC").unwrap())
+                    .sanitize(html)
+                    .ok()
+            );
+            assert_eq!(sanitize(sanitizer, html).as_deref(), Some("<b>Hi</b>"));
+
+            selma_sanitizer_free(sanitizer);
+        }
+    }
+
+    #[test]
+    fn it_refuses_unknown_profiles() {
+        unsafe {
+            assert!(selma_sanitizer_new(c("NOPE").as_ptr()).is_null());
+        }
+    }
+
+    #[test]
+    fn it_sanitizes_with_what_it_is_told_to_allow() {
+        unsafe {
+            let sanitizer = selma_sanitizer_new(ptr::null());
+            assert_eq!(sanitize(sanitizer, "<p>Hi</p>").as_deref(), Some("Hi"));
+
+            selma_sanitizer_allow_element(sanitizer, c("a").as_ptr());
+            selma_sanitizer_allow_element(sanitizer, c("p").as_ptr());
+            selma_sanitizer_allow_attribute(sanitizer, c("a").as_ptr(), c("href").as_ptr());
+            selma_sanitizer_allow_protocol(
+                sanitizer,
+                c("a").as_ptr(),
+                c("href").as_ptr(),
+                c("https").as_ptr(),
+            );
+            selma_sanitizer_allow_protocol(
+                sanitizer,
+                c("a").as_ptr(),
+                c("href").as_ptr(),
+                c(":relative").as_ptr(),
+            );
+
+            assert_eq!(
+                sanitize(
+                    sanitizer,
+                    r#"<p title="t">Hi</p><a href="https://example.com/">a</a><a href="/b">b</a><a href="javascript:x()">c</a>"#
+                )
+                .as_deref(),
+                Some(r#"<p>Hi</p><a href="https://example.com/">a</a><a href="/b">b</a><a>c</a>"#)
+            );
+
+            selma_sanitizer_free(sanitizer);
+        }
+    }
+
+    #[test]
+    fn it_ignores_null_arguments() {
+        unsafe {
+            selma_sanitizer_allow_element(ptr::null_mut(), c("p").as_ptr());
+            selma_sanitizer_allow_attribute(ptr::null_mut(), c("a").as_ptr(), c("href").as_ptr());
+            selma_sanitizer_allow_protocol(
+                ptr::null_mut(),
+                c("a").as_ptr(),
+                c("href").as_ptr(),
+                c("https").as_ptr(),
+            );
+            assert_eq!(sanitize(ptr::null(), "<p>Hi</p>"), None);
+
+            let sanitizer = selma_sanitizer_new(ptr::null());
+            selma_sanitizer_allow_element(sanitizer, ptr::null());
+            selma_sanitizer_allow_attribute(sanitizer, c("a").as_ptr(), ptr::null());
+            selma_sanitizer_allow_protocol(
+                sanitizer,
+                c("a").as_ptr(),
+                c("href").as_ptr(),
+                ptr::null(),
+            );
+            assert!(selma_sanitize(sanitizer, ptr::null()).is_null());
+            selma_sanitizer_free(sanitizer);
+
+            selma_sanitizer_free(ptr::null_mut());
+            selma_string_free(ptr::null_mut());
+        }
+    }
+}
@@ -0,0 +1,22 @@
+//! The parts of Selma which don't depend on Ruby: the table of HTML tags, the
+//! built-in sanitizer profiles, the sanitizer itself and the typographer. The
+//! Ruby extension uses them through magnus, and `ffi` exposes them to
+//! everything else through a C ABI (which `wasm` lets WebAssembly hosts use,
+//! too). Of the rewriting pipeline, only the sanitizer, and the tokenizer the
+//! serializer writes its output with, have moved here: the rewriter, whose
+//! handlers are Ruby objects, and the serializer and the rest around it still
+//! live in the extension, which is more than a thin wrapper.
+
+pub mod css;
+pub mod ffi;
+pub mod profiles;
+pub mod sanitize;
+pub mod sanitizer;
+pub mod tags;
+pub mod tokens;
+pub mod typographer;
+#[cfg(target_arch = "wasm32")]
+pub mod wasm;
+
+/// The version of `selma-core` (not of the gem).
+pub const VERSION: &str = env!("CARGO_PKG_VERSION");
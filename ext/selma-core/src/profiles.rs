@@ -0,0 +1,469 @@
+/// A sanitization config which is constructed natively, rather than parsed
+/// from a Ruby hash. These mirror the hashes in `Selma::Sanitizer::Config`,
+/// which are themselves based on the Sanitize gem's profiles.
+pub struct Profile {
+    /// The name of the matching constant in `Selma::Sanitizer::Config`
+    pub config_name: &'static str,
+    /// A profile whose rules are applied before this one's
+    pub extends: Option<&'static Profile>,
+    pub elements: &'static [&'static str],
+    pub attributes: &'static [(&'static str, &'static [&'static str])],
+    pub protocols: &'static [(&'static str, &'static str, &'static [&'static str])],
+    pub remove_contents: &'static [&'static str],
+    pub whitespace_elements: &'static [&'static str],
+    pub allow_comments: bool,
+    pub allow_doctype: bool,
+    pub name_prefix: Option<&'static str>,
+}
+
+impl Profile {
+    /// The built-in profile with this `config_name`, ignoring case.
+    pub fn find(name: &str) -> Option<&'static Profile> {
+        [&BASIC, &RESTRICTED, &RELAXED, &GITHUB]
+            .into_iter()
+            .find(|profile| profile.config_name.eq_ignore_ascii_case(name))
+    }
+
+    /// Does this profile (or one it extends) allow the element?
+    pub fn allows_element(&self, element: &str) -> bool {
+        self.elements
+            .iter()
+            .any(|allowed| allowed.eq_ignore_ascii_case(element))
+            || self
+                .extends
+                .is_some_and(|base| base.allows_element(element))
+    }
+
+    /// Does this profile (or one it extends) allow the attribute on the
+    /// element, or on every element?
+    pub fn allows_attribute(&self, element: &str, attribute: &str) -> bool {
+        self.attributes.iter().any(|(allowed_element, attrs)| {
+            (*allowed_element == "all" || allowed_element.eq_ignore_ascii_case(element))
+                && attrs
+                    .iter()
+                    .any(|allowed| allowed.eq_ignore_ascii_case(attribute))
+        }) || self
+            .extends
+            .is_some_and(|base| base.allows_attribute(element, attribute))
+    }
+}
+
+/// Stands in for Ruby's `:relative` protocol symbol.
+pub const RELATIVE: &str = ":relative";
+
+const WHITESPACE_ELEMENTS: &[&str] = &[
+    "address",
+    "article",
+    "aside",
+    "blockquote",
+    "br",
+    "dd",
+    "div",
+    "dl",
+    "dt",
+    "footer",
+    "h1",
+    "h2",
+    "h3",
+    "h4",
+    "h5",
+    "h6",
+    "header",
+    "hgroup",
+    "hr",
+    "li",
+    "nav",
+    "ol",
+    "p",
+    "pre",
+    "section",
+    "ul",
+];
+
+const BASIC_ELEMENTS: &[&str] = &[
+    "a",
+    "abbr",
+    "blockquote",
+    "b",
+    "br",
+    "cite",
+    "code",
+    "dd",
+    "dfn",
+    "dl",
+    "dt",
+    "em",
+    "i",
+    "kbd",
+    "li",
+    "mark",
+    "ol",
+    "p",
+    "pre",
+    "q",
+    "s",
+    "samp",
+    "small",
+    "strike",
+    "strong",
+    "sub",
+    "sup",
+    "time",
+    "u",
+    "ul",
+    "var",
+];
+
+const BASIC_ATTRIBUTES: &[(&str, &[&str])] = &[
+    ("a", &["href"]),
+    ("abbr", &["title"]),
+    ("blockquote", &["cite"]),
+    ("dfn", &["title"]),
+    ("q", &["cite"]),
+    ("time", &["datetime", "pubdate"]),
+];
+
+const BASIC_PROTOCOLS: &[(&str, &str, &[&str])] = &[
+    ("a", "href", &["ftp", "http", "https", "mailto", RELATIVE]),
+    ("blockquote", "cite", &["http", "https", RELATIVE]),
+    ("q", "cite", &["http", "https", RELATIVE]),
+];
+
+pub const BASIC: Profile = Profile {
+    config_name: "BASIC",
+    extends: None,
+    elements: BASIC_ELEMENTS,
+    attributes: BASIC_ATTRIBUTES,
+    protocols: BASIC_PROTOCOLS,
+    remove_contents: &[],
+    whitespace_elements: &[],
+    allow_comments: false,
+    allow_doctype: true,
+    name_prefix: None,
+};
+
+pub const RESTRICTED: Profile = Profile {
+    config_name: "RESTRICTED",
+    extends: None,
+    elements: &["b", "em", "i", "strong", "u"],
+    attributes: &[],
+    protocols: &[],
+    remove_contents: &[],
+    whitespace_elements: WHITESPACE_ELEMENTS,
+    allow_comments: false,
+    allow_doctype: true,
+    name_prefix: None,
+};
+
+const RELAXED_ELEMENTS: &[&str] = &[
+    "address",
+    "article",
+    "aside",
+    "bdi",
+    "bdo",
+    "body",
+    "caption",
+    "col",
+    "colgroup",
+    "data",
+    "del",
+    "div",
+    "figcaption",
+    "figure",
+    "footer",
+    "h1",
+    "h2",
+    "h3",
+    "h4",
+    "h5",
+    "h6",
+    "head",
+    "header",
+    "hgroup",
+    "hr",
+    "html",
+    "img",
+    "ins",
+    "main",
+    "nav",
+    "rp",
+    "rt",
+    "ruby",
+    "section",
+    "span",
+    "style",
+    "summary",
+    "sup",
+    "table",
+    "tbody",
+    "td",
+    "tfoot",
+    "th",
+    "thead",
+    "title",
+    "tr",
+    "wbr",
+];
+
+const RELAXED_ATTRIBUTES: &[(&str, &[&str])] = &[
+    (
+        "all",
+        &[
+            "class",
+            "dir",
+            "hidden",
+            "id",
+            "lang",
+            "style",
+            "tabindex",
+            "title",
+            "translate",
+        ],
+    ),
+    ("a", &["href", "hreflang", "name", "rel"]),
+    ("col", &["span", "width"]),
+    ("colgroup", &["span", "width"]),
+    ("data", &["value"]),
+    ("del", &["cite", "datetime"]),
+    (
+        "img",
+        &["align", "alt", "border", "height", "src", "srcset", "width"],
+    ),
+    ("ins", &["cite", "datetime"]),
+    ("li", &["value"]),
+    ("ol", &["reversed", "start", "type"]),
+    ("style", &["media", "scoped", "type"]),
+    (
+        "table",
+        &[
+            "align",
+            "bgcolor",
+            "border",
+            "cellpadding",
+            "cellspacing",
+            "frame",
+            "rules",
+            "sortable",
+            "summary",
+            "width",
+        ],
+    ),
+    (
+        "td",
+        &[
+            "abbr", "align", "axis", "colspan", "headers", "rowspan", "valign", "width",
+        ],
+    ),
+    (
+        "th",
+        &[
+            "abbr", "align", "axis", "colspan", "headers", "rowspan", "scope", "sorted", "valign",
+            "width",
+        ],
+    ),
+    ("ul", &["type"]),
+];
+
+const RELAXED_PROTOCOLS: &[(&str, &str, &[&str])] = &[
+    ("del", "cite", &["http", "https", RELATIVE]),
+    ("img", "src", &["http", "https", RELATIVE]),
+    ("ins", "cite", &["http", "https", RELATIVE]),
+];
+
+pub const RELAXED: Profile = Profile {
+    config_name: "RELAXED",
+    extends: Some(&BASIC),
+    elements: RELAXED_ELEMENTS,
+    attributes: RELAXED_ATTRIBUTES,
+    protocols: RELAXED_PROTOCOLS,
+    remove_contents: &[],
+    whitespace_elements: &[],
+    allow_comments: false,
+    allow_doctype: true,
+    name_prefix: None,
+};
+
+const GITHUB_ELEMENTS: &[&str] = &[
+    "a",
+    "abbr",
+    "b",
+    "bdo",
+    "blockquote",
+    "br",
+    "caption",
+    "cite",
+    "code",
+    "dd",
+    "del",
+    "details",
+    "dfn",
+    "div",
+    "dl",
+    "dt",
+    "em",
+    "figcaption",
+    "figure",
+    "h1",
+    "h2",
+    "h3",
+    "h4",
+    "h5",
+    "h6",
+    "hr",
+    "i",
+    "img",
+    "ins",
+    "kbd",
+    "li",
+    "mark",
+    "ol",
+    "p",
+    "pre",
+    "q",
+    "rp",
+    "rt",
+    "ruby",
+    "s",
+    "samp",
+    "small",
+    "span",
+    "strike",
+    "strong",
+    "sub",
+    "summary",
+    "sup",
+    "table",
+    "tbody",
+    "td",
+    "tfoot",
+    "th",
+    "thead",
+    "time",
+    "tr",
+    "tt",
+    "ul",
+    "var",
+    "wbr",
+];
+
+const GITHUB_ATTRIBUTES: &[(&str, &[&str])] = &[
+    (
+        "all",
+        &[
+            "abbr",
+            "accept",
+            "accept-charset",
+            "accesskey",
+            "align",
+            "alt",
+            "aria-describedby",
+            "aria-hidden",
+            "aria-label",
+            "aria-labelledby",
+            "axis",
+            "border",
+            "cellpadding",
+            "cellspacing",
+            "char",
+            "charoff",
+            "charset",
+            "checked",
+            "clear",
+            "color",
+            "cols",
+            "colspan",
+            "compact",
+            "coords",
+            "datetime",
+            "dir",
+            "disabled",
+            "enctype",
+            "for",
+            "frame",
+            "headers",
+            "height",
+            "hreflang",
+            "hspace",
+            "ismap",
+            "itemprop",
+            "label",
+            "lang",
+            "maxlength",
+            "media",
+            "multiple",
+            "name",
+            "nohref",
+            "noshade",
+            "nowrap",
+            "open",
+            "progress",
+            "prompt",
+            "readonly",
+            "rel",
+            "rev",
+            "role",
+            "rows",
+            "rowspan",
+            "rules",
+            "scope",
+            "selected",
+            "shape",
+            "size",
+            "span",
+            "start",
+            "summary",
+            "tabindex",
+            "target",
+            "title",
+            "type",
+            "usemap",
+            "valign",
+            "value",
+            "vspace",
+            "width",
+        ],
+    ),
+    ("a", &["href"]),
+    ("blockquote", &["cite"]),
+    ("del", &["cite"]),
+    ("div", &["itemscope", "itemtype"]),
+    ("img", &["src", "longdesc"]),
+    ("ins", &["cite"]),
+    ("q", &["cite"]),
+];
+
+const GITHUB_PROTOCOLS: &[(&str, &str, &[&str])] = &[
+    ("a", "href", &["http", "https", "mailto", RELATIVE]),
+    ("blockquote", "cite", &["http", "https", RELATIVE]),
+    ("del", "cite", &["http", "https", RELATIVE]),
+    ("img", "longdesc", &["http", "https", RELATIVE]),
+    ("img", "src", &["http", "https", RELATIVE]),
+    ("ins", "cite", &["http", "https", RELATIVE]),
+    ("q", "cite", &["http", "https", RELATIVE]),
+];
+
+const GITHUB_REMOVE_CONTENTS: &[&str] = &[
+    "iframe",
+    "math",
+    "noembed",
+    "noframes",
+    "noscript",
+    "plaintext",
+    "script",
+    "style",
+    "svg",
+    "xmp",
+];
+
+pub const GITHUB: Profile = Profile {
+    config_name: "GITHUB",
+    extends: None,
+    elements: GITHUB_ELEMENTS,
+    attributes: GITHUB_ATTRIBUTES,
+    protocols: GITHUB_PROTOCOLS,
+    remove_contents: GITHUB_REMOVE_CONTENTS,
+    whitespace_elements: WHITESPACE_ELEMENTS,
+    allow_comments: false,
+    allow_doctype: false,
+    name_prefix: Some("user-content-"),
+};
@@ -0,0 +1,857 @@
+//! Sanitizing a whole document: the `Sanitizer` is applied to every element,
+//! comment and bit of text lol_html comes across, in a first pass, and the tag
+//! filter in a second, once everything the first let through is known.
+
+use std::{
+    borrow::Cow,
+    cell::{Cell, RefCell},
+    fmt,
+    rc::Rc,
+    time::{Duration, Instant},
+};
+
+use lol_html::{
+    doc_comments, doc_text, doctype, element,
+    errors::RewritingError,
+    html_content::{ContentType, Element, TextType},
+    text, DocumentContentHandlers, ElementContentHandlers, HtmlRewriter, Selector, Settings,
+};
+
+use crate::{
+    sanitizer::{NoscriptPolicy, Removal, Sanitizer},
+    tags::Tag,
+};
+
+/// The elements whose contents the parser reads as text, rather than markup,
+/// besides `<script>` and `<style>`, whose contents are never kept.
+const TEXT_CONTENT_ELEMENTS: &[&str] = &[
+    "iframe",
+    "noembed",
+    "noframes",
+    "noscript",
+    "plaintext",
+    "textarea",
+    "title",
+    "xmp",
+];
+
+/// Why a document couldn't be sanitized.
+#[derive(Clone, Debug, PartialEq)]
+pub enum SanitizeError {
+    /// It couldn't be parsed (or written back out), or the sanitizer refused it
+    Rewriting(String),
+    /// It wasn't done by the `Deadline`
+    Timeout(String),
+}
+
+impl fmt::Display for SanitizeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Rewriting(message) | Self::Timeout(message) => f.write_str(message),
+        }
+    }
+}
+
+impl std::error::Error for SanitizeError {}
+
+/// What to do with markup that lol_html can't parse without knowing what came
+/// before it in the DOM, like `<select><xmp>`, where browsers disagree with
+/// each other (or with a streaming parser) on what follows.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum Malformed {
+    /// Fail with a `SanitizeError::Rewriting`
+    #[default]
+    Error,
+    /// Parse it the way lol_html guesses best, and carry on
+    PassThrough,
+    /// Drop what's left of the document, from there on
+    Drop,
+}
+
+impl Malformed {
+    /// Whether lol_html should stop at markup it can't be sure of.
+    pub fn strict(self) -> bool {
+        self != Self::PassThrough
+    }
+
+    /// Lets a write which stopped at such markup through, when that's what
+    /// `Drop` wants; everything before it has already been written out.
+    pub fn tolerate(self, written: Result<(), RewritingError>) -> Result<(), RewritingError> {
+        match written {
+            Err(RewritingError::ParsingAmbiguity(_)) if self == Self::Drop => Ok(()),
+            written => written,
+        }
+    }
+}
+
+/// When a rewrite with a `timeout:` has to be done by. lol_html can't be
+/// interrupted, so this is only checked between the chunks of input it's
+/// given (and, in the rewriter, before each call into a handler); a handler which never
+/// returns is never timed out.
+#[derive(Clone, Copy, Default)]
+pub struct Deadline(Option<(Instant, Duration)>);
+
+impl Deadline {
+    /// Small enough that lol_html doesn't go long without the deadline being
+    /// checked, big enough that text isn't split into many more chunks.
+    const CHUNK_SIZE: usize = 64 * 1024;
+
+    pub fn new(start: Instant, timeout: Option<Duration>) -> Self {
+        Self(timeout.map(|timeout| (start + timeout, timeout)))
+    }
+
+    pub fn check(self) -> Result<(), SanitizeError> {
+        match self.0 {
+            Some((deadline, timeout)) if Instant::now() >= deadline => {
+                Err(SanitizeError::Timeout(format!(
+                    "the rewrite took longer than its timeout of {} seconds",
+                    timeout.as_secs_f64()
+                )))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// What to hand lol_html at a time; without a timeout, there's no need to
+    /// split the input up.
+    pub fn chunks(self, input: &[u8]) -> std::slice::Chunks<'_, u8> {
+        match self.0 {
+            Some(_) => input.chunks(Self::CHUNK_SIZE),
+            None => input.chunks(input.len().max(1)),
+        }
+    }
+}
+
+/// Everything a sanitizing pass keeps track of, or goes by, besides the
+/// sanitizers themselves. The sanitizers are only ever read from while
+/// sanitizing, so the same one can be used by any number of rewriters, and by
+/// the `<noscript>` and `srcdoc` passes nested within a rewrite, without any of
+/// them seeing the others' state; what belongs to a single document is kept
+/// here (and in the pass itself) instead.
+#[derive(Clone, Copy)]
+pub struct SanitizeContext<'a> {
+    /// Called with each element the sanitizer takes out, and why
+    pub on_removal: &'a dyn Fn(&Element, Removal),
+    pub malformed: Malformed,
+    pub deadline: Deadline,
+    /// How many `srcdoc` (or `<noscript>`) documents deep the pass is
+    pub nesting: usize,
+}
+
+/// How deep `srcdoc` documents (and `<noscript>` contents) are sanitized; each
+/// level takes two more rewriters' worth of stack, so any deeper are dropped.
+const MAX_NESTING: usize = 3;
+
+impl<'a> SanitizeContext<'a> {
+    /// The context for a document nested within this one, unless it's nested
+    /// too deeply to be sanitized.
+    fn nested(self) -> Option<Self> {
+        (self.nesting < MAX_NESTING).then_some(Self {
+            nesting: self.nesting + 1,
+            ..self
+        })
+    }
+}
+
+fn ignore_removal(_element: &Element, _reason: Removal) {}
+
+impl Default for SanitizeContext<'_> {
+    fn default() -> Self {
+        Self {
+            on_removal: &ignore_removal,
+            malformed: Malformed::default(),
+            deadline: Deadline::default(),
+            nesting: 0,
+        }
+    }
+}
+
+/// Which of the scoped sanitizers is in charge of the element being
+/// sanitized: that of the innermost open element which matched its selector,
+/// if there is one.
+#[derive(Default)]
+struct SanitizerScopes {
+    open: Rc<RefCell<Vec<usize>>>,
+    /// a void element which matched, and so is only in scope until it's sanitized
+    void: Cell<Option<usize>>,
+}
+
+impl SanitizerScopes {
+    fn enter(&self, element: &mut Element, index: usize) {
+        match element.end_tag_handlers() {
+            Some(end_tag_handlers) => {
+                self.open.borrow_mut().push(index);
+
+                let open = self.open.clone();
+                end_tag_handlers.push(Box::new(move |_end_tag| {
+                    open.borrow_mut().pop();
+                    Ok(())
+                }));
+            }
+            None => self.void.set(Some(index)),
+        }
+    }
+
+    fn leave_void(&self) {
+        self.void.set(None);
+    }
+
+    fn current<'s>(
+        &self,
+        sanitizer: &'s Sanitizer,
+        scoped_sanitizers: &[(&str, &'s Sanitizer)],
+    ) -> &'s Sanitizer {
+        match self
+            .void
+            .get()
+            .or_else(|| self.open.borrow().last().copied())
+        {
+            Some(index) => scoped_sanitizers[index].1,
+            None => sanitizer,
+        }
+    }
+}
+
+/// How many of the open elements are ones whose subtree is to be left alone:
+/// trusted ones the sanitizer passes through, or (in the rewriter) ones a
+/// handler asked to skip, by returning `:skip_children` or calling
+/// `Element#skip!`.
+#[derive(Clone, Default)]
+pub struct SkippedSubtrees(Rc<Cell<usize>>);
+
+impl SkippedSubtrees {
+    pub fn skipping(&self) -> bool {
+        self.0.get() > 0
+    }
+
+    pub fn skip(&self, element: &mut Element) {
+        // elements which can't have an end tag (like `<br>`) have no subtree
+        if let Some(end_tag_handlers) = element.end_tag_handlers() {
+            self.0.set(self.0.get() + 1);
+
+            let depth = self.0.clone();
+            end_tag_handlers.push(Box::new(move |_end_tag| {
+                depth.set(depth.get() - 1);
+                Ok(())
+            }));
+        }
+    }
+}
+
+/// How many of the open elements are kept `<pre>`, `<textarea>` or `<code>`
+/// elements, within which whitespace is significant.
+#[derive(Clone, Default)]
+struct Preformatted(Rc<Cell<usize>>);
+
+impl Preformatted {
+    fn within(&self) -> bool {
+        self.0.get() > 0
+    }
+
+    fn enter(&self, element: &mut Element) {
+        if let Some(end_tag_handlers) = element.end_tag_handlers() {
+            self.0.set(self.0.get() + 1);
+
+            let depth = self.0.clone();
+            end_tag_handlers.push(Box::new(move |_end_tag| {
+                depth.set(depth.get() - 1);
+                Ok(())
+            }));
+        }
+    }
+}
+
+impl Sanitizer {
+    /// Sanitizes a document (or fragment) on its own.
+    pub fn sanitize(&self, html: &str) -> Result<String, SanitizeError> {
+        let sanitized = sanitize(self, &[], html, SanitizeContext::default())?;
+
+        String::from_utf8(sanitized).map_err(|err| {
+            SanitizeError::Rewriting(format!(
+                "Failed to sanitize HTML: output is not valid UTF-8 ({err})"
+            ))
+        })
+    }
+}
+
+/// Sanitizes `html` with `sanitizer`, or whichever of the `scoped_sanitizers`
+/// is in charge of each element: that of the innermost element matching its
+/// selector, if there is one.
+pub fn sanitize(
+    sanitizer: &Sanitizer,
+    scoped_sanitizers: &[(&str, &Sanitizer)],
+    html: &str,
+    context: SanitizeContext,
+) -> Result<Vec<u8>, SanitizeError> {
+    let SanitizeContext {
+        on_removal,
+        malformed,
+        deadline,
+        ..
+    } = context;
+    let html = match sanitizer.control_characters {
+        Some(policy) => Sanitizer::filter_control_characters(policy, html)?,
+        None => Cow::Borrowed(html),
+    };
+
+    let mut first_pass_html = vec![];
+    {
+        let noscript_content = RefCell::new(String::new());
+        let text_content = RefCell::new(String::new());
+        // trusted subtrees, which are left just as they are
+        let passthrough = SkippedSubtrees::default();
+        let preformatted = Preformatted::default();
+        let scopes = SanitizerScopes::default();
+        let all_sanitizers =
+            || std::iter::once(sanitizer).chain(scoped_sanitizers.iter().map(|(_, s)| *s));
+        let mut document_content_handlers: Vec<DocumentContentHandlers> = vec![];
+        if !sanitizer.allow_doctype {
+            document_content_handlers.push(doctype!(|d| {
+                sanitizer.remove_doctype(d);
+                Ok(())
+            }));
+        }
+        if all_sanitizers().any(|sanitizer| {
+            !sanitizer.allow_comments
+                || !sanitizer.allow_conditional_comments
+                || !sanitizer.allow_cdata
+        }) {
+            document_content_handlers.push(doc_comments!(|c| {
+                let sanitizer = scopes.current(sanitizer, scoped_sanitizers);
+                if !passthrough.skipping() && sanitizer.should_remove_comment(c) {
+                    sanitizer.remove_comment(c);
+                }
+                Ok(())
+            }));
+        }
+        // within SVG and MathML, CDATA sections are text, which is dangerous if
+        // the element around them doesn't survive: `<![CDATA[<img onerror=...>]]>`
+        // is a comment that ends early, followed by markup, anywhere else
+        if !sanitizer.allow_cdata {
+            document_content_handlers.push(doc_text!(|t| {
+                if t.text_type() == TextType::CDataSection && !passthrough.skipping() {
+                    t.remove();
+                }
+                Ok(())
+            }));
+        }
+        // a `<` at the end of some text is marked, to be written back once what
+        // follows it is known (`rewrite_text` escapes every `<`, when it's used)
+        if !sanitizer.normalize_entities && sanitizer.unicode_filter.is_none() {
+            document_content_handlers.push(doc_text!(|t| {
+                if t.text_type() != TextType::Data || t.removed() || passthrough.skipping() {
+                    return Ok(());
+                }
+                if let Some(text) = t.as_str().strip_suffix('<') {
+                    let marked = format!("{text}{}", Sanitizer::less_than_marker());
+                    t.replace(&marked, ContentType::Html);
+                }
+                Ok(())
+            }));
+        }
+        if sanitizer.normalize_entities || sanitizer.unicode_filter.is_some() {
+            document_content_handlers.push(doc_text!(|t| {
+                // only text which entities are decoded in; `<script>`, `<style>`
+                // and the like are left as they are
+                if !matches!(t.text_type(), TextType::Data | TextType::RCData)
+                    || passthrough.skipping()
+                {
+                    return Ok(());
+                }
+
+                // an entity may be split across chunks
+                text_content.borrow_mut().push_str(t.as_str());
+                if !t.last_in_text_node() {
+                    t.remove();
+                    return Ok(());
+                }
+
+                let text = text_content.take();
+                match sanitizer.rewrite_text(&text) {
+                    Some(rewritten) => t.replace(&rewritten, ContentType::Html),
+                    None => t.replace(&text, ContentType::Html),
+                }
+                Ok(())
+            }));
+        }
+        let mut element_content_handlers: Vec<(Cow<Selector>, ElementContentHandlers)> = vec![];
+        for (index, (selector, _)) in scoped_sanitizers.iter().enumerate() {
+            let scopes = &scopes;
+            element_content_handlers.push(element!(selector, move |el| {
+                scopes.enter(el, index);
+                Ok(())
+            }));
+        }
+        element_content_handlers.push(element!("*", |el| {
+            let sanitizer = scopes.current(sanitizer, scoped_sanitizers);
+            scopes.leave_void();
+            if passthrough.skipping() {
+                return Ok(());
+            }
+            if sanitizer.is_passthrough(el) {
+                passthrough.skip(el);
+                return Ok(());
+            }
+            if let Err(err) = sanitizer.normalize_amp_element(el) {
+                return Err(format!("could not normalize <{}>: {err}", el.tag_name()).into());
+            }
+            let removal = sanitizer.try_remove_element(el, preformatted.within());
+            if el.removed() {
+                sanitizer.insert_embed_fallback(el);
+                on_removal(el, removal.unwrap_or(Removal::NotAllowed));
+                return Ok(());
+            }
+            if let Err(err) = sanitizer.sanitize_attributes(el) {
+                return Err(format!(
+                    "could not sanitize the attributes of <{}>: {err}",
+                    el.tag_name()
+                )
+                .into());
+            }
+            if let Err(err) = sanitize_srcdoc(sanitizer, el, context) {
+                return Err(format!("could not sanitize the `srcdoc` of <iframe>: {err}").into());
+            }
+            match sanitizer.enforce_integrity(el) {
+                Ok(Some(removal)) => {
+                    on_removal(el, removal);
+                    return Ok(());
+                }
+                Ok(None) => {}
+                Err(err) => {
+                    return Err(format!(
+                        "could not enforce the `integrity` of <{}>: {err}",
+                        el.tag_name()
+                    )
+                    .into())
+                }
+            }
+            if let Err(err) = sanitizer.enforce_iframe(el) {
+                return Err(format!("could not enforce the policy of <iframe>: {err}").into());
+            }
+            if let Some(removal) = sanitizer.enforce_media(el) {
+                on_removal(el, removal);
+                return Ok(());
+            }
+            match sanitizer.normalize_image(el) {
+                Ok(Some(removal)) => {
+                    on_removal(el, removal);
+                    Ok(())
+                }
+                Ok(None) => Ok(()),
+                Err(err) => Err(format!("could not normalize <img>: {err}").into()),
+            }
+        }));
+        // after the sanitizer's handler, since the whitespace around the
+        // element itself isn't within it
+        if all_sanitizers().any(|sanitizer| sanitizer.preserve_preformatted_whitespace) {
+            element_content_handlers.push(element!("pre, textarea, code", |el| {
+                if !el.removed() {
+                    preformatted.enter(el);
+                }
+                Ok(())
+            }));
+        }
+
+        if sanitizer.tables.is_some() {
+            element_content_handlers.push(element!("table > tr", |el| {
+                if !passthrough.skipping() {
+                    sanitizer.wrap_stray_row(el);
+                }
+                Ok(())
+            }));
+            element_content_handlers.push(element!("table, td, th", |el| {
+                if !passthrough.skipping() {
+                    sanitizer.normalize_table(el);
+                }
+                Ok(())
+            }));
+        }
+
+        // `<noscript>` contents arrive as text, which is only safe to keep as text
+        match sanitizer.noscript {
+            Some(NoscriptPolicy::Escape) => {
+                element_content_handlers.push(text!("noscript", |t| {
+                    if passthrough.skipping() {
+                        return Ok(());
+                    }
+                    let content = t.as_str().to_string();
+                    t.replace(&content, ContentType::Text);
+                    Ok(())
+                }));
+            }
+            Some(NoscriptPolicy::Unwrap) => {
+                element_content_handlers.push(text!("noscript", |t| {
+                    if passthrough.skipping() {
+                        return Ok(());
+                    }
+                    // the contents may come in several chunks, but need to be
+                    // sanitized all at once
+                    noscript_content.borrow_mut().push_str(t.as_str());
+                    if !t.last_in_text_node() {
+                        t.remove();
+                        return Ok(());
+                    }
+
+                    let content = noscript_content.take();
+                    let Some(nested) = context.nested() else {
+                        t.remove();
+                        return Ok(());
+                    };
+                    let sanitized = sanitize(
+                        scopes.current(sanitizer, scoped_sanitizers),
+                        &[],
+                        &content,
+                        nested,
+                    )
+                    .and_then(|sanitized| {
+                        String::from_utf8(sanitized).map_err(|err| {
+                            SanitizeError::Rewriting(format!("output is not valid UTF-8 ({err})"))
+                        })
+                    });
+                    match sanitized {
+                        Ok(sanitized) => {
+                            t.replace(&sanitized, ContentType::Html);
+                            Ok(())
+                        }
+                        Err(err) => Err(format!(
+                            "could not sanitize the contents of <noscript>: {err}"
+                        )
+                        .into()),
+                    }
+                }));
+            }
+            Some(NoscriptPolicy::Remove) | None => {}
+        }
+
+        // the raw text of the removed elements `escape_contents:` names is
+        // kept, but only as text
+        for name in sanitizer.get_escaped_content_elements() {
+            element_content_handlers.push(text!(name, |t| {
+                if passthrough.skipping() {
+                    return Ok(());
+                }
+                let content = t.as_str().to_string();
+                t.replace(&content, ContentType::Text);
+                Ok(())
+            }));
+        }
+
+        // and that of any other element the parser reads as text is, too, once
+        // the element around it is gone: `<title><img onerror=...></title>`
+        // is markup anywhere else
+        for name in TEXT_CONTENT_ELEMENTS {
+            let scopes = &scopes;
+            let passthrough = &passthrough;
+            let entities_rewritten =
+                sanitizer.normalize_entities || sanitizer.unicode_filter.is_some();
+            element_content_handlers.push(text!(name, move |t| {
+                if passthrough.skipping()
+                    || t.removed()
+                    || !scopes
+                        .current(sanitizer, scoped_sanitizers)
+                        .escapes_text_of(name)
+                {
+                    return Ok(());
+                }
+
+                match t.text_type() {
+                    // entities are decoded in RCDATA, so only `<` and `>` (which
+                    // `rewrite_text` escapes as well) need escaping
+                    TextType::RCData if !entities_rewritten => {
+                        let content = t.as_str().replace('<', "&lt;").replace('>', "&gt;");
+                        t.replace(&content, ContentType::Html);
+                    }
+                    TextType::RawText | TextType::PlainText => {
+                        let content = t.as_str().to_string();
+                        t.replace(&content, ContentType::Text);
+                    }
+                    _ => {}
+                }
+                Ok(())
+            }));
+        }
+
+        let mut rewriter = HtmlRewriter::new(
+            Settings {
+                document_content_handlers,
+                element_content_handlers,
+                strict: malformed.strict(),
+                // TODO: allow for MemorySettings to be defined
+                ..Settings::default()
+            },
+            |c: &[u8]| first_pass_html.extend_from_slice(c),
+        );
+
+        for chunk in deadline.chunks(html.as_bytes()) {
+            deadline.check()?;
+            if let Err(err) = malformed.tolerate(rewriter.write(chunk)) {
+                // a `<noscript>` or `srcdoc` may have run out of time
+                deadline.check()?;
+                return Err(SanitizeError::Rewriting(format!(
+                    "Failed to sanitize HTML: {err}"
+                )));
+            }
+        }
+    }
+
+    let mut output = vec![];
+    {
+        let passthrough = SkippedSubtrees::default();
+        let passthrough_selector = sanitizer
+            .get_passthrough_attribute()
+            .map(|attribute| format!("[{attribute}]"));
+        let mut element_content_handlers: Vec<(Cow<Selector>, ElementContentHandlers)> = vec![];
+        // the trusted subtrees are found all over again, to keep the tag filter
+        // out of them, and to take away what marked them as trusted
+        if let Some(selector) = &passthrough_selector {
+            element_content_handlers.push(element!(selector, |el| {
+                if sanitizer.is_passthrough(el) {
+                    passthrough.skip(el);
+                    sanitizer.remove_passthrough_attribute(el);
+                }
+                Ok(())
+            }));
+        }
+        if sanitizer.escape_tagfilter {
+            element_content_handlers.push(element!(Tag::ESCAPEWORTHY_TAGS_CSS, |el| {
+                if passthrough.skipping() {
+                    return Ok(());
+                }
+                let should_remove = sanitizer.allow_element(el);
+                if should_remove {
+                    sanitizer.force_remove_element(el);
+                    on_removal(el, Removal::Tagfilter);
+                }
+
+                Ok(())
+            }));
+        }
+
+        let mut rewriter = HtmlRewriter::new(
+            Settings {
+                element_content_handlers,
+                strict: malformed.strict(),
+                ..Settings::default()
+            },
+            |c: &[u8]| output.extend_from_slice(c),
+        );
+
+        for chunk in deadline.chunks(first_pass_html.as_slice()) {
+            deadline.check()?;
+            if let Err(err) = malformed.tolerate(rewriter.write(chunk)) {
+                return Err(SanitizeError::Rewriting(format!(
+                    "Failed to sanitize HTML: {err}"
+                )));
+            }
+        }
+    }
+
+    Ok(Sanitizer::unmark_less_thans(
+        Sanitizer::drop_boolean_values(output),
+    ))
+}
+
+/// An `<iframe srcdoc>` is a whole other document, which gets sanitized the
+/// same way as the one it's in, unless it's nested too deeply to be.
+fn sanitize_srcdoc(
+    sanitizer: &Sanitizer,
+    el: &mut Element,
+    context: SanitizeContext,
+) -> Result<(), SanitizeError> {
+    if !Tag::is_iframe(Tag::tag_from_element(el)) {
+        return Ok(());
+    }
+    let srcdoc = match el.get_attribute("srcdoc") {
+        None => return Ok(()),
+        Some(srcdoc) => srcdoc,
+    };
+
+    let Some(nested) = context.nested() else {
+        el.remove_attribute("srcdoc");
+        return Ok(());
+    };
+
+    let unescaped = escapist::unescape_html(srcdoc.as_bytes());
+    let unescaped = String::from_utf8_lossy(&unescaped).to_string();
+    let sanitized = sanitize(sanitizer, &[], &unescaped, nested)?;
+
+    let mut buf = String::new();
+    escapist::escape_html(&mut buf, &String::from_utf8_lossy(&sanitized)).unwrap();
+    el.set_attribute("srcdoc", &buf)
+        .map_err(|err| SanitizeError::Rewriting(err.to_string()))
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use proptest::prelude::*;
+
+    use crate::{
+        profiles::{Profile, BASIC, GITHUB, RELAXED, RESTRICTED},
+        sanitizer::Sanitizer,
+    };
+
+    pub(crate) const PROFILES: [&Profile; 4] = [&BASIC, &RESTRICTED, &RELAXED, &GITHUB];
+
+    const ELEMENTS: &[&str] = &[
+        "a",
+        "b",
+        "blink",
+        "blockquote",
+        "code",
+        "custom-element",
+        "div",
+        "em",
+        "font",
+        "i",
+        "li",
+        "marquee",
+        "p",
+        "pre",
+        "span",
+        "strong",
+        "table",
+        "td",
+        "tr",
+        "ul",
+    ];
+    const VOID_ELEMENTS: &[&str] = &["br", "hr", "img"];
+    const ATTRIBUTES: &[&str] = &[
+        r#"class="c""#,
+        r#"href="https://example.com/""#,
+        r#"href="javascript:alert(1)""#,
+        r#"href="/relative""#,
+        r#"onclick="x()""#,
+        r#"src="https://example.com/x.png""#,
+        r#"style="color: red""#,
+        r#"title="t""#,
+    ];
+    const WORDS: &[&str] = &["lorem", "ipsum", "dolor", "sit", "amet"];
+
+    /// Up to two attributes, no two of them with the same name.
+    fn attributes() -> impl Strategy<Value = String> {
+        prop::sample::subsequence(ATTRIBUTES, 0..=2).prop_map(|picked| {
+            let mut names = vec![];
+            let mut attributes = String::new();
+            for attribute in picked {
+                let name = attribute.split('=').next();
+                if !names.contains(&name) {
+                    names.push(name);
+                    attributes.push(' ');
+                    attributes.push_str(attribute);
+                }
+            }
+            attributes
+        })
+    }
+
+    fn words() -> impl Strategy<Value = String> {
+        prop::sample::subsequence(WORDS, 1..=3).prop_map(|words| words.join(" "))
+    }
+
+    /// Well-formed documents, three elements deep at most, like the ones the
+    /// gem's property tests sanitize (but with only text in a `<script>`, which
+    /// is all the parser would find there).
+    pub(crate) fn document() -> impl Strategy<Value = String> {
+        let leaf = prop_oneof![
+            words(),
+            (prop::sample::select(VOID_ELEMENTS), attributes())
+                .prop_map(|(element, attributes)| format!("<{element}{attributes}>")),
+            (attributes(), words())
+                .prop_map(|(attributes, words)| format!("<script{attributes}>{words}</script>")),
+        ];
+        let node = leaf.prop_recursive(3, 64, 4, |inner| {
+            (
+                prop::sample::select(ELEMENTS),
+                attributes(),
+                prop::collection::vec(inner, 1..=4),
+            )
+                .prop_map(|(element, attributes, children)| {
+                    format!("<{element}{attributes}>{}</{element}>", children.concat())
+                })
+        });
+
+        prop::collection::vec(node, 1..=4).prop_map(|nodes| nodes.concat())
+    }
+
+    /// The tags in `html`, as whether each closes an element, and its name.
+    fn tags(html: &str) -> Vec<(bool, String)> {
+        html.split('<')
+            .skip(1)
+            .filter_map(|tag| {
+                let (closing, tag) = match tag.strip_prefix('/') {
+                    Some(tag) => (true, tag),
+                    None => (false, tag),
+                };
+                let name: String = tag
+                    .chars()
+                    .take_while(|c| !c.is_ascii_whitespace() && !matches!(c, '/' | '>'))
+                    .collect();
+                let starts_with_letter = name.starts_with(|c: char| c.is_ascii_alphabetic());
+
+                (starts_with_letter && tag.contains('>')).then(|| (closing, name.to_lowercase()))
+            })
+            .collect()
+    }
+
+    fn balanced(html: &str) -> bool {
+        let mut open = vec![];
+        for (closing, name) in tags(html) {
+            if VOID_ELEMENTS.contains(&name.as_str()) {
+                continue;
+            }
+            if !closing {
+                open.push(name);
+            } else if open.pop() != Some(name) {
+                return false;
+            }
+        }
+
+        open.is_empty()
+    }
+
+    proptest! {
+        #[test]
+        fn sanitizing_is_idempotent(html in document()) {
+            for profile in PROFILES {
+                let sanitizer = Sanitizer::from_profile(profile);
+                let sanitized = sanitizer.sanitize(&html).unwrap();
+
+                prop_assert_eq!(
+                    sanitizer.sanitize(&sanitized).unwrap(),
+                    sanitized.clone(),
+                    "{} changed its own output, from {:?}",
+                    profile.config_name,
+                    html
+                );
+            }
+        }
+
+        #[test]
+        fn only_allowed_elements_are_kept(html in document()) {
+            for profile in PROFILES {
+                let sanitized = Sanitizer::from_profile(profile).sanitize(&html).unwrap();
+
+                for (_, name) in tags(&sanitized) {
+                    prop_assert!(
+                        profile.allows_element(&name),
+                        "{} kept <{name}> in {sanitized:?}, from {html:?}",
+                        profile.config_name
+                    );
+                }
+            }
+        }
+
+        #[test]
+        fn the_output_is_balanced(html in document()) {
+            for profile in PROFILES {
+                let sanitized = Sanitizer::from_profile(profile).sanitize(&html).unwrap();
+
+                prop_assert!(
+                    balanced(&sanitized),
+                    "{} left unbalanced tags in {sanitized:?}, from {html:?}",
+                    profile.config_name
+                );
+            }
+        }
+    }
+}
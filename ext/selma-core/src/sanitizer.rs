@@ -0,0 +1,2445 @@
+//! The allowlist sanitizer: which elements, attributes, classes and protocols
+//! are kept, and everything else a config can ask of it. It's configured in
+//! Rust, whether from one of the `profiles`, from a Ruby hash (by the
+//! extension) or through `ffi`, and `sanitize` runs it over a document.
+
+use std::{
+    borrow::{BorrowMut, Cow},
+    collections::{hash_map::RandomState, HashMap},
+    hash::{BuildHasher, Hasher},
+    sync::OnceLock,
+};
+
+use lol_html::{
+    errors::{AttributeNameError, TagNameError},
+    html_content::{Comment, ContentType, Doctype, Element, EndTag},
+};
+
+use crate::{css::CssPolicy, profiles::Profile, sanitize::SanitizeError};
+
+#[derive(Clone, Debug, Default)]
+struct ElementSanitizer {
+    allowed_attrs: Vec<String>,
+    required_attrs: Vec<String>,
+    allowed_classes: Vec<String>,
+    protocol_sanitizers: HashMap<String, Vec<String>>,
+    added_attrs: Vec<(String, String)>,
+    removed_attrs: Vec<String>,
+    boolean_attrs: Vec<String>,
+}
+
+// Stand-ins for each kind of relative URL, in a list of allowed protocols.
+// None of them could be the name of an actual protocol.
+const FRAGMENT: &str = "#";
+const ROOT_RELATIVE: &str = "/";
+const PATH_RELATIVE: &str = "./";
+const PROTOCOL_RELATIVE: &str = "//";
+// what `:relative` has always allowed of path-relative URLs: those with a `/`
+// or `#` before any `:` (`docs/page`, `page#fn:1`), but not bare ones (`page`)
+const SEPARATED_PATH: &str = ".//";
+
+/// The attributes whose URLs are fetched as soon as the element is shown,
+/// which `strip_remote_content` keeps from being fetched from other hosts.
+const REMOTE_CONTENT_ATTRIBUTES: &[&str] = &["background", "poster", "src", "srcset"];
+
+/// The symbols which can be used in place of a protocol, and the kinds of
+/// relative URL each one allows.
+pub const RELATIVE_URLS: &[(&str, &[&str])] = &[
+    (
+        "relative",
+        &[FRAGMENT, ROOT_RELATIVE, PROTOCOL_RELATIVE, SEPARATED_PATH],
+    ),
+    ("fragment", &[FRAGMENT]),
+    ("root_relative", &[ROOT_RELATIVE]),
+    ("path_relative", &[PATH_RELATIVE]),
+    ("protocol_relative", &[PROTOCOL_RELATIVE]),
+];
+
+/// Why the sanitizer took an element out, as given to `on_removal:`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Removal {
+    /// The element isn't allowed
+    NotAllowed,
+    /// It's a `<noscript>`, which `noscript:` removes or unwraps
+    Noscript,
+    /// It's a `<form>`, which `neutralize_forms:` unwraps
+    Form,
+    /// It's an image which `strip_tracking_pixels:` found to be a tracker
+    TrackingPixel,
+    /// It's an image without a `src`, which `images:` requires
+    MissingSrc,
+    /// It's one of the elements the tag filter escapes, like `<script>`
+    Tagfilter,
+    /// It's a declarative shadow root, which `shadow_roots:` removes or flattens
+    ShadowRoot,
+    /// It's a `<script>` or `<link>` without a valid `integrity`, which
+    /// `integrity:` requires
+    MissingIntegrity,
+    /// It's a `<track>` whose `kind` isn't one of `media:`'s `track_kinds`
+    TrackKind,
+}
+
+impl Removal {
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::NotAllowed => "not_allowed",
+            Self::Noscript => "noscript",
+            Self::Form => "form",
+            Self::TrackingPixel => "tracking_pixel",
+            Self::MissingSrc => "missing_src",
+            Self::Tagfilter => "tagfilter",
+            Self::ShadowRoot => "shadow_root",
+            Self::MissingIntegrity => "missing_integrity",
+            Self::TrackKind => "track_kind",
+        }
+    }
+}
+
+/// What to do with `<noscript>` elements, whose contents are parsed as text
+/// when scripting is enabled but as markup when it isn't.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum NoscriptPolicy {
+    /// Drop the element, along with everything in it
+    Remove,
+    /// Drop the element, but keep its contents, sanitized as markup
+    Unwrap,
+    /// Keep its contents, but escaped, so that they can only ever be text
+    Escape,
+}
+
+/// What to do with the `integrity` and `crossorigin` of the `<script>` and
+/// `<link>` elements which are kept.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum IntegrityPolicy {
+    /// Remove them
+    Strip,
+    /// Remove the elements without a well-formed `integrity`, and make sure the
+    /// rest have a `crossorigin`
+    Require,
+}
+
+/// What to do with declarative shadow roots: `<template>` elements with a
+/// `shadowrootmode`, which browsers attach to the element they're in.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ShadowRoots {
+    /// Drop the element, along with everything in it
+    Remove,
+    /// Drop the element, but keep its contents, as part of the light DOM
+    Flatten,
+    /// Keep the element (and its `shadowroot*` attributes), whether or not
+    /// `<template>` is allowed, with its contents sanitized like the rest
+    Sanitize,
+}
+
+impl ShadowRoots {
+    /// What makes a `<template>` a shadow root, and how it's set up.
+    const ATTRIBUTES: [&'static str; 4] = [
+        "shadowrootmode",
+        "shadowrootdelegatesfocus",
+        "shadowrootclonable",
+        "shadowrootserializable",
+    ];
+}
+
+/// What to do with characters which change how the text around them is
+/// displayed, without being visible themselves (see `is_deceptive_char`).
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum UnicodeFilter {
+    /// Remove them
+    Strip,
+    /// Replace them with a visible stand-in, like `[U+202E]`
+    Escape,
+}
+
+/// What to do with NUL bytes and the other C0 control characters (besides
+/// tabs, newlines, form feeds and carriage returns) in the HTML itself.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ControlCharacters {
+    /// Remove them before the HTML is parsed
+    Strip,
+    /// Refuse to sanitize the HTML at all
+    Reject,
+}
+
+/// What to do with an element that has more attributes than it's allowed.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum AttributeOverflow {
+    /// Drop the attributes after the first `count`, before any are sanitized
+    Drop,
+    /// Remove the element, along with everything in it
+    Remove,
+}
+
+/// What to do with an element which has the same attribute more than once,
+/// like `<a href="/safe" href="javascript:...">`. Browsers only ever see the
+/// first one.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum DuplicateAttributes {
+    /// Drop all but the first, before any are sanitized
+    #[default]
+    Drop,
+    /// Remove the element, along with everything in it
+    Remove,
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct AttributeLimit {
+    pub count: usize,
+    pub overflow: AttributeOverflow,
+}
+
+/// What to do with an attribute whose value is longer than it's allowed to be.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum AttributeValueOverflow {
+    /// Remove the attribute
+    Drop,
+    /// Cut the value short, at a character boundary
+    Truncate,
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct AttributeValueLimit {
+    pub bytes: usize,
+    pub overflow: AttributeValueOverflow,
+}
+
+/// What to do with a `class` that has too many classes in it, or too long
+/// a class.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ClassOverflow {
+    /// Drop the classes which are too long, and those after the first `tokens`
+    Drop,
+    /// Remove the `class` attribute
+    Remove,
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct ClassLimit {
+    pub tokens: Option<usize>,
+    pub token_bytes: Option<usize>,
+    pub overflow: ClassOverflow,
+}
+
+/// Marks the subtrees which trusted code has put in otherwise untrusted HTML,
+/// to be left just as they are: any element whose `attribute` is `token`.
+#[derive(Clone, Debug)]
+pub struct Passthrough {
+    attribute: String,
+    token: String,
+}
+
+/// Attributes which are removed no matter what else allows them, as a defense in
+/// depth against allowlists that are broader than they mean to be.
+#[derive(Clone, Debug, Default)]
+pub struct ParanoidDenylist {
+    attribute_prefixes: Vec<String>,
+    protocols: Vec<String>,
+}
+
+impl ParanoidDenylist {
+    fn denies(&self, attr_name: &str, attr_val: &str) -> bool {
+        let attr_name = attr_name.to_ascii_lowercase();
+        if self
+            .attribute_prefixes
+            .iter()
+            .any(|prefix| attr_name.starts_with(prefix.as_str()))
+        {
+            return true;
+        }
+
+        // browsers ignore whitespace and control characters within a protocol,
+        // so `java\tscript:` is still `javascript:`
+        let protocol = match attr_val.split_once(':') {
+            Some((protocol, _)) => protocol,
+            None => return false,
+        };
+        let protocol = protocol
+            .chars()
+            .filter(|c| !c.is_ascii_whitespace() && !c.is_control())
+            .collect::<String>()
+            .to_ascii_lowercase();
+
+        self.protocols.contains(&protocol)
+    }
+}
+
+/// How kept `<img>` elements are normalized.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct ImagePolicy {
+    /// Whether to set `loading="lazy"` and `decoding="async"`
+    pub lazy: bool,
+    pub max_width: Option<u32>,
+    pub max_height: Option<u32>,
+    /// Whether to drop images which don't have a `src` (once sanitized)
+    pub require_src: bool,
+}
+
+/// How the `media`, `sizes` and `type` of kept `<source>` elements are
+/// checked, so that a `<picture>` (or `<video>`) picks between them the way it
+/// was meant to.
+#[derive(Clone, Debug, Default)]
+pub struct SourcePolicy {
+    /// the MIME types a `type` can have
+    pub types: Vec<String>,
+}
+
+impl SourcePolicy {
+    const ATTRIBUTES: &'static [&'static str] = &["media", "sizes", "type"];
+
+    fn allows(&self, attr_name: &str, attr_val: &str) -> bool {
+        match attr_name {
+            "media" => Self::is_media_query_list(attr_val),
+            "sizes" => Self::is_source_size_list(attr_val),
+            "type" => {
+                // `video/mp4; codecs="avc1.4d002a"` is a `video/mp4`
+                let essence = attr_val.split(';').next().unwrap_or_default().trim();
+                self.types
+                    .iter()
+                    .any(|allowed| allowed.eq_ignore_ascii_case(essence))
+            }
+            _ => false,
+        }
+    }
+
+    /// A (loose) check that a `media` is a list of media queries, like
+    /// `screen and (min-width: 600px)` or `(width >= 40em), print`: words,
+    /// numbers and comparisons, in balanced parentheses, without any functions
+    /// (like `url(...)`) or anything else CSS might make something of.
+    fn is_media_query_list(value: &str) -> bool {
+        value.split(',').all(|query| {
+            let query = query.trim();
+            let mut depth: usize = 0;
+            let mut previous = ' ';
+
+            for c in query.chars() {
+                match c {
+                    '(' if previous.is_ascii_alphanumeric() || previous == '-' => return false,
+                    '(' => depth += 1,
+                    ')' if depth == 0 => return false,
+                    ')' => depth -= 1,
+                    c if c.is_ascii_alphanumeric() || c.is_ascii_whitespace() => {}
+                    '-' | '_' | '.' | ':' | '/' | '<' | '>' | '=' => {}
+                    _ => return false,
+                }
+                previous = c;
+            }
+
+            !query.is_empty() && depth == 0
+        })
+    }
+
+    /// Whether a `sizes` is a list of source sizes, like `(max-width: 600px)
+    /// 100vw, 50vw`: each a length (or `auto`), after a media condition for all
+    /// but the last.
+    fn is_source_size_list(value: &str) -> bool {
+        value.split(',').all(|size| {
+            let size = size.trim();
+            let (condition, length) =
+                match size.rfind(|c: char| c.is_ascii_whitespace() || c == ')') {
+                    Some(index) => (&size[..=index], size[index + 1..].trim()),
+                    None => ("", size),
+                };
+
+            (condition.trim().is_empty() || Self::is_media_query_list(condition))
+                && Self::is_length(length)
+        })
+    }
+
+    fn is_length(value: &str) -> bool {
+        if value == "auto" || value == "0" {
+            return true;
+        }
+
+        let unit_start = value
+            .find(|c: char| !c.is_ascii_digit() && c != '.')
+            .unwrap_or(value.len());
+        let (number, unit) = value.split_at(unit_start);
+
+        !number.is_empty()
+            && number.parse::<f64>().is_ok()
+            && !unit.is_empty()
+            && unit.bytes().all(|b| b.is_ascii_alphabetic())
+    }
+}
+
+/// What's put in place of a removed `<iframe>`, `<embed>` or `<object>`.
+#[derive(Clone, Debug)]
+pub struct EmbedFallback {
+    /// the text of the link to the embedded content
+    pub text: String,
+    /// HTML in which `%{url}`, `%{text}` and `%{link}` are replaced (escaped);
+    /// just the link, without one
+    pub template: Option<String>,
+}
+
+impl EmbedFallback {
+    /// The placeholder for an embed of `url`, which is only linked to if it's
+    /// an `http:` or `https:` URL.
+    fn render(&self, url: Option<String>) -> String {
+        let url = url
+            .map(|url| {
+                let unescaped = escapist::unescape_html(url.trim().as_bytes());
+                String::from_utf8_lossy(&unescaped).to_string()
+            })
+            .filter(|url| {
+                let lowercase = url.to_ascii_lowercase();
+                lowercase.starts_with("https://") || lowercase.starts_with("http://")
+            });
+
+        let mut text = String::new();
+        escapist::escape_html(&mut text, &self.text).unwrap();
+        let href = url
+            .as_deref()
+            .map(Sanitizer::escape_url)
+            .unwrap_or_default();
+        let link = if url.is_some() {
+            format!("<a href=\"{href}\">{text}</a>")
+        } else {
+            text.clone()
+        };
+
+        match &self.template {
+            Some(template) => template
+                .replace("%{url}", &href)
+                .replace("%{text}", &text)
+                .replace("%{link}", &link),
+            None => link,
+        }
+    }
+}
+
+/// How the `sandbox` of a kept `<iframe>` is set.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum SandboxMode {
+    /// Set it to the configured tokens, whatever the `<iframe>` had
+    Replace,
+    /// Keep the tokens the `<iframe>` already had, as long as they're
+    /// configured, so that it can be sandboxed more tightly but never less;
+    /// one without a `sandbox` gets the configured tokens
+    Merge,
+}
+
+/// How kept `<iframe>`s are restricted.
+#[derive(Clone, Debug)]
+pub struct IframePolicy {
+    /// the `sandbox` tokens which are allowed
+    sandbox: Vec<String>,
+    sandbox_mode: SandboxMode,
+    /// the allowed `referrerpolicy` values, the first of which replaces any
+    /// other (or a missing one)
+    referrer_policies: Option<Vec<String>>,
+    /// the permissions policy features which can be in `allow`
+    allow: Option<Vec<String>>,
+}
+
+impl IframePolicy {
+    /// Does the policy manage this attribute, rather than the allowlist?
+    fn manages(&self, attr_name: &str) -> bool {
+        match attr_name {
+            "sandbox" => true,
+            "referrerpolicy" => self.referrer_policies.is_some(),
+            "allow" => self.allow.is_some(),
+            _ => false,
+        }
+    }
+
+    /// The `sandbox` tokens to set, given the ones the `<iframe>` had.
+    fn sandbox(&self, own: Option<String>) -> String {
+        let tokens: Vec<String> = match (self.sandbox_mode, own) {
+            (SandboxMode::Merge, Some(own)) => {
+                let mut tokens: Vec<String> = vec![];
+                for token in own.split_ascii_whitespace() {
+                    let token = token.to_ascii_lowercase();
+                    if self.sandbox.contains(&token) && !tokens.contains(&token) {
+                        tokens.push(token);
+                    }
+                }
+                tokens
+            }
+            _ => self.sandbox.clone(),
+        };
+
+        tokens.join(" ")
+    }
+
+    /// The directives of `allow` whose features are allowed.
+    fn allow(&self, own: &str) -> Option<String> {
+        let features = self.allow.as_ref()?;
+        let directives: Vec<&str> = own
+            .split(';')
+            .map(str::trim)
+            .filter(|directive| {
+                directive
+                    .split_ascii_whitespace()
+                    .next()
+                    .is_some_and(|feature| features.contains(&feature.to_ascii_lowercase()))
+            })
+            .collect();
+
+        if directives.is_empty() {
+            None
+        } else {
+            Some(directives.join("; "))
+        }
+    }
+}
+
+/// How `<audio>`, `<video>` and their `<track>`s are kept.
+#[derive(Clone, Debug, Default)]
+pub struct MediaPolicy {
+    /// the `kind`s of `<track>` which are kept
+    track_kinds: Vec<String>,
+}
+
+impl MediaPolicy {
+    const ELEMENTS: &'static [&'static str] = &["audio", "video", "source", "track"];
+    const ATTRIBUTES: &'static [(&'static str, &'static [&'static str])] = &[
+        (
+            "audio",
+            &["autoplay", "controls", "loop", "muted", "preload", "src"],
+        ),
+        (
+            "video",
+            &[
+                "autoplay",
+                "controls",
+                "height",
+                "loop",
+                "muted",
+                "playsinline",
+                "poster",
+                "preload",
+                "src",
+                "width",
+            ],
+        ),
+        ("source", &["src", "type"]),
+        ("track", &["default", "kind", "label", "src", "srclang"]),
+    ];
+    /// the URLs which are fetched, and which can be `https:` or relative,
+    /// unless `protocols` says otherwise
+    const URLS: &'static [(&'static str, &'static str)] = &[
+        ("audio", "src"),
+        ("video", "src"),
+        ("video", "poster"),
+        ("source", "src"),
+        ("track", "src"),
+    ];
+}
+
+/// How kept tables are normalized.
+#[derive(Clone, Debug, Default)]
+pub struct TablePolicy {
+    /// Whether to wrap `<tr>`s which are directly within a `<table>` in a `<tbody>`
+    pub wrap_rows: bool,
+    /// `colspan` and `rowspan` values above this are removed
+    pub max_span: Option<u32>,
+    /// The class of a `<div>` to wrap tables in, if any
+    pub wrapper_class: Option<String>,
+}
+
+#[derive(Clone, Debug)]
+pub struct Sanitizer {
+    flags: [u8; crate::tags::Tag::TAG_COUNT],
+    custom_flags: HashMap<String, u8>,
+    allowed_attrs: Vec<String>,
+    allowed_classes: Vec<String>,
+    protocol_sanitizers: HashMap<String, Vec<String>>,
+    element_sanitizers: HashMap<String, ElementSanitizer>,
+
+    pub escape_tagfilter: bool,
+    pub allow_comments: bool,
+    pub allow_conditional_comments: bool,
+    pub allow_cdata: bool,
+    pub allow_doctype: bool,
+    pub normalize_entities: bool,
+    pub name_prefix: Option<String>,
+    pub noscript: Option<NoscriptPolicy>,
+    pub shadow_roots: Option<ShadowRoots>,
+    pub integrity: Option<IntegrityPolicy>,
+    pub duplicate_attributes: DuplicateAttributes,
+    pub unicode_filter: Option<UnicodeFilter>,
+    pub control_characters: Option<ControlCharacters>,
+    pub attribute_limit: Option<AttributeLimit>,
+    pub attribute_value_limit: Option<AttributeValueLimit>,
+    pub class_limit: Option<ClassLimit>,
+    pub passthrough: Option<Passthrough>,
+    pub paranoid: Option<ParanoidDenylist>,
+    pub neutralize_forms: bool,
+    pub fragment_links: bool,
+    pub preserve_preformatted_whitespace: bool,
+    pub images: Option<ImagePolicy>,
+    pub sources: Option<SourcePolicy>,
+    pub media: Option<MediaPolicy>,
+    pub iframes: Option<IframePolicy>,
+    pub embed_fallback: Option<EmbedFallback>,
+    pub tables: Option<TablePolicy>,
+    pub tracker_hosts: Option<Vec<String>>,
+    pub normalize_amp: bool,
+    pub css: Option<CssPolicy>,
+    pub strip_remote_content: bool,
+}
+
+impl Default for Sanitizer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Sanitizer {
+    pub const ALLOW: u8 = (1 << 0);
+    // pub const ESCAPE_TAGFILTER: u8 = (1 << 1);
+    pub const REMOVE_CONTENTS: u8 = (1 << 2);
+    pub const WRAP_WHITESPACE: u8 = (1 << 3);
+    pub const VOID: u8 = (1 << 4);
+    pub const RAW_TEXT: u8 = (1 << 5);
+    pub const ESCAPE_CONTENTS: u8 = (1 << 6);
+
+    /// A sanitizer which allows nothing, until it's told otherwise.
+    pub fn new() -> Self {
+        let mut element_sanitizers = HashMap::new();
+        crate::tags::Tag::html_tags().iter().for_each(|html_tag| {
+            let es = ElementSanitizer::default();
+            element_sanitizers.insert(
+                crate::tags::Tag::element_name_from_enum(html_tag).to_string(),
+                es,
+            );
+        });
+
+        Self {
+            flags: [0; crate::tags::Tag::TAG_COUNT],
+            custom_flags: HashMap::new(),
+            allowed_attrs: vec![],
+            allowed_classes: vec![],
+            protocol_sanitizers: HashMap::new(),
+            element_sanitizers,
+
+            escape_tagfilter: true,
+            allow_comments: false,
+            allow_conditional_comments: false,
+            allow_cdata: false,
+            allow_doctype: true,
+            normalize_entities: false,
+            name_prefix: None,
+            noscript: None,
+            shadow_roots: None,
+            integrity: None,
+            duplicate_attributes: DuplicateAttributes::Drop,
+            unicode_filter: None,
+            control_characters: None,
+            attribute_limit: None,
+            attribute_value_limit: None,
+            class_limit: None,
+            passthrough: None,
+            paranoid: None,
+            neutralize_forms: false,
+            fragment_links: false,
+            preserve_preformatted_whitespace: false,
+            images: None,
+            sources: None,
+            media: None,
+            iframes: None,
+            embed_fallback: None,
+            tables: None,
+            tracker_hosts: None,
+            normalize_amp: false,
+            css: None,
+            strip_remote_content: false,
+        }
+    }
+
+    /// A sanitizer which allows what one of the built-in profiles does.
+    pub fn from_profile(profile: &Profile) -> Self {
+        let mut sanitizer = Self::new();
+        sanitizer.apply_profile(profile);
+
+        sanitizer
+    }
+
+    /// Allows what `profile` (and the profile it extends) allows, on top of
+    /// what's already allowed.
+    pub fn apply_profile(&mut self, profile: &Profile) {
+        if let Some(base) = profile.extends {
+            self.apply_profile(base);
+        }
+
+        for element in profile.elements {
+            self.set_flag(element, Self::ALLOW, true);
+        }
+
+        for (element_name, attrs) in profile.attributes {
+            for attr_name in attrs.iter() {
+                self.allow_attribute(element_name, attr_name, true);
+            }
+        }
+
+        for (element_name, attr_name, protocols) in profile.protocols {
+            let protocols = protocols
+                .iter()
+                .flat_map(|protocol| match protocol.strip_prefix(':') {
+                    Some(name) => Self::relative_protocols(name),
+                    None => vec![protocol.to_string()],
+                })
+                .collect();
+            self.add_allowed_protocols(element_name, attr_name, protocols);
+        }
+
+        for element in profile.remove_contents {
+            self.set_flag(element, Self::REMOVE_CONTENTS, true);
+        }
+
+        for element in profile.whitespace_elements {
+            self.set_flag(element, Self::WRAP_WHITESPACE, true);
+        }
+
+        self.allow_comments = profile.allow_comments;
+        self.allow_doctype = profile.allow_doctype;
+        self.set_name_prefix(profile.name_prefix.map(|prefix| prefix.to_string()));
+    }
+
+    /// Toggle a sanitizer option on or off.
+    pub fn set_flag(&mut self, tag_name: &str, flag: u8, set: bool) {
+        let tag_name = tag_name.to_lowercase();
+        let tag = crate::tags::Tag::tag_from_tag_name(tag_name.as_str());
+
+        let flags = if Self::is_custom_tag(tag) {
+            let unknown_flags = self.flags[tag.index];
+            self.custom_flags.entry(tag_name).or_insert(unknown_flags)
+        } else {
+            &mut self.flags[tag.index]
+        };
+
+        if set {
+            *flags |= flag;
+        } else {
+            *flags &= !flag;
+        }
+    }
+
+    /// Toggles all sanitization options on or off.
+    pub fn set_all_flags(&mut self, flag: u8, set: bool) {
+        if set {
+            crate::tags::Tag::html_tags()
+                .iter()
+                .enumerate()
+                .for_each(|(iter, _)| {
+                    self.flags[iter] |= flag;
+                });
+            self.custom_flags
+                .values_mut()
+                .for_each(|flags| *flags |= flag);
+        } else {
+            crate::tags::Tag::html_tags()
+                .iter()
+                .enumerate()
+                .for_each(|(iter, _)| {
+                    self.flags[iter] &= !flag;
+                });
+            self.custom_flags
+                .values_mut()
+                .for_each(|flags| *flags &= !flag);
+        }
+    }
+
+    pub fn escape_tagfilter(&self, e: &mut Element) -> bool {
+        if self.escape_tagfilter {
+            let tag = crate::tags::Tag::tag_from_element(e);
+            if crate::tags::Tag::is_tag_escapeworthy(tag) {
+                e.remove();
+                return true;
+            }
+        }
+
+        false
+    }
+
+    pub fn should_remove_comment(&self, c: &Comment) -> bool {
+        if !self.allow_comments {
+            return true;
+        }
+
+        let text = c.text();
+        (!self.allow_conditional_comments && Self::is_conditional_comment(&text))
+            || (!self.allow_cdata && Self::is_cdata_comment(&text))
+    }
+
+    pub fn remove_comment(&self, c: &mut Comment) {
+        c.remove();
+    }
+
+    /// Matches both the "downlevel-hidden" form (`<!--[if IE]>...<![endif]-->`,
+    /// one comment) and the "downlevel-revealed" one (`<![if !IE]>...<![endif]>`,
+    /// whose markers are each parsed as a comment).
+    fn is_conditional_comment(text: &str) -> bool {
+        let text = text.trim().to_ascii_lowercase();
+
+        text.starts_with("[if") || text.starts_with("[endif") || text.ends_with("<![endif]")
+    }
+
+    /// In HTML content, `<![CDATA[...]]>` is parsed as a comment.
+    fn is_cdata_comment(text: &str) -> bool {
+        text.starts_with("[CDATA[")
+    }
+
+    pub fn remove_doctype(&self, d: &mut Doctype) {
+        d.remove();
+    }
+
+    /// Applies `normalize_entities` and `unicode_filter` to some (raw) text,
+    /// returning `None` when neither of them changes it.
+    pub fn rewrite_text(&self, text: &str) -> Option<String> {
+        if !self.normalize_entities && self.unicode_filter.is_none() {
+            return None;
+        }
+
+        let unescaped = escapist::unescape_html(text.as_bytes());
+        let unescaped = String::from_utf8_lossy(&unescaped);
+        let filtered = self
+            .unicode_filter
+            .and_then(|filter| Self::filter_unicode(filter, &unescaped));
+
+        if filtered.is_none() && !self.normalize_entities {
+            return None;
+        }
+
+        Some(Self::escape_text(filtered.as_deref().unwrap_or(&unescaped)))
+    }
+
+    /// Escapes only what has to be in text: `&`, `<` and `>`. After unescaping,
+    /// this makes `&eacute;`, `&#233;` and `é` all come out as `é`.
+    fn escape_text(text: &str) -> String {
+        let mut normalized = String::with_capacity(text.len());
+        for c in text.chars() {
+            match c {
+                '&' => normalized.push_str("&amp;"),
+                '<' => normalized.push_str("&lt;"),
+                '>' => normalized.push_str("&gt;"),
+                c => normalized.push(c),
+            }
+        }
+
+        normalized
+    }
+
+    /// Escapes a URL for an attribute, changing as little of it as it can. What
+    /// HTML needs escaped in an attribute (`&` and quotes) is, and what can't
+    /// be in a URL at all (whitespace, control characters, `<` and `>`) is
+    /// percent-encoded, but everything else is left as it was written: `%`
+    /// (whether or not it starts an escape), non-ASCII hosts and paths, and
+    /// characters like `[` and `|`, which browsers encode for themselves where
+    /// they need to be.
+    pub fn escape_url(url: &str) -> String {
+        let mut escaped = String::with_capacity(url.len());
+        for c in url.chars() {
+            match c {
+                '&' => escaped.push_str("&amp;"),
+                '\'' => escaped.push_str("&#x27;"),
+                '"' | '<' | '>' | ' ' => escaped.push_str(&format!("%{:02X}", c as u32)),
+                c if c.is_control() => {
+                    let mut bytes = [0; 4];
+                    for byte in c.encode_utf8(&mut bytes).bytes() {
+                        escaped.push_str(&format!("%{byte:02X}"));
+                    }
+                }
+                c => escaped.push(c),
+            }
+        }
+
+        escaped
+    }
+
+    /// Applies the integrity policy to a `<script>` or `<link>` which was kept,
+    /// once its attributes have been sanitized, removing it if it doesn't pass.
+    pub fn enforce_integrity(
+        &self,
+        element: &mut Element,
+    ) -> Result<Option<Removal>, AttributeNameError> {
+        if !crate::tags::Tag::is_subresource(crate::tags::Tag::tag_from_element(element)) {
+            return Ok(None);
+        }
+
+        let policy = match self.integrity {
+            Some(policy) => policy,
+            None => return Ok(None),
+        };
+
+        match policy {
+            IntegrityPolicy::Strip => {
+                element.remove_attribute("integrity");
+                element.remove_attribute("crossorigin");
+            }
+            IntegrityPolicy::Require => {
+                let valid = element
+                    .get_attribute("integrity")
+                    .is_some_and(|integrity| Self::is_valid_integrity(&integrity));
+                if !valid {
+                    element.remove();
+                    return Ok(Some(Removal::MissingIntegrity));
+                }
+
+                // the subresource has to be fetched with CORS to be checked
+                let crossorigin = element.get_attribute("crossorigin");
+                if !crossorigin.is_some_and(|crossorigin| {
+                    crossorigin.is_empty()
+                        || crossorigin.eq_ignore_ascii_case("anonymous")
+                        || crossorigin.eq_ignore_ascii_case("use-credentials")
+                }) {
+                    element.set_attribute("crossorigin", "anonymous")?;
+                }
+            }
+        }
+
+        Ok(None)
+    }
+
+    /// Whether an `integrity` is a list of well-formed SHA-256, SHA-384 or
+    /// SHA-512 hashes, like `sha384-oqVuAfXRKap7fdgcCY5uykM6+R9GqQ8K/uxy9rx7HNQlGYl1kPzQho1wx4JwY8wC`.
+    fn is_valid_integrity(integrity: &str) -> bool {
+        let mut hashes = integrity.split_ascii_whitespace().peekable();
+
+        hashes.peek().is_some()
+            && hashes.all(|hash| {
+                // anything after a `?` is an option, which browsers ignore
+                let hash = hash.split('?').next().unwrap_or_default();
+                let (algorithm, digest) = match hash.split_once('-') {
+                    Some(parts) => parts,
+                    None => return false,
+                };
+                let length = match algorithm.to_ascii_lowercase().as_str() {
+                    "sha256" => 44,
+                    "sha384" => 64,
+                    "sha512" => 88,
+                    _ => return false,
+                };
+
+                let unpadded = digest.trim_end_matches('=');
+                digest.len() == length
+                    && digest.len() - unpadded.len() <= 2
+                    && unpadded
+                        .bytes()
+                        .all(|b| b.is_ascii_alphanumeric() || b == b'+' || b == b'/')
+            })
+    }
+
+    fn is_control_character(c: char) -> bool {
+        c.is_ascii_control() && c != '\x7f' && !matches!(c, '\t' | '\n' | '\x0c' | '\r')
+    }
+
+    /// Deals with the control characters in `html` according to `policy`,
+    /// before it's parsed, failing at the first one if they're rejected.
+    pub fn filter_control_characters(
+        policy: ControlCharacters,
+        html: &str,
+    ) -> Result<Cow<'_, str>, SanitizeError> {
+        let index = match html.find(Self::is_control_character) {
+            Some(index) => index,
+            None => return Ok(Cow::Borrowed(html)),
+        };
+
+        match policy {
+            ControlCharacters::Strip => Ok(Cow::Owned(
+                html.chars()
+                    .filter(|&c| !Self::is_control_character(c))
+                    .collect(),
+            )),
+            ControlCharacters::Reject => Err(SanitizeError::Rewriting(format!(
+                "Failed to sanitize HTML: found a control character (U+{:04X}) at byte {index}",
+                html.as_bytes()[index]
+            ))),
+        }
+    }
+
+    /// Strips or escapes the deceptive characters in `text` (which has already
+    /// been unescaped), returning `None` when there aren't any.
+    pub fn filter_unicode(filter: UnicodeFilter, text: &str) -> Option<String> {
+        if !text.chars().any(Self::is_deceptive_char) {
+            return None;
+        }
+
+        let mut filtered = String::with_capacity(text.len());
+        for c in text.chars() {
+            if !Self::is_deceptive_char(c) {
+                filtered.push(c);
+            } else if filter == UnicodeFilter::Escape {
+                filtered.push_str(&format!("[U+{:04X}]", c as u32));
+            }
+        }
+
+        Some(filtered)
+    }
+
+    /// Bidi embeddings, overrides and isolates, which can make text read
+    /// differently from how it's stored (like `\u202Egnp.exe`), plus invisible
+    /// characters which can hide inside otherwise identical-looking words.
+    /// The zero-width (non-)joiners are left alone, since emoji sequences and
+    /// several scripts depend on them.
+    fn is_deceptive_char(c: char) -> bool {
+        matches!(
+            c,
+            '\u{061C}' // arabic letter mark
+            | '\u{115F}' | '\u{1160}' | '\u{3164}' | '\u{FFA0}' // hangul fillers
+            | '\u{180E}' // mongolian vowel separator
+            | '\u{200B}' // zero-width space
+            | '\u{200E}' | '\u{200F}' // left-to-right and right-to-left marks
+            | '\u{202A}'..='\u{202E}' // embeddings and overrides
+            | '\u{2060}'..='\u{2064}' // word joiner and invisible operators
+            | '\u{2066}'..='\u{2069}' // isolates
+            | '\u{FEFF}' // zero-width no-break space
+        )
+    }
+
+    /// Denies event handler (or other) attributes, and attributes using dangerous
+    /// protocols, after the allowlist has been applied.
+    pub fn set_paranoid(&mut self, attribute_prefixes: Vec<String>, protocols: Vec<String>) {
+        self.paranoid = Some(ParanoidDenylist {
+            attribute_prefixes: attribute_prefixes
+                .iter()
+                .map(|prefix| prefix.to_ascii_lowercase())
+                .collect(),
+            protocols: protocols
+                .iter()
+                .map(|protocol| protocol.to_ascii_lowercase())
+                .collect(),
+        });
+    }
+
+    const FORM_SUBMISSION_ATTRIBUTES: &'static [&'static str] =
+        &["action", "formaction", "method", "formmethod"];
+
+    fn neutralize_form_attributes(
+        &self,
+        element: &mut Element,
+        tag: crate::tags::Tag,
+    ) -> Result<(), AttributeNameError> {
+        if !self.neutralize_forms {
+            return Ok(());
+        }
+
+        for attr_name in Self::FORM_SUBMISSION_ATTRIBUTES {
+            element.remove_attribute(attr_name);
+        }
+
+        if crate::tags::Tag::is_form_control(tag) {
+            element.set_attribute("disabled", "")?;
+        }
+
+        Ok(())
+    }
+
+    /// Makes sure `element` has each attribute only once, before its attributes
+    /// are looked at one by one; returns whether the element was removed.
+    /// Otherwise, every attribute after the first with its name goes, since
+    /// lol_html only ever finds (and sanitizes) the first.
+    fn dedupe_attributes(&self, policy: DuplicateAttributes, element: &mut Element) -> bool {
+        let attributes: Vec<(String, String)> = element
+            .attributes()
+            .iter()
+            .map(|attr| (attr.name(), attr.value()))
+            .collect();
+        let duplicated = attributes
+            .iter()
+            .enumerate()
+            .any(|(index, (name, _))| attributes[..index].iter().any(|(seen, _)| seen == name));
+        if !duplicated {
+            return false;
+        }
+
+        if policy == DuplicateAttributes::Remove {
+            self.force_remove_element(element);
+            return true;
+        }
+
+        // they're all taken out and the first of each put back, to keep them
+        // in the same order
+        for (attr_name, _) in attributes.iter() {
+            element.remove_attribute(attr_name);
+        }
+        for (index, (attr_name, attr_val)) in attributes.iter().enumerate() {
+            if !attributes[..index]
+                .iter()
+                .any(|(seen, _)| seen == attr_name)
+            {
+                // it was a valid name when it was parsed
+                let _ = element.set_attribute(attr_name, attr_val);
+            }
+        }
+
+        false
+    }
+
+    /// Enforces the attribute limit on `element`, before its attributes are
+    /// looked at one by one; returns whether the element was removed.
+    fn limit_attributes(&self, limit: AttributeLimit, element: &mut Element) -> bool {
+        if element.attributes().len() <= limit.count {
+            return false;
+        }
+
+        match limit.overflow {
+            AttributeOverflow::Remove => {
+                self.force_remove_element(element);
+                true
+            }
+            AttributeOverflow::Drop => {
+                let extras: Vec<String> = element.attributes()[limit.count..]
+                    .iter()
+                    .map(|attr| attr.name())
+                    .collect();
+                // from the last one, which keeps each removal cheap
+                for attr_name in extras.iter().rev() {
+                    element.remove_attribute(attr_name);
+                }
+                false
+            }
+        }
+    }
+
+    /// Leaves the subtree of any element whose `attribute` is `token` unsanitized,
+    /// and takes the attribute away.
+    pub fn set_passthrough(&mut self, attribute: String, token: String) {
+        self.passthrough = Some(Passthrough {
+            attribute: attribute.to_ascii_lowercase(),
+            token,
+        });
+    }
+
+    pub fn get_passthrough_attribute(&self) -> Option<String> {
+        self.passthrough
+            .as_ref()
+            .map(|passthrough| passthrough.attribute.clone())
+    }
+
+    pub fn is_passthrough(&self, element: &Element) -> bool {
+        match &self.passthrough {
+            Some(passthrough) => element
+                .get_attribute(&passthrough.attribute)
+                .is_some_and(|token| token == passthrough.token),
+            None => false,
+        }
+    }
+
+    pub fn remove_passthrough_attribute(&self, element: &mut Element) {
+        if let Some(passthrough) = &self.passthrough {
+            element.remove_attribute(&passthrough.attribute);
+        }
+    }
+
+    /// Inserts the embed fallback (if there is one) where a removed `<iframe>`,
+    /// `<embed>` or `<object>` was.
+    pub fn insert_embed_fallback(&self, element: &mut Element) {
+        let tag = crate::tags::Tag::tag_from_element(element);
+        if !crate::tags::Tag::is_embed(tag) {
+            return;
+        }
+
+        if let Some(fallback) = &self.embed_fallback {
+            let url = if tag.index == crate::tags::HTMLTag::OBJECT as usize {
+                element.get_attribute("data")
+            } else {
+                element.get_attribute("src")
+            };
+            element.before(&fallback.render(url), ContentType::Html);
+        }
+    }
+
+    /// Restricts kept `<iframe>`s: their `sandbox` is always set (see
+    /// `SandboxMode`), and their `referrerpolicy` and `allow` are checked
+    /// against `referrer_policies` and `allow`, when they're given.
+    pub fn set_iframe_policy(
+        &mut self,
+        sandbox: Vec<String>,
+        sandbox_mode: SandboxMode,
+        referrer_policies: Option<Vec<String>>,
+        allow: Option<Vec<String>>,
+    ) {
+        let lowercase = |values: Vec<String>| {
+            values
+                .iter()
+                .map(|v| v.trim().to_ascii_lowercase())
+                .collect()
+        };
+
+        self.iframes = Some(IframePolicy {
+            sandbox: lowercase(sandbox),
+            sandbox_mode,
+            referrer_policies: referrer_policies.map(lowercase),
+            allow: allow.map(lowercase),
+        });
+    }
+
+    /// Applies the iframe policy to a kept (and sanitized) `<iframe>`.
+    pub fn enforce_iframe(&self, element: &mut Element) -> Result<(), AttributeNameError> {
+        if !crate::tags::Tag::is_iframe(crate::tags::Tag::tag_from_element(element)) {
+            return Ok(());
+        }
+
+        let policy = match &self.iframes {
+            Some(policy) => policy,
+            None => return Ok(()),
+        };
+
+        element.set_attribute("sandbox", &policy.sandbox(element.get_attribute("sandbox")))?;
+
+        if let Some(referrer_policies) = &policy.referrer_policies {
+            let allowed = element
+                .get_attribute("referrerpolicy")
+                .is_some_and(|own| referrer_policies.contains(&own.trim().to_ascii_lowercase()));
+            if !allowed {
+                match referrer_policies.first() {
+                    Some(referrer_policy) => {
+                        element.set_attribute("referrerpolicy", referrer_policy)?
+                    }
+                    None => element.remove_attribute("referrerpolicy"),
+                }
+            }
+        }
+
+        if let Some(own) = element.get_attribute("allow") {
+            match policy.allow(&own) {
+                Some(allow) => element.set_attribute("allow", &allow)?,
+                None => element.remove_attribute("allow"),
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Allows `<audio>` and `<video>` (along with their `<source>`s and
+    /// `<track>`s, and the attributes they need), with only `track_kinds` of
+    /// `<track>`, and without `autoplay` unless they're `muted`.
+    pub fn set_media_policy(&mut self, track_kinds: Vec<String>) {
+        for element in MediaPolicy::ELEMENTS {
+            self.set_flag(element, Self::ALLOW, true);
+        }
+        for (element_name, attrs) in MediaPolicy::ATTRIBUTES {
+            for attr_name in attrs.iter() {
+                self.allow_attribute(element_name, attr_name, true);
+            }
+        }
+        for (element_name, attr_name) in MediaPolicy::URLS {
+            let configured = {
+                let global = self.protocol_sanitizers.contains_key(*attr_name);
+                let element_sanitizers = &mut self.element_sanitizers;
+                global
+                    || Self::get_element_sanitizer(element_sanitizers, element_name)
+                        .protocol_sanitizers
+                        .contains_key(*attr_name)
+            };
+            if !configured {
+                let mut protocols = vec!["https".to_string()];
+                protocols.extend(Self::relative_protocols("relative"));
+                self.add_allowed_protocols(element_name, attr_name, protocols);
+            }
+        }
+
+        self.media = Some(MediaPolicy {
+            track_kinds: track_kinds
+                .iter()
+                .map(|kind| kind.to_ascii_lowercase())
+                .collect(),
+        });
+    }
+
+    /// Applies the media policy to a kept (and sanitized) `<audio>`, `<video>`
+    /// or `<track>`, returning whether it was removed.
+    pub fn enforce_media(&self, element: &mut Element) -> Option<Removal> {
+        let tag = crate::tags::Tag::tag_from_element(element);
+        let policy = self.media.as_ref()?;
+
+        if crate::tags::Tag::is_media(tag) {
+            // browsers only let media play by itself when it's muted anyway
+            if element.has_attribute("autoplay") && !element.has_attribute("muted") {
+                element.remove_attribute("autoplay");
+            }
+        } else if crate::tags::Tag::is_track(tag) {
+            // a `<track>` without a `kind` is `subtitles`
+            let kind = element
+                .get_attribute("kind")
+                .map_or("subtitles".to_string(), |kind| {
+                    kind.trim().to_ascii_lowercase()
+                });
+            if !policy.track_kinds.contains(&kind) {
+                element.remove();
+                return Some(Removal::TrackKind);
+            }
+        }
+
+        None
+    }
+
+    /// Applies the image policy to a (sanitized) `<img>`, returning whether it
+    /// was removed.
+    pub fn normalize_image(
+        &self,
+        element: &mut Element,
+    ) -> Result<Option<Removal>, AttributeNameError> {
+        if !crate::tags::Tag::is_img(crate::tags::Tag::tag_from_element(element)) {
+            return Ok(None);
+        }
+
+        if self.is_tracking_pixel(element) {
+            element.remove();
+            return Ok(Some(Removal::TrackingPixel));
+        }
+
+        let policy = match self.images {
+            Some(policy) => policy,
+            None => return Ok(None),
+        };
+
+        let has_src = element
+            .get_attribute("src")
+            .is_some_and(|src| !src.trim().is_empty());
+        if policy.require_src && !has_src {
+            element.remove();
+            return Ok(Some(Removal::MissingSrc));
+        }
+
+        if policy.lazy {
+            element.set_attribute("loading", "lazy")?;
+            element.set_attribute("decoding", "async")?;
+        }
+
+        Self::clamp_image_dimensions(element, policy.max_width, policy.max_height)?;
+
+        Ok(None)
+    }
+
+    /// AMP elements and the HTML elements they stand in for. Their attributes
+    /// are mostly the same, and whatever's left is up to the allowlist.
+    const AMP_ELEMENTS: &'static [(&'static str, &'static str)] = &[
+        ("amp-anim", "img"),
+        ("amp-audio", "audio"),
+        ("amp-iframe", "iframe"),
+        ("amp-img", "img"),
+        ("amp-video", "video"),
+    ];
+
+    pub fn normalize_amp_element(&self, element: &mut Element) -> Result<(), TagNameError> {
+        if !self.normalize_amp {
+            return Ok(());
+        }
+
+        let tag_name = element.tag_name().to_ascii_lowercase();
+        let html_name = match Self::AMP_ELEMENTS
+            .iter()
+            .find(|(amp_name, _)| *amp_name == tag_name)
+        {
+            Some((_, html_name)) => *html_name,
+            None => return Ok(()),
+        };
+
+        element.set_tag_name(html_name)?;
+        element.remove_attribute("layout");
+
+        // `<img>` is void, so the fallbacks and placeholders within an
+        // `<amp-img>` have nowhere to go
+        if html_name == "img" {
+            element.set_inner_content("", ContentType::Text);
+            if let Some(end_tag_handlers) = element.end_tag_handlers() {
+                end_tag_handlers.push(Box::new(|end| {
+                    end.remove();
+                    Ok(())
+                }));
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Which hosts (and their subdomains) images are considered trackers from,
+    /// or `None` to keep tracking pixels.
+    pub fn set_strip_tracking_pixels(&mut self, hosts: Option<Vec<String>>) {
+        self.tracker_hosts = hosts.map(|hosts| {
+            hosts
+                .iter()
+                .map(|host| host.trim_matches('.').to_ascii_lowercase())
+                .filter(|host| !host.is_empty())
+                .collect()
+        });
+    }
+
+    /// An image is a tracking pixel if it's (at most) 1x1, or comes from one
+    /// of the tracker hosts.
+    fn is_tracking_pixel(&self, element: &Element) -> bool {
+        let tracker_hosts = match &self.tracker_hosts {
+            Some(hosts) => hosts,
+            None => return false,
+        };
+
+        let dimension = |attr_name: &str| {
+            element
+                .get_attribute(attr_name)
+                .and_then(|size| size.trim().trim_end_matches("px").parse::<u32>().ok())
+        };
+        let tiny = match (dimension("width"), dimension("height")) {
+            (Some(0), _) | (_, Some(0)) => true,
+            (Some(width), Some(height)) => width <= 1 && height <= 1,
+            _ => false,
+        };
+        if tiny {
+            return true;
+        }
+
+        let host = match element
+            .get_attribute("src")
+            .as_deref()
+            .and_then(Self::url_host)
+        {
+            Some(host) => host,
+            None => return false,
+        };
+
+        tracker_hosts.iter().any(|tracker| {
+            host == *tracker
+                || host
+                    .strip_suffix(tracker.as_str())
+                    .is_some_and(|subdomain| subdomain.ends_with('.'))
+        })
+    }
+
+    /// The (lowercased) host of an absolute (or protocol-relative) URL.
+    fn url_host(url: &str) -> Option<String> {
+        let url = url.trim();
+        let after_scheme = match url.find("//") {
+            Some(index)
+                if url[..index]
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || matches!(c, '+' | '-' | '.' | ':')) =>
+            {
+                &url[index + 2..]
+            }
+            _ => return None,
+        };
+
+        let authority = after_scheme
+            .split(['/', '?', '#', '\\'])
+            .next()
+            .unwrap_or_default();
+        let host = authority.rsplit('@').next().unwrap_or_default();
+        let host = host.split(':').next().unwrap_or_default();
+
+        if host.is_empty() {
+            None
+        } else {
+            Some(host.trim_end_matches('.').to_ascii_lowercase())
+        }
+    }
+
+    /// Shrinks `width` and `height` to fit within the maximums, keeping their
+    /// ratio when both are given. Values which aren't plain numbers are removed.
+    fn clamp_image_dimensions(
+        element: &mut Element,
+        max_width: Option<u32>,
+        max_height: Option<u32>,
+    ) -> Result<(), AttributeNameError> {
+        if max_width.is_none() && max_height.is_none() {
+            return Ok(());
+        }
+
+        let mut dimension = |attr_name: &str| -> Option<u32> {
+            let value = element.get_attribute(attr_name)?;
+            match value.trim().parse::<u32>() {
+                Ok(size) => Some(size),
+                Err(_) => {
+                    element.remove_attribute(attr_name);
+                    None
+                }
+            }
+        };
+        let width = dimension("width");
+        let height = dimension("height");
+
+        let scale = |size: Option<u32>, max: Option<u32>| match (size, max) {
+            (Some(size), Some(max)) if size > max && size > 0 => max as f64 / size as f64,
+            _ => 1.0,
+        };
+        let ratio = scale(width, max_width).min(scale(height, max_height));
+        if ratio >= 1.0 {
+            return Ok(());
+        }
+
+        if let Some(width) = width {
+            let width = ((width as f64 * ratio).round() as u32).max(1);
+            element.set_attribute("width", &width.to_string())?;
+        }
+        if let Some(height) = height {
+            let height = ((height as f64 * ratio).round() as u32).max(1);
+            element.set_attribute("height", &height.to_string())?;
+        }
+
+        Ok(())
+    }
+
+    pub fn set_table_policy(
+        &mut self,
+        wrap_rows: bool,
+        max_span: Option<u32>,
+        wrapper_class: Option<String>,
+    ) {
+        self.tables = Some(TablePolicy {
+            wrap_rows,
+            max_span,
+            wrapper_class: wrapper_class.filter(|class| !class.is_empty()),
+        });
+    }
+
+    /// Wraps a `<tr>` within a `<table>` (and not its `<thead>`, `<tbody>` or
+    /// `<tfoot>`) in a `<tbody>` of its own, so that every row has one.
+    pub fn wrap_stray_row(&self, element: &mut Element) {
+        let wrap_rows = self.tables.as_ref().is_some_and(|tables| tables.wrap_rows);
+        if !wrap_rows || element.removed() {
+            return;
+        }
+
+        element.before("<tbody>", ContentType::Html);
+        if let Some(end_tag_handlers) = element.end_tag_handlers() {
+            end_tag_handlers.push(Box::new(|end| {
+                end.after("</tbody>", ContentType::Html);
+                Ok(())
+            }));
+        }
+    }
+
+    /// Removes overly large spans from table cells, and wraps tables in a
+    /// `<div>`, when asked to.
+    pub fn normalize_table(&self, element: &mut Element) {
+        let tables = match &self.tables {
+            Some(tables) => tables,
+            None => return,
+        };
+        if element.removed() {
+            return;
+        }
+
+        let tag = crate::tags::Tag::tag_from_element(element);
+        if crate::tags::Tag::is_table(tag) {
+            if let Some(class) = &tables.wrapper_class {
+                let mut buf = String::new();
+                escapist::escape_html(&mut buf, class).unwrap();
+
+                element.before(&format!("<div class=\"{buf}\">"), ContentType::Html);
+                if let Some(end_tag_handlers) = element.end_tag_handlers() {
+                    end_tag_handlers.push(Box::new(|end| {
+                        end.after("</div>", ContentType::Html);
+                        Ok(())
+                    }));
+                }
+            }
+        } else if let Some(max_span) = tables.max_span {
+            for attr_name in ["colspan", "rowspan"] {
+                let too_big = element.get_attribute(attr_name).is_some_and(|span| {
+                    span.trim()
+                        .parse::<u32>()
+                        .map_or(true, |span| span > max_span)
+                });
+                if too_big {
+                    element.remove_attribute(attr_name);
+                }
+            }
+        }
+    }
+
+    /// The prefix to apply to `id` and `name` attributes, and to fragment links.
+    pub fn set_name_prefix(&mut self, name_prefix: Option<String>) {
+        self.name_prefix = match name_prefix {
+            Some(prefix) if !prefix.is_empty() => Some(prefix),
+            _ => None,
+        };
+    }
+
+    /// Allows (or, with `allow` false, stops allowing) an attribute on an
+    /// element, or on every element, as `"all"`.
+    pub fn allow_attribute(&mut self, element_name: &str, attr_name: &str, allow: bool) {
+        if element_name == "all" {
+            let allowed_attrs = &mut self.allowed_attrs;
+            Self::set_allowed(allowed_attrs, &attr_name.to_string(), allow);
+        } else {
+            let element_sanitizers = &mut self.element_sanitizers;
+            let element_sanitizer = Self::get_element_sanitizer(element_sanitizers, element_name);
+
+            Self::set_allowed(
+                &mut element_sanitizer.allowed_attrs,
+                &attr_name.to_string(),
+                allow,
+            );
+        }
+    }
+
+    /// Allows (or stops allowing) a class on an element, or on every element.
+    pub fn allow_class(&mut self, element_name: &str, class_name: &str, allow: bool) {
+        if element_name == "all" {
+            let allowed_classes = &mut self.allowed_classes;
+            Self::set_allowed(allowed_classes, &class_name.to_string(), allow);
+        } else {
+            let element_sanitizers = &mut self.element_sanitizers;
+            let element_sanitizer = Self::get_element_sanitizer(element_sanitizers, element_name);
+
+            let allowed_classes = element_sanitizer.allowed_classes.borrow_mut();
+            Self::set_allowed(allowed_classes, &class_name.to_string(), allow)
+        }
+    }
+
+    /// What's allowed in place of a protocol by one of the relative URL symbols,
+    /// like `:fragment`.
+    pub fn relative_protocols(name: &str) -> Vec<String> {
+        RELATIVE_URLS
+            .iter()
+            .find(|(relative, _)| *relative == name)
+            .map(|(_, kinds)| kinds.iter().map(|kind| kind.to_string()).collect())
+            .unwrap_or_default()
+    }
+
+    /// Allows each of the protocols (and the kinds of relative URL from
+    /// `relative_protocols`) in an element's attribute.
+    pub fn add_allowed_protocols(
+        &mut self,
+        element_name: &str,
+        attr_name: &str,
+        protocols: Vec<String>,
+    ) {
+        // protocols for "all" elements apply to any element that doesn't have
+        // its own for the attribute
+        let protocol_sanitizers = if element_name == "all" {
+            &mut self.protocol_sanitizers
+        } else {
+            let element_sanitizers = &mut self.element_sanitizers;
+            let element_sanitizer = Self::get_element_sanitizer(element_sanitizers, element_name);
+            &mut element_sanitizer.protocol_sanitizers
+        };
+
+        let protocol_list = protocol_sanitizers
+            .entry(attr_name.to_string())
+            .or_default();
+        for protocol in protocols {
+            if !protocol_list.contains(&protocol) {
+                protocol_list.push(protocol);
+            }
+        }
+    }
+
+    /// Takes protocols out of an attribute's list. The (possibly empty) list is
+    /// kept, so an element's attribute doesn't fall back to the protocols for
+    /// "all" elements once its own are gone.
+    pub fn remove_allowed_protocols(
+        &mut self,
+        element_name: &str,
+        attr_name: &str,
+        protocols: &[String],
+    ) {
+        let protocol_sanitizers = if element_name == "all" {
+            &mut self.protocol_sanitizers
+        } else {
+            let element_sanitizers = &mut self.element_sanitizers;
+            let element_sanitizer = Self::get_element_sanitizer(element_sanitizers, element_name);
+            &mut element_sanitizer.protocol_sanitizers
+        };
+
+        if let Some(protocol_list) = protocol_sanitizers.get_mut(attr_name) {
+            protocol_list.retain(|protocol| !protocols.contains(protocol));
+        }
+    }
+
+    /// Forces an attribute (and its value) onto every kept instance of an element.
+    pub fn add_attribute(&mut self, element_name: &str, attr_name: String, attr_value: String) {
+        let element_sanitizers = &mut self.element_sanitizers;
+        let element_sanitizer = Self::get_element_sanitizer(element_sanitizers, element_name);
+
+        let added_attrs = &mut element_sanitizer.added_attrs;
+        match added_attrs.iter_mut().find(|(name, _)| name == &attr_name) {
+            Some((_, value)) => *value = attr_value,
+            None => added_attrs.push((attr_name, attr_value)),
+        }
+    }
+
+    /// Strips an attribute from an element, even if it's otherwise allowed.
+    pub fn remove_attribute(&mut self, element_name: &str, attr_name: &str, remove: bool) {
+        let element_sanitizers = &mut self.element_sanitizers;
+        let element_sanitizer = Self::get_element_sanitizer(element_sanitizers, element_name);
+
+        Self::set_allowed(
+            &mut element_sanitizer.removed_attrs,
+            &attr_name.to_string(),
+            remove,
+        );
+    }
+
+    /// Treats an attribute of an element as a boolean one, like the standard
+    /// ones (`controls`, `disabled`, `open`, ...), so that its value is dropped
+    /// rather than checked.
+    pub fn set_boolean_attribute(&mut self, element_name: &str, attr_name: &str, boolean: bool) {
+        let element_sanitizers = &mut self.element_sanitizers;
+        let element_sanitizer = Self::get_element_sanitizer(element_sanitizers, element_name);
+
+        Self::set_allowed(
+            &mut element_sanitizer.boolean_attrs,
+            &attr_name.to_lowercase(),
+            boolean,
+        );
+    }
+
+    fn set_allowed(set: &mut Vec<String>, attr_name: &String, allow: bool) {
+        if !allow {
+            set.retain(|x| x != attr_name);
+        } else if !set.contains(attr_name) {
+            set.push(attr_name.to_string());
+        }
+    }
+
+    pub fn sanitize_attributes(&self, element: &mut Element) -> Result<(), AttributeNameError> {
+        let tag = crate::tags::Tag::tag_from_element(element);
+        let tag_name = &element.tag_name();
+        let element_sanitizer = Self::find_element_sanitizer(&self.element_sanitizers, tag_name);
+
+        if self.dedupe_attributes(self.duplicate_attributes, element) {
+            return Ok(());
+        }
+
+        if let Some(limit) = self.attribute_limit {
+            if self.limit_attributes(limit, element) {
+                return Ok(());
+            }
+        }
+
+        // the attributes are copied, since `element` can't be changed while
+        // they're borrowed; they're kept in the order they were written, so
+        // they're sanitized (and come out) in that order
+        let attributes: Vec<(String, String)> = element
+            .attributes()
+            .iter()
+            .map(|a| (a.name(), a.value()))
+            .collect();
+
+        for (attr_name, attr_val) in attributes.iter() {
+            // you can actually embed <!-- ... --> inside
+            // an HTML tag to pass malicious data. If this is
+            // encountered, remove the entire element to be safe.
+            if attr_name.starts_with("<!--") {
+                self.force_remove_element(element);
+                return Ok(());
+            }
+
+            // a boolean attribute is on whatever its value is, so the value
+            // isn't filtered or checked, just dropped. lol_html always writes
+            // one out, so it's given a marker which `drop_boolean_values`
+            // takes out, along with the `=""` around it
+            if Self::is_boolean_attribute(&element_sanitizer, tag, attr_name) {
+                if !self.should_keep_attribute(element, &element_sanitizer, attr_name, "")? {
+                    element.remove_attribute(attr_name);
+                } else {
+                    element.set_attribute(attr_name, Self::boolean_value_marker())?;
+                }
+                continue;
+            }
+
+            // first, trim leading spaces and unescape any encodings
+            let trimmed = attr_val.trim_start();
+            let x = escapist::unescape_html(trimmed.as_bytes());
+            let mut unescaped_attr_val = String::from_utf8_lossy(&x).to_string();
+            let mut filtered = false;
+            if let Some(filter) = self.unicode_filter {
+                if let Some(unicode_filtered) = Self::filter_unicode(filter, &unescaped_attr_val) {
+                    unescaped_attr_val = unicode_filtered;
+                    filtered = true;
+                }
+            }
+
+            if attr_name == "class" {
+                match self.sanitize_class_attribute(&element_sanitizer, &unescaped_attr_val) {
+                    None => {
+                        element.remove_attribute(attr_name);
+                        continue;
+                    }
+                    Some(Cow::Owned(classes)) if classes != unescaped_attr_val => {
+                        unescaped_attr_val = classes;
+                        filtered = true;
+                    }
+                    Some(_) => {}
+                }
+            }
+
+            if attr_name == "style" {
+                if let Some(css) = &self.css {
+                    match css.sanitize(&unescaped_attr_val) {
+                        None => {
+                            element.remove_attribute(attr_name);
+                            continue;
+                        }
+                        Some(style) if style != unescaped_attr_val => {
+                            unescaped_attr_val = style;
+                            filtered = true;
+                        }
+                        Some(_) => {}
+                    }
+                }
+            }
+
+            if let Some(limit) = self.attribute_value_limit {
+                if unescaped_attr_val.len() > limit.bytes {
+                    match limit.overflow {
+                        AttributeValueOverflow::Drop => {
+                            element.remove_attribute(attr_name);
+                            continue;
+                        }
+                        AttributeValueOverflow::Truncate => {
+                            let mut end = limit.bytes;
+                            while !unescaped_attr_val.is_char_boundary(end) {
+                                end -= 1;
+                            }
+                            unescaped_attr_val.truncate(end);
+                            filtered = true;
+                        }
+                    }
+                }
+            }
+
+            let should_keep_attrubute = match self.should_keep_attribute(
+                element,
+                &element_sanitizer,
+                attr_name,
+                &unescaped_attr_val,
+            ) {
+                Ok(should_keep) => should_keep,
+                Err(e) => {
+                    return Err(e);
+                }
+            };
+
+            if !should_keep_attrubute
+                || (self.strip_remote_content
+                    && Self::loads_remote_content(attr_name, &unescaped_attr_val))
+            {
+                element.remove_attribute(attr_name);
+            } else {
+                // Prevent the use of `<meta>` elements that set a charset other than UTF-8,
+                // since output is always UTF-8.
+                if crate::tags::Tag::is_meta(tag) {
+                    if attr_name == "charset" && unescaped_attr_val != "utf-8" {
+                        match element.set_attribute(attr_name, "utf-8") {
+                            Ok(_) => {}
+                            Err(err) => {
+                                return Err(err);
+                            }
+                        }
+                    }
+                } else if !unescaped_attr_val.is_empty() || filtered {
+                    let unescaped_attr_val = self.apply_name_prefix(attr_name, unescaped_attr_val);
+
+                    // ...then, escape any special characters, for security
+                    let buf = if attr_name == "href" {
+                        Self::escape_url(&unescaped_attr_val)
+                    } else {
+                        let mut buf = String::new();
+                        escapist::escape_html(&mut buf, unescaped_attr_val.as_str()).unwrap();
+                        buf
+                    };
+
+                    match element.set_attribute(attr_name, &buf) {
+                        Ok(_) => {}
+                        Err(err) => {
+                            return Err(err);
+                        }
+                    }
+                }
+            }
+        }
+
+        match Self::add_attributes(element, &element_sanitizer) {
+            Ok(_) => {}
+            Err(err) => {
+                return Err(err);
+            }
+        }
+
+        self.neutralize_form_attributes(element, tag)?;
+
+        let required = &element_sanitizer.required_attrs;
+        if required.contains(&"*".to_string()) {
+            return Ok(());
+        }
+        for attr in element.attributes().iter() {
+            let attr_name = &attr.name();
+            if required.contains(attr_name) {
+                return Ok(());
+            }
+        }
+
+        Ok(())
+    }
+
+    /// What the boolean attributes the sanitizer keeps are given as values, so
+    /// that they can be found in its output. No document could guess it.
+    fn boolean_value_marker() -> &'static str {
+        static MARKER: OnceLock<String> = OnceLock::new();
+        MARKER.get_or_init(|| {
+            let nonce = RandomState::new().build_hasher().finish();
+            format!("selma-boolean-{nonce:x}")
+        })
+    }
+
+    /// An attribute's value as it was written, which for a boolean attribute is
+    /// nothing.
+    pub fn unmarked_value(value: String) -> String {
+        if value == Self::boolean_value_marker() {
+            String::new()
+        } else {
+            value
+        }
+    }
+
+    /// Writes the boolean attributes in `html` the sanitizer kept without
+    /// values: `<details open>`, rather than `<details open="">`.
+    pub fn drop_boolean_values(html: Vec<u8>) -> Vec<u8> {
+        let value = format!("=\"{}\"", Self::boolean_value_marker());
+        let value = value.as_bytes();
+        let find = |html: &[u8]| html.windows(value.len()).position(|w| w == value);
+        if find(&html).is_none() {
+            return html;
+        }
+
+        let mut output = Vec::with_capacity(html.len());
+        let mut rest = html.as_slice();
+        while let Some(index) = find(rest) {
+            output.extend_from_slice(&rest[..index]);
+            rest = &rest[index + value.len()..];
+        }
+        output.extend_from_slice(rest);
+        output
+    }
+
+    /// What a `<` at the end of some text is written as until what follows it
+    /// is known, since it starts a tag should the element after it be removed:
+    /// `<<span>img onerror=...>`.
+    pub fn less_than_marker() -> &'static str {
+        static MARKER: OnceLock<String> = OnceLock::new();
+        MARKER.get_or_init(|| {
+            let nonce = RandomState::new().build_hasher().finish();
+            format!("selma-less-than-{nonce:x}")
+        })
+    }
+
+    /// Writes each `<` in `html` marked by `less_than_marker` back: escaped if
+    /// it'd start a tag with what follows it, and left out at the very end, as
+    /// a trailing `<` always has been.
+    pub fn unmark_less_thans(html: Vec<u8>) -> Vec<u8> {
+        let marker = Self::less_than_marker().as_bytes();
+        let find = |html: &[u8]| html.windows(marker.len()).position(|w| w == marker);
+        if find(&html).is_none() {
+            return html;
+        }
+
+        let mut output = Vec::with_capacity(html.len());
+        let mut rest = html.as_slice();
+        while let Some(index) = find(rest) {
+            output.extend_from_slice(&rest[..index]);
+            rest = &rest[index + marker.len()..];
+            match rest.first() {
+                None => {}
+                Some(c) if c.is_ascii_alphabetic() || matches!(c, b'/' | b'!' | b'?') => {
+                    output.extend_from_slice(b"&lt;")
+                }
+                Some(_) => output.push(b'<'),
+            }
+        }
+        output.extend_from_slice(rest);
+        output
+    }
+
+    fn is_boolean_attribute(
+        element_sanitizer: &ElementSanitizer,
+        tag: crate::tags::Tag,
+        attr_name: &String,
+    ) -> bool {
+        crate::tags::Tag::is_boolean_attribute(tag, attr_name)
+            || element_sanitizer.boolean_attrs.contains(attr_name)
+    }
+
+    fn add_attributes(
+        element: &mut Element,
+        element_sanitizer: &ElementSanitizer,
+    ) -> Result<(), AttributeNameError> {
+        for (attr_name, attr_val) in element_sanitizer.added_attrs.iter() {
+            let mut buf = String::new();
+            escapist::escape_html(&mut buf, attr_val.as_str()).unwrap();
+
+            // token lists, like `rel` and `class`, are merged rather than replaced
+            let new_val = match element.get_attribute(attr_name) {
+                Some(existing) if crate::tags::Tag::is_token_list_attribute(attr_name) => {
+                    Self::merge_tokens(&existing, &buf)
+                }
+                _ => buf,
+            };
+
+            match element.set_attribute(attr_name, &new_val) {
+                Ok(_) => {}
+                Err(err) => {
+                    return Err(err);
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Appends the whitespace-separated `tokens` to `existing`, skipping any duplicates.
+    pub fn merge_tokens(existing: &str, tokens: &str) -> String {
+        let mut merged: Vec<&str> = existing.split_whitespace().collect();
+        tokens.split_whitespace().for_each(|token| {
+            if !merged.contains(&token) {
+                merged.push(token);
+            }
+        });
+
+        merged.join(" ")
+    }
+
+    fn should_keep_attribute(
+        &self,
+        element: &mut Element,
+        element_sanitizer: &ElementSanitizer,
+        attr_name: &String,
+        attr_val: &str,
+    ) -> Result<bool, AttributeNameError> {
+        // these are checked, rather than allowed, once there's a policy for them
+        if let Some(sources) = &self.sources {
+            if SourcePolicy::ATTRIBUTES.contains(&attr_name.as_str())
+                && crate::tags::Tag::is_source(crate::tags::Tag::tag_from_element(element))
+            {
+                return Ok(sources.allows(attr_name, attr_val));
+            }
+        }
+
+        // the policy sets (or filters) these itself, once they've been kept
+        if let Some(iframes) = &self.iframes {
+            if iframes.manages(attr_name)
+                && crate::tags::Tag::is_iframe(crate::tags::Tag::tag_from_element(element))
+            {
+                return Ok(true);
+            }
+        }
+
+        if self.shadow_roots == Some(ShadowRoots::Sanitize)
+            && ShadowRoots::ATTRIBUTES.contains(&attr_name.as_str())
+            && crate::tags::Tag::is_template(crate::tags::Tag::tag_from_element(element))
+        {
+            return Ok(attr_name != "shadowrootmode" || matches!(attr_val, "open" | "closed"));
+        }
+
+        let mut allowed: bool = false;
+        let element_allowed_attrs = element_sanitizer.allowed_attrs.contains(attr_name);
+        let sanitizer_allowed_attrs = self.allowed_attrs.contains(attr_name);
+
+        if element_allowed_attrs {
+            allowed = true;
+        }
+
+        if !allowed && sanitizer_allowed_attrs {
+            allowed = true;
+        }
+
+        if !allowed {
+            return Ok(false);
+        }
+
+        // exceptions to the allowlist are evaluated last
+        if element_sanitizer.removed_attrs.contains(attr_name) {
+            return Ok(false);
+        }
+
+        if let Some(paranoid) = &self.paranoid {
+            if paranoid.denies(attr_name, attr_val) {
+                return Ok(false);
+            }
+        }
+
+        // this is markup rather than a URL, and is sanitized as such (by the
+        // rewriter) once it's kept
+        if attr_name == "srcdoc"
+            && crate::tags::Tag::is_iframe(crate::tags::Tag::tag_from_element(element))
+        {
+            return Ok(true);
+        }
+
+        if attr_name == "href"
+            && self.fragment_links
+            && crate::tags::Tag::is_anchor(crate::tags::Tag::tag_from_element(element))
+            && !attr_val.starts_with('#')
+        {
+            return Ok(false);
+        }
+
+        let protocol_sanitizer_values = element_sanitizer
+            .protocol_sanitizers
+            .get(attr_name)
+            .or_else(|| self.protocol_sanitizers.get(attr_name));
+        match protocol_sanitizer_values {
+            None => {
+                // has a protocol, but no sanitization list
+                if !attr_val.is_empty() && Self::has_protocol(attr_val) {
+                    return Ok(false);
+                }
+            }
+            Some(protocol_sanitizer_values) => {
+                if !attr_val.is_empty()
+                    && !Self::has_allowed_protocol(protocol_sanitizer_values, attr_val)
+                {
+                    return Ok(false);
+                }
+            }
+        }
+
+        Ok(true)
+    }
+
+    /// Prefixes `id` and `name` values, as well as the targets of fragment links,
+    /// so that user content can't clobber the ids of the surrounding page.
+    fn apply_name_prefix(&self, attr_name: &str, attr_val: String) -> String {
+        let prefix = match &self.name_prefix {
+            None => return attr_val,
+            Some(prefix) => prefix,
+        };
+
+        if attr_name == "id" || attr_name == "name" {
+            if attr_val.starts_with(prefix.as_str()) {
+                return attr_val;
+            }
+            return format!("{prefix}{attr_val}");
+        }
+
+        if attr_name == "href" {
+            if let Some(fragment) = attr_val.strip_prefix('#') {
+                if fragment.is_empty() || fragment.starts_with(prefix.as_str()) {
+                    return attr_val;
+                }
+                return format!("#{prefix}{fragment}");
+            }
+        }
+
+        attr_val
+    }
+
+    fn has_protocol(attr_val: &str) -> bool {
+        attr_val.contains("://")
+    }
+
+    /// Whether the URL `attr_val` has one of `protocols_allowed`, or is one of
+    /// the kinds of relative URL among them (see `RELATIVE_URLS`).
+    pub fn has_allowed_protocol(protocols_allowed: &[String], attr_val: &str) -> bool {
+        let allows = |kind: &str| protocols_allowed.iter().any(|allowed| allowed == kind);
+
+        let mut chars = attr_val.chars();
+        match (chars.next(), chars.next()) {
+            (Some('#'), _) => return allows(FRAGMENT),
+            // browsers treat backslashes here like slashes
+            (Some('/' | '\\'), Some('/' | '\\')) => return allows(PROTOCOL_RELATIVE),
+            (Some('/' | '\\'), _) => return allows(ROOT_RELATIVE),
+            _ => {}
+        }
+
+        // the protocol ends at the first `:`, unless a `/`, `?` or `#` shows up
+        // first, in which case (or without any of them) this is a relative URL
+        let pos = match attr_val
+            .char_indices()
+            .find(|(_, c)| matches!(c, ':' | '/' | '?' | '#'))
+        {
+            Some((pos, ':')) => pos,
+            _ => {
+                return allows(PATH_RELATIVE)
+                    || Self::is_separated_path(protocols_allowed, attr_val)
+            }
+        };
+
+        // Allow protocol name to be case-insensitive
+        let protocol = attr_val[0..pos].to_lowercase();
+
+        protocols_allowed.contains(&protocol)
+    }
+
+    /// Whether `attr_val` is a URL (or, in a `srcset`, has one) which the
+    /// browser would fetch from another host as soon as `attr_name` is shown:
+    /// an `http:`, `https:` or protocol-relative one.
+    fn loads_remote_content(attr_name: &str, attr_val: &str) -> bool {
+        if !REMOTE_CONTENT_ATTRIBUTES.contains(&attr_name) {
+            return false;
+        }
+
+        let remote = ["http", "https", PROTOCOL_RELATIVE].map(String::from);
+        if attr_name == "srcset" {
+            // each candidate starts after a comma; splitting at every comma
+            // only finds more places a URL could start
+            return attr_val
+                .split(',')
+                .any(|candidate| Self::has_allowed_protocol(&remote, candidate.trim_start()));
+        }
+
+        Self::has_allowed_protocol(&remote, attr_val)
+    }
+
+    fn is_separated_path(protocols_allowed: &[String], attr_val: &str) -> bool {
+        protocols_allowed
+            .iter()
+            .any(|allowed| allowed == SEPARATED_PATH)
+            && attr_val
+                .chars()
+                .find(|c| matches!(c, ':' | '/' | '#'))
+                .is_some_and(|c| c != ':')
+    }
+
+    /// Applies the class limit and the allowed classes to a `class` value,
+    /// returning the classes which are left, or `None` if the attribute should
+    /// be removed.
+    fn sanitize_class_attribute<'v>(
+        &self,
+        element_sanitizer: &ElementSanitizer,
+        attr_val: &'v str,
+    ) -> Option<Cow<'v, str>> {
+        let allowed_global = &self.allowed_classes;
+        let allowed_local = &element_sanitizer.allowed_classes;
+        let unfiltered = allowed_global.is_empty() && allowed_local.is_empty();
+
+        // No class filters, so everything goes through
+        if unfiltered && self.class_limit.is_none() {
+            return Some(Cow::Borrowed(attr_val));
+        }
+
+        let mut valid_classes: Vec<&str> = vec![];
+        for (index, class) in attr_val.split_whitespace().enumerate() {
+            if let Some(limit) = self.class_limit {
+                let too_many = limit.tokens.is_some_and(|tokens| index >= tokens);
+                let too_long = limit.token_bytes.is_some_and(|bytes| class.len() > bytes);
+
+                match limit.overflow {
+                    ClassOverflow::Remove if too_many || too_long => return None,
+                    // the rest aren't even looked at
+                    ClassOverflow::Drop if too_many => break,
+                    ClassOverflow::Drop if too_long => continue,
+                    _ => {}
+                }
+            }
+
+            if unfiltered
+                || allowed_global.iter().any(|allowed| allowed == class)
+                || allowed_local.iter().any(|allowed| allowed == class)
+            {
+                valid_classes.push(class);
+            }
+        }
+
+        if valid_classes.is_empty() && !unfiltered {
+            return None;
+        }
+
+        Some(Cow::Owned(valid_classes.join(" ")))
+    }
+
+    pub fn allow_element(&self, element: &mut Element) -> bool {
+        let flags: u8 = self.get_flags(element);
+
+        (flags & Self::ALLOW) == 0
+    }
+
+    /// Removes the element if it isn't allowed (or a policy says to),
+    /// returning why. `preformatted` is whether it's within a kept `<pre>`,
+    /// `<textarea>` or `<code>`.
+    pub fn try_remove_element(&self, element: &mut Element, preformatted: bool) -> Option<Removal> {
+        let tag = crate::tags::Tag::tag_from_element(element);
+        let mut flags: u8 = self.get_flags(element);
+        if preformatted && self.preserve_preformatted_whitespace {
+            flags &= !Self::WRAP_WHITESPACE;
+        }
+        let self_closing = Self::is_void(tag, flags);
+
+        if crate::tags::Tag::is_noscript(tag) && !element.removed() {
+            let flags = match self.noscript {
+                Some(NoscriptPolicy::Remove) => Some(Self::REMOVE_CONTENTS),
+                Some(NoscriptPolicy::Unwrap) => Some(0),
+                Some(NoscriptPolicy::Escape) | None => None,
+            };
+
+            if let Some(flags) = flags {
+                Self::remove_element(element, self_closing, flags);
+                Self::check_if_end_tag_needs_removal(element, self_closing);
+
+                return Some(Removal::Noscript);
+            }
+        }
+
+        if crate::tags::Tag::is_template(tag)
+            && !element.removed()
+            && element.has_attribute("shadowrootmode")
+        {
+            match self.shadow_roots {
+                Some(ShadowRoots::Remove) => {
+                    Self::remove_element(element, self_closing, Self::REMOVE_CONTENTS);
+                    Self::check_if_end_tag_needs_removal(element, self_closing);
+
+                    return Some(Removal::ShadowRoot);
+                }
+                Some(ShadowRoots::Flatten) => {
+                    Self::remove_element(element, self_closing, 0);
+                    Self::check_if_end_tag_needs_removal(element, self_closing);
+
+                    return Some(Removal::ShadowRoot);
+                }
+                Some(ShadowRoots::Sanitize) => return None,
+                None => {}
+            }
+        }
+
+        if crate::tags::Tag::is_form(tag) && !element.removed() && self.neutralize_forms {
+            Self::remove_element(element, self_closing, flags & Self::WRAP_WHITESPACE);
+            Self::check_if_end_tag_needs_removal(element, self_closing);
+
+            return Some(Removal::Form);
+        }
+
+        let should_remove = !element.removed() && self.allow_element(element);
+
+        if should_remove {
+            if (flags & Self::ESCAPE_CONTENTS) != 0 && crate::tags::Tag::has_raw_text_content(tag) {
+                // the rewriter escapes the text as it comes
+                Self::remove_element(element, self_closing, flags & Self::WRAP_WHITESPACE);
+            } else if crate::tags::Tag::has_text_content(tag)
+                || crate::tags::Tag::is_template(tag)
+                || (flags & Self::RAW_TEXT) != 0
+            {
+                Self::remove_element(element, self_closing, Self::REMOVE_CONTENTS);
+            } else {
+                Self::remove_element(element, self_closing, flags);
+            }
+
+            Self::check_if_end_tag_needs_removal(element, self_closing);
+        } else {
+            // anything in <iframe> must be removed, if it's kept
+            if crate::tags::Tag::is_iframe(tag) {
+                if flags != 0 {
+                    element.set_inner_content(" ", ContentType::Text);
+                } else {
+                    element.set_inner_content("", ContentType::Text);
+                }
+            }
+        }
+
+        should_remove.then_some(Removal::NotAllowed)
+    }
+
+    /// The elements which `escape_contents:` keeps the text of, escaped, when
+    /// they're removed (because they aren't allowed).
+    pub fn get_escaped_content_elements(&self) -> Vec<&'static str> {
+        crate::tags::Tag::html_tags()
+            .iter()
+            .map(crate::tags::Tag::element_name_from_enum)
+            .filter(|name| {
+                let tag = crate::tags::Tag::tag_from_tag_name(name);
+                let flags = self.flags[tag.index];
+
+                crate::tags::Tag::has_raw_text_content(tag)
+                    && (flags & Self::ESCAPE_CONTENTS) != 0
+                    && (flags & Self::ALLOW) == 0
+            })
+            .collect()
+    }
+
+    /// Whether the text of a removed `name` element, which the parser reads as
+    /// text rather than markup (like that of `<title>` or `<xmp>`), has to be
+    /// escaped to be kept: nothing else removes it, or escapes it already.
+    pub fn escapes_text_of(&self, name: &str) -> bool {
+        let tag = crate::tags::Tag::tag_from_tag_name(name);
+        let flags = self.flags[tag.index];
+        let handled = Self::ALLOW | Self::REMOVE_CONTENTS | Self::RAW_TEXT | Self::ESCAPE_CONTENTS;
+
+        (flags & handled) == 0 && (!crate::tags::Tag::is_noscript(tag) || self.noscript.is_none())
+    }
+
+    /// Elements outside of `Tag::html_tags()` all share the `UNKNOWN` tag, so their
+    /// flags are tracked by name. Ones which were never configured fall back to
+    /// whatever was set for `UNKNOWN`.
+    fn get_flags(&self, element: &Element) -> u8 {
+        let tag_name = element.tag_name().to_lowercase();
+        let tag = crate::tags::Tag::tag_from_tag_name(tag_name.as_str());
+
+        if Self::is_custom_tag(tag) {
+            if let Some(flags) = self.custom_flags.get(&tag_name) {
+                return *flags;
+            }
+        }
+
+        self.flags[tag.index]
+    }
+
+    fn is_custom_tag(tag: crate::tags::Tag) -> bool {
+        tag.index == crate::tags::HTMLTag::UNKNOWN as usize
+    }
+
+    /// Whether an element can't have contents (or an end tag), like `<br>`, or
+    /// an element `void_elements` names.
+    fn is_void(tag: crate::tags::Tag, flags: u8) -> bool {
+        tag.self_closing || (flags & Self::VOID) != 0
+    }
+
+    fn remove_element(element: &mut Element, self_closing: bool, flags: u8) {
+        let wrap_whitespace = (flags & Self::WRAP_WHITESPACE) != 0;
+        let remove_contents = (flags & Self::REMOVE_CONTENTS) != 0;
+
+        if remove_contents {
+            element.remove();
+        } else {
+            if wrap_whitespace {
+                if self_closing {
+                    // there's nothing between a void element's start tag and
+                    // where it ends, and lol_html puts what comes `after` a
+                    // custom element after its end tag, which never comes
+                    element.before(" ", ContentType::Text);
+                } else {
+                    element.before(" ", ContentType::Text);
+                    element.after(" ", ContentType::Text);
+                }
+            }
+            element.remove_and_keep_content();
+        }
+    }
+
+    pub fn force_remove_element(&self, element: &mut Element) {
+        let tag = crate::tags::Tag::tag_from_element(element);
+        let self_closing = Self::is_void(tag, self.get_flags(element));
+        Self::remove_element(element, self_closing, Self::REMOVE_CONTENTS);
+        Self::check_if_end_tag_needs_removal(element, self_closing);
+    }
+
+    fn check_if_end_tag_needs_removal(element: &mut Element, self_closing: bool) {
+        if element.removed() && !self_closing {
+            // elements which can't have an end tag have no handlers to push onto
+            if let Some(end_tag_handlers) = element.end_tag_handlers() {
+                end_tag_handlers.push(Box::new(move |end| {
+                    Self::remove_end_tag(end);
+                    Ok(())
+                }));
+            }
+        }
+    }
+
+    fn remove_end_tag(end_tag: &mut EndTag) {
+        end_tag.remove();
+    }
+
+    /// Like `get_element_sanitizer`, but for while sanitizing, when nothing is
+    /// changed: an element that hasn't been configured gets an empty one of its
+    /// own, rather than one being added for it.
+    fn find_element_sanitizer<'a>(
+        element_sanitizers: &'a HashMap<String, ElementSanitizer>,
+        element_name: &str,
+    ) -> Cow<'a, ElementSanitizer> {
+        match element_sanitizers.get(&element_name.to_lowercase()) {
+            Some(element_sanitizer) => Cow::Borrowed(element_sanitizer),
+            None => Cow::Owned(ElementSanitizer::default()),
+        }
+    }
+
+    fn get_element_sanitizer<'a>(
+        element_sanitizers: &'a mut HashMap<String, ElementSanitizer>,
+        element_name: &str,
+    ) -> &'a mut ElementSanitizer {
+        element_sanitizers
+            .entry(element_name.to_lowercase())
+            .or_default()
+    }
+}
@@ -164,11 +164,15 @@ pub enum HTMLTag {
     TT,
     RTC,
     DIALOG,
+    PICTURE,
+    PORTAL,
+    SEARCH,
+    SLOT,
     UNKNOWN,
 }
 
 impl Tag {
-    pub const TAG_COUNT: usize = 151;
+    pub const TAG_COUNT: usize = 155;
 
     /// Identifies whether this is an HTML tag whose contents
     /// are considered "text nodes", and thus, must be removed
@@ -179,16 +183,185 @@ impl Tag {
             || tag.index == HTMLTag::SVG as usize
     }
 
+    /// Is this an element whose contents are raw text, rather than markup, and
+    /// so can be kept as escaped text when the element's removed?
+    pub fn has_raw_text_content(tag: Tag) -> bool {
+        tag.index == HTMLTag::NOEMBED as usize
+            || tag.index == HTMLTag::NOFRAMES as usize
+            || tag.index == HTMLTag::PLAINTEXT as usize
+            || tag.index == HTMLTag::SCRIPT as usize
+            || tag.index == HTMLTag::STYLE as usize
+            || tag.index == HTMLTag::XMP as usize
+    }
+
+    /// Is this tag a `<template>`? Its contents are inert until scripted, so
+    /// they're never worth keeping once the template itself is gone.
+    pub fn is_template(tag: Tag) -> bool {
+        tag.index == HTMLTag::TEMPLATE as usize
+    }
+
     /// Is this tag an `<iframe>`?
     pub fn is_iframe(tag: Tag) -> bool {
         tag.index == HTMLTag::IFRAME as usize
     }
 
+    /// Is this tag a `<noscript>`?
+    pub fn is_noscript(tag: Tag) -> bool {
+        tag.index == HTMLTag::NOSCRIPT as usize
+    }
+
+    /// Is this tag a `<form>`?
+    pub fn is_form(tag: Tag) -> bool {
+        tag.index == HTMLTag::FORM as usize
+    }
+
+    /// Is this tag something which can be filled in or pressed, and disabled?
+    pub fn is_form_control(tag: Tag) -> bool {
+        tag.index == HTMLTag::INPUT as usize
+            || tag.index == HTMLTag::BUTTON as usize
+            || tag.index == HTMLTag::SELECT as usize
+            || tag.index == HTMLTag::TEXTAREA as usize
+    }
+
+    /// Is this tag an `<a>`?
+    pub fn is_anchor(tag: Tag) -> bool {
+        tag.index == HTMLTag::A as usize
+    }
+
+    /// Is this tag a `<script>` or `<link>`, which can load a subresource that
+    /// `integrity` checks?
+    pub fn is_subresource(tag: Tag) -> bool {
+        tag.index == HTMLTag::SCRIPT as usize || tag.index == HTMLTag::LINK as usize
+    }
+
+    /// Is this tag an `<img>`?
+    pub fn is_img(tag: Tag) -> bool {
+        tag.index == HTMLTag::IMG as usize
+    }
+
+    pub fn is_source(tag: Tag) -> bool {
+        tag.index == HTMLTag::SOURCE as usize
+    }
+
+    /// Is this tag an `<iframe>`, `<embed>` or `<object>`?
+    pub fn is_embed(tag: Tag) -> bool {
+        tag.index == HTMLTag::IFRAME as usize
+            || tag.index == HTMLTag::EMBED as usize
+            || tag.index == HTMLTag::OBJECT as usize
+    }
+
+    pub fn is_track(tag: Tag) -> bool {
+        tag.index == HTMLTag::TRACK as usize
+    }
+
+    /// Is this tag an `<audio>` or a `<video>`?
+    pub fn is_media(tag: Tag) -> bool {
+        tag.index == HTMLTag::AUDIO as usize || tag.index == HTMLTag::VIDEO as usize
+    }
+
+    /// Is this tag a `<table>`?
+    pub fn is_table(tag: Tag) -> bool {
+        tag.index == HTMLTag::TABLE as usize
+    }
+
     /// Is this tag a `<meta>`?
     pub fn is_meta(tag: Tag) -> bool {
         tag.index == HTMLTag::META as usize
     }
 
+    /// Is this tag one of `<h1>` through `<h6>`?
+    pub fn is_heading(tag: Tag) -> bool {
+        tag.index >= HTMLTag::H1 as usize && tag.index <= HTMLTag::H6 as usize
+    }
+
+    /// Is this an element which text flows through, like `<em>`, rather than
+    /// one which breaks it up, like `<p>` or `<br>`?
+    pub fn is_phrasing(tag: Tag) -> bool {
+        [
+            HTMLTag::A,
+            HTMLTag::ABBR,
+            HTMLTag::B,
+            HTMLTag::BDI,
+            HTMLTag::BDO,
+            HTMLTag::CITE,
+            HTMLTag::CODE,
+            HTMLTag::DATA,
+            HTMLTag::DEL,
+            HTMLTag::DFN,
+            HTMLTag::EM,
+            HTMLTag::I,
+            HTMLTag::INS,
+            HTMLTag::KBD,
+            HTMLTag::MARK,
+            HTMLTag::Q,
+            HTMLTag::S,
+            HTMLTag::SAMP,
+            HTMLTag::SMALL,
+            HTMLTag::SPAN,
+            HTMLTag::STRONG,
+            HTMLTag::SUB,
+            HTMLTag::SUP,
+            HTMLTag::TIME,
+            HTMLTag::TT,
+            HTMLTag::U,
+            HTMLTag::VAR,
+        ]
+        .iter()
+        .any(|&phrasing| tag.index == phrasing as usize)
+    }
+
+    /// Is this an element which is laid out as a block of its own, so that the
+    /// whitespace around its tags is never rendered?
+    pub fn is_block(tag: Tag) -> bool {
+        [
+            HTMLTag::HTML,
+            HTMLTag::BODY,
+            HTMLTag::ADDRESS,
+            HTMLTag::ARTICLE,
+            HTMLTag::ASIDE,
+            HTMLTag::BLOCKQUOTE,
+            HTMLTag::CAPTION,
+            HTMLTag::DD,
+            HTMLTag::DETAILS,
+            HTMLTag::DIALOG,
+            HTMLTag::DIV,
+            HTMLTag::DL,
+            HTMLTag::DT,
+            HTMLTag::FIELDSET,
+            HTMLTag::FIGCAPTION,
+            HTMLTag::FIGURE,
+            HTMLTag::FOOTER,
+            HTMLTag::FORM,
+            HTMLTag::H1,
+            HTMLTag::H2,
+            HTMLTag::H3,
+            HTMLTag::H4,
+            HTMLTag::H5,
+            HTMLTag::H6,
+            HTMLTag::HEADER,
+            HTMLTag::HGROUP,
+            HTMLTag::HR,
+            HTMLTag::LI,
+            HTMLTag::MAIN,
+            HTMLTag::NAV,
+            HTMLTag::OL,
+            HTMLTag::P,
+            HTMLTag::PRE,
+            HTMLTag::SECTION,
+            HTMLTag::SUMMARY,
+            HTMLTag::TABLE,
+            HTMLTag::TBODY,
+            HTMLTag::TD,
+            HTMLTag::TFOOT,
+            HTMLTag::TH,
+            HTMLTag::THEAD,
+            HTMLTag::TR,
+            HTMLTag::UL,
+        ]
+        .iter()
+        .any(|&block| tag.index == block as usize)
+    }
+
     /// Is this tag something which needs to be removed?
     pub fn is_tag_escapeworthy(tag: Tag) -> bool {
         tag.index == HTMLTag::TITLE as usize
@@ -205,6 +378,53 @@ impl Tag {
             || tag.index == HTMLTag::XMP as usize
     }
 
+    /// Is this attribute's value a whitespace-separated list of tokens?
+    pub fn is_token_list_attribute(attr_name: &str) -> bool {
+        matches!(attr_name, "class" | "rel" | "rev" | "sandbox" | "headers")
+    }
+
+    /// Is this attribute a boolean one on this tag, which is on when it's
+    /// there at all, whatever its value? (`hidden` isn't, since it can be
+    /// `until-found`.)
+    pub fn is_boolean_attribute(tag: Tag, attr_name: &str) -> bool {
+        if matches!(attr_name, "autofocus" | "inert" | "itemscope") {
+            return true;
+        }
+
+        let tags: &[HTMLTag] = match attr_name {
+            "allowfullscreen" => &[HTMLTag::IFRAME],
+            "async" | "defer" | "nomodule" => &[HTMLTag::SCRIPT],
+            "autoplay" | "controls" | "loop" | "muted" => &[HTMLTag::AUDIO, HTMLTag::VIDEO],
+            "playsinline" => &[HTMLTag::VIDEO],
+            "checked" => &[HTMLTag::INPUT],
+            "default" => &[HTMLTag::TRACK],
+            "disabled" => &[
+                HTMLTag::BUTTON,
+                HTMLTag::FIELDSET,
+                HTMLTag::INPUT,
+                HTMLTag::OPTGROUP,
+                HTMLTag::OPTION,
+                HTMLTag::SELECT,
+                HTMLTag::TEXTAREA,
+            ],
+            "formnovalidate" => &[HTMLTag::BUTTON, HTMLTag::INPUT],
+            "ismap" => &[HTMLTag::IMG],
+            "multiple" => &[HTMLTag::INPUT, HTMLTag::SELECT],
+            "novalidate" => &[HTMLTag::FORM],
+            "open" => &[HTMLTag::DETAILS, HTMLTag::DIALOG],
+            "readonly" => &[HTMLTag::INPUT, HTMLTag::TEXTAREA],
+            "required" => &[HTMLTag::INPUT, HTMLTag::SELECT, HTMLTag::TEXTAREA],
+            "reversed" => &[HTMLTag::OL],
+            "selected" => &[HTMLTag::OPTION],
+            "shadowrootclonable" | "shadowrootdelegatesfocus" | "shadowrootserializable" => {
+                &[HTMLTag::TEMPLATE]
+            }
+            _ => &[],
+        };
+
+        tags.iter().any(|&t| tag.index == t as usize)
+    }
+
     pub const ESCAPEWORTHY_TAGS_CSS: &'static str =
         "title, textarea, style, xmp, iframe, noembed, noframes, script, plaintext";
 
@@ -969,9 +1189,29 @@ impl Tag {
                     index: 149,
                     self_closing: false,
                 },
+                "picture" => Tag {
+                    name: "picture",
+                    index: 150,
+                    self_closing: false,
+                },
+                "portal" => Tag {
+                    name: "portal",
+                    index: 151,
+                    self_closing: false,
+                },
+                "search" => Tag {
+                    name: "search",
+                    index: 152,
+                    self_closing: false,
+                },
+                "slot" => Tag {
+                    name: "slot",
+                    index: 153,
+                    self_closing: false,
+                },
                 &_ => Tag {
                     name: "unknown",
-                    index: 150,
+                    index: 154,
                     self_closing: false,
                 },
             }
@@ -1130,6 +1370,10 @@ impl Tag {
             HTMLTag::TT => "tt",
             HTMLTag::RTC => "rtc",
             HTMLTag::DIALOG => "dialog",
+            HTMLTag::PICTURE => "picture",
+            HTMLTag::PORTAL => "portal",
+            HTMLTag::SEARCH => "search",
+            HTMLTag::SLOT => "slot",
             _ => "unknown",
         }
     }
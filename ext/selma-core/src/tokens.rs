@@ -0,0 +1,720 @@
+//! The tokenizer the extension's `minify:`, `pretty:`, `xhtml:` and
+//! `canonical:` options write their output with. They work on the HTML the
+//! rewriter has already written out, since lol_html writes tags out the way it
+//! found them, and can't leave an attribute value unquoted; this splits that
+//! HTML up the way lol_html (and a browser) would, which the tests check.
+
+use crate::tags::Tag;
+
+impl Token<'_> {
+    pub fn is_doctype(raw: &str) -> bool {
+        raw.get(..9)
+            .is_some_and(|start| start.eq_ignore_ascii_case("<!doctype"))
+    }
+
+    /// Whether this is `<!DOCTYPE html>`, however it's written.
+    pub fn is_html_doctype(raw: &str) -> bool {
+        let inside = raw[2..].trim_end_matches('>');
+        let mut words = inside.split_ascii_whitespace();
+
+        words
+            .next()
+            .is_some_and(|word| word.eq_ignore_ascii_case("doctype"))
+            && words
+                .next()
+                .is_some_and(|word| word.eq_ignore_ascii_case("html"))
+            && words.next().is_none()
+    }
+
+    pub fn raw(&self) -> &str {
+        match self {
+            Self::Text(raw) | Self::RawText(raw) | Self::Comment(raw) | Self::Other(raw) => raw,
+            Self::StartTag(tag) => tag.raw,
+            Self::EndTag { raw, .. } => raw,
+        }
+    }
+}
+
+pub enum Token<'h> {
+    Text(&'h str),
+    /// the contents of a `<script>`, `<textarea>` or the like, which aren't markup
+    RawText(&'h str),
+    Comment(&'h str),
+    /// doctypes, CDATA sections (in SVG and MathML) and bogus comments, like
+    /// anything else which starts with `<!`, `<?` or `</` but isn't an end tag;
+    /// or a tag which never ends
+    Other(&'h str),
+    StartTag(StartTag<'h>),
+    EndTag {
+        name: &'h str,
+        raw: &'h str,
+    },
+}
+
+pub struct StartTag<'h> {
+    pub raw: &'h str,
+    pub name: &'h str,
+    pub attributes: Vec<Attribute<'h>>,
+    pub self_closing: bool,
+}
+
+pub struct Attribute<'h> {
+    pub name: &'h str,
+    /// the value, as it was written, and the quote it was written in
+    pub value: Option<(&'h str, Option<char>)>,
+}
+
+impl StartTag<'_> {
+    /// The elements whose whitespace is kept as it is.
+    pub const PREFORMATTED: &'static [&'static str] = &["pre", "listing"];
+
+    pub fn is_block(&self) -> bool {
+        Tag::is_block(Tag::tag_from_tag_name(&self.name.to_ascii_lowercase()))
+    }
+
+    pub fn is_preformatted(&self) -> bool {
+        Self::PREFORMATTED.contains(&self.name.to_ascii_lowercase().as_str())
+    }
+}
+
+/// Splits serialized HTML up into the tokens which matter to the extension's
+/// serializer, much like a browser would, though without ever giving up on any
+/// of it.
+pub struct Tokens<'h> {
+    html: &'h str,
+    position: usize,
+    /// the name of the element whose text (up to its end tag) is raw
+    raw_text: Option<String>,
+    /// the namespaces of the `<svg>`s and `<math>`s that are open, and of the
+    /// HTML within them, which decide whether text is raw and CDATA allowed
+    namespaces: Vec<Namespace>,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum Namespace {
+    Html,
+    Svg,
+    MathMl,
+}
+
+impl<'h> Tokens<'h> {
+    /// Elements whose contents are text, rather than markup, to the tokenizer.
+    const RAW_TEXT: &'static [&'static str] = &[
+        "iframe",
+        "noembed",
+        "noframes",
+        "noscript",
+        "plaintext",
+        "script",
+        "style",
+        "textarea",
+        "title",
+        "xmp",
+    ];
+
+    /// HTML elements which end any SVG or MathML they're in.
+    const FOREIGN_EXITS: &'static [&'static str] = &[
+        "b",
+        "big",
+        "blockquote",
+        "body",
+        "br",
+        "center",
+        "code",
+        "dd",
+        "div",
+        "dl",
+        "dt",
+        "em",
+        "embed",
+        "h1",
+        "h2",
+        "h3",
+        "h4",
+        "h5",
+        "h6",
+        "head",
+        "hr",
+        "i",
+        "img",
+        "li",
+        "listing",
+        "menu",
+        "meta",
+        "nobr",
+        "ol",
+        "p",
+        "pre",
+        "ruby",
+        "s",
+        "small",
+        "span",
+        "strike",
+        "strong",
+        "sub",
+        "sup",
+        "table",
+        "tt",
+        "u",
+        "ul",
+        "var",
+    ];
+
+    pub fn new(html: &'h str) -> Self {
+        Self {
+            html,
+            position: 0,
+            raw_text: None,
+            namespaces: vec![Namespace::Html],
+        }
+    }
+
+    fn namespace(&self) -> Namespace {
+        *self.namespaces.last().unwrap_or(&Namespace::Html)
+    }
+
+    fn take(&mut self, length: usize) -> &'h str {
+        let start = self.position;
+        self.position = (start + length).min(self.html.len());
+        &self.html[start..self.position]
+    }
+
+    fn raw_text(&mut self, name: &str) -> Token<'h> {
+        let rest = &self.html[self.position..];
+        let end = if name == "plaintext" {
+            None
+        } else {
+            // `</script` at the very end is text, rather than an end tag
+            let closing = format!("</{name}");
+            rest.as_bytes()
+                .windows(closing.len())
+                .enumerate()
+                .find(|(index, window)| {
+                    window.eq_ignore_ascii_case(closing.as_bytes())
+                        && rest
+                            .as_bytes()
+                            .get(index + closing.len())
+                            .is_some_and(|&b| b.is_ascii_whitespace() || b == b'/' || b == b'>')
+                })
+                .map(|(index, _)| index)
+        };
+
+        Token::RawText(self.take(end.unwrap_or(rest.len())))
+    }
+
+    fn start_tag(&mut self) -> Token<'h> {
+        let (name, attributes, self_closing, length) = match self.tag(1) {
+            Some(tag) => tag,
+            None => return Token::Other(self.take(self.html.len())),
+        };
+
+        let lowercase_name = name.to_ascii_lowercase();
+        if self.start_namespace(&lowercase_name, &attributes, self_closing)
+            && Self::RAW_TEXT.contains(&lowercase_name.as_str())
+        {
+            self.raw_text = Some(lowercase_name);
+        }
+
+        Token::StartTag(StartTag {
+            raw: self.take(length),
+            name,
+            attributes,
+            self_closing,
+        })
+    }
+
+    /// The name, attributes and whether it closes itself of the tag here, whose
+    /// name starts `from` in, along with its length; or `None` if it never
+    /// ends. End tags have attributes too, as far as the tokenizer's concerned.
+    #[allow(clippy::type_complexity)]
+    fn tag(&self, from: usize) -> Option<(&'h str, Vec<Attribute<'h>>, bool, usize)> {
+        let rest: &'h str = &self.html[self.position..];
+        let bytes = rest.as_bytes();
+        let ends_name = |b: u8| b.is_ascii_whitespace() || b == b'/' || b == b'>';
+
+        let mut i = from;
+        while i < bytes.len() && !ends_name(bytes[i]) {
+            i += 1;
+        }
+        let name = &rest[from..i];
+
+        let mut attributes = vec![];
+        let mut self_closing = false;
+        loop {
+            while i < bytes.len() && (bytes[i].is_ascii_whitespace() || bytes[i] == b'/') {
+                self_closing = bytes[i] == b'/' && bytes.get(i + 1) == Some(&b'>');
+                i += 1;
+            }
+            match bytes.get(i) {
+                None => return None,
+                Some(b'>') => break,
+                Some(_) => {}
+            }
+            self_closing = false;
+
+            let start = i;
+            i += 1;
+            while i < bytes.len() && !ends_name(bytes[i]) && bytes[i] != b'=' {
+                i += 1;
+            }
+            let attribute_name = &rest[start..i];
+
+            let mut j = i;
+            while j < bytes.len() && bytes[j].is_ascii_whitespace() {
+                j += 1;
+            }
+            if bytes.get(j) != Some(&b'=') {
+                attributes.push(Attribute {
+                    name: attribute_name,
+                    value: None,
+                });
+                continue;
+            }
+
+            i = j + 1;
+            while i < bytes.len() && bytes[i].is_ascii_whitespace() {
+                i += 1;
+            }
+            let value = match bytes.get(i) {
+                Some(&quote) if quote == b'"' || quote == b'\'' => {
+                    let end = i + 1 + rest[i + 1..].find(quote as char)?;
+                    let value = &rest[i + 1..end];
+                    i = end + 1;
+                    (value, Some(quote as char))
+                }
+                _ => {
+                    let start = i;
+                    while i < bytes.len() && !bytes[i].is_ascii_whitespace() && bytes[i] != b'>' {
+                        i += 1;
+                    }
+                    (&rest[start..i], None)
+                }
+            };
+            attributes.push(Attribute {
+                name: attribute_name,
+                value: Some(value),
+            });
+        }
+
+        Some((name, attributes, self_closing, i + 1))
+    }
+
+    /// Follows a start tag into (or out of) SVG and MathML, the way lol_html
+    /// does, and so without the tree: even `<svg/>` is never left. Whether the
+    /// tag was in HTML, where its text can be raw.
+    fn start_namespace(
+        &mut self,
+        name: &str,
+        attributes: &[Attribute],
+        self_closing: bool,
+    ) -> bool {
+        let namespace = self.namespace();
+        let is_attribute = |attribute: &Attribute, names: &[&str]| {
+            names
+                .iter()
+                .any(|name| attribute.name.eq_ignore_ascii_case(name))
+        };
+
+        match name {
+            "svg" => self.namespaces.push(Namespace::Svg),
+            "math" => self.namespaces.push(Namespace::MathMl),
+            _ if namespace == Namespace::Html => return true,
+            _ if Self::FOREIGN_EXITS.contains(&name) => {
+                self.namespaces.pop();
+            }
+            "font"
+                if attributes
+                    .iter()
+                    .any(|a| is_attribute(a, &["color", "face", "size"])) =>
+            {
+                self.namespaces.pop();
+            }
+            _ if self_closing => {}
+            _ if Self::is_integration_point(namespace, name) => {
+                self.namespaces.push(Namespace::Html);
+            }
+            "annotation-xml" if namespace == Namespace::MathMl => {
+                let html = attributes.iter().any(|attribute| {
+                    is_attribute(attribute, &["encoding"])
+                        && attribute.value.is_some_and(|(value, _)| {
+                            value.eq_ignore_ascii_case("text/html")
+                                || value.eq_ignore_ascii_case("application/xhtml+xml")
+                        })
+                });
+                if html {
+                    self.namespaces.push(Namespace::Html);
+                }
+            }
+            _ => {}
+        }
+
+        false
+    }
+
+    /// Follows an end tag out of SVG or MathML, or the HTML within them.
+    fn end_namespace(&mut self, name: &str) {
+        let name = name.to_ascii_lowercase();
+        let leaves = match self.namespace() {
+            Namespace::Html => match self.namespaces.len().checked_sub(2) {
+                Some(outer) => {
+                    let outer = self.namespaces[outer];
+                    Self::is_integration_point(outer, &name)
+                        || (outer == Namespace::MathMl && name == "annotation-xml")
+                }
+                None => false,
+            },
+            Namespace::Svg => name == "svg",
+            Namespace::MathMl => name == "math",
+        };
+
+        if leaves {
+            self.namespaces.pop();
+        }
+    }
+
+    /// The SVG and MathML elements whose contents are HTML again (which, for
+    /// `<annotation-xml>`, depends on its `encoding`).
+    fn is_integration_point(namespace: Namespace, name: &str) -> bool {
+        match namespace {
+            Namespace::Svg => ["desc", "foreignobject", "title"].contains(&name),
+            Namespace::MathMl => ["mi", "mn", "mo", "ms", "mtext"].contains(&name),
+            Namespace::Html => false,
+        }
+    }
+
+    fn until(&mut self, from: usize, end: &str) -> &'h str {
+        let rest = &self.html[self.position..];
+        let length = rest
+            .get(from..)
+            .and_then(|after| after.find(end))
+            .map_or(rest.len(), |index| from + index + end.len());
+
+        self.take(length)
+    }
+}
+
+impl<'h> Iterator for Tokens<'h> {
+    type Item = Token<'h>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if let Some(name) = self.raw_text.take() {
+            if self.position < self.html.len() {
+                return Some(self.raw_text(&name));
+            }
+        }
+
+        let rest = &self.html[self.position..];
+        let bytes = rest.as_bytes();
+        let second = *bytes.get(1).unwrap_or(&0);
+        let third = *bytes.get(2).unwrap_or(&0);
+
+        let token = match *bytes.first()? {
+            b'<' if rest.starts_with("<!--") => {
+                // `<!-->` and `<!--->` are comments, too
+                let end = rest[2..].find('>').filter(|&index| {
+                    let inside = &rest[4..(index + 2).max(4)];
+                    inside.is_empty() || inside == "-"
+                });
+                match end {
+                    Some(index) => Token::Comment(self.take(index + 3)),
+                    None => {
+                        // `--!>` ends one, too
+                        let length = ["-->", "--!>"]
+                            .iter()
+                            .filter_map(|end| {
+                                rest[4..].find(end).map(|index| 4 + index + end.len())
+                            })
+                            .min()
+                            .unwrap_or(rest.len());
+                        Token::Comment(self.take(length))
+                    }
+                }
+            }
+            // CDATA sections are only for SVG and MathML; in HTML, they're
+            // bogus comments, which end at the first `>`
+            b'<' if rest.starts_with("<![CDATA[") && self.namespace() != Namespace::Html => {
+                Token::Other(self.until(9, "]]>"))
+            }
+            b'<' if second == b'!' || second == b'?' => Token::Other(self.until(2, ">")),
+            b'<' if second == b'/' && third.is_ascii_alphabetic() => match self.tag(2) {
+                Some((name, _, _, length)) => {
+                    self.end_namespace(name);
+                    Token::EndTag {
+                        name,
+                        raw: self.take(length),
+                    }
+                }
+                None => Token::Other(self.take(rest.len())),
+            },
+            // `</` before anything but a letter starts a bogus comment, like
+            // `<!`, unless it's at the very end
+            b'<' if second == b'/' && rest.len() > 2 => Token::Other(self.until(2, ">")),
+            b'<' if second.is_ascii_alphabetic() => self.start_tag(),
+            first => {
+                // a `<` which doesn't start a tag is just text
+                let from = usize::from(first == b'<');
+                let length = rest[from..]
+                    .find('<')
+                    .map_or(rest.len(), |index| index + from);
+                Token::Text(self.take(length))
+            }
+        };
+
+        Some(token)
+    }
+}
+
+/// Checks `Tokens` against lol_html, for the tests and the `tokens` fuzz
+/// target, by putting what each of them finds into the same form.
+#[cfg(any(test, fuzzing))]
+pub mod check {
+    use std::{cell::RefCell, rc::Rc};
+
+    use lol_html::{doc_comments, doc_text, doctype, element, HtmlRewriter, Settings};
+
+    use super::*;
+
+    /// What a tokenizer found, in a form both `Tokens` and lol_html can be put
+    /// into: names in lowercase, text joined up, and comments without their
+    /// `<!--` and `-->`.
+    #[derive(Debug, PartialEq)]
+    pub enum Event {
+        StartTag {
+            name: String,
+            attributes: Vec<(String, String)>,
+            self_closing: bool,
+        },
+        EndTag(String),
+        Text(String),
+        Comment(String),
+        Doctype,
+    }
+
+    fn push_text(events: &mut Vec<Event>, text: &str) {
+        if text.is_empty() {
+            return;
+        }
+        match events.last_mut() {
+            Some(Event::Text(last)) => last.push_str(text),
+            _ => events.push(Event::Text(text.to_string())),
+        }
+    }
+
+    /// `html`'s tokens, leaving out the end tags which don't close an open
+    /// element, since lol_html only tells an element's handlers about its end.
+    pub fn tokens(html: &str) -> Vec<Event> {
+        let mut events = vec![];
+        let mut open = vec![];
+        let mut tokens = Tokens::new(html);
+        while let Some(token) = tokens.next() {
+            let event = match token {
+                Token::Text(text) | Token::RawText(text) => {
+                    push_text(&mut events, text);
+                    continue;
+                }
+                Token::Comment(raw) => {
+                    let inside = match raw {
+                        "<!-->" | "<!--->" => "",
+                        raw => &raw[4..],
+                    };
+                    // one which never ends leaves out the start of an end
+                    let inside = ["-->", "--!>", "--!", "--", "-"]
+                        .iter()
+                        .find_map(|end| inside.strip_suffix(end))
+                        .unwrap_or(inside);
+                    Event::Comment(inside.to_string())
+                }
+                Token::Other(raw) if Token::is_doctype(raw) => Event::Doctype,
+                // in SVG and MathML, a CDATA section is text
+                Token::Other(raw)
+                    if raw.starts_with("<![CDATA[") && tokens.namespace() != Namespace::Html =>
+                {
+                    push_text(&mut events, raw[9..].trim_end_matches("]]>"));
+                    continue;
+                }
+                // anything else is a bogus comment, without its `<!` (or `<`)
+                // and `>`
+                Token::Other(raw) => {
+                    let inside = raw.strip_suffix('>').unwrap_or(raw);
+                    let second = *inside.as_bytes().get(1).unwrap_or(&0);
+                    let third = *inside.as_bytes().get(2).unwrap_or(&0);
+                    if second.is_ascii_alphabetic()
+                        || (second == b'/' && third.is_ascii_alphabetic())
+                    {
+                        // a tag which never ends is dropped
+                        continue;
+                    }
+
+                    match inside.get(..2) {
+                        // `</>` is nothing at all
+                        Some("</") if inside.len() == 2 => continue,
+                        Some("<!" | "</") => Event::Comment(inside[2..].to_string()),
+                        _ => Event::Comment(inside[1..].to_string()),
+                    }
+                }
+                Token::StartTag(tag) => {
+                    let name = tag.name.to_ascii_lowercase();
+                    if !Tag::tag_from_tag_name(&name).self_closing {
+                        open.push(name.clone());
+                    }
+
+                    Event::StartTag {
+                        name,
+                        attributes: tag
+                            .attributes
+                            .iter()
+                            .map(|attribute| {
+                                let value = attribute.value.map_or("", |(value, _)| value);
+                                (attribute.name.to_ascii_lowercase(), value.to_string())
+                            })
+                            .collect(),
+                        self_closing: tag.self_closing,
+                    }
+                }
+                Token::EndTag { name, .. } => {
+                    let name = name.to_ascii_lowercase();
+                    match open.iter().rposition(|open| *open == name) {
+                        Some(position) => open.truncate(position),
+                        None => continue,
+                    }
+
+                    Event::EndTag(name)
+                }
+            };
+            events.push(event);
+        }
+
+        events
+    }
+
+    /// What lol_html finds in `html`, or `None` if it can't parse it, or would
+    /// parse it differently to a browser: it doesn't treat what's after a
+    /// `<script>` or the like as text when one of its attributes is missing
+    /// its value (`<script a=>`), where `Tokens` and browsers do.
+    pub fn lol_html(html: &str) -> Option<Vec<Event>> {
+        let misread = Tokens::new(html).any(|token| match token {
+            Token::StartTag(tag) => {
+                Tokens::RAW_TEXT.contains(&tag.name.to_ascii_lowercase().as_str())
+                    && tag
+                        .attributes
+                        .iter()
+                        .any(|attribute| attribute.value == Some(("", None)))
+            }
+            _ => false,
+        });
+        if misread {
+            return None;
+        }
+
+        let events = Rc::new(RefCell::new(vec![]));
+
+        let start_tags = Rc::clone(&events);
+        let texts = Rc::clone(&events);
+        let comments = Rc::clone(&events);
+        let doctypes = Rc::clone(&events);
+        let mut rewriter = HtmlRewriter::new(
+            Settings {
+                element_content_handlers: vec![element!("*", move |el| {
+                    start_tags.borrow_mut().push(Event::StartTag {
+                        name: el.tag_name(),
+                        attributes: el
+                            .attributes()
+                            .iter()
+                            .map(|attribute| (attribute.name(), attribute.value()))
+                            .collect(),
+                        self_closing: el.is_self_closing(),
+                    });
+
+                    if let Some(end_tag_handlers) = el.end_tag_handlers() {
+                        let end_tags = Rc::clone(&start_tags);
+                        end_tag_handlers.push(Box::new(move |end| {
+                            end_tags
+                                .borrow_mut()
+                                .push(Event::EndTag(end.name().to_ascii_lowercase()));
+                            Ok(())
+                        }));
+                    }
+                    Ok(())
+                })],
+                document_content_handlers: vec![
+                    doc_text!(move |t| {
+                        push_text(&mut texts.borrow_mut(), t.as_str());
+                        Ok(())
+                    }),
+                    doc_comments!(move |c| {
+                        comments.borrow_mut().push(Event::Comment(c.text()));
+                        Ok(())
+                    }),
+                    doctype!(move |_| {
+                        doctypes.borrow_mut().push(Event::Doctype);
+                        Ok(())
+                    }),
+                ],
+                ..Settings::default()
+            },
+            |_: &[u8]| {},
+        );
+        // markup lol_html can't be sure how to parse is an error
+        rewriter.write(html.as_bytes()).ok()?;
+        rewriter.end().ok()?;
+
+        Rc::try_unwrap(events).ok().map(RefCell::into_inner)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use proptest::prelude::*;
+
+    use super::check::{lol_html, tokens};
+    use crate::{sanitize, sanitizer::Sanitizer};
+
+    #[test]
+    fn it_splits_html_up_like_lol_html() {
+        for html in [
+            r#"<p class=a title='b "c"' hidden>x</p>"#,
+            "<br/><img src=x /><img src=x/>",
+            r#"<script>if (a < b) { "</p>" }</script>"#,
+            "<textarea><b>x</b></textarea><title>a<b</title><style>p > a {}</style>",
+            "<SCRIPT>x</Script ><xmp><p></xmp>",
+            "<title>x</title",
+            "<script>x</script ",
+            "<!-- c --><!----><!-->x<!--->y<!-- d --!>z<!-- e -",
+            "<!DOCTYPE html><?xml x?><!x>",
+            "1 < 2 <3 </ 4 <",
+            "a</>b</",
+            r#"<a href="x>y">z</a>"#,
+            "<plaintext><p></p>",
+            "<svg><style><b>x</b></style></svg>",
+            "<math><title><b>x</b></title></math>",
+            "<p>x</b></p>",
+            "<script/>x</script><textarea/>y</textarea>",
+            "<noscript><p>x</p></noscript>",
+            "<![CDATA[a>b]]><svg><![CDATA[a>b]]></svg>",
+            "<svg><foreignObject><style><b>x</b></style></foreignObject><style><i>y</i></style></svg>",
+            "<svg><p><style><b>x</b></style>",
+            "<svg><font color=red><style><b>x</b></style>",
+            "<svg><desc/><style><b>x</b></style></svg>",
+            "<math><mi><style><b>x</b></style></mi><mtext>y</mtext></math><style><b>z</b></style>",
+            r#"<math><annotation-xml encoding="text/html"><style><b>x</b></style></annotation-xml></math>"#,
+            "<svg/><style><b>x</b></style>",
+            r#"</a title=">">x"#,
+        ] {
+            assert_eq!(Some(tokens(html)), lol_html(html), "{html}");
+        }
+    }
+
+    proptest! {
+        // on the documents the sanitizer's properties are checked with, and
+        // what each profile makes of them
+        #[test]
+        fn it_splits_sanitized_html_up_like_lol_html(html in sanitize::tests::document()) {
+            prop_assert_eq!(Some(tokens(&html)), lol_html(&html));
+
+            for profile in sanitize::tests::PROFILES {
+                let sanitized = Sanitizer::from_profile(profile).sanitize(&html).unwrap();
+                prop_assert_eq!(Some(tokens(&sanitized)), lol_html(&sanitized));
+            }
+        }
+    }
+}
@@ -0,0 +1,91 @@
+use std::cell::Cell;
+
+use crate::tags::Tag;
+
+/// Turns straight quotes into curly ones, `---` and `--` into em and en
+/// dashes, and `...` into an ellipsis, like SmartyPants. Whether a quote opens
+/// or closes depends on what comes before it, which may well be in an earlier
+/// text node (as in `"<em>Hi</em>"`), so the last character seen is kept
+/// until the next block-level element starts.
+pub struct Typographer {
+    last_char: Cell<Option<char>>,
+}
+
+impl Typographer {
+    /// Elements whose text is never touched, since it's code (or meant to be
+    /// typed out) rather than prose.
+    const EXCEPT: [&'static str; 7] = ["code", "kbd", "pre", "samp", "script", "style", "tt"];
+
+    pub fn new() -> Self {
+        Self {
+            last_char: Cell::new(None),
+        }
+    }
+
+    pub fn is_excepted(&self, ancestors: &[String]) -> bool {
+        ancestors
+            .iter()
+            .any(|ancestor| Self::EXCEPT.contains(&ancestor.as_str()))
+    }
+
+    /// Quotes never close across the start of a block-level element.
+    pub fn enter_element(&self, tag_name: &str) {
+        if !Tag::is_phrasing(Tag::tag_from_tag_name(tag_name)) {
+            self.last_char.set(None);
+        }
+    }
+
+    /// Remembers the end of text which is left as it is.
+    pub fn skip(&self, text: &str) {
+        if let Some(c) = text.chars().last() {
+            self.last_char.set(Some(c));
+        }
+    }
+
+    pub fn smarten(&self, text: &str) -> String {
+        let chars: Vec<char> = text.chars().collect();
+        let mut smartened = String::with_capacity(text.len());
+        let mut last_char = self.last_char.get();
+
+        let mut index = 0;
+        while index < chars.len() {
+            let repeats = chars[index..]
+                .iter()
+                .take(3)
+                .take_while(|&&c| c == chars[index])
+                .count();
+
+            let (c, length) = match (chars[index], repeats) {
+                ('-', 3) => ('—', 3),
+                ('-', 2) => ('–', 2),
+                ('.', 3) => ('…', 3),
+                ('"', _) if Self::opens(last_char) => ('“', 1),
+                ('"', _) => ('”', 1),
+                ('\'', _) if Self::opens(last_char) => ('‘', 1),
+                ('\'', _) => ('’', 1),
+                (c, _) => (c, 1),
+            };
+
+            smartened.push(c);
+            last_char = Some(c);
+            index += length;
+        }
+
+        self.last_char.set(last_char);
+        smartened
+    }
+
+    /// Whether a quote after `last_char` opens a quotation.
+    fn opens(last_char: Option<char>) -> bool {
+        match last_char {
+            None => true,
+            Some(c) => c.is_whitespace() || "([{<—–-/“‘".contains(c),
+        }
+    }
+}
+
+impl Default for Typographer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
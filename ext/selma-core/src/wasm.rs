@@ -0,0 +1,31 @@
+//! Lets a WebAssembly host hand strings to the functions in `ffi`: it can't
+//! allocate in the module's memory itself, so it asks for a buffer with
+//! `selma_alloc`, writes a NUL-terminated string into it, and gives it back
+//! with `selma_dealloc` once the call returns. `js/selma_core.js` does this.
+//!
+//! Everything in `ffi` is exported from the WebAssembly build as it is, so
+//! `selma_sanitizer_new`, the `selma_sanitizer_allow_*` functions and
+//! `selma_sanitize` sanitize a document just as the gem would, with the same
+//! config.
+
+/// Allocates `len` bytes, for the host to write into.
+#[no_mangle]
+pub extern "C" fn selma_alloc(len: usize) -> *mut u8 {
+    let mut buffer = Vec::<u8>::with_capacity(len);
+    let ptr = buffer.as_mut_ptr();
+    std::mem::forget(buffer);
+    ptr
+}
+
+/// Frees a buffer from `selma_alloc`.
+///
+/// # Safety
+///
+/// `ptr` must have come from `selma_alloc(len)`, and must not be used (or
+/// freed) again.
+#[no_mangle]
+pub unsafe extern "C" fn selma_dealloc(ptr: *mut u8, len: usize) {
+    if !ptr.is_null() {
+        drop(Vec::from_raw_parts(ptr, 0, len));
+    }
+}
@@ -0,0 +1,21 @@
+// Records which lol_html was built in, for `Selma::NATIVE_INFO`, from the
+// workspace's Cargo.lock (which cargo has resolved by the time this runs).
+fn main() {
+    let lockfile = concat!(env!("CARGO_MANIFEST_DIR"), "/../../Cargo.lock");
+    println!("cargo:rerun-if-changed={lockfile}");
+
+    let version = std::fs::read_to_string(lockfile)
+        .ok()
+        .and_then(|lock| {
+            let mut lines = lock.lines();
+            lines.find(|line| *line == "name = \"lol_html\"")?;
+            lines
+                .next()?
+                .strip_prefix("version = \"")?
+                .strip_suffix('"')
+                .map(str::to_string)
+        })
+        .unwrap_or_else(|| "unknown".to_string());
+
+    println!("cargo:rustc-env=SELMA_LOL_HTML_VERSION={version}");
+}
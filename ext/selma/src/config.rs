@@ -0,0 +1,856 @@
+use magnus::{
+    class, r_hash::ForEach, value::ReprValue, Integer, Module, RArray, RClass, RHash, RString,
+    Symbol, Value,
+};
+
+use selma_core::sanitizer::RELATIVE_URLS;
+
+use crate::errors::config_error;
+
+/// Every key `Selma::Sanitizer#setup` knows how to read.
+const KNOWN_KEYS: &[&str] = &[
+    "add_attributes",
+    "allow_cdata",
+    "allow_comments",
+    "allow_conditional_comments",
+    "allow_doctype",
+    "attributes",
+    "boolean_attributes",
+    "control_characters",
+    "css",
+    "duplicate_attributes",
+    "elements",
+    "embed_fallback",
+    "escape_contents",
+    "escape_tagfilter",
+    "fragment_links",
+    "iframes",
+    "images",
+    "integrity",
+    "max_attribute_value_bytes",
+    "max_attributes",
+    "max_classes",
+    "media",
+    "name_prefix",
+    "neutralize_forms",
+    "normalize_amp",
+    "normalize_entities",
+    "noscript",
+    "paranoid",
+    "passthrough",
+    "preserve_preformatted_whitespace",
+    "protocols",
+    "raw_text_elements",
+    "remove_attributes",
+    "remove_contents",
+    "shadow_roots",
+    "sources",
+    "strip_remote_content",
+    "strip_tracking_pixels",
+    "tables",
+    "unicode_filter",
+    "void_elements",
+    "whitespace_elements",
+];
+
+/// Something in a config which is valid, but which can't have any effect,
+/// like protocols for an attribute that's never kept.
+#[derive(Clone, Debug)]
+pub struct ConfigWarning {
+    pub path: String,
+    pub message: String,
+}
+
+/// Checks a sanitizer config hash, raising `Selma::ConfigError` (naming the
+/// offending key) for anything `Selma::Sanitizer#setup` would otherwise
+/// silently ignore or choke on, and returning a warning for each part of it
+/// which would be ignored but is harmless.
+pub fn validate(config: RHash) -> Result<Vec<ConfigWarning>, magnus::Error> {
+    let mut warnings = vec![];
+
+    for (key, _) in hash_entries(config)? {
+        let known = Symbol::from_value(key)
+            .and_then(|sym| sym.name().ok())
+            .is_some_and(|name| KNOWN_KEYS.contains(&name.as_ref()));
+
+        if !known {
+            return Err(config_error(format!(
+                "config[{}]: unknown key",
+                key.inspect()
+            )));
+        }
+    }
+
+    let elements = match lookup(config, "elements") {
+        None => vec![],
+        Some(value) => element_names(value, "config[:elements]")?,
+    };
+    let is_allowed = |element_name: &str| {
+        elements
+            .iter()
+            .any(|allowed| allowed.eq_ignore_ascii_case(element_name))
+    };
+    // each (element, attribute) pair that's kept, with "all" for any element
+    let mut attributes: Vec<(String, String)> = vec![];
+
+    if let Some(value) = lookup(config, "attributes") {
+        for (element, attrs) in hash_of(value, "config[:attributes]")? {
+            let path = format!("config[:attributes][{}]", element.inspect());
+            let element_name = name_of(element, &path)?;
+
+            if element_name != "all" && !is_allowed(&element_name) {
+                return Err(config_error(format!(
+                    "{path}: attributes given for `{element_name}`, which is not in config[:elements]"
+                )));
+            }
+
+            for (index, attr) in list_of(attrs, &path)?.into_iter().enumerate() {
+                let attr_name = name_of(attr, &format!("{path}[{index}]"))?;
+                attributes.push((element_name.to_lowercase(), attr_name.to_lowercase()));
+            }
+        }
+    }
+
+    if let Some(value) = lookup(config, "add_attributes") {
+        for (element, attrs) in hash_of(value, "config[:add_attributes]")? {
+            let path = format!("config[:add_attributes][{}]", element.inspect());
+            let name = element_name(element, &path)?;
+
+            if !is_allowed(&name) {
+                warnings.push(ConfigWarning {
+                    message: format!(
+                        "attributes added to `{name}`, which is not in config[:elements], so they'll never be seen"
+                    ),
+                    path: path.clone(),
+                });
+            }
+
+            for (attr, attr_value) in hash_of(attrs, &path)? {
+                let path = format!("{path}[{}]", attr.inspect());
+                name_of(attr, &path)?;
+
+                if RString::from_value(attr_value).is_none() {
+                    return Err(config_error(format!(
+                        "{path}: expected a String, got {}",
+                        attr_value.inspect()
+                    )));
+                }
+            }
+        }
+    }
+
+    if let Some(value) = lookup(config, "remove_attributes") {
+        for (element, attrs) in hash_of(value, "config[:remove_attributes]")? {
+            let path = format!("config[:remove_attributes][{}]", element.inspect());
+            element_name(element, &path)?;
+
+            for (index, attr) in list_of(attrs, &path)?.into_iter().enumerate() {
+                name_of(attr, &format!("{path}[{index}]"))?;
+            }
+        }
+    }
+
+    if let Some(value) = lookup(config, "boolean_attributes") {
+        for (element, attrs) in hash_of(value, "config[:boolean_attributes]")? {
+            let path = format!("config[:boolean_attributes][{}]", element.inspect());
+            element_name(element, &path)?;
+
+            for (index, attr) in list_of(attrs, &path)?.into_iter().enumerate() {
+                name_of(attr, &format!("{path}[{index}]"))?;
+            }
+        }
+    }
+
+    if let Some(value) = lookup(config, "protocols") {
+        for (element, attrs) in hash_of(value, "config[:protocols]")? {
+            let path = format!("config[:protocols][{}]", element.inspect());
+            let name = element_name(element, &path)?;
+            let element_allowed = name == "all" || is_allowed(&name);
+
+            if !element_allowed {
+                warnings.push(ConfigWarning {
+                    message: format!(
+                        "protocols given for `{name}`, which is not in config[:elements], so they're ignored"
+                    ),
+                    path: path.clone(),
+                });
+            }
+
+            for (attr, protocols) in hash_of(attrs, &path)? {
+                let path = format!("{path}[{}]", attr.inspect());
+                let attr_name = name_of(attr, &path)?.to_lowercase();
+                let attr_allowed = attributes.iter().any(|(element, attr)| {
+                    (element == "all" || element.eq_ignore_ascii_case(&name)) && *attr == attr_name
+                });
+
+                if element_allowed && !attr_allowed {
+                    warnings.push(ConfigWarning {
+                        message: format!(
+                            "protocols given for `{attr_name}` on `{name}`, which is not in config[:attributes], so they're ignored"
+                        ),
+                        path: path.clone(),
+                    });
+                }
+
+                // a lone protocol is allowed, and is treated as a list of one
+                let protocols = match RArray::from_value(protocols) {
+                    Some(_) => list_of(protocols, &path)?,
+                    None => vec![protocols],
+                };
+
+                for (index, protocol) in protocols.into_iter().enumerate() {
+                    validate_protocol(protocol, &format!("{path}[{index}]"))?;
+                }
+            }
+        }
+    }
+
+    if let Some(value) = lookup(config, "remove_contents") {
+        if !is_boolean(value) {
+            element_names(value, "config[:remove_contents]")?;
+        }
+    }
+
+    if let Some(value) = lookup(config, "escape_contents") {
+        let path = "config[:escape_contents]";
+        for (index, element) in list_of(value, path)?.into_iter().enumerate() {
+            let path = format!("{path}[{index}]");
+            let name = element_name(element, &path)?.to_lowercase();
+            let tag = crate::tags::Tag::tag_from_tag_name(&name);
+
+            if !crate::tags::Tag::has_raw_text_content(tag) {
+                return Err(config_error(format!(
+                    "{path}: `{name}` doesn't contain raw text, so its contents can't be escaped"
+                )));
+            }
+
+            if is_allowed(&name) {
+                warnings.push(ConfigWarning {
+                    message: format!(
+                        "contents of `{name}` escaped, but it's in config[:elements], so it's never removed"
+                    ),
+                    path,
+                });
+            }
+        }
+    }
+
+    for key in ["whitespace_elements", "void_elements", "raw_text_elements"] {
+        if let Some(value) = lookup(config, key) {
+            element_names(value, &format!("config[:{key}]"))?;
+        }
+    }
+
+    for key in [
+        "escape_tagfilter",
+        "allow_comments",
+        "allow_conditional_comments",
+        "allow_cdata",
+        "allow_doctype",
+        "normalize_entities",
+        "neutralize_forms",
+        "normalize_amp",
+        "fragment_links",
+        "preserve_preformatted_whitespace",
+        "strip_remote_content",
+    ] {
+        if let Some(value) = lookup(config, key) {
+            if !is_boolean(value) {
+                return Err(config_error(format!(
+                    "config[:{key}]: expected true or false, got {}",
+                    value.inspect()
+                )));
+            }
+        }
+    }
+
+    if let Some(value) = lookup(config, "name_prefix") {
+        if RString::from_value(value).is_none() {
+            return Err(config_error(format!(
+                "config[:name_prefix]: expected a String, got {}",
+                value.inspect()
+            )));
+        }
+    }
+
+    if let Some(value) = lookup(config, "noscript") {
+        let known = Symbol::from_value(value)
+            .and_then(|sym| sym.name().ok())
+            .is_some_and(|name| NOSCRIPT_POLICIES.contains(&name.as_ref()));
+
+        if !known {
+            return Err(config_error(format!(
+                "config[:noscript]: expected one of :remove, :unwrap or :escape, got {}",
+                value.inspect()
+            )));
+        }
+    }
+
+    if let Some(value) = lookup(config, "integrity") {
+        let known = Symbol::from_value(value)
+            .and_then(|sym| sym.name().ok())
+            .is_some_and(|name| INTEGRITY_POLICIES.contains(&name.as_ref()));
+
+        if !known {
+            return Err(config_error(format!(
+                "config[:integrity]: expected :strip or :require, got {}",
+                value.inspect()
+            )));
+        }
+    }
+
+    if let Some(value) = lookup(config, "duplicate_attributes") {
+        let known = Symbol::from_value(value)
+            .and_then(|sym| sym.name().ok())
+            .is_some_and(|name| DUPLICATE_ATTRIBUTE_POLICIES.contains(&name.as_ref()));
+
+        if !known {
+            return Err(config_error(format!(
+                "config[:duplicate_attributes]: expected :drop or :remove, got {}",
+                value.inspect()
+            )));
+        }
+    }
+
+    if let Some(value) = lookup(config, "shadow_roots") {
+        let known = Symbol::from_value(value)
+            .and_then(|sym| sym.name().ok())
+            .is_some_and(|name| SHADOW_ROOT_POLICIES.contains(&name.as_ref()));
+
+        if !known {
+            return Err(config_error(format!(
+                "config[:shadow_roots]: expected one of :remove, :flatten or :sanitize, got {}",
+                value.inspect()
+            )));
+        }
+    }
+
+    if let Some(value) = lookup(config, "images") {
+        for (key, option) in hash_of(value, "config[:images]")? {
+            let path = format!("config[:images][{}]", key.inspect());
+            let name = Symbol::from_value(key).and_then(|sym| sym.name().ok());
+
+            match name.as_deref() {
+                Some("lazy" | "require_src") => {
+                    if !is_boolean(option) {
+                        return Err(config_error(format!(
+                            "{path}: expected true or false, got {}",
+                            option.inspect()
+                        )));
+                    }
+                }
+                Some("max_width" | "max_height") => {
+                    let positive = Integer::from_value(option)
+                        .and_then(|size| size.to_i64().ok())
+                        .is_some_and(|size| size > 0 && size <= u32::MAX as i64);
+
+                    if !option.is_nil() && !positive {
+                        return Err(config_error(format!(
+                            "{path}: expected a positive Integer, got {}",
+                            option.inspect()
+                        )));
+                    }
+                }
+                _ => return Err(config_error(format!("{path}: unknown key"))),
+            }
+        }
+    }
+
+    if let Some(value) = lookup(config, "sources") {
+        if !is_boolean(value) {
+            for (key, option) in hash_of(value, "config[:sources]")? {
+                let path = format!("config[:sources][{}]", key.inspect());
+                let name = Symbol::from_value(key).and_then(|sym| sym.name().ok());
+
+                match name.as_deref() {
+                    Some("types") => {
+                        for (index, mime_type) in list_of(option, &path)?.into_iter().enumerate() {
+                            let valid = RString::from_value(mime_type)
+                                .and_then(|s| s.to_string().ok())
+                                .is_some_and(|s| {
+                                    s.split_once('/')
+                                        .is_some_and(|(a, b)| !a.is_empty() && !b.is_empty())
+                                });
+
+                            if !valid {
+                                return Err(config_error(format!(
+                                    "{path}[{index}]: expected a MIME type String, got {}",
+                                    mime_type.inspect()
+                                )));
+                            }
+                        }
+                    }
+                    _ => return Err(config_error(format!("{path}: unknown key"))),
+                }
+            }
+        }
+    }
+
+    if let Some(value) = lookup(config, "css") {
+        if !is_boolean(value) {
+            for (key, option) in hash_of(value, "config[:css]")? {
+                let path = format!("config[:css][{}]", key.inspect());
+                let name = Symbol::from_value(key).and_then(|sym| sym.name().ok());
+
+                match name.as_deref() {
+                    Some("properties") => {
+                        for (index, property) in list_of(option, &path)?.into_iter().enumerate() {
+                            name_of(property, &format!("{path}[{index}]"))?;
+                        }
+                    }
+                    _ => return Err(config_error(format!("{path}: unknown key"))),
+                }
+            }
+        }
+    }
+
+    if let Some(value) = lookup(config, "embed_fallback") {
+        if !is_boolean(value) {
+            for (key, option) in hash_of(value, "config[:embed_fallback]")? {
+                let path = format!("config[:embed_fallback][{}]", key.inspect());
+                let name = Symbol::from_value(key).and_then(|sym| sym.name().ok());
+
+                match name.as_deref() {
+                    Some("text" | "template") => {
+                        if RString::from_value(option).is_none() {
+                            return Err(config_error(format!(
+                                "{path}: expected a String, got {}",
+                                option.inspect()
+                            )));
+                        }
+                    }
+                    _ => return Err(config_error(format!("{path}: unknown key"))),
+                }
+            }
+        }
+    }
+
+    if let Some(value) = lookup(config, "iframes") {
+        if !is_boolean(value) {
+            for (key, option) in hash_of(value, "config[:iframes]")? {
+                let path = format!("config[:iframes][{}]", key.inspect());
+                let name = Symbol::from_value(key).and_then(|sym| sym.name().ok());
+
+                match name.as_deref() {
+                    Some("sandbox" | "referrerpolicy" | "allow") => {
+                        for (index, token) in list_of(option, &path)?.into_iter().enumerate() {
+                            if RString::from_value(token).is_none() {
+                                return Err(config_error(format!(
+                                    "{path}[{index}]: expected a String, got {}",
+                                    token.inspect()
+                                )));
+                            }
+                        }
+                    }
+                    Some("sandbox_mode") => {
+                        let known = Symbol::from_value(option)
+                            .and_then(|sym| sym.name().ok())
+                            .is_some_and(|name| SANDBOX_MODES.contains(&name.as_ref()));
+
+                        if !known {
+                            return Err(config_error(format!(
+                                "{path}: expected :replace or :merge, got {}",
+                                option.inspect()
+                            )));
+                        }
+                    }
+                    _ => return Err(config_error(format!("{path}: unknown key"))),
+                }
+            }
+        }
+    }
+
+    if let Some(value) = lookup(config, "media") {
+        if !is_boolean(value) {
+            for (key, option) in hash_of(value, "config[:media]")? {
+                let path = format!("config[:media][{}]", key.inspect());
+                let name = Symbol::from_value(key).and_then(|sym| sym.name().ok());
+
+                match name.as_deref() {
+                    Some("track_kinds") => {
+                        for (index, kind) in list_of(option, &path)?.into_iter().enumerate() {
+                            if RString::from_value(kind).is_none() {
+                                return Err(config_error(format!(
+                                    "{path}[{index}]: expected a String, got {}",
+                                    kind.inspect()
+                                )));
+                            }
+                        }
+                    }
+                    _ => return Err(config_error(format!("{path}: unknown key"))),
+                }
+            }
+        }
+    }
+
+    if let Some(value) = lookup(config, "strip_tracking_pixels") {
+        if !is_boolean(value) {
+            for (index, host) in list_of(value, "config[:strip_tracking_pixels]")?
+                .into_iter()
+                .enumerate()
+            {
+                if RString::from_value(host).is_none() {
+                    return Err(config_error(format!(
+                        "config[:strip_tracking_pixels][{index}]: expected a host String, got {}",
+                        host.inspect()
+                    )));
+                }
+            }
+        }
+    }
+
+    if let Some(value) = lookup(config, "passthrough") {
+        let mut keys = vec![];
+        for (key, option) in hash_of(value, "config[:passthrough]")? {
+            let path = format!("config[:passthrough][{}]", key.inspect());
+            let name = Symbol::from_value(key).and_then(|sym| sym.name().ok());
+
+            match name.as_deref() {
+                Some("attribute") => {
+                    let attribute = name_of(option, &path)?;
+                    if !is_valid_attribute_name(&attribute) {
+                        return Err(config_error(format!(
+                            "{path}: `{attribute}` is not a valid attribute name"
+                        )));
+                    }
+                }
+                Some("token") => {
+                    if !RString::from_value(option).is_some_and(|token| !token.is_empty()) {
+                        return Err(config_error(format!(
+                            "{path}: expected a non-empty String, got {}",
+                            option.inspect()
+                        )));
+                    }
+                }
+                _ => return Err(config_error(format!("{path}: unknown key"))),
+            }
+            keys.extend(name);
+        }
+
+        for required in ["attribute", "token"] {
+            if !keys.iter().any(|key| key == required) {
+                return Err(config_error(format!(
+                    "config[:passthrough][:{required}]: is required"
+                )));
+            }
+        }
+    }
+
+    if let Some(value) = lookup(config, "tables") {
+        for (key, option) in hash_of(value, "config[:tables]")? {
+            let path = format!("config[:tables][{}]", key.inspect());
+            let name = Symbol::from_value(key).and_then(|sym| sym.name().ok());
+
+            match name.as_deref() {
+                Some("wrap_rows") => {
+                    if !is_boolean(option) {
+                        return Err(config_error(format!(
+                            "{path}: expected true or false, got {}",
+                            option.inspect()
+                        )));
+                    }
+                }
+                Some("max_span") => {
+                    let positive = Integer::from_value(option)
+                        .and_then(|size| size.to_i64().ok())
+                        .is_some_and(|size| size > 0 && size <= u32::MAX as i64);
+
+                    if !option.is_nil() && !positive {
+                        return Err(config_error(format!(
+                            "{path}: expected a positive Integer, got {}",
+                            option.inspect()
+                        )));
+                    }
+                }
+                Some("wrapper_class") => {
+                    if !option.is_nil() && RString::from_value(option).is_none() {
+                        return Err(config_error(format!(
+                            "{path}: expected a String, got {}",
+                            option.inspect()
+                        )));
+                    }
+                }
+                _ => return Err(config_error(format!("{path}: unknown key"))),
+            }
+        }
+    }
+
+    if let Some(value) = lookup(config, "paranoid") {
+        if !is_boolean(value) {
+            for (key, list) in hash_of(value, "config[:paranoid]")? {
+                let path = format!("config[:paranoid][{}]", key.inspect());
+                let known = Symbol::from_value(key)
+                    .and_then(|sym| sym.name().ok())
+                    .is_some_and(|name| {
+                        matches!(name.as_ref(), "attribute_prefixes" | "protocols")
+                    });
+
+                if !known {
+                    return Err(config_error(format!("{path}: unknown key")));
+                }
+
+                for (index, item) in list_of(list, &path)?.into_iter().enumerate() {
+                    if RString::from_value(item).is_none() {
+                        return Err(config_error(format!(
+                            "{path}[{index}]: expected a String, got {}",
+                            item.inspect()
+                        )));
+                    }
+                }
+            }
+        }
+    }
+
+    if let Some(value) = lookup(config, "unicode_filter") {
+        let known = Symbol::from_value(value)
+            .and_then(|sym| sym.name().ok())
+            .is_some_and(|name| UNICODE_FILTERS.contains(&name.as_ref()));
+
+        if !known {
+            return Err(config_error(format!(
+                "config[:unicode_filter]: expected :strip or :escape, got {}",
+                value.inspect()
+            )));
+        }
+    }
+
+    if let Some(value) = lookup(config, "max_attributes") {
+        validate_limit(
+            value,
+            "config[:max_attributes]",
+            &["count"],
+            ATTRIBUTE_OVERFLOWS,
+        )?;
+    }
+
+    if let Some(value) = lookup(config, "max_attribute_value_bytes") {
+        validate_limit(
+            value,
+            "config[:max_attribute_value_bytes]",
+            &["bytes"],
+            ATTRIBUTE_VALUE_OVERFLOWS,
+        )?;
+    }
+
+    if let Some(value) = lookup(config, "max_classes") {
+        validate_limit(
+            value,
+            "config[:max_classes]",
+            &["tokens", "token_bytes"],
+            CLASS_OVERFLOWS,
+        )?;
+    }
+
+    if let Some(value) = lookup(config, "control_characters") {
+        let known = Symbol::from_value(value)
+            .and_then(|sym| sym.name().ok())
+            .is_some_and(|name| CONTROL_CHARACTER_POLICIES.contains(&name.as_ref()));
+
+        if !known {
+            return Err(config_error(format!(
+                "config[:control_characters]: expected :strip or :reject, got {}",
+                value.inspect()
+            )));
+        }
+    }
+
+    Ok(warnings)
+}
+
+const NOSCRIPT_POLICIES: &[&str] = &["remove", "unwrap", "escape"];
+const SHADOW_ROOT_POLICIES: &[&str] = &["remove", "flatten", "sanitize"];
+const INTEGRITY_POLICIES: &[&str] = &["strip", "require"];
+const DUPLICATE_ATTRIBUTE_POLICIES: &[&str] = &["drop", "remove"];
+const SANDBOX_MODES: &[&str] = &["replace", "merge"];
+const UNICODE_FILTERS: &[&str] = &["strip", "escape"];
+const CONTROL_CHARACTER_POLICIES: &[&str] = &["strip", "reject"];
+const ATTRIBUTE_OVERFLOWS: &[&str] = &["drop", "remove"];
+const ATTRIBUTE_VALUE_OVERFLOWS: &[&str] = &["drop", "truncate"];
+const CLASS_OVERFLOWS: &[&str] = &["drop", "remove"];
+
+/// A limit is either a positive Integer, or a Hash of them (keyed by
+/// `sizes`), along with what to do when it's exceeded, as an `:overflow`
+/// Symbol.
+fn validate_limit(
+    value: Value,
+    path: &str,
+    sizes: &[&str],
+    overflows: &[&str],
+) -> Result<(), magnus::Error> {
+    if Integer::from_value(value).is_some() {
+        return validate_positive(value, path);
+    }
+
+    for (key, option) in hash_of(value, path)? {
+        let path = format!("{path}[{}]", key.inspect());
+        let name = Symbol::from_value(key).and_then(|sym| sym.name().ok());
+
+        match name.as_deref() {
+            Some("overflow") => {
+                let known = Symbol::from_value(option)
+                    .and_then(|sym| sym.name().ok())
+                    .is_some_and(|name| overflows.contains(&name.as_ref()));
+
+                if !known {
+                    let expected = overflows
+                        .iter()
+                        .map(|overflow| format!(":{overflow}"))
+                        .collect::<Vec<_>>()
+                        .join(" or ");
+                    return Err(config_error(format!(
+                        "{path}: expected {expected}, got {}",
+                        option.inspect()
+                    )));
+                }
+            }
+            Some(name) if sizes.contains(&name) => validate_positive(option, &path)?,
+            _ => return Err(config_error(format!("{path}: unknown key"))),
+        }
+    }
+
+    Ok(())
+}
+
+fn validate_positive(value: Value, path: &str) -> Result<(), magnus::Error> {
+    let positive = Integer::from_value(value)
+        .and_then(|size| size.to_i64().ok())
+        .is_some_and(|size| size > 0 && size <= u32::MAX as i64);
+
+    if !positive {
+        return Err(config_error(format!(
+            "{path}: expected a positive Integer, got {}",
+            value.inspect()
+        )));
+    }
+
+    Ok(())
+}
+
+/// Fetches a (symbol) key from the config, treating `nil` as missing.
+fn lookup(config: RHash, key: &str) -> Option<Value> {
+    config.get(Symbol::new(key)).filter(|value| !value.is_nil())
+}
+
+fn hash_entries(hash: RHash) -> Result<Vec<(Value, Value)>, magnus::Error> {
+    let mut entries = vec![];
+    hash.foreach(|key: Value, value: Value| {
+        entries.push((key, value));
+        Ok(ForEach::Continue)
+    })?;
+
+    Ok(entries)
+}
+
+fn hash_of(value: Value, path: &str) -> Result<Vec<(Value, Value)>, magnus::Error> {
+    match RHash::from_value(value) {
+        Some(hash) => hash_entries(hash),
+        None => Err(config_error(format!(
+            "{path}: expected a Hash, got {}",
+            value.inspect()
+        ))),
+    }
+}
+
+/// Arrays (possibly nested) and Sets are both accepted, since that's what
+/// `Selma::Sanitizer::Config.merge` produces.
+fn list_of(value: Value, path: &str) -> Result<Vec<Value>, magnus::Error> {
+    let set_class: RClass = class::object().const_get("Set")?;
+
+    let array = if RArray::from_value(value).is_some() || value.is_kind_of(set_class) {
+        value
+            .funcall::<_, _, RArray>("to_a", ())?
+            .funcall::<_, _, RArray>("flatten", ())?
+    } else {
+        return Err(config_error(format!(
+            "{path}: expected an Array, got {}",
+            value.inspect()
+        )));
+    };
+
+    array.each().collect()
+}
+
+fn name_of(value: Value, path: &str) -> Result<String, magnus::Error> {
+    if let Some(string) = RString::from_value(value) {
+        return string.to_string();
+    }
+
+    if let Some(symbol) = Symbol::from_value(value) {
+        return Ok(symbol.name()?.to_string());
+    }
+
+    Err(config_error(format!(
+        "{path}: expected a String or Symbol, got {}",
+        value.inspect()
+    )))
+}
+
+fn element_name(value: Value, path: &str) -> Result<String, magnus::Error> {
+    let name = name_of(value, path)?;
+
+    if !is_valid_element_name(&name) {
+        return Err(config_error(format!(
+            "{path}: `{name}` is not a valid element name"
+        )));
+    }
+
+    Ok(name)
+}
+
+fn element_names(value: Value, path: &str) -> Result<Vec<String>, magnus::Error> {
+    list_of(value, path)?
+        .into_iter()
+        .enumerate()
+        .map(|(index, element)| element_name(element, &format!("{path}[{index}]")))
+        .collect()
+}
+
+fn is_valid_element_name(name: &str) -> bool {
+    let mut chars = name.chars();
+
+    match chars.next() {
+        Some(c) if c.is_ascii_alphabetic() => {
+            chars.all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.' | ':'))
+        }
+        _ => false,
+    }
+}
+
+/// Attribute names which can go in a selector as they are.
+fn is_valid_attribute_name(name: &str) -> bool {
+    let mut chars = name.chars();
+
+    match chars.next() {
+        Some(c) if c.is_ascii_alphabetic() => {
+            chars.all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_'))
+        }
+        _ => false,
+    }
+}
+
+fn validate_protocol(protocol: Value, path: &str) -> Result<(), magnus::Error> {
+    if let Some(string) = RString::from_value(protocol) {
+        if !string.is_empty() {
+            return Ok(());
+        }
+    } else if let Some(symbol) = Symbol::from_value(protocol) {
+        let name = symbol.name()?;
+        if RELATIVE_URLS.iter().any(|(relative, _)| *relative == name) {
+            return Ok(());
+        }
+    }
+
+    let relatives: Vec<String> = RELATIVE_URLS
+        .iter()
+        .map(|(relative, _)| format!(":{relative}"))
+        .collect();
+    Err(config_error(format!(
+        "{path}: expected a protocol String or one of {}, got {}",
+        relatives.join(", "),
+        protocol.inspect()
+    )))
+}
+
+fn is_boolean(value: Value) -> bool {
+    value.is_kind_of(class::true_class()) || value.is_kind_of(class::false_class())
+}
@@ -0,0 +1,144 @@
+use magnus::{
+    exception, value::Lazy, value::ReprValue, Attr, Class, ExceptionClass, Module, RModule, Ruby,
+    Value,
+};
+use selma_core::sanitize::SanitizeError;
+
+/// The superclass of every error Selma raises.
+pub static SELMA_ERROR: Lazy<ExceptionClass> = Lazy::new(|ruby| {
+    selma_module(ruby)
+        .define_error("Error", exception::standard_error())
+        .expect("cannot define Selma::Error")
+});
+
+/// Raised when a sanitizer config can't be understood.
+pub static CONFIG_ERROR: Lazy<ExceptionClass> = Lazy::new(|ruby| {
+    selma_module(ruby)
+        .define_error("ConfigError", ruby.get_inner(&SELMA_ERROR))
+        .expect("cannot define Selma::ConfigError")
+});
+
+/// Raised when lol_html can't parse or rewrite a document.
+pub static REWRITING_ERROR: Lazy<ExceptionClass> = Lazy::new(|ruby| {
+    selma_module(ruby)
+        .define_error("RewritingError", ruby.get_inner(&SELMA_ERROR))
+        .expect("cannot define Selma::RewritingError")
+});
+
+/// Raised when an object is used while it's in no state to be, like calling
+/// `Selma::Rewriter#rewrite` from within one of its own handlers.
+pub static INVALID_STATE_ERROR: Lazy<ExceptionClass> = Lazy::new(|ruby| {
+    selma_module(ruby)
+        .define_error("InvalidStateError", ruby.get_inner(&SELMA_ERROR))
+        .expect("cannot define Selma::InvalidStateError")
+});
+
+/// Raised by `Selma::Rewriter#rewrite` when a handler calls `halt!(raise: true)`.
+pub static HALTED_ERROR: Lazy<ExceptionClass> = Lazy::new(|ruby| {
+    selma_module(ruby)
+        .define_error("HaltedError", ruby.get_inner(&SELMA_ERROR))
+        .expect("cannot define Selma::HaltedError")
+});
+
+/// Raised by `Selma::Rewriter#rewrite` when it runs past its `timeout:`.
+pub static TIMEOUT_ERROR: Lazy<ExceptionClass> = Lazy::new(|ruby| {
+    selma_module(ruby)
+        .define_error("TimeoutError", ruby.get_inner(&SELMA_ERROR))
+        .expect("cannot define Selma::TimeoutError")
+});
+
+/// Raised when a `Selma::Selector` is given CSS it can't understand. This is an
+/// `ArgumentError` (rather than a `Selma::Error`) since it's always a bad argument.
+pub static SELECTOR_ERROR: Lazy<ExceptionClass> = Lazy::new(|ruby| {
+    let class = selma_module(ruby)
+        .define_error("SelectorError", exception::arg_error())
+        .expect("cannot define Selma::SelectorError");
+
+    class
+        .define_attr("position", Attr::Read)
+        .expect("cannot define `Selma::SelectorError#position`");
+    class
+        .define_attr("reason", Attr::Read)
+        .expect("cannot define `Selma::SelectorError#reason`");
+
+    class
+});
+
+fn selma_module(ruby: &Ruby) -> RModule {
+    ruby.define_module("Selma")
+        .expect("cannot define ::Selma module")
+}
+
+pub fn config_error<T: Into<String>>(message: T) -> magnus::Error {
+    let ruby = Ruby::get().unwrap();
+    magnus::Error::new(ruby.get_inner(&CONFIG_ERROR), message.into())
+}
+
+pub fn rewriting_error<T: Into<String>>(message: T) -> magnus::Error {
+    let ruby = Ruby::get().unwrap();
+    magnus::Error::new(ruby.get_inner(&REWRITING_ERROR), message.into())
+}
+
+pub fn invalid_state_error<T: Into<String>>(message: T) -> magnus::Error {
+    let ruby = Ruby::get().unwrap();
+    magnus::Error::new(ruby.get_inner(&INVALID_STATE_ERROR), message.into())
+}
+
+pub fn halted_error<T: Into<String>>(message: T) -> magnus::Error {
+    let ruby = Ruby::get().unwrap();
+    magnus::Error::new(ruby.get_inner(&HALTED_ERROR), message.into())
+}
+
+pub fn timeout_error<T: Into<String>>(message: T) -> magnus::Error {
+    let ruby = Ruby::get().unwrap();
+    magnus::Error::new(ruby.get_inner(&TIMEOUT_ERROR), message.into())
+}
+
+/// The error `selma_core` couldn't sanitize a document with, as whichever
+/// `Selma::Error` it amounts to.
+pub fn sanitize_error(error: SanitizeError) -> magnus::Error {
+    match error {
+        SanitizeError::Rewriting(message) => rewriting_error(message),
+        SanitizeError::Timeout(message) => timeout_error(message),
+    }
+}
+
+/// `position` is the (character) offset into the CSS where parsing went wrong.
+pub fn selector_error<T: Into<String>>(
+    message: T,
+    position: usize,
+    reason: String,
+) -> magnus::Error {
+    let ruby = Ruby::get().unwrap();
+    let error = match ruby
+        .get_inner(&SELECTOR_ERROR)
+        .new_instance((message.into(),))
+    {
+        Ok(error) => error,
+        Err(err) => return err,
+    };
+
+    if let Err(err) = error.funcall::<_, _, Value>("instance_variable_set", ("@position", position))
+    {
+        return err;
+    }
+    if let Err(err) = error.funcall::<_, _, Value>("instance_variable_set", ("@reason", reason)) {
+        return err;
+    }
+
+    error.into()
+}
+
+pub fn init(_m_selma: RModule) -> Result<(), magnus::Error> {
+    let ruby = Ruby::get().unwrap();
+
+    Lazy::force(&SELMA_ERROR, &ruby);
+    Lazy::force(&CONFIG_ERROR, &ruby);
+    Lazy::force(&REWRITING_ERROR, &ruby);
+    Lazy::force(&INVALID_STATE_ERROR, &ruby);
+    Lazy::force(&HALTED_ERROR, &ruby);
+    Lazy::force(&TIMEOUT_ERROR, &ruby);
+    Lazy::force(&SELECTOR_ERROR, &ruby);
+
+    Ok(())
+}
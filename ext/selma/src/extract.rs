@@ -0,0 +1,497 @@
+use lol_html::html_content::{Element, TextChunk, TextType};
+use magnus::{
+    class, exception, r_hash::ForEach, value::ReprValue, Error, Integer, RArray, RHash, Symbol,
+    Value,
+};
+use std::{
+    cell::{Cell, RefCell},
+    rc::Rc,
+};
+
+use crate::tags::Tag;
+
+/// What a rewriter's `extract:` option asks it to gather from the documents
+/// it rewrites, along the way.
+#[derive(Clone, Debug, Default)]
+pub struct Extraction {
+    word_count: Option<WordCountOptions>,
+    /// how many bytes of text to sample
+    text_sample: Option<usize>,
+    first_image: bool,
+    /// about how many characters of paragraph text to excerpt
+    excerpt: Option<usize>,
+}
+
+#[derive(Clone, Debug)]
+struct WordCountOptions {
+    sections: bool,
+    words_per_minute: usize,
+}
+
+impl Extraction {
+    const KNOWN_KEYS: &'static [&'static str] =
+        &["word_count", "text_sample", "first_image", "excerpt"];
+
+    const WORDS_PER_MINUTE: usize = 200;
+
+    pub fn new(rb_extract: Option<RHash>) -> Result<Self, Error> {
+        let rb_extract = match rb_extract {
+            Some(rb_extract) => rb_extract,
+            None => return Ok(Self::default()),
+        };
+
+        for key in hash_keys(rb_extract)? {
+            let known = Symbol::from_value(key)
+                .and_then(|sym| sym.name().ok())
+                .is_some_and(|name| Self::KNOWN_KEYS.contains(&name.as_ref()));
+
+            if !known {
+                return Err(argument_error(format!(
+                    "extract[{}]: unknown key",
+                    key.inspect()
+                )));
+            }
+        }
+
+        let text_sample = match lookup(rb_extract, "text_sample") {
+            Some(_) => Some(positive_integer(rb_extract, "text_sample", "extract")?),
+            None => None,
+        };
+        let excerpt = match lookup(rb_extract, "excerpt") {
+            Some(_) => Some(positive_integer(rb_extract, "excerpt", "extract")?),
+            None => None,
+        };
+
+        Ok(Self {
+            word_count: Self::word_count_options(lookup(rb_extract, "word_count"))?,
+            text_sample,
+            first_image: lookup(rb_extract, "first_image").is_some_and(|v| v.to_bool()),
+            excerpt,
+        })
+    }
+
+    fn word_count_options(value: Option<Value>) -> Result<Option<WordCountOptions>, Error> {
+        let value = match value {
+            Some(value) if value.to_bool() => value,
+            _ => return Ok(None),
+        };
+
+        let mut options = WordCountOptions {
+            sections: false,
+            words_per_minute: Self::WORDS_PER_MINUTE,
+        };
+        if value.is_kind_of(class::true_class()) {
+            return Ok(Some(options));
+        }
+
+        let rb_options = match RHash::from_value(value) {
+            Some(rb_options) => rb_options,
+            None => {
+                return Err(argument_error(format!(
+                    "extract[:word_count]: expected true or a Hash, got {}",
+                    value.inspect()
+                )))
+            }
+        };
+
+        for key in hash_keys(rb_options)? {
+            let name = Symbol::from_value(key).and_then(|sym| sym.name().ok());
+            match name.as_deref() {
+                Some("sections") => {
+                    options.sections = lookup(rb_options, "sections").is_some_and(|v| v.to_bool())
+                }
+                Some("words_per_minute") => {
+                    options.words_per_minute =
+                        positive_integer(rb_options, "words_per_minute", "extract[:word_count]")?
+                }
+                _ => {
+                    return Err(argument_error(format!(
+                        "extract[:word_count][{}]: unknown key",
+                        key.inspect()
+                    )))
+                }
+            }
+        }
+
+        Ok(Some(options))
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.word_count.is_none()
+            && self.text_sample.is_none()
+            && !self.first_image
+            && self.excerpt.is_none()
+    }
+}
+
+/// What's been extracted from the latest rewrite, for `Selma::Rewriter#extracted`.
+#[derive(Clone, Debug, Default)]
+pub struct Extracted {
+    word_count: Option<WordCount>,
+    text_sample: Option<String>,
+    /// the `src` of the first image, if it was asked for
+    first_image: Option<Option<String>>,
+    excerpt: Option<String>,
+}
+
+#[derive(Clone, Debug, Default)]
+struct WordCount {
+    words: usize,
+    characters: usize,
+    words_per_minute: usize,
+    sections: Option<Vec<Section>>,
+}
+
+#[derive(Clone, Debug, Default)]
+struct Section {
+    heading: Option<String>,
+    words: usize,
+    characters: usize,
+}
+
+impl Extracted {
+    pub fn to_hash(&self) -> Result<RHash, Error> {
+        let rb_extracted = RHash::new();
+
+        if let Some(word_count) = &self.word_count {
+            let rb_word_count = RHash::new();
+            rb_word_count.aset(Symbol::new("words"), word_count.words)?;
+            rb_word_count.aset(Symbol::new("characters"), word_count.characters)?;
+            rb_word_count.aset(
+                Symbol::new("reading_time"),
+                word_count.words.div_ceil(word_count.words_per_minute),
+            )?;
+
+            if let Some(sections) = &word_count.sections {
+                let rb_sections = RArray::new();
+                for section in sections {
+                    let rb_section = RHash::new();
+                    rb_section.aset(Symbol::new("heading"), section.heading.clone())?;
+                    rb_section.aset(Symbol::new("words"), section.words)?;
+                    rb_section.aset(Symbol::new("characters"), section.characters)?;
+                    rb_sections.push(rb_section)?;
+                }
+                rb_word_count.aset(Symbol::new("sections"), rb_sections)?;
+            }
+
+            rb_extracted.aset(Symbol::new("word_count"), rb_word_count)?;
+        }
+
+        if let Some(text_sample) = &self.text_sample {
+            rb_extracted.aset(Symbol::new("text_sample"), text_sample.as_str())?;
+        }
+
+        if let Some(first_image) = &self.first_image {
+            rb_extracted.aset(Symbol::new("first_image"), first_image.clone())?;
+        }
+
+        if let Some(excerpt) = &self.excerpt {
+            rb_extracted.aset(Symbol::new("excerpt"), excerpt.as_str())?;
+        }
+
+        Ok(rb_extracted)
+    }
+}
+
+/// Gathers what an `Extraction` asks for while a document is rewritten. Only
+/// visible text is looked at, as it was after it was sanitized.
+pub struct Extractor {
+    extracted: RefCell<Extracted>,
+    /// whether the last character of text was part of a word, which carries
+    /// over into the next text node, unless an element in between breaks it up
+    in_word: Cell<bool>,
+    /// how many headings are open, whose text goes into their section's `heading`
+    heading_depth: Cell<usize>,
+    text_sample: Option<TextSample>,
+    excerpt: Option<TextSample>,
+}
+
+/// Where a sample of the text is at; the text itself is in `Extracted`.
+struct TextSample {
+    limit: usize,
+    /// whether `limit` is in characters, rather than bytes
+    in_characters: bool,
+    length: Cell<usize>,
+    /// whether whitespace (or the start of a block) came before what's next
+    space: Cell<bool>,
+    full: Cell<bool>,
+    /// whether the sample filled up in the middle of a word
+    cut_mid_word: Cell<bool>,
+}
+
+impl TextSample {
+    fn new(limit: usize, in_characters: bool) -> Self {
+        Self {
+            limit,
+            in_characters,
+            length: Cell::new(0),
+            space: Cell::new(false),
+            full: Cell::new(false),
+            cut_mid_word: Cell::new(false),
+        }
+    }
+
+    fn width(&self, c: char) -> usize {
+        if self.in_characters {
+            1
+        } else {
+            c.len_utf8()
+        }
+    }
+
+    /// Adds `text` to `sample`, with its whitespace collapsed, until it's
+    /// full; a character which doesn't fit ends it.
+    fn push(&self, sample: &mut String, text: &str) {
+        for c in text.chars() {
+            if self.full.get() {
+                return;
+            }
+
+            if c.is_whitespace() {
+                self.space.set(true);
+                continue;
+            }
+
+            let space = self.space.replace(false) && !sample.is_empty();
+            let width = usize::from(space) + self.width(c);
+            if self.length.get() + width > self.limit {
+                self.full.set(true);
+                self.cut_mid_word.set(!space);
+                return;
+            }
+
+            if space {
+                sample.push(' ');
+            }
+            sample.push(c);
+            self.length.set(self.length.get() + width);
+        }
+    }
+}
+
+impl Extractor {
+    /// Elements whose text never shows up on the page.
+    const INVISIBLE: [&'static str; 6] =
+        ["head", "noscript", "script", "style", "template", "title"];
+
+    pub fn new(extraction: &Extraction) -> Self {
+        let word_count = extraction.word_count.as_ref().map(|options| WordCount {
+            words_per_minute: options.words_per_minute,
+            sections: options.sections.then(Vec::new),
+            ..WordCount::default()
+        });
+
+        let text_sample = extraction
+            .text_sample
+            .map(|limit| TextSample::new(limit, false));
+        let excerpt = extraction.excerpt.map(|limit| TextSample::new(limit, true));
+
+        Self {
+            extracted: RefCell::new(Extracted {
+                word_count,
+                text_sample: text_sample.as_ref().map(|_| String::new()),
+                first_image: extraction.first_image.then_some(None),
+                excerpt: excerpt.as_ref().map(|_| String::new()),
+            }),
+            in_word: Cell::new(false),
+            heading_depth: Cell::new(0),
+            text_sample,
+            excerpt,
+        }
+    }
+
+    pub fn finish(&self) -> Extracted {
+        let mut extracted = self.extracted.borrow().clone();
+
+        if let Some(sections) = extracted
+            .word_count
+            .as_mut()
+            .and_then(|word_count| word_count.sections.as_mut())
+        {
+            for section in sections.iter_mut() {
+                if let Some(heading) = section.heading.as_mut() {
+                    *heading = heading.split_whitespace().collect::<Vec<_>>().join(" ");
+                }
+            }
+        }
+
+        // an excerpt which was cut short ends at a word, and says so
+        if let (Some(excerpt), Some(sample)) = (extracted.excerpt.as_mut(), &self.excerpt) {
+            if sample.full.get() {
+                if sample.cut_mid_word.get() {
+                    if let Some(index) = excerpt.rfind(' ') {
+                        excerpt.truncate(index);
+                    }
+                }
+                excerpt.push('…');
+            }
+        }
+
+        extracted
+    }
+
+    pub fn enter_element(self: &Rc<Self>, element: &mut Element, tag_name: &str) {
+        let tag = Tag::tag_from_tag_name(tag_name);
+        if !Tag::is_phrasing(tag) {
+            self.in_word.set(false);
+            for sample in [&self.text_sample, &self.excerpt].into_iter().flatten() {
+                sample.space.set(true);
+            }
+        }
+
+        if tag_name == "img" {
+            self.find_image(element);
+        }
+
+        if !Tag::is_heading(tag) {
+            return;
+        }
+
+        let mut extracted = self.extracted.borrow_mut();
+        let sections = match extracted
+            .word_count
+            .as_mut()
+            .and_then(|word_count| word_count.sections.as_mut())
+        {
+            Some(sections) => sections,
+            None => return,
+        };
+
+        if let Some(end_tag_handlers) = element.end_tag_handlers() {
+            // a heading within a heading is just more of its text
+            if self.heading_depth.get() == 0 {
+                sections.push(Section {
+                    heading: Some(String::new()),
+                    ..Section::default()
+                });
+            }
+            self.heading_depth.set(self.heading_depth.get() + 1);
+
+            let extractor = self.clone();
+            end_tag_handlers.push(Box::new(move |_end_tag| {
+                extractor
+                    .heading_depth
+                    .set(extractor.heading_depth.get() - 1);
+                extractor.in_word.set(false);
+                Ok(())
+            }));
+        }
+    }
+
+    /// Keeps the first image's `src`, once the sanitizer's been at it.
+    fn find_image(&self, element: &Element) {
+        let mut extracted = self.extracted.borrow_mut();
+        if let Some(first_image @ None) = extracted.first_image.as_mut() {
+            if element.removed() {
+                return;
+            }
+
+            *first_image = element
+                .get_attribute("src")
+                .filter(|src| !src.trim().is_empty());
+        }
+    }
+
+    pub fn push_text(&self, chunk: &TextChunk, ancestors: &[String]) {
+        if chunk.removed()
+            || !matches!(chunk.text_type(), TextType::Data | TextType::RCData)
+            || ancestors
+                .iter()
+                .any(|ancestor| Self::INVISIBLE.contains(&ancestor.as_str()))
+        {
+            return;
+        }
+
+        let text = String::from_utf8_lossy(&escapist::unescape_html(chunk.as_str().as_bytes()))
+            .into_owned();
+
+        let mut extracted = self.extracted.borrow_mut();
+        if let Some(word_count) = extracted.word_count.as_mut() {
+            self.count_words(word_count, &text);
+        }
+        if let (Some(sample), Some(text_sample)) =
+            (extracted.text_sample.as_mut(), &self.text_sample)
+        {
+            text_sample.push(sample, &text);
+        }
+        if let (Some(sample), Some(excerpt)) = (extracted.excerpt.as_mut(), &self.excerpt) {
+            if ancestors.iter().any(|ancestor| ancestor == "p") {
+                excerpt.push(sample, &text);
+            }
+        }
+    }
+
+    fn count_words(&self, word_count: &mut WordCount, text: &str) {
+        let in_heading = self.heading_depth.get() > 0;
+
+        // text before the first heading has a section of its own
+        let section = match word_count.sections.as_mut() {
+            Some(sections) => {
+                if sections.is_empty() && !text.trim().is_empty() {
+                    sections.push(Section::default());
+                }
+                sections.last_mut()
+            }
+            None => None,
+        };
+
+        let mut words = 0;
+        let mut characters = 0;
+        for c in text.chars() {
+            if c.is_whitespace() {
+                self.in_word.set(false);
+                continue;
+            }
+
+            characters += 1;
+            if !self.in_word.get() {
+                words += 1;
+                self.in_word.set(true);
+            }
+        }
+
+        word_count.words += words;
+        word_count.characters += characters;
+
+        if let Some(section) = section {
+            if in_heading {
+                if let Some(heading) = section.heading.as_mut() {
+                    heading.push_str(text);
+                }
+            } else {
+                section.words += words;
+                section.characters += characters;
+            }
+        }
+    }
+}
+
+fn argument_error(message: String) -> Error {
+    Error::new(exception::arg_error(), message)
+}
+
+/// Fetches a (symbol) key from a hash, treating `nil` as missing.
+fn lookup(hash: RHash, key: &str) -> Option<Value> {
+    hash.get(Symbol::new(key)).filter(|value| !value.is_nil())
+}
+
+fn hash_keys(hash: RHash) -> Result<Vec<Value>, Error> {
+    let mut keys = vec![];
+    hash.foreach(|key: Value, _value: Value| {
+        keys.push(key);
+        Ok(ForEach::Continue)
+    })?;
+
+    Ok(keys)
+}
+
+fn positive_integer(hash: RHash, key: &str, path: &str) -> Result<usize, Error> {
+    let value = lookup(hash, key);
+    match value.and_then(Integer::from_value).map(|i| i.to_usize()) {
+        Some(Ok(integer)) if integer > 0 => Ok(integer),
+        _ => Err(argument_error(format!(
+            "{path}[:{key}]: expected a positive Integer, got {}",
+            value.map_or("nil".to_string(), |value| value.inspect())
+        ))),
+    }
+}
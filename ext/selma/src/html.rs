@@ -1,4 +1,4 @@
-use magnus::{Error, Module, RModule};
+use magnus::{function, Error, Module, Object, RModule};
 
 #[derive(Clone, Debug)]
 #[magnus::wrap(class = "Selma::HTML")]
@@ -9,6 +9,10 @@ pub fn init(m_selma: RModule) -> Result<(), Error> {
         .define_class("HTML", magnus::class::object())
         .expect("cannot define class Selma::HTML");
 
+    c_html
+        .define_singleton_method("to_markdown", function!(crate::markdown::to_markdown, -1))
+        .expect("cannot define singleton method `to_markdown`");
+
     element::init(c_html).expect("cannot define Selma::HTML::Element class");
     end_tag::init(c_html).expect("cannot define Selma::HTML::EndTag class");
     text_chunk::init(c_html).expect("cannot define Selma::HTML::TextChunk class");
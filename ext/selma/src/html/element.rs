@@ -1,10 +1,24 @@
-use crate::native_ref_wrap::NativeRefWrap;
-use lol_html::html_content::Element;
-use magnus::{exception, method, Error, Module, RArray, RClass, RHash, RString, Value};
+use crate::{
+    native_ref_wrap::NativeRefWrap,
+    rewriter::{SkippedSubtrees, SubtreeMarkers, TextCaptures},
+    sanitizer::SelmaSanitizer,
+    tags::Tag,
+};
+use lol_html::html_content::{ContentType, Element};
+use magnus::{
+    block::Proc, exception, method, r_hash::ForEach, scan_args, Error, Module, RArray, RClass,
+    RHash, RString, Value,
+};
+use std::rc::Rc;
 
 struct HTMLElement {
     element: NativeRefWrap<Element<'static, 'static>>,
     ancestors: Vec<String>,
+    sanitizer: Option<NativeRefWrap<SelmaSanitizer>>,
+    context: RHash,
+    subtree_markers: NativeRefWrap<SubtreeMarkers>,
+    text_captures: Rc<TextCaptures>,
+    skipped_subtrees: SkippedSubtrees,
 }
 
 #[magnus::wrap(class = "Selma::HTML::Element")]
@@ -14,15 +28,36 @@ pub struct SelmaHTMLElement(std::cell::RefCell<HTMLElement>);
 unsafe impl Send for SelmaHTMLElement {}
 
 impl SelmaHTMLElement {
-    pub fn new(element: &mut Element, ancestors: &[String]) -> Self {
+    pub fn new(
+        element: &mut Element,
+        ancestors: &[String],
+        sanitizer: Option<&SelmaSanitizer>,
+        context: RHash,
+        subtree_markers: &SubtreeMarkers,
+        text_captures: &Rc<TextCaptures>,
+        skipped_subtrees: &SkippedSubtrees,
+    ) -> Self {
         let (ref_wrap, _anchor) = NativeRefWrap::wrap_mut(element);
 
         Self(std::cell::RefCell::new(HTMLElement {
             element: ref_wrap,
             ancestors: ancestors.to_owned(),
+            sanitizer: sanitizer.map(|sanitizer| NativeRefWrap::wrap(sanitizer).0),
+            context,
+            subtree_markers: NativeRefWrap::wrap(subtree_markers).0,
+            text_captures: text_captures.clone(),
+            skipped_subtrees: skipped_subtrees.clone(),
         }))
     }
 
+    fn scan_text_args(&self, args: &[Value]) -> Result<(String, ContentType), Error> {
+        // reading the arguments, and rendering Markdown, can call back into Ruby,
+        // which may well use this same object, so it can't stay borrowed
+        let sanitizer = self.0.borrow().sanitizer.clone();
+
+        crate::scan_text_args(args, sanitizer.as_ref())
+    }
+
     fn tag_name(&self) -> Result<String, Error> {
         let binding = self.0.borrow();
 
@@ -42,7 +77,10 @@ impl SelmaHTMLElement {
         if let Ok(element) = binding.element.get_mut() {
             match element.set_tag_name(&name) {
                 Ok(_) => Ok(()),
-                Err(err) => Err(Error::new(exception::runtime_error(), format!("{err:?}"))),
+                Err(err) => Err(Error::new(
+                    exception::arg_error(),
+                    format!("cannot rename <{}> to `{name}`: {err}", element.tag_name()),
+                )),
             }
         } else {
             Err(Error::new(
@@ -65,6 +103,32 @@ impl SelmaHTMLElement {
         }
     }
 
+    fn is_void(&self) -> Result<bool, Error> {
+        let mut binding = self.0.borrow_mut();
+
+        if let Ok(e) = binding.element.get_mut() {
+            Ok(Tag::tag_from_element(e).self_closing)
+        } else {
+            Err(Error::new(
+                exception::runtime_error(),
+                "`is_void` is not available",
+            ))
+        }
+    }
+
+    fn can_have_content(&self) -> Result<bool, Error> {
+        let mut binding = self.0.borrow_mut();
+
+        if let Ok(e) = binding.element.get_mut() {
+            Ok(e.can_have_content() && !Tag::tag_from_element(e).self_closing)
+        } else {
+            Err(Error::new(
+                exception::runtime_error(),
+                "`can_have_content` is not available",
+            ))
+        }
+    }
+
     fn has_attribute(&self, attr: String) -> Result<bool, Error> {
         let binding = self.0.borrow();
 
@@ -78,10 +142,17 @@ impl SelmaHTMLElement {
         }
     }
 
-    fn get_attribute(&self, attr: String) -> Option<String> {
+    fn get_attribute(&self, attr: String) -> Result<Option<String>, Error> {
         let binding = self.0.borrow();
-        let element = binding.element.get();
-        element.unwrap().get_attribute(&attr)
+
+        if let Ok(e) = binding.element.get() {
+            Ok(e.get_attribute(&attr))
+        } else {
+            Err(Error::new(
+                exception::runtime_error(),
+                "`get_attribute` is not available",
+            ))
+        }
     }
 
     fn set_attribute(&self, attr: String, value: String) -> Result<String, Error> {
@@ -102,6 +173,115 @@ impl SelmaHTMLElement {
         }
     }
 
+    /// Sets (or, for `nil` values, removes) several attributes at once.
+    fn set_attributes(&self, attrs: RHash) -> Result<RHash, Error> {
+        let mut entries: Vec<(String, Option<String>)> = vec![];
+        attrs.foreach(|attr: String, value: Option<String>| {
+            entries.push((attr, value));
+            Ok(ForEach::Continue)
+        })?;
+        let mut binding = self.0.borrow_mut();
+
+        if let Ok(element) = binding.element.get_mut() {
+            for (attr, value) in entries {
+                match value {
+                    None => element.remove_attribute(&attr),
+                    Some(value) => {
+                        if let Err(err) = element.set_attribute(&attr, &value) {
+                            return Err(Error::new(
+                                exception::runtime_error(),
+                                format!("AttributeNameError: {err:?}"),
+                            ));
+                        }
+                    }
+                }
+            }
+            Ok(attrs)
+        } else {
+            Err(Error::new(
+                exception::runtime_error(),
+                "`set_attributes` is not available",
+            ))
+        }
+    }
+
+    /// Adds each of the whitespace-separated `tokens` to the attribute `attr`
+    /// (like `class` or `rel`), unless it's already there, creating it if need be.
+    fn append_attribute_token(&self, attr: String, tokens: String) -> Result<(), Error> {
+        let mut binding = self.0.borrow_mut();
+        let element = match binding.element.get_mut() {
+            Ok(element) => element,
+            Err(_) => {
+                return Err(Error::new(
+                    exception::runtime_error(),
+                    "`append_attribute_token` is not available",
+                ))
+            }
+        };
+
+        let current = element.get_attribute(&attr).unwrap_or_default();
+        let mut list: Vec<&str> = current.split_ascii_whitespace().collect();
+        for token in tokens.split_ascii_whitespace() {
+            if !list.contains(&token) {
+                list.push(token);
+            }
+        }
+
+        match element.set_attribute(&attr, &list.join(" ")) {
+            Ok(_) => Ok(()),
+            Err(err) => Err(Error::new(
+                exception::runtime_error(),
+                format!("AttributeNameError: {err:?}"),
+            )),
+        }
+    }
+
+    /// Removes each of the whitespace-separated `tokens` from the attribute
+    /// `attr`, and the attribute itself once it's left without any.
+    fn remove_attribute_token(&self, attr: String, tokens: String) -> Result<(), Error> {
+        let mut binding = self.0.borrow_mut();
+        let element = match binding.element.get_mut() {
+            Ok(element) => element,
+            Err(_) => {
+                return Err(Error::new(
+                    exception::runtime_error(),
+                    "`remove_attribute_token` is not available",
+                ))
+            }
+        };
+
+        let current = match element.get_attribute(&attr) {
+            Some(current) => current,
+            None => return Ok(()),
+        };
+        let removed: Vec<&str> = tokens.split_ascii_whitespace().collect();
+        let list: Vec<&str> = current
+            .split_ascii_whitespace()
+            .filter(|token| !removed.contains(token))
+            .collect();
+
+        if list.is_empty() {
+            element.remove_attribute(&attr);
+            return Ok(());
+        }
+
+        match element.set_attribute(&attr, &list.join(" ")) {
+            Ok(_) => Ok(()),
+            Err(err) => Err(Error::new(
+                exception::runtime_error(),
+                format!("AttributeNameError: {err:?}"),
+            )),
+        }
+    }
+
+    fn add_class(&self, classes: String) -> Result<(), Error> {
+        self.append_attribute_token("class".to_string(), classes)
+    }
+
+    fn remove_class(&self, classes: String) -> Result<(), Error> {
+        self.remove_attribute_token("class".to_string(), classes)
+    }
+
     fn remove_attribute(&self, attr: String) {
         let mut binding = self.0.borrow_mut();
 
@@ -115,16 +295,9 @@ impl SelmaHTMLElement {
         let hash = RHash::new();
 
         if let Ok(e) = binding.element.get() {
-            e.attributes()
-                .iter()
-                .for_each(|attr| match hash.aset(attr.name(), attr.value()) {
-                    Ok(_) => {}
-                    Err(err) => Err(Error::new(
-                        exception::runtime_error(),
-                        format!("AttributeNameError: {err:?}"),
-                    ))
-                    .unwrap(),
-                });
+            for attr in e.attributes().iter() {
+                hash.aset(attr.name(), attr.value())?;
+            }
         }
         Ok(hash)
     }
@@ -133,89 +306,237 @@ impl SelmaHTMLElement {
         let binding = self.0.borrow();
         let array = RArray::new();
 
-        binding
-            .ancestors
-            .iter()
-            .for_each(|ancestor| match array.push(RString::new(ancestor)) {
-                Ok(_) => {}
-                Err(err) => {
-                    Err(Error::new(exception::runtime_error(), format!("{err:?}"))).unwrap()
-                }
-            });
+        for ancestor in binding.ancestors.iter() {
+            array.push(RString::new(ancestor))?;
+        }
 
         Ok(array)
     }
 
-    fn before(&self, args: &[Value]) -> Result<(), Error> {
-        let mut binding = self.0.borrow_mut();
-        let element = binding.element.get_mut().unwrap();
+    fn context(&self) -> Result<RHash, Error> {
+        let binding = self.0.borrow();
 
-        let (text_str, content_type) = match crate::scan_text_args(args) {
+        // the context only lives as long as the rewrite does
+        if binding.element.get().is_ok() {
+            Ok(binding.context)
+        } else {
+            Err(Error::new(
+                exception::runtime_error(),
+                "`context` is not available",
+            ))
+        }
+    }
+
+    fn before(&self, args: &[Value]) -> Result<(), Error> {
+        let (text_str, content_type) = match self.scan_text_args(args) {
             Ok((text_str, content_type)) => (text_str, content_type),
             Err(err) => return Err(err),
         };
 
+        let mut binding = self.0.borrow_mut();
+        let element = match binding.element.get_mut() {
+            Ok(element) => element,
+            Err(_) => {
+                return Err(Error::new(
+                    exception::runtime_error(),
+                    "`before` is not available",
+                ))
+            }
+        };
+
         element.before(&text_str, content_type);
 
         Ok(())
     }
 
     fn after(&self, args: &[Value]) -> Result<(), Error> {
-        let mut binding = self.0.borrow_mut();
-        let element = binding.element.get_mut().unwrap();
-
-        let (text_str, content_type) = match crate::scan_text_args(args) {
+        let (text_str, content_type) = match self.scan_text_args(args) {
             Ok((text_str, content_type)) => (text_str, content_type),
             Err(err) => return Err(err),
         };
 
+        let mut binding = self.0.borrow_mut();
+        let element = match binding.element.get_mut() {
+            Ok(element) => element,
+            Err(_) => {
+                return Err(Error::new(
+                    exception::runtime_error(),
+                    "`after` is not available",
+                ))
+            }
+        };
+
         element.after(&text_str, content_type);
 
         Ok(())
     }
 
     fn prepend(&self, args: &[Value]) -> Result<(), Error> {
-        let mut binding = self.0.borrow_mut();
-        let element = binding.element.get_mut().unwrap();
-
-        let (text_str, content_type) = match crate::scan_text_args(args) {
+        let (text_str, content_type) = match self.scan_text_args(args) {
             Ok((text_str, content_type)) => (text_str, content_type),
             Err(err) => return Err(err),
         };
 
+        let mut binding = self.0.borrow_mut();
+        let element = match binding.element.get_mut() {
+            Ok(element) => element,
+            Err(_) => {
+                return Err(Error::new(
+                    exception::runtime_error(),
+                    "`prepend` is not available",
+                ))
+            }
+        };
+
         element.prepend(&text_str, content_type);
 
         Ok(())
     }
 
     fn append(&self, args: &[Value]) -> Result<(), Error> {
-        let mut binding = self.0.borrow_mut();
-        let element = binding.element.get_mut().unwrap();
-
-        let (text_str, content_type) = match crate::scan_text_args(args) {
+        let (text_str, content_type) = match self.scan_text_args(args) {
             Ok((text_str, content_type)) => (text_str, content_type),
             Err(err) => return Err(err),
         };
 
+        let mut binding = self.0.borrow_mut();
+        let element = match binding.element.get_mut() {
+            Ok(element) => element,
+            Err(_) => {
+                return Err(Error::new(
+                    exception::runtime_error(),
+                    "`append` is not available",
+                ))
+            }
+        };
+
         element.append(&text_str, content_type);
 
         Ok(())
     }
 
     fn set_inner_content(&self, args: &[Value]) -> Result<(), Error> {
-        let mut binding = self.0.borrow_mut();
-        let element = binding.element.get_mut().unwrap();
-
-        let (inner_content, content_type) = match crate::scan_text_args(args) {
+        let (inner_content, content_type) = match self.scan_text_args(args) {
             Ok((inner_content, content_type)) => (inner_content, content_type),
             Err(err) => return Err(err),
         };
 
+        let mut binding = self.0.borrow_mut();
+        let element = match binding.element.get_mut() {
+            Ok(element) => element,
+            Err(_) => {
+                return Err(Error::new(
+                    exception::runtime_error(),
+                    "`set_inner_content` is not available",
+                ))
+            }
+        };
+
         element.set_inner_content(&inner_content, content_type);
 
         Ok(())
     }
 
+    fn replace(&self, args: &[Value]) -> Result<(), Error> {
+        let (text_str, content_type) = match self.scan_text_args(args) {
+            Ok((text_str, content_type)) => (text_str, content_type),
+            Err(err) => return Err(err),
+        };
+
+        let mut binding = self.0.borrow_mut();
+        let element = match binding.element.get_mut() {
+            Ok(element) => element,
+            Err(_) => {
+                return Err(Error::new(
+                    exception::runtime_error(),
+                    "`replace` is not available",
+                ))
+            }
+        };
+
+        element.replace(&text_str, content_type);
+
+        Ok(())
+    }
+
+    /// The most HTML `capture_subtree` buffers, unless it's told otherwise.
+    const DEFAULT_CAPTURE_LIMIT: usize = 1024 * 1024;
+
+    /// @yard
+    /// @def capture_subtree(max_bytes: 1_048_576) { |html| ... }
+    /// Once the rewrite is over, yields all of the element's HTML, itself
+    /// included, and replaces it with what the block returns (unless that's
+    /// `nil`). Elements whose HTML is longer than `max_bytes` are left as they are.
+    #[allow(clippy::let_unit_value)]
+    fn capture_subtree(&self, args: &[Value]) -> Result<(), Error> {
+        let args = scan_args::scan_args::<(), (), (), (), _, Proc>(args)?;
+        let kwargs = scan_args::get_kwargs::<_, (), (Option<usize>,), ()>(
+            args.keywords,
+            &[],
+            &["max_bytes"],
+        )?;
+        let (max_bytes,) = kwargs.optional;
+        let block = args.block;
+
+        let mut binding = self.0.borrow_mut();
+        let binding = &mut *binding;
+        let (element, subtree_markers) =
+            match (binding.element.get_mut(), binding.subtree_markers.get()) {
+                (Ok(element), Ok(subtree_markers)) => (element, subtree_markers),
+                _ => {
+                    return Err(Error::new(
+                        exception::runtime_error(),
+                        "`capture_subtree` is not available",
+                    ))
+                }
+            };
+
+        if element.removed() {
+            return Ok(());
+        }
+
+        subtree_markers.mark_captured(
+            element,
+            block,
+            max_bytes.unwrap_or(Self::DEFAULT_CAPTURE_LIMIT),
+        )
+    }
+
+    /// @yard
+    /// @def text_content(replace: false) { |text| ... }
+    /// Yields all of the text within the element, unescaped and without any
+    /// markup, once its end tag is reached. With `replace: true`, the element's
+    /// content is replaced with what the block returns, as text (or, given
+    /// `nil`, with the text it yielded).
+    #[allow(clippy::let_unit_value)]
+    fn text_content(&self, args: &[Value]) -> Result<(), Error> {
+        let args = scan_args::scan_args::<(), (), (), (), _, Proc>(args)?;
+        let kwargs =
+            scan_args::get_kwargs::<_, (), (Option<bool>,), ()>(args.keywords, &[], &["replace"])?;
+        let (replace,) = kwargs.optional;
+        let block = args.block;
+
+        let mut binding = self.0.borrow_mut();
+        let binding = &mut *binding;
+        let element = match binding.element.get_mut() {
+            Ok(element) => element,
+            Err(_) => {
+                return Err(Error::new(
+                    exception::runtime_error(),
+                    "`text_content` is not available",
+                ))
+            }
+        };
+
+        if element.removed() {
+            return Ok(());
+        }
+
+        binding
+            .text_captures
+            .capture(element, block, replace.unwrap_or(false))
+    }
+
     fn remove(&self) {
         let mut binding = self.0.borrow_mut();
 
@@ -232,6 +553,21 @@ impl SelmaHTMLElement {
         }
     }
 
+    fn skip(&self) -> Result<(), Error> {
+        let mut binding = self.0.borrow_mut();
+        let skipped_subtrees = binding.skipped_subtrees.clone();
+
+        if let Ok(e) = binding.element.get_mut() {
+            skipped_subtrees.skip(e);
+            Ok(())
+        } else {
+            Err(Error::new(
+                exception::runtime_error(),
+                "`skip!` is not available",
+            ))
+        }
+    }
+
     fn is_removed(&self) -> Result<bool, Error> {
         let binding = self.0.borrow();
 
@@ -257,6 +593,11 @@ pub fn init(c_html: RClass) -> Result<(), Error> {
         "self_closing?",
         method!(SelmaHTMLElement::is_self_closing, 0),
     )?;
+    c_element.define_method("void?", method!(SelmaHTMLElement::is_void, 0))?;
+    c_element.define_method(
+        "can_have_content?",
+        method!(SelmaHTMLElement::can_have_content, 0),
+    )?;
     c_element.define_method("[]", method!(SelmaHTMLElement::get_attribute, 1))?;
     c_element.define_method("[]=", method!(SelmaHTMLElement::set_attribute, 2))?;
     c_element.define_method(
@@ -268,7 +609,22 @@ pub fn init(c_html: RClass) -> Result<(), Error> {
         method!(SelmaHTMLElement::has_attribute, 1),
     )?;
     c_element.define_method("attributes", method!(SelmaHTMLElement::get_attributes, 0))?;
+    c_element.define_method(
+        "append_attribute_token",
+        method!(SelmaHTMLElement::append_attribute_token, 2),
+    )?;
+    c_element.define_method(
+        "remove_attribute_token",
+        method!(SelmaHTMLElement::remove_attribute_token, 2),
+    )?;
+    c_element.define_method("add_class", method!(SelmaHTMLElement::add_class, 1))?;
+    c_element.define_method("remove_class", method!(SelmaHTMLElement::remove_class, 1))?;
+    c_element.define_method(
+        "set_attributes",
+        method!(SelmaHTMLElement::set_attributes, 1),
+    )?;
     c_element.define_method("ancestors", method!(SelmaHTMLElement::get_ancestors, 0))?;
+    c_element.define_method("context", method!(SelmaHTMLElement::context, 0))?;
 
     c_element.define_method("before", method!(SelmaHTMLElement::before, -1))?;
     c_element.define_method("after", method!(SelmaHTMLElement::after, -1))?;
@@ -279,12 +635,20 @@ pub fn init(c_html: RClass) -> Result<(), Error> {
         method!(SelmaHTMLElement::set_inner_content, -1),
     )?;
 
+    c_element.define_method("replace", method!(SelmaHTMLElement::replace, -1))?;
+    c_element.define_method(
+        "capture_subtree",
+        method!(SelmaHTMLElement::capture_subtree, -1),
+    )?;
+    c_element.define_method("text_content", method!(SelmaHTMLElement::text_content, -1))?;
+
     c_element.define_method("remove", method!(SelmaHTMLElement::remove, 0))?;
     c_element.define_method(
         "remove_and_keep_content",
         method!(SelmaHTMLElement::remove_and_keep_content, 0),
     )?;
     c_element.define_method("removed?", method!(SelmaHTMLElement::is_removed, 0))?;
+    c_element.define_method("skip!", method!(SelmaHTMLElement::skip, 0))?;
 
     Ok(())
 }
@@ -1,9 +1,10 @@
 use crate::native_ref_wrap::NativeRefWrap;
 use lol_html::html_content::EndTag;
-use magnus::{method, Error, Module, RClass};
+use magnus::{exception, method, Error, Module, RClass, RHash};
 
 struct HTMLEndTag {
     end_tag: NativeRefWrap<EndTag<'static>>,
+    context: RHash,
 }
 
 #[magnus::wrap(class = "Selma::HTML::EndTag")]
@@ -13,14 +14,40 @@ pub struct SelmaHTMLEndTag(std::cell::RefCell<HTMLEndTag>);
 unsafe impl Send for SelmaHTMLEndTag {}
 
 impl SelmaHTMLEndTag {
-    pub fn new(end_tag: &mut EndTag) -> Self {
+    pub fn new(end_tag: &mut EndTag, context: RHash) -> Self {
         let (ref_wrap, _anchor) = NativeRefWrap::wrap(end_tag);
 
-        Self(std::cell::RefCell::new(HTMLEndTag { end_tag: ref_wrap }))
+        Self(std::cell::RefCell::new(HTMLEndTag {
+            end_tag: ref_wrap,
+            context,
+        }))
     }
 
-    fn tag_name(&self) -> String {
-        self.0.borrow().end_tag.get().unwrap().name()
+    fn context(&self) -> Result<RHash, Error> {
+        let binding = self.0.borrow();
+
+        // the context only lives as long as the rewrite does
+        if binding.end_tag.get().is_ok() {
+            Ok(binding.context)
+        } else {
+            Err(Error::new(
+                exception::runtime_error(),
+                "`context` is not available",
+            ))
+        }
+    }
+
+    fn tag_name(&self) -> Result<String, Error> {
+        let binding = self.0.borrow();
+
+        if let Ok(end_tag) = binding.end_tag.get() {
+            Ok(end_tag.name())
+        } else {
+            Err(Error::new(
+                exception::runtime_error(),
+                "`tag_name` is not available",
+            ))
+        }
     }
 }
 
@@ -30,6 +57,7 @@ pub fn init(c_html: RClass) -> Result<(), Error> {
         .expect("cannot define class Selma::HTML::EndTag");
 
     c_end_tag.define_method("tag_name", method!(SelmaHTMLEndTag::tag_name, 0))?;
+    c_end_tag.define_method("context", method!(SelmaHTMLEndTag::context, 0))?;
 
     Ok(())
 }
@@ -1,9 +1,12 @@
-use crate::native_ref_wrap::NativeRefWrap;
-use lol_html::html_content::{TextChunk, TextType};
-use magnus::{exception, method, Error, Module, RClass, Symbol, Value};
+use crate::{native_ref_wrap::NativeRefWrap, sanitizer::SelmaSanitizer};
+use lol_html::html_content::{ContentType, TextChunk, TextType};
+use magnus::{exception, method, Error, Module, RArray, RClass, RHash, RString, Symbol, Value};
 
 struct HTMLTextChunk {
     text_chunk: NativeRefWrap<TextChunk<'static>>,
+    ancestors: Vec<String>,
+    sanitizer: Option<NativeRefWrap<SelmaSanitizer>>,
+    context: RHash,
 }
 
 #[magnus::wrap(class = "Selma::HTML::TextChunk")]
@@ -13,14 +16,30 @@ pub struct SelmaHTMLTextChunk(std::cell::RefCell<HTMLTextChunk>);
 unsafe impl Send for SelmaHTMLTextChunk {}
 
 impl SelmaHTMLTextChunk {
-    pub fn new(text_chunk: &mut TextChunk) -> Self {
+    pub fn new(
+        text_chunk: &mut TextChunk,
+        ancestors: &[String],
+        sanitizer: Option<&SelmaSanitizer>,
+        context: RHash,
+    ) -> Self {
         let (ref_wrap, _anchor) = NativeRefWrap::wrap_mut(text_chunk);
 
         Self(std::cell::RefCell::new(HTMLTextChunk {
             text_chunk: ref_wrap,
+            ancestors: ancestors.to_owned(),
+            sanitizer: sanitizer.map(|sanitizer| NativeRefWrap::wrap(sanitizer).0),
+            context,
         }))
     }
 
+    fn scan_text_args(&self, args: &[Value]) -> Result<(String, ContentType), Error> {
+        // reading the arguments, and rendering Markdown, can call back into Ruby,
+        // which may well use this same object, so it can't stay borrowed
+        let sanitizer = self.0.borrow().sanitizer.clone();
+
+        crate::scan_text_args(args, sanitizer.as_ref())
+    }
+
     fn to_s(&self) -> Result<String, Error> {
         let binding = self.0.borrow();
 
@@ -54,43 +73,92 @@ impl SelmaHTMLTextChunk {
         }
     }
 
-    fn before(&self, args: &[Value]) -> Result<(), Error> {
-        let mut binding = self.0.borrow_mut();
-        let text_chunk = binding.text_chunk.get_mut().unwrap();
+    fn get_ancestors(&self) -> Result<RArray, Error> {
+        let binding = self.0.borrow();
+        let array = RArray::new();
+
+        for ancestor in binding.ancestors.iter() {
+            array.push(RString::new(ancestor))?;
+        }
+
+        Ok(array)
+    }
+
+    fn context(&self) -> Result<RHash, Error> {
+        let binding = self.0.borrow();
+
+        // the context only lives as long as the rewrite does
+        if binding.text_chunk.get().is_ok() {
+            Ok(binding.context)
+        } else {
+            Err(Error::new(
+                exception::runtime_error(),
+                "`context` is not available",
+            ))
+        }
+    }
 
-        let (text_str, content_type) = match crate::scan_text_args(args) {
+    fn before(&self, args: &[Value]) -> Result<(), Error> {
+        let (text_str, content_type) = match self.scan_text_args(args) {
             Ok((text_str, content_type)) => (text_str, content_type),
             Err(err) => return Err(err),
         };
 
+        let mut binding = self.0.borrow_mut();
+        let text_chunk = match binding.text_chunk.get_mut() {
+            Ok(text_chunk) => text_chunk,
+            Err(_) => {
+                return Err(Error::new(
+                    exception::runtime_error(),
+                    "`before` is not available",
+                ))
+            }
+        };
+
         text_chunk.before(&text_str, content_type);
 
         Ok(())
     }
 
     fn after(&self, args: &[Value]) -> Result<(), Error> {
-        let mut binding = self.0.borrow_mut();
-        let text_chunk = binding.text_chunk.get_mut().unwrap();
-
-        let (text_str, content_type) = match crate::scan_text_args(args) {
+        let (text_str, content_type) = match self.scan_text_args(args) {
             Ok((text_str, content_type)) => (text_str, content_type),
             Err(err) => return Err(err),
         };
 
+        let mut binding = self.0.borrow_mut();
+        let text_chunk = match binding.text_chunk.get_mut() {
+            Ok(text_chunk) => text_chunk,
+            Err(_) => {
+                return Err(Error::new(
+                    exception::runtime_error(),
+                    "`after` is not available",
+                ))
+            }
+        };
+
         text_chunk.after(&text_str, content_type);
 
         Ok(())
     }
 
     fn replace(&self, args: &[Value]) -> Result<(), Error> {
-        let mut binding = self.0.borrow_mut();
-        let text_chunk = binding.text_chunk.get_mut().unwrap();
-
-        let (text_str, content_type) = match crate::scan_text_args(args) {
+        let (text_str, content_type) = match self.scan_text_args(args) {
             Ok((text_str, content_type)) => (text_str, content_type),
             Err(err) => return Err(err),
         };
 
+        let mut binding = self.0.borrow_mut();
+        let text_chunk = match binding.text_chunk.get_mut() {
+            Ok(text_chunk) => text_chunk,
+            Err(_) => {
+                return Err(Error::new(
+                    exception::runtime_error(),
+                    "`replace` is not available",
+                ))
+            }
+        };
+
         text_chunk.replace(&text_str, content_type);
 
         Ok(())
@@ -105,6 +173,8 @@ pub fn init(c_html: RClass) -> Result<(), Error> {
     c_text_chunk.define_method("to_s", method!(SelmaHTMLTextChunk::to_s, 0))?;
     c_text_chunk.define_method("content", method!(SelmaHTMLTextChunk::to_s, 0))?;
     c_text_chunk.define_method("text_type", method!(SelmaHTMLTextChunk::text_type, 0))?;
+    c_text_chunk.define_method("ancestors", method!(SelmaHTMLTextChunk::get_ancestors, 0))?;
+    c_text_chunk.define_method("context", method!(SelmaHTMLTextChunk::context, 0))?;
     c_text_chunk.define_method("before", method!(SelmaHTMLTextChunk::before, -1))?;
     c_text_chunk.define_method("after", method!(SelmaHTMLTextChunk::after, -1))?;
     c_text_chunk.define_method("replace", method!(SelmaHTMLTextChunk::replace, -1))?;
@@ -1,17 +1,37 @@
 extern crate core;
 
 use lol_html::html_content::ContentType;
-use magnus::{define_module, exception, scan_args, Error, Symbol, Value};
+use magnus::{define_module, exception, scan_args, value::ReprValue, Error, Symbol, Value};
+use native_ref_wrap::NativeRefWrap;
+use sanitizer::SelmaSanitizer;
 
+// the parts of Selma which don't need Ruby
+pub use selma_core::{profiles, tags, typographer};
+
+pub mod config;
+pub mod errors;
+pub mod extract;
 pub mod html;
+pub mod markdown;
+pub mod native_info;
 pub mod native_ref_wrap;
+pub mod registry;
 pub mod rewriter;
 pub mod sanitizer;
 pub mod selector;
-pub mod tags;
+pub mod serializer;
+pub mod text_replace;
 
+/// Reads the `(content, as: content_type)` arguments of the methods handlers
+/// add content with. HTML is run through the rewriter's `handler_sanitizer:`,
+/// if it has one, and Markdown is rendered by `Selma.markdown_renderer` before
+/// it's sanitized the same way (which, for Markdown, isn't optional). Text is
+/// escaped by lol_html either way.
 #[allow(clippy::let_unit_value)]
-fn scan_text_args(args: &[Value]) -> Result<(String, ContentType), magnus::Error> {
+fn scan_text_args(
+    args: &[Value],
+    sanitizer: Option<&NativeRefWrap<SelmaSanitizer>>,
+) -> Result<(String, ContentType), magnus::Error> {
     let args = scan_args::scan_args(args)?;
     let (text,): (String,) = args.required;
     let _: () = args.optional;
@@ -22,28 +42,69 @@ fn scan_text_args(args: &[Value]) -> Result<(String, ContentType), magnus::Error
     let kwargs = scan_args::get_kwargs::<_, (Symbol,), (), ()>(args.keywords, &["as"], &[])?;
     let as_sym = kwargs.required.0;
     let as_sym_str = as_sym.name().unwrap();
-    let content_type = if as_sym_str == "text" {
-        ContentType::Text
+    let sanitizer = match sanitizer.map(|sanitizer| sanitizer.get()) {
+        Some(Ok(sanitizer)) => Some(sanitizer),
+        Some(Err(err)) => return Err(Error::new(exception::runtime_error(), err)),
+        None => None,
+    };
+
+    if as_sym_str == "text" {
+        Ok((text, ContentType::Text))
     } else if as_sym_str == "html" {
-        ContentType::Html
+        match sanitizer {
+            Some(sanitizer) => Ok((
+                rewriter::SelmaRewriter::sanitize_fragment(sanitizer, text)?,
+                ContentType::Html,
+            )),
+            None => Ok((text, ContentType::Html)),
+        }
+    } else if as_sym_str == "markdown" {
+        let sanitizer = match sanitizer {
+            Some(sanitizer) => sanitizer,
+            None => {
+                return Err(Error::new(
+                    exception::runtime_error(),
+                    "`as: :markdown` needs the rewriter to have a `handler_sanitizer:`",
+                ))
+            }
+        };
+
+        let html = render_markdown(text)?;
+        Ok((
+            rewriter::SelmaRewriter::sanitize_fragment(sanitizer, html)?,
+            ContentType::Html,
+        ))
     } else {
-        return Err(Error::new(
+        Err(Error::new(
             exception::runtime_error(),
             format!("unknown symbol `{as_sym_str:?}`"),
+        ))
+    }
+}
+
+fn render_markdown(markdown: String) -> Result<String, magnus::Error> {
+    let renderer: Value = magnus::eval("Selma.markdown_renderer")?;
+    if renderer.is_nil() {
+        return Err(Error::new(
+            exception::runtime_error(),
+            "`as: :markdown` needs a `Selma.markdown_renderer` to render it with",
         ));
-    };
+    }
 
-    Ok((text, content_type))
+    renderer.funcall("call", (markdown,))
 }
 
 #[magnus::init]
 fn init() -> Result<(), Error> {
     let m_selma = define_module("Selma").expect("cannot define ::Selma module");
 
+    errors::init(m_selma).expect("cannot define Selma::Error classes");
     sanitizer::init(m_selma).expect("cannot define Selma::Sanitizer class");
     rewriter::init(m_selma).expect("cannot define Selma::Rewriter class");
     html::init(m_selma).expect("cannot define Selma::HTML class");
     selector::init(m_selma).expect("cannot define Selma::Selector class");
+    native_info::init(m_selma).expect("cannot define Selma::NATIVE_INFO");
+    registry::init(m_selma).expect("cannot define Selma::Registry module");
 
     Ok(())
 }
@@ -0,0 +1,555 @@
+use std::{borrow::Cow, cell::RefCell, rc::Rc};
+
+use lol_html::{
+    doc_text, element,
+    html_content::{Element, TextType},
+    DocumentContentHandlers, ElementContentHandlers, HtmlRewriter, Selector, Settings,
+};
+use magnus::{scan_args, typed_data::Obj, value::ReprValue, Value};
+
+use crate::{
+    errors::rewriting_error, rewriter::SelmaRewriter, sanitizer::SelmaSanitizer, tags::Tag,
+};
+
+/// @yard
+/// @def to_markdown(html, sanitizer: Selma::Sanitizer.new(Selma::Sanitizer::Config::DEFAULT))
+/// Converts HTML into Markdown, after sanitizing it, in a single pass of the
+/// same parser the rewriter uses. Headings, paragraphs, links, images,
+/// emphasis, lists, blockquotes, code and rules become their Markdown
+/// equivalents; any other element is reduced to its content.
+/// @param html [String] The HTML to convert
+/// @param sanitizer [Selma::Sanitizer] The sanitizer to clean the HTML with first; `nil` to convert it as it is
+/// @return [String]
+#[allow(clippy::let_unit_value)]
+pub fn to_markdown(args: &[Value]) -> Result<String, magnus::Error> {
+    let args = scan_args::scan_args(args)?;
+    let (html,): (String,) = args.required;
+    let _: () = args.optional;
+    let _: () = args.splat;
+    let _: () = args.trailing;
+    let _: () = args.block;
+
+    let kwargs = scan_args::get_kwargs::<_, (), (Option<Option<Obj<SelmaSanitizer>>>,), ()>(
+        args.keywords,
+        &[],
+        &["sanitizer"],
+    )?;
+    let (rb_sanitizer,) = kwargs.optional;
+
+    let sanitizer = match rb_sanitizer {
+        // no `sanitizer:` provided, use default
+        None => {
+            let sanitizer = Obj::wrap(SelmaSanitizer::new(&[])?);
+            sanitizer.funcall::<&str, (), Value>("setup", ())?;
+            Some(sanitizer)
+        }
+        Some(None) => None,
+        Some(Some(sanitizer)) => {
+            sanitizer.funcall::<&str, (), Value>("setup", ())?;
+            Some(sanitizer)
+        }
+    };
+    let html = match sanitizer {
+        Some(sanitizer) => SelmaRewriter::sanitize_fragment(sanitizer.get(), html)?,
+        None => html,
+    };
+
+    convert(&html)
+}
+
+fn convert(html: &str) -> Result<String, magnus::Error> {
+    let markdown = Rc::new(RefCell::new(Markdown::default()));
+
+    {
+        let element_content_handlers: Vec<(Cow<Selector>, ElementContentHandlers)> =
+            vec![element!("*", |el| {
+                let end = markdown.borrow_mut().start(el);
+                if let (Some(name), Some(end_tag_handlers)) = (end, el.end_tag_handlers()) {
+                    let markdown = markdown.clone();
+                    end_tag_handlers.push(Box::new(move |_end_tag| {
+                        markdown.borrow_mut().end(&name);
+                        Ok(())
+                    }));
+                }
+                Ok(())
+            })];
+        let document_content_handlers: Vec<DocumentContentHandlers> = vec![doc_text!(|t| {
+            let mut markdown = markdown.borrow_mut();
+            markdown.chunk.push_str(t.as_str());
+            if !t.last_in_text_node() {
+                return Ok(());
+            }
+
+            let text = std::mem::take(&mut markdown.chunk);
+            match t.text_type() {
+                TextType::Data | TextType::RCData => {
+                    let unescaped = escapist::unescape_html(text.as_bytes());
+                    markdown.text(&String::from_utf8_lossy(&unescaped));
+                }
+                _ => markdown.text(&text),
+            }
+            Ok(())
+        })];
+
+        let mut rewriter = HtmlRewriter::new(
+            Settings {
+                element_content_handlers,
+                document_content_handlers,
+                ..Settings::default()
+            },
+            |_: &[u8]| {},
+        );
+        if let Err(err) = rewriter.write(html.as_bytes()) {
+            return Err(rewriting_error(format!(
+                "Failed to convert HTML to Markdown: {err}"
+            )));
+        }
+        if let Err(err) = rewriter.end() {
+            return Err(rewriting_error(format!(
+                "Failed to convert HTML to Markdown: {err}"
+            )));
+        }
+    }
+
+    let markdown = markdown.take();
+    Ok(markdown.output.trim_end().to_string())
+}
+
+/// The Markdown written so far, and what's open around where it's up to.
+#[derive(Default)]
+struct Markdown {
+    output: String,
+    /// what each line starts with, within the blockquotes and list items which
+    /// are open
+    prefixes: Vec<String>,
+    lists: Vec<List>,
+    /// how many line breaks are owed before whatever comes next
+    breaks: usize,
+    /// whether a `<br>` is owed
+    hard_break: bool,
+    /// whether a space is owed, for the whitespace which was collapsed
+    space: bool,
+    /// whether a line was started, but not its prefix
+    mid_line: bool,
+    /// whether the last thing written was a list item's marker, or a heading's,
+    /// which blocks within it go right after
+    after_marker: bool,
+    /// the opening `*`s and `[`s which are waiting for their content
+    marks: String,
+    /// how each open `<a>` ends, if it's a link
+    links: Vec<Option<String>>,
+    /// the text of the `<code>` being collected
+    code: Option<String>,
+    /// the info string and text of the `<pre>` being collected
+    pre: Option<(String, String)>,
+    /// how many of the elements whose text isn't content are open
+    skipping: usize,
+    /// the text node being collected, since entities may be split
+    chunk: String,
+}
+
+struct List {
+    ordered: bool,
+    next: u64,
+}
+
+impl Markdown {
+    /// Elements whose text isn't content.
+    const SKIPPED: &'static [&'static str] = &["head", "script", "style", "template"];
+
+    /// Handles an element's start tag, returning its name when there's
+    /// something to do once it ends.
+    fn start(&mut self, el: &mut Element) -> Option<String> {
+        let name = el.tag_name().to_ascii_lowercase();
+
+        if self.skipping > 0 {
+            return None;
+        }
+        if Self::SKIPPED.contains(&name.as_str()) {
+            if !el.can_have_content() {
+                return None;
+            }
+            self.skipping += 1;
+            return Some(name);
+        }
+
+        match name.as_str() {
+            "h1" | "h2" | "h3" | "h4" | "h5" | "h6" => {
+                let level = usize::from(name.as_bytes()[1] - b'0');
+                self.block(2);
+                self.marker(&format!("{} ", "#".repeat(level)));
+            }
+            "blockquote" => {
+                // a quote which starts a list item starts on the same line
+                if self.after_marker {
+                    self.marker("> ");
+                }
+                self.block(2);
+                self.prefixes.push("> ".to_string());
+            }
+            "ul" | "ol" => {
+                self.block(if self.lists.is_empty() { 2 } else { 1 });
+                let start = el
+                    .get_attribute("start")
+                    .and_then(|start| start.parse().ok());
+                self.lists.push(List {
+                    ordered: name == "ol",
+                    next: start.unwrap_or(1),
+                });
+            }
+            "li" => {
+                self.after_marker = false;
+                self.block(1);
+                let marker = match self.lists.last_mut() {
+                    Some(list) if list.ordered => {
+                        list.next += 1;
+                        format!("{}. ", list.next - 1)
+                    }
+                    _ => "- ".to_string(),
+                };
+                self.marker(&marker);
+                self.prefixes.push(" ".repeat(marker.len()));
+            }
+            "pre" => {
+                self.block(2);
+                self.pre = Some((Self::language(el).unwrap_or_default(), String::new()));
+            }
+            "code" => match self.pre.as_mut() {
+                Some((info, _)) => {
+                    if info.is_empty() {
+                        *info = Self::language(el).unwrap_or_default();
+                    }
+                    return None;
+                }
+                None => self.code = Some(String::new()),
+            },
+            "em" | "i" => self.open_mark("*"),
+            "strong" | "b" => self.open_mark("**"),
+            "del" | "s" | "strike" => self.open_mark("~~"),
+            "a" => {
+                let link = el.get_attribute("href").map(|href| {
+                    let title = el.get_attribute("title");
+                    format!("]({})", Self::destination(&href, title.as_deref()))
+                });
+                if link.is_some() {
+                    self.open_mark("[");
+                }
+                self.links.push(link);
+            }
+            "img" => {
+                if self.code.is_some() || self.pre.is_some() {
+                    return None;
+                }
+                if let Some(src) = el.get_attribute("src") {
+                    let alt = el.get_attribute("alt").unwrap_or_default();
+                    let title = el.get_attribute("title");
+                    let image = format!(
+                        "![{}]({})",
+                        Self::escape(&alt.split_whitespace().collect::<Vec<_>>().join(" "), false),
+                        Self::destination(&src, title.as_deref())
+                    );
+                    self.write(&image);
+                }
+                return None;
+            }
+            "br" => {
+                self.hard_break = self.mid_line;
+                return None;
+            }
+            "hr" => {
+                self.block(2);
+                self.write("---");
+                self.block(2);
+                return None;
+            }
+            "tr" => self.block(1),
+            // tables are flattened, a row to a line
+            "td" | "th" => {
+                self.space = self.mid_line;
+                return None;
+            }
+            _ if Tag::is_block(Tag::tag_from_tag_name(&name)) => self.block(2),
+            _ => return None,
+        }
+
+        Some(name)
+    }
+
+    /// Handles the end tag of an element which `start` had something to do
+    /// with.
+    fn end(&mut self, name: &str) {
+        if Self::SKIPPED.contains(&name) {
+            self.skipping -= 1;
+            return;
+        }
+
+        match name {
+            "h1" | "h2" | "h3" | "h4" | "h5" | "h6" => {
+                self.after_marker = false;
+                self.block(2);
+            }
+            "blockquote" => {
+                self.after_marker = false;
+                self.prefixes.pop();
+                self.block(2);
+            }
+            "ul" | "ol" => {
+                self.lists.pop();
+                self.block(if self.lists.is_empty() { 2 } else { 1 });
+            }
+            "li" => {
+                self.after_marker = false;
+                self.prefixes.pop();
+                self.block(1);
+            }
+            "tr" => self.block(1),
+            "pre" => {
+                if let Some((info, code)) = self.pre.take() {
+                    self.fenced(&info, &code);
+                }
+                self.block(2);
+            }
+            "code" => {
+                if let Some(code) = self.code.take() {
+                    self.code_span(&code);
+                }
+            }
+            "em" | "i" => self.close_mark("*"),
+            "strong" | "b" => self.close_mark("**"),
+            "del" | "s" | "strike" => self.close_mark("~~"),
+            "a" => {
+                if let Some(Some(link)) = self.links.pop() {
+                    // a link to nothing is left out
+                    match self.marks.strip_suffix('[') {
+                        Some(marks) => self.marks = marks.to_string(),
+                        None if self.code.is_none() && self.pre.is_none() => {
+                            self.output.push_str(&link)
+                        }
+                        None => {}
+                    }
+                }
+            }
+            _ => {
+                self.after_marker = false;
+                self.block(2);
+            }
+        }
+    }
+
+    fn text(&mut self, text: &str) {
+        if self.skipping > 0 {
+            return;
+        }
+        if let Some((_, code)) = self.pre.as_mut() {
+            code.push_str(text);
+            return;
+        }
+        if let Some(code) = self.code.as_mut() {
+            code.push_str(text);
+            return;
+        }
+
+        let mut word_start = None;
+        for (index, c) in text.char_indices() {
+            if c.is_whitespace() {
+                if let Some(start) = word_start.take() {
+                    self.word(&text[start..index]);
+                }
+                self.space = self.mid_line && !self.after_marker;
+            } else if word_start.is_none() {
+                word_start = Some(index);
+            }
+        }
+        if let Some(start) = word_start {
+            self.word(&text[start..]);
+        }
+    }
+
+    fn word(&mut self, word: &str) {
+        let line_start = !self.mid_line || self.breaks > 0 || self.hard_break || self.after_marker;
+        self.flush();
+        self.output.push_str(&Self::escape(word, line_start));
+    }
+
+    /// Writes out Markdown's own syntax, which isn't escaped.
+    fn write(&mut self, markdown: &str) {
+        self.flush();
+        self.output.push_str(markdown);
+    }
+
+    /// Writes out what a line (say, a list item's) starts with, which whatever
+    /// follows goes right after.
+    fn marker(&mut self, marker: &str) {
+        self.write(marker);
+        self.after_marker = true;
+    }
+
+    /// Asks for (at least) this many line breaks before whatever comes next;
+    /// two make a blank line.
+    fn block(&mut self, breaks: usize) {
+        if self.after_marker {
+            return;
+        }
+        self.breaks = self.breaks.max(breaks);
+        self.hard_break = false;
+        self.space = false;
+    }
+
+    /// Writes out the line breaks, prefixes, space and marks which are owed,
+    /// before some content.
+    fn flush(&mut self) {
+        if self.breaks > 0 && !self.output.is_empty() {
+            let prefix = self.prefixes.concat();
+            self.output.push('\n');
+            for _ in 1..self.breaks {
+                self.output.push_str(prefix.trim_end());
+                self.output.push('\n');
+            }
+            self.mid_line = false;
+        } else if self.hard_break {
+            self.output.push_str("\\\n");
+            self.mid_line = false;
+        }
+        self.breaks = 0;
+        self.hard_break = false;
+
+        if !self.mid_line {
+            self.output.push_str(&self.prefixes.concat());
+            self.mid_line = true;
+            self.space = false;
+        }
+        if self.space {
+            self.output.push(' ');
+            self.space = false;
+        }
+        self.output.push_str(&self.marks);
+        self.marks.clear();
+        self.after_marker = false;
+    }
+
+    fn open_mark(&mut self, mark: &str) {
+        if self.code.is_none() && self.pre.is_none() {
+            self.marks.push_str(mark);
+        }
+    }
+
+    /// Closes some emphasis, before any whitespace which was collapsed, so that
+    /// `<em>so </em>much` comes out as `*so* much`.
+    fn close_mark(&mut self, mark: &str) {
+        if self.code.is_some() || self.pre.is_some() {
+            return;
+        }
+
+        // emphasis around nothing is left out
+        match self.marks.strip_suffix(mark) {
+            Some(marks) => self.marks = marks.to_string(),
+            None => self.output.push_str(mark),
+        }
+    }
+
+    fn code_span(&mut self, code: &str) {
+        let code = code.replace(['\n', '\r'], " ");
+        if code.is_empty() {
+            return;
+        }
+
+        let fence = "`".repeat(Self::longest_run(&code, '`') + 1);
+        let padding = if code.starts_with('`') || code.ends_with('`') {
+            " "
+        } else {
+            ""
+        };
+        self.write(&format!("{fence}{padding}{code}{padding}{fence}"));
+    }
+
+    fn fenced(&mut self, info: &str, code: &str) {
+        let code = code.strip_suffix('\n').unwrap_or(code);
+        let fence = "`".repeat(Self::longest_run(code, '`').max(2) + 1);
+        let prefix = self.prefixes.concat();
+
+        self.write(&fence);
+        self.output.push_str(info);
+        for line in code.split('\n') {
+            self.output.push('\n');
+            self.output.push_str(&prefix);
+            self.output.push_str(line);
+        }
+        self.output.push('\n');
+        self.output.push_str(&prefix);
+        self.output.push_str(&fence);
+    }
+
+    /// The language of a `<pre>` or `<code>`, from a `language-` (or `lang-`)
+    /// class.
+    fn language(el: &Element) -> Option<String> {
+        el.get_attribute("class")?
+            .split_ascii_whitespace()
+            .find_map(|class| {
+                class
+                    .strip_prefix("language-")
+                    .or_else(|| class.strip_prefix("lang-"))
+            })
+            .filter(|language| !language.contains('`'))
+            .map(str::to_string)
+    }
+
+    fn destination(url: &str, title: Option<&str>) -> String {
+        let url = if url.is_empty() || url.contains([' ', '(', ')', '<', '>']) {
+            format!("<{}>", url.replace('<', "%3C").replace('>', "%3E"))
+        } else {
+            url.to_string()
+        };
+
+        match title {
+            Some(title) => format!("{url} \"{}\"", title.replace('"', "\\\"")),
+            None => url,
+        }
+    }
+
+    fn longest_run(text: &str, c: char) -> usize {
+        let mut longest = 0;
+        let mut run = 0;
+        for next in text.chars() {
+            run = if next == c { run + 1 } else { 0 };
+            longest = longest.max(run);
+        }
+
+        longest
+    }
+
+    /// Escapes whatever Markdown would take for syntax in a word of text; at
+    /// the start of a line, that includes what starts a heading, a quote or a
+    /// list item.
+    fn escape(word: &str, line_start: bool) -> String {
+        let mut escaped = String::with_capacity(word.len());
+
+        if line_start {
+            if word.starts_with(['#', '>', '-', '+', '=']) {
+                escaped.push('\\');
+            } else if let Some(digits) = word.find(|c: char| !c.is_ascii_digit()) {
+                if digits > 0 && word[digits..].starts_with(['.', ')']) {
+                    escaped.push_str(&word[..digits]);
+                    escaped.push('\\');
+                    escaped.push_str(&Self::escape(&word[digits..], false));
+                    return escaped;
+                }
+            }
+        }
+
+        for (index, c) in word.char_indices() {
+            let reference = c == '&'
+                && word[index + 1..].find(';').is_some_and(|end| {
+                    end > 0
+                        && word[index + 1..index + 1 + end]
+                            .bytes()
+                            .all(|b| b.is_ascii_alphanumeric() || b == b'#')
+                });
+            if reference || matches!(c, '\\' | '`' | '*' | '_' | '[' | ']' | '<' | '~') {
+                escaped.push('\\');
+            }
+            escaped.push(c);
+        }
+
+        escaped
+    }
+}
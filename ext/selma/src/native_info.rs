@@ -0,0 +1,79 @@
+use lol_html::MemorySettings;
+use magnus::{value::ReprValue, Error, Module, RArray, RHash, RModule, RString, Symbol};
+
+/// The parts of CSS that lol_html can match (and so, that handlers'
+/// selectors can use).
+const SELECTOR_COMBINATORS: &[&str] = &["descendant", "child"];
+const SELECTOR_PSEUDO_CLASSES: &[&str] = &[
+    "first-child",
+    "first-of-type",
+    "not",
+    "nth-child",
+    "nth-of-type",
+];
+const SELECTOR_ATTRIBUTE_OPERATORS: &[&str] = &["=", "~=", "|=", "^=", "$=", "*="];
+
+fn frozen_string(string: &str) -> RString {
+    let string = RString::new(string);
+    string.freeze();
+    string
+}
+
+fn frozen_strings(strings: &[&str]) -> Result<RArray, Error> {
+    let array = RArray::new();
+    for string in strings {
+        array.push(frozen_string(string))?;
+    }
+    array.freeze();
+    Ok(array)
+}
+
+/// Defines `Selma::NATIVE_INFO`, describing what the extension was built
+/// with, for bug reports and for apps to check what's supported at runtime.
+/// Everything in it is frozen.
+pub fn init(m_selma: RModule) -> Result<(), Error> {
+    // the rewriters always use lol_html's default memory settings
+    let memory = MemorySettings::default();
+    let memory_limits = RHash::new();
+    memory_limits.aset(Symbol::new("configurable"), false)?;
+    memory_limits.aset(
+        Symbol::new("preallocated_parsing_buffer_size"),
+        memory.preallocated_parsing_buffer_size,
+    )?;
+    memory_limits.aset(
+        Symbol::new("max_allowed_memory_usage"),
+        memory.max_allowed_memory_usage,
+    )?;
+    memory_limits.freeze();
+
+    let selectors = RHash::new();
+    selectors.aset(
+        Symbol::new("combinators"),
+        frozen_strings(SELECTOR_COMBINATORS)?,
+    )?;
+    selectors.aset(
+        Symbol::new("pseudo_classes"),
+        frozen_strings(SELECTOR_PSEUDO_CLASSES)?,
+    )?;
+    selectors.aset(
+        Symbol::new("attribute_operators"),
+        frozen_strings(SELECTOR_ATTRIBUTE_OPERATORS)?,
+    )?;
+    selectors.freeze();
+
+    let info = RHash::new();
+    info.aset(
+        Symbol::new("lol_html_version"),
+        frozen_string(env!("SELMA_LOL_HTML_VERSION")),
+    )?;
+    info.aset(
+        Symbol::new("selma_core_version"),
+        frozen_string(selma_core::VERSION),
+    )?;
+    info.aset(Symbol::new("encodings"), frozen_strings(&["UTF-8"])?)?;
+    info.aset(Symbol::new("memory_limits"), memory_limits)?;
+    info.aset(Symbol::new("selectors"), selectors)?;
+    info.freeze();
+
+    m_selma.const_set("NATIVE_INFO", info)
+}
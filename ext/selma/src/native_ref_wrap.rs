@@ -34,6 +34,16 @@ pub struct NativeRefWrap<R> {
     poisoned: Rc<Cell<bool>>,
 }
 
+// NOTE: copies share the anchor, so they're poisoned together.
+impl<R> Clone for NativeRefWrap<R> {
+    fn clone(&self) -> Self {
+        NativeRefWrap {
+            inner_ptr: self.inner_ptr,
+            poisoned: Rc::clone(&self.poisoned),
+        }
+    }
+}
+
 impl<R> NativeRefWrap<R> {
     pub fn wrap<I>(inner: &I) -> (Self, Anchor) {
         let wrap = NativeRefWrap {
@@ -0,0 +1,78 @@
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use magnus::{
+    function,
+    typed_data::Obj,
+    value::{Lazy, ReprValue},
+    Error, Module, Object, RArray, RHash, RModule, Ruby, Symbol, Value,
+};
+
+use crate::{rewriter::SelmaRewriter, sanitizer::SelmaSanitizer};
+
+/// The registered policies, by name, as `[generation, rewriter, sanitizer,
+/// handlers]`. What the rewriter was built from is kept alongside it, so that
+/// nothing it holds on to is collected while the policy's registered.
+///
+/// Every function here holds the GVL and, with only symbols for keys, never
+/// calls back into Ruby, so none of them can be interleaved with another
+/// thread's, and the registry needs no lock of its own.
+static ENTRIES: Lazy<RHash> = Lazy::new(|_ruby| RHash::new());
+
+/// Bumped by every `register`, so that threads can tell that their copy of a
+/// policy's rewriter is out of date.
+static GENERATION: AtomicUsize = AtomicUsize::new(0);
+
+fn store(
+    name: Symbol,
+    rewriter: Obj<SelmaRewriter>,
+    sanitizer: Option<Obj<SelmaSanitizer>>,
+    handlers: RArray,
+) -> Result<(), Error> {
+    let ruby = Ruby::get().unwrap();
+    let generation = GENERATION.fetch_add(1, Ordering::Relaxed) + 1;
+
+    let entry = RArray::with_capacity(4);
+    entry.push(generation)?;
+    entry.push(rewriter)?;
+    entry.push(sanitizer)?;
+    entry.push(handlers)?;
+    entry.freeze();
+    ruby.get_inner(&ENTRIES).aset(name, entry)
+}
+
+/// The policy `name`'s `[generation, rewriter]`, or `nil` if there's no such
+/// policy.
+fn fetch(name: Symbol) -> Result<Option<(usize, Obj<SelmaRewriter>)>, Error> {
+    let ruby = Ruby::get().unwrap();
+    match ruby.get_inner(&ENTRIES).get(name) {
+        None => Ok(None),
+        Some(entry) => {
+            let entry = RArray::from_value(entry).unwrap();
+            Ok(Some((entry.entry(0)?, entry.entry(1)?)))
+        }
+    }
+}
+
+fn delete(name: Symbol) -> Result<bool, Error> {
+    let ruby = Ruby::get().unwrap();
+    let deleted: Option<Value> = ruby.get_inner(&ENTRIES).delete(name)?;
+    Ok(deleted.is_some())
+}
+
+fn names() -> Result<RArray, Error> {
+    let ruby = Ruby::get().unwrap();
+    ruby.get_inner(&ENTRIES).funcall("keys", ())
+}
+
+pub fn init(m_selma: RModule) -> Result<(), Error> {
+    let m_registry = m_selma
+        .define_module("Registry")
+        .expect("cannot define module Selma::Registry");
+
+    m_registry.define_singleton_method("store", function!(store, 4))?;
+    m_registry.define_singleton_method("fetch", function!(fetch, 1))?;
+    m_registry.define_singleton_method("delete", function!(delete, 1))?;
+    m_registry.define_singleton_method("native_names", function!(names, 0))?;
+
+    Ok(())
+}
@@ -1,286 +1,1582 @@
 use lol_html::{
-    doc_comments, doctype, element,
-    html_content::{Element, TextChunk},
+    doc_comments, doc_text, element,
+    errors::RewritingError,
+    html_content::{ContentType, Element, TextChunk},
     text, DocumentContentHandlers, ElementContentHandlers, HtmlRewriter, Selector, Settings,
 };
 use magnus::{
-    exception, function, method, scan_args,
-    typed_data::Obj,
-    value::{Opaque, ReprValue},
-    Module, Object, RArray, RModule, Ruby, Value,
+    block::Proc,
+    exception, function, gc, method,
+    r_hash::ForEach,
+    scan_args,
+    typed_data::{DataTypeFunctions, Obj},
+    value::{qtrue, Opaque, ReprValue},
+    Module, Object, RArray, RHash, RModule, Ruby, Symbol, Value,
 };
 
-use std::{borrow::Cow, cell::RefCell, primitive::str, rc::Rc};
+use std::{
+    borrow::Cow,
+    cell::{Cell, RefCell},
+    collections::hash_map::RandomState,
+    hash::{BuildHasher, Hasher},
+    primitive::str,
+    rc::Rc,
+    time::{Duration, Instant},
+};
+
+use selma_core::{
+    sanitize::{self, Deadline, Malformed, SanitizeContext},
+    sanitizer::{Removal, Sanitizer},
+};
+
+pub use selma_core::sanitize::SkippedSubtrees;
 
 use crate::{
+    errors::{halted_error, invalid_state_error, rewriting_error, sanitize_error},
+    extract::{Extracted, Extraction, Extractor},
     html::{element::SelmaHTMLElement, end_tag::SelmaHTMLEndTag, text_chunk::SelmaHTMLTextChunk},
     sanitizer::SelmaSanitizer,
     selector::SelmaSelector,
+    serializer::Serializer,
     tags::Tag,
+    text_replace::{SelmaTextReplace, TextReplacements},
 };
 
 #[derive(Clone)]
 pub struct Handler {
     rb_handler: Opaque<Value>,
     rb_selector: Opaque<Obj<SelmaSelector>>,
-    // total_element_handler_calls: usize,
-    // total_elapsed_element_handlers: f64,
+    total_element_handler_calls: Cell<usize>,
+    total_elapsed_element_handlers: Cell<f64>,
+    total_text_handler_calls: Cell<usize>,
+    total_elapsed_text_handlers: Cell<f64>,
+}
 
-    // total_text_handler_calls: usize,
-    // total_elapsed_text_handlers: f64,
+impl Handler {
+    fn reset_stats(&self) {
+        self.total_element_handler_calls.set(0);
+        self.total_elapsed_element_handlers.set(0.0);
+        self.total_text_handler_calls.set(0);
+        self.total_elapsed_text_handlers.set(0.0);
+    }
 }
 
 pub struct Rewriter {
     sanitizer: Option<SelmaSanitizer>,
+    /// sanitizers used instead of `sanitizer` for the elements matching a selector
+    scoped_sanitizers: Vec<(String, SelmaSanitizer)>,
+    handler_sanitizer: Option<SelmaSanitizer>,
     handlers: Vec<Handler>,
-    // total_elapsed: f64,
+    text_replacements: Vec<SelmaTextReplace>,
+    typographer: bool,
+    extraction: Extraction,
+    /// what `extraction` asked for, from the latest rewrite
+    extracted: RefCell<Extracted>,
+    profile: bool,
+    parser_stats: ParserStats,
+    malformed: Malformed,
+    on_removal: Option<Opaque<Value>>,
+    removals: Removals,
+    /// given to every `<script>` and `<style>` as its `nonce`
+    csp_nonce: Option<String>,
+    serializer: Serializer,
+    /// how long a `#rewrite` may take
+    timeout: Option<Duration>,
+    total_bytes_in: Cell<usize>,
+    total_bytes_out: Cell<usize>,
+    total_elapsed_sanitization: Cell<f64>,
+    total_elapsed: Cell<f64>,
+}
+
+/// What the parser went through during the handler pass of the latest
+/// rewrite, for `#last_run_stats`. The tokens are only counted when profiling,
+/// since that takes handlers of its own.
+#[derive(Default)]
+struct ParserStats {
+    bytes_parsed: Cell<usize>,
+    selectors: Cell<usize>,
+    elements: Cell<usize>,
+    text_chunks: Cell<usize>,
+    comments: Cell<usize>,
+}
+
+impl ParserStats {
+    fn reset(&self) {
+        self.bytes_parsed.set(0);
+        self.selectors.set(0);
+        self.elements.set(0);
+        self.text_chunks.set(0);
+        self.comments.set(0);
+    }
+
+    fn increment(count: &Cell<usize>) {
+        count.set(count.get() + 1);
+    }
+}
+
+/// An element one of a handler's selectors matched, for `#explain`.
+struct Match {
+    handler_index: usize,
+    /// `element` or `text_within`
+    kind: &'static str,
+    selector: String,
+    tag_name: String,
+    /// where its start tag ends
+    end: usize,
+}
+
+/// The elements the sanitizer took out during the latest rewrite. Each one is
+/// only kept track of when there's an `on_removal:` callback, which is then
+/// called for all of them once the sanitizer's done, rather than in the middle
+/// of parsing.
+#[derive(Default)]
+struct Removals {
+    count: Cell<usize>,
+    removed: Option<RefCell<Vec<Removed>>>,
+}
+
+struct Removed {
+    tag_name: String,
+    reason: Removal,
+    attributes: Vec<(String, String)>,
+}
+
+impl Removals {
+    fn logged() -> Self {
+        Self {
+            count: Cell::new(0),
+            removed: Some(RefCell::new(vec![])),
+        }
+    }
+
+    fn reset(&self) {
+        self.count.set(0);
+        if let Some(removed) = &self.removed {
+            removed.borrow_mut().clear();
+        }
+    }
+
+    fn record(&self, element: &Element, reason: Removal) {
+        self.count.set(self.count.get() + 1);
+
+        if let Some(removed) = &self.removed {
+            let attributes = element
+                .attributes()
+                .iter()
+                .map(|attr| (attr.name(), Sanitizer::unmarked_value(attr.value())))
+                .collect();
+            removed.borrow_mut().push(Removed {
+                tag_name: element.tag_name(),
+                reason,
+                attributes,
+            });
+        }
+    }
+
+    /// Calls `on_removal` with each element which was recorded, as
+    /// `(tag_name, reason, attributes)`.
+    fn report(&self, on_removal: Value) -> Result<(), magnus::Error> {
+        let removed = match &self.removed {
+            Some(removed) => removed.take(),
+            None => return Ok(()),
+        };
+
+        for removed in removed {
+            let rb_attributes = RHash::new();
+            for (name, value) in removed.attributes {
+                rb_attributes.aset(name, value)?;
+            }
+            on_removal.funcall::<_, _, Value>(
+                "call",
+                (
+                    removed.tag_name,
+                    Symbol::new(removed.reason.name()),
+                    rb_attributes,
+                ),
+            )?;
+        }
+
+        Ok(())
+    }
+}
+
+/// The `malformed:` policy, which raises a `Selma::RewritingError` by default.
+fn malformed_from_symbol(symbol: Option<Symbol>) -> Result<Malformed, magnus::Error> {
+    let symbol = match symbol {
+        Some(symbol) => symbol,
+        None => return Ok(Malformed::default()),
+    };
+
+    match symbol.name()?.as_ref() {
+        "error" => Ok(Malformed::Error),
+        "pass_through" => Ok(Malformed::PassThrough),
+        "drop" => Ok(Malformed::Drop),
+        name => Err(magnus::Error::new(
+            exception::arg_error(),
+            format!("unknown malformed markup policy `:{name}`"),
+        )),
+    }
+}
+
+/// What a handler which called `halt!` wants `#rewrite` to do.
+#[derive(Clone, Copy, PartialEq)]
+enum Halt {
+    ReturnOutput,
+    Raise,
+}
+
+/// The Ruby objects a rewriter holds on to, which the GC has to be told about.
+struct GcRoots {
+    handlers: Vec<(Opaque<Value>, Opaque<Obj<SelmaSelector>>)>,
+    on_removal: Option<Opaque<Value>>,
+}
+
+impl GcRoots {
+    fn new(handlers: &[Handler], on_removal: Option<Opaque<Value>>) -> Self {
+        Self {
+            handlers: handlers
+                .iter()
+                .map(|handler| (handler.rb_handler, handler.rb_selector))
+                .collect(),
+            on_removal,
+        }
+    }
+}
+
+/// The rewriter itself stays borrowed for as long as it's rewriting, so
+/// whether a handler has halted the rewrite, and what the GC must mark, are
+/// kept beside it.
+#[magnus::wrap(class = "Selma::Rewriter", mark)]
+pub struct SelmaRewriter(std::cell::RefCell<Rewriter>, Cell<Option<Halt>>, GcRoots);
+
+impl DataTypeFunctions for SelmaRewriter {
+    fn mark(&self, marker: &gc::Marker) {
+        for (rb_handler, rb_selector) in self.2.handlers.iter() {
+            marker.mark(*rb_handler);
+            marker.mark(*rb_selector);
+        }
+        if let Some(on_removal) = self.2.on_removal {
+            marker.mark(on_removal);
+        }
+    }
+}
+
+/// Brackets elements in the output with comments no document could guess, so
+/// that they can be cut out of it once the rewrite is over. That's how elements
+/// which a handler's `relocate:` selector matches are handed to its
+/// `handle_relocated`, and how `Element#capture_subtree` gets to see all of an
+/// element's HTML.
+pub struct SubtreeMarkers {
+    prefix: String,
+    marked: Cell<bool>,
+    /// the blocks given to `capture_subtree`, kept here (and so on the stack)
+    /// to be safe from the GC until they're called
+    captures: RArray,
+    capture_limits: RefCell<Vec<usize>>,
+}
+
+enum Subtree {
+    Relocated(usize),
+    Captured(usize),
+}
+
+impl SubtreeMarkers {
+    fn new() -> Self {
+        let nonce = RandomState::new().build_hasher().finish();
+
+        Self {
+            prefix: format!("<!--selma-subtree:{nonce:x}:"),
+            marked: Cell::new(false),
+            captures: RArray::new(),
+            capture_limits: RefCell::new(vec![]),
+        }
+    }
+
+    fn mark(&self, element: &mut Element, id: &str) {
+        self.marked.set(true);
+        element.before(&format!("{}{id}:start-->", self.prefix), ContentType::Html);
+        element.after(&format!("{}{id}:end-->", self.prefix), ContentType::Html);
+    }
+
+    fn mark_relocated(&self, element: &mut Element, handler_index: usize) {
+        self.mark(element, &format!("r{handler_index}"));
+    }
+
+    pub fn mark_captured(
+        &self,
+        element: &mut Element,
+        block: Proc,
+        max_bytes: usize,
+    ) -> Result<(), magnus::Error> {
+        let capture_index = self.captures.len();
+        self.captures.push(block)?;
+        self.capture_limits.borrow_mut().push(max_bytes);
+
+        self.mark(element, &format!("c{capture_index}"));
+        Ok(())
+    }
+
+    fn parse_marker(&self, marker: &str, handler_count: usize) -> Option<(Subtree, bool)> {
+        let (id, kind) = marker.split_once(':')?;
+        let index = id.get(1..)?.parse::<usize>().ok()?;
+        let subtree = match id.as_bytes()[0] {
+            b'r' if index < handler_count => Subtree::Relocated(index),
+            b'c' if index < self.captures.len() => Subtree::Captured(index),
+            _ => return None,
+        };
+
+        Some((subtree, kind == "start"))
+    }
+
+    /// Replaces every captured subtree in `html` with what its block returned,
+    /// and removes the relocated ones, returning what's left along with what
+    /// each handler relocated, in document order. Subtrees within a relocated
+    /// one stay where they are, and elements which were removed after all
+    /// (leaving nothing between their markers) aren't relocated.
+    fn extract(
+        &self,
+        html: &str,
+        handler_count: usize,
+        handler_sanitizer: Option<&SelmaSanitizer>,
+    ) -> Result<(String, Vec<Vec<String>>), magnus::Error> {
+        let mut remaining = String::with_capacity(html.len());
+        let mut relocated: Vec<Vec<String>> = vec![vec![]; handler_count];
+        let mut open: Vec<(Subtree, String)> = vec![];
+
+        let mut rest = html;
+        loop {
+            let (before, marker) = match rest.find(&self.prefix) {
+                Some(index) => (&rest[..index], Some(&rest[index + self.prefix.len()..])),
+                None => (rest, None),
+            };
+            match open.last_mut() {
+                Some((_, html)) => html.push_str(before),
+                None => remaining.push_str(before),
+            }
+
+            let marker = match marker {
+                Some(marker) => marker,
+                None => break,
+            };
+            let end = marker.find("-->").unwrap_or(marker.len());
+            let parsed = self.parse_marker(&marker[..end], handler_count);
+            rest = marker.get(end + 3..).unwrap_or("");
+
+            match parsed {
+                Some((subtree, true)) => open.push((subtree, String::new())),
+                Some((_, false)) => {
+                    if let Some((subtree, html)) = open.pop() {
+                        let nested = !open.is_empty();
+                        let html =
+                            self.close(subtree, html, nested, handler_sanitizer, &mut relocated)?;
+                        match open.last_mut() {
+                            Some((_, outer)) => outer.push_str(&html),
+                            None => remaining.push_str(&html),
+                        }
+                    }
+                }
+                None => {}
+            }
+        }
+
+        // elements which are never closed run to the end of the document
+        while let Some((subtree, html)) = open.pop() {
+            let nested = !open.is_empty();
+            let html = self.close(subtree, html, nested, handler_sanitizer, &mut relocated)?;
+            match open.last_mut() {
+                Some((_, outer)) => outer.push_str(&html),
+                None => remaining.push_str(&html),
+            }
+        }
+
+        Ok((remaining, relocated))
+    }
+
+    /// Removes the markers from `html`, leaving every subtree where it is.
+    fn strip(&self, html: Vec<u8>) -> Vec<u8> {
+        if !self.marked.get() {
+            return html;
+        }
+
+        let html = String::from_utf8_lossy(&html);
+        let mut stripped = String::with_capacity(html.len());
+        let mut rest: &str = &html;
+        while let Some(index) = rest.find(&self.prefix) {
+            stripped.push_str(&rest[..index]);
+            rest = match rest[index..].find("-->") {
+                Some(end) => &rest[index + end + 3..],
+                None => "",
+            };
+        }
+        stripped.push_str(rest);
+
+        stripped.into_bytes()
+    }
+
+    /// What a subtree leaves behind where it was.
+    fn close(
+        &self,
+        subtree: Subtree,
+        html: String,
+        nested: bool,
+        handler_sanitizer: Option<&SelmaSanitizer>,
+        relocated: &mut [Vec<String>],
+    ) -> Result<String, magnus::Error> {
+        match subtree {
+            Subtree::Relocated(_) if nested => Ok(html),
+            Subtree::Relocated(handler_index) => {
+                if !html.is_empty() {
+                    relocated[handler_index].push(html);
+                }
+                Ok(String::new())
+            }
+            Subtree::Captured(capture_index) => {
+                // subtrees which are too big are left as they are
+                if html.len() > self.capture_limits.borrow()[capture_index] {
+                    return Ok(html);
+                }
+
+                let block: Proc = self.captures.entry(capture_index as isize)?;
+                match block.call::<_, Option<String>>((html.clone(),))? {
+                    Some(replacement) => match handler_sanitizer {
+                        Some(sanitizer) => SelmaRewriter::sanitize_fragment(sanitizer, replacement),
+                        None => Ok(replacement),
+                    },
+                    None => Ok(html),
+                }
+            }
+        }
+    }
+}
+
+/// The text within the elements which handlers called `text_content` on, for
+/// as long as they're open. It's kept as it appears in the document, and only
+/// unescaped once an element's end tag is reached.
+pub struct TextCaptures {
+    open: RefCell<Vec<Option<String>>>,
+    /// the blocks given to `text_content`; this lives on the heap, where the GC
+    /// can't see it, so the rewrite keeps the array in its `gc_anchors` too
+    blocks: RArray,
+    handler_error: Rc<RefCell<Option<magnus::Error>>>,
+}
+
+impl TextCaptures {
+    fn new(handler_error: Rc<RefCell<Option<magnus::Error>>>) -> Self {
+        Self {
+            open: RefCell::new(vec![]),
+            blocks: RArray::new(),
+            handler_error,
+        }
+    }
+
+    fn push_text(&self, text: &str) {
+        for captured in self.open.borrow_mut().iter_mut().flatten() {
+            captured.push_str(text);
+        }
+    }
+
+    /// Starts buffering the text within `element`, and yields it to `block` at
+    /// its end tag. With `replace`, the element's content is swapped for what
+    /// the block returns, or, given `nil`, just its text.
+    pub fn capture(
+        self: &Rc<Self>,
+        element: &mut Element,
+        block: Proc,
+        replace: bool,
+    ) -> Result<(), magnus::Error> {
+        // elements which can't have an end tag (like `<br>`) have no text
+        if !element.can_have_content() {
+            return Ok(());
+        }
+
+        self.blocks.push(block)?;
+        let capture_index = {
+            let mut open = self.open.borrow_mut();
+            open.push(Some(String::new()));
+            open.len() - 1
+        };
+
+        if replace {
+            element.set_inner_content("", ContentType::Text);
+        }
+
+        let text_captures = self.clone();
+        if let Some(end_tag_handlers) = element.end_tag_handlers() {
+            end_tag_handlers.push(Box::new(move |end_tag| {
+                let captured = text_captures.open.borrow_mut()[capture_index]
+                    .take()
+                    .unwrap_or_default();
+                let text = String::from_utf8_lossy(&escapist::unescape_html(captured.as_bytes()))
+                    .into_owned();
+
+                let replacement = text_captures
+                    .blocks
+                    .entry::<Proc>(capture_index as isize)
+                    .and_then(|block| block.call::<_, Option<String>>((text.clone(),)));
+                let replacement = match replacement {
+                    Ok(replacement) => replacement,
+                    Err(err) => {
+                        return Err(SelmaRewriter::stash_handler_error(
+                            &text_captures.handler_error,
+                            err,
+                        ))
+                    }
+                };
+
+                if replace {
+                    end_tag.before(&replacement.unwrap_or(text), ContentType::Text);
+                }
+                Ok(())
+            }));
+        }
+
+        Ok(())
+    }
 }
 
-#[magnus::wrap(class = "Selma::Rewriter")]
-pub struct SelmaRewriter(std::cell::RefCell<Rewriter>);
+type RewriterValues = (
+    Option<Option<Obj<SelmaSanitizer>>>,
+    Option<RArray>,
+    Option<Value>,
+    Option<bool>,
+    Option<RHash>,
+    Option<bool>,
+    Option<Symbol>,
+    Option<Value>,
+    Option<RHash>,
+    RewriterOptions,
+);
+
+/// The keywords `Rewriter.new` takes beyond the nine `scan_args` can pick out
+/// on its own; they're read from whatever keywords are left over.
+#[derive(Default)]
+struct RewriterOptions {
+    csp_nonce: Option<String>,
+    minify: Option<Value>,
+    pretty: Option<Value>,
+    xhtml: bool,
+    canonical: Option<Value>,
+    timeout: Option<Duration>,
+}
+
+impl RewriterOptions {
+    fn from_hash(rest: RHash) -> Result<Self, magnus::Error> {
+        let mut options = Self::default();
+
+        rest.foreach(|key: Symbol, value: Value| {
+            match key.name()?.as_ref() {
+                "csp_nonce" => options.csp_nonce = Self::csp_nonce(value)?,
+                "minify" => options.minify = Some(value),
+                "pretty" => options.pretty = Some(value),
+                "xhtml" => options.xhtml = value.to_bool(),
+                "canonical" => options.canonical = Some(value),
+                "timeout" => options.timeout = Self::timeout(value)?,
+                name => {
+                    return Err(magnus::Error::new(
+                        exception::arg_error(),
+                        format!("unknown keyword: :{name}"),
+                    ))
+                }
+            }
+            Ok(ForEach::Continue)
+        })?;
+
+        Ok(options)
+    }
+
+    /// A nonce has to be a base64 value for a `Content-Security-Policy` to
+    /// match it, like `nonce-rAnd0m`.
+    fn csp_nonce(value: Value) -> Result<Option<String>, magnus::Error> {
+        let nonce: Option<String> = value.try_convert()?;
+
+        if let Some(nonce) = &nonce {
+            let unpadded = nonce.trim_end_matches('=');
+            let valid = !unpadded.is_empty()
+                && nonce.len() - unpadded.len() <= 2
+                && unpadded
+                    .bytes()
+                    .all(|b| b.is_ascii_alphanumeric() || matches!(b, b'+' | b'/' | b'-' | b'_'));
 
-type RewriterValues = (Option<Option<Obj<SelmaSanitizer>>>, Option<RArray>);
+            if !valid {
+                return Err(magnus::Error::new(
+                    exception::arg_error(),
+                    format!("`csp_nonce:` must be a base64 value, got {nonce:?}"),
+                ));
+            }
+        }
+
+        Ok(nonce)
+    }
+
+    /// In seconds, like `Timeout.timeout`; `nil` is no timeout at all.
+    fn timeout(value: Value) -> Result<Option<Duration>, magnus::Error> {
+        let seconds: Option<f64> = value.try_convert()?;
+
+        match seconds {
+            None => Ok(None),
+            Some(seconds) => match Duration::try_from_secs_f64(seconds) {
+                Ok(timeout) if !timeout.is_zero() => Ok(Some(timeout)),
+                _ => Err(magnus::Error::new(
+                    exception::arg_error(),
+                    format!("`timeout:` must be a positive number of seconds, got {seconds}"),
+                )),
+            },
+        }
+    }
+}
 
 impl SelmaRewriter {
     const SELMA_ON_END_TAG: &'static str = "on_end_tag";
     const SELMA_HANDLE_ELEMENT: &'static str = "handle_element";
     const SELMA_HANDLE_TEXT_CHUNK: &'static str = "handle_text_chunk";
+    const SELMA_HANDLE_RELOCATED: &'static str = "handle_relocated";
+    const SELMA_SKIP_CHILDREN: &'static str = "skip_children";
+    const SELMA_BEFORE_REWRITE: &'static str = "before_rewrite";
+    const SELMA_AFTER_REWRITE: &'static str = "after_rewrite";
 
     /// @yard
     /// @def new(sanitizer: Selma::Sanitizer.new(Selma::Sanitizer::Config::DEFAULT), handlers: [])
     /// @param sanitizer [Selma::Sanitizer] The sanitizer which performs the initial cleanup
     /// @param handlers  [Array<Selma::Selector>] The handlers to use to perform HTML rewriting
+    /// @param handler_sanitizer [Selma::Sanitizer, true] What to sanitize the HTML handlers inject with; `true` uses `sanitizer`
+    /// @param typographer [Boolean] Whether to curl quotes, and turn `---`, `--` and `...` into dashes and ellipses
+    /// @param extract [Hash] What to gather from the text while rewriting, for `#extracted`
+    /// @param profile [Boolean] Whether to count the tokens the parser sees, for `#last_run_stats`
+    /// @param malformed [Symbol] What to do with markup the parser can't be sure of: `:error`, `:pass_through` or `:drop`
+    /// @param on_removal [Proc] Called with `(tag_name, reason, attributes)` for each element the sanitizer removes
+    /// @param scoped_sanitizers [Hash<String, Selma::Sanitizer>] Sanitizers to use instead of `sanitizer` within the elements matching each selector
+    /// @param csp_nonce [String] The `nonce` to give every `<script>` and `<style>` which is left, for a strict `Content-Security-Policy`
+    /// @param minify [Boolean, Hash] Whether to collapse the output's `whitespace`, and strip its `comments` and redundant `quotes`
+    /// @param pretty [Boolean, Hash] Whether to put the output's blocks on lines of their own, nested ones indented by `indent` (2 spaces)
+    /// @param xhtml [Boolean] Whether to write the output out so that XML parsers can read it, with `<br />` and quoted attributes
+    /// @param canonical [Boolean, Hash] Whether to write the output out the same way every time, with sorted attributes, normalized character references and, unless `whitespace` is false, collapsed whitespace
+    /// @param timeout [Numeric] How many seconds a `#rewrite` may take before it raises a `Selma::TimeoutError`
     /// @return [Selma::Rewriter]
     fn new(args: &[Value]) -> Result<Self, magnus::Error> {
-        let (rb_sanitizer, rb_handlers) = Self::scan_parse_args(args)?;
+        let (
+            rb_sanitizer,
+            rb_handlers,
+            rb_handler_sanitizer,
+            rb_typographer,
+            rb_extract,
+            rb_profile,
+            rb_malformed,
+            rb_on_removal,
+            rb_scoped_sanitizers,
+            options,
+        ) = Self::scan_parse_args(args)?;
+        let malformed = malformed_from_symbol(rb_malformed)?;
+        let typographer = rb_typographer.unwrap_or(false);
+        let extraction = Extraction::new(rb_extract)?;
+        let serializer = Serializer::new(
+            options.minify,
+            options.pretty,
+            options.xhtml,
+            options.canonical,
+        )?;
 
         let sanitizer = match rb_sanitizer {
-            None => {
-                // no `sanitizer:` provided, use default
-                let default_sanitizer = SelmaSanitizer::new(&[])?;
-                let wrapped_sanitizer = Obj::wrap(default_sanitizer);
-                wrapped_sanitizer.funcall::<&str, (), Value>("setup", ())?;
-                Some(wrapped_sanitizer.get().to_owned())
-            }
+            // no `sanitizer:` provided, use default
+            None => Some(Self::setup_sanitizer(Obj::wrap(SelmaSanitizer::new(&[])?))?),
             Some(sanitizer_value) => match sanitizer_value {
                 None => None, // no `sanitizer:` provided, use default
-                Some(sanitizer) => {
-                    sanitizer.funcall::<&str, (), Value>("setup", ())?;
-                    Some(sanitizer.get().to_owned())
-                }
+                Some(sanitizer) => Some(Self::setup_sanitizer(sanitizer)?),
             },
         };
 
-        let handlers = match rb_handlers {
-            None => vec![],
-            Some(rb_handlers) => {
-                let mut handlers: Vec<Handler> = vec![];
-
-                for h in rb_handlers.each() {
-                    let rb_handler = h.unwrap();
-
-                    // prevents missing #selector from ruining things
-                    if !rb_handler.respond_to("selector", true).unwrap() {
-                        let classname = unsafe { rb_handler.classname() };
-                        return Err(magnus::Error::new(
-                            exception::no_method_error(),
-                            format!(
-                                "Could not call #selector on {classname:?}; is this an object that defines it?",
+        let (handlers, text_replacements) = match rb_handlers {
+            None => (vec![], vec![]),
+            Some(rb_handlers) => Self::build_handlers(rb_handlers)?,
+        };
 
-                            ),
-                        ));
-                    }
+        if sanitizer.is_none()
+            && handlers.is_empty()
+            && text_replacements.is_empty()
+            && !typographer
+            && extraction.is_empty()
+            && options.csp_nonce.is_none()
+            && serializer.is_empty()
+        {
+            return Err(Self::nothing_to_do_error());
+        }
 
-                    let rb_selector: Obj<SelmaSelector> = match rb_handler.funcall("selector", ()) {
-                        Err(err) => {
+        let handler_sanitizer = match rb_handler_sanitizer {
+            Some(value) if value.to_bool() => {
+                if value.equal(qtrue())? {
+                    match &sanitizer {
+                        Some(sanitizer) => Some(sanitizer.clone()),
+                        None => {
                             return Err(magnus::Error::new(
-                                exception::type_error(),
-                                format!("Error instantiating selector: {err:?}"),
-                            ));
+                                exception::arg_error(),
+                                "`handler_sanitizer: true` needs a sanitizer to reuse",
+                            ))
                         }
-                        Ok(rb_selector) => rb_selector,
-                    };
-                    let handler = Handler {
-                        rb_handler: Opaque::from(rb_handler),
-                        rb_selector: Opaque::from(rb_selector),
-                        // total_element_handler_calls: 0,
-                        // total_elapsed_element_handlers: 0.0,
-
-                        // total_text_handler_calls: 0,
-                        // total_elapsed_text_handlers: 0.0,
-                    };
-                    handlers.push(handler);
+                    }
+                } else {
+                    Some(Self::setup_sanitizer(value.try_convert()?)?)
                 }
-                handlers
+            }
+            _ => None, // `nil` or `false`
+        };
+
+        let mut scoped_sanitizers = vec![];
+        if let Some(rb_scoped_sanitizers) = rb_scoped_sanitizers {
+            if sanitizer.is_none() && !rb_scoped_sanitizers.is_empty() {
+                return Err(magnus::Error::new(
+                    exception::arg_error(),
+                    "`scoped_sanitizers:` needs a sanitizer for everything else",
+                ));
+            }
+
+            rb_scoped_sanitizers.foreach(|css: String, scoped: Obj<SelmaSanitizer>| {
+                SelmaSelector::parse_css("scoped_sanitizers", &css)?;
+                scoped_sanitizers.push((css, Self::setup_sanitizer(scoped)?));
+                Ok(ForEach::Continue)
+            })?;
+        }
+
+        let on_removal = rb_on_removal.map(Opaque::from);
+        let roots = GcRoots::new(&handlers, on_removal);
+
+        Ok(Self(
+            std::cell::RefCell::new(Rewriter {
+                sanitizer,
+                scoped_sanitizers,
+                handler_sanitizer,
+                handlers,
+                text_replacements,
+                typographer,
+                extraction,
+                extracted: RefCell::new(Extracted::default()),
+                profile: rb_profile.unwrap_or(false),
+                parser_stats: ParserStats::default(),
+                malformed,
+                removals: match rb_on_removal {
+                    Some(_) => Removals::logged(),
+                    None => Removals::default(),
+                },
+                on_removal,
+                csp_nonce: options.csp_nonce,
+                serializer,
+                timeout: options.timeout,
+                total_bytes_in: Cell::new(0),
+                total_bytes_out: Cell::new(0),
+                total_elapsed_sanitization: Cell::new(0.0),
+                total_elapsed: Cell::new(0.0),
+            }),
+            Cell::new(None),
+            roots,
+        ))
+    }
+
+    fn setup_sanitizer(sanitizer: Obj<SelmaSanitizer>) -> Result<SelmaSanitizer, magnus::Error> {
+        sanitizer.funcall::<&str, (), Value>("setup", ())?;
+        Ok(sanitizer.get().to_owned())
+    }
+
+    /// Sorts `rb_handlers` into the handlers which call into Ruby, and the
+    /// `TextReplace`s, which don't.
+    fn build_handlers(
+        rb_handlers: RArray,
+    ) -> Result<(Vec<Handler>, Vec<SelmaTextReplace>), magnus::Error> {
+        let mut handlers: Vec<Handler> = vec![];
+        let mut text_replacements: Vec<SelmaTextReplace> = vec![];
+
+        for h in rb_handlers.each() {
+            let rb_handler = h.unwrap();
+
+            // native handlers never call into Ruby
+            if let Ok(text_replace) = rb_handler.try_convert::<Obj<SelmaTextReplace>>() {
+                text_replacements.push(text_replace.get().to_owned());
+                continue;
+            }
+
+            // prevents missing #selector from ruining things
+            if !rb_handler.respond_to("selector", true).unwrap() {
+                let classname = unsafe { rb_handler.classname() };
+                return Err(magnus::Error::new(
+                    exception::no_method_error(),
+                    format!(
+                        "Could not call #selector on {classname:?}; is this an object that defines it?",
+
+                    ),
+                ));
+            }
+
+            let rb_selector: Obj<SelmaSelector> = match rb_handler.funcall("selector", ()) {
+                Err(err) => {
+                    return Err(magnus::Error::new(
+                        exception::type_error(),
+                        format!("Error instantiating selector: {err:?}"),
+                    ));
+                }
+                Ok(rb_selector) => rb_selector,
+            };
+            let handler = Handler {
+                rb_handler: Opaque::from(rb_handler),
+                rb_selector: Opaque::from(rb_selector),
+                total_element_handler_calls: Cell::new(0),
+                total_elapsed_element_handlers: Cell::new(0.0),
+                total_text_handler_calls: Cell::new(0),
+                total_elapsed_text_handlers: Cell::new(0.0),
+            };
+            handlers.push(handler);
+        }
+
+        Ok((handlers, text_replacements))
+    }
+
+    fn nothing_to_do_error() -> magnus::Error {
+        magnus::Error::new(
+            exception::arg_error(),
+            "Must provide a sanitizer or a handler",
+        )
+    }
+
+    /// @yard
+    /// @def with(sanitizer: nil, handlers: nil)
+    /// Derives a rewriter which is the same as this one, except for the
+    /// `sanitizer:` or `handlers:` given, which replace this one's. Everything
+    /// else, including sanitizers which have already been set up, is copied as it
+    /// is rather than built again, so it's cheap to derive one per request.
+    /// @param sanitizer [Selma::Sanitizer, nil] The sanitizer which performs the initial cleanup
+    /// @param handlers [Array<Selma::Selector>] The handlers to use to perform HTML rewriting
+    /// @return [Selma::Rewriter]
+    #[allow(clippy::let_unit_value)]
+    fn with(&self, args: &[Value]) -> Result<Self, magnus::Error> {
+        let args = scan_args::scan_args(args)?;
+        let _: () = args.required;
+        let _: () = args.optional;
+        let _: () = args.splat;
+        let _: () = args.trailing;
+        let _: () = args.block;
+
+        let kwargs = scan_args::get_kwargs::<
+            _,
+            (),
+            (Option<Option<Obj<SelmaSanitizer>>>, Option<RArray>),
+            (),
+        >(args.keywords, &[], &["sanitizer", "handlers"])?;
+        let (rb_sanitizer, rb_handlers) = kwargs.optional;
+
+        let binding = match self.0.try_borrow() {
+            Ok(binding) => binding,
+            Err(_) => {
+                return Err(invalid_state_error(
+                    "cannot call `with` while this rewriter is rewriting",
+                ))
             }
         };
 
-        if sanitizer.is_none() && handlers.is_empty() {
+        let sanitizer = match rb_sanitizer {
+            None => binding.sanitizer.clone(),
+            Some(None) => None,
+            Some(Some(sanitizer)) => Some(Self::setup_sanitizer(sanitizer)?),
+        };
+        if sanitizer.is_none() && !binding.scoped_sanitizers.is_empty() {
             return Err(magnus::Error::new(
                 exception::arg_error(),
-                "Must provide a sanitizer or a handler",
+                "`scoped_sanitizers:` needs a sanitizer for everything else",
             ));
         }
 
-        Ok(Self(std::cell::RefCell::new(Rewriter {
-            sanitizer,
-            handlers,
-            // total_elapsed: 0.0,
-        })))
-    }
+        let (handlers, text_replacements) = match rb_handlers {
+            None => (binding.handlers.clone(), binding.text_replacements.clone()),
+            Some(rb_handlers) => Self::build_handlers(rb_handlers)?,
+        };
+
+        if sanitizer.is_none()
+            && handlers.is_empty()
+            && text_replacements.is_empty()
+            && !binding.typographer
+            && binding.extraction.is_empty()
+            && binding.csp_nonce.is_none()
+            && binding.serializer.is_empty()
+        {
+            return Err(Self::nothing_to_do_error());
+        }
+
+        for handler in handlers.iter() {
+            handler.reset_stats();
+        }
+
+        let roots = GcRoots::new(&handlers, binding.on_removal);
+
+        Ok(Self(
+            std::cell::RefCell::new(Rewriter {
+                sanitizer,
+                scoped_sanitizers: binding.scoped_sanitizers.clone(),
+                handler_sanitizer: binding.handler_sanitizer.clone(),
+                handlers,
+                text_replacements,
+                typographer: binding.typographer,
+                extraction: binding.extraction.clone(),
+                extracted: RefCell::new(Extracted::default()),
+                profile: binding.profile,
+                parser_stats: ParserStats::default(),
+                malformed: binding.malformed,
+                removals: match binding.on_removal {
+                    Some(_) => Removals::logged(),
+                    None => Removals::default(),
+                },
+                on_removal: binding.on_removal,
+                csp_nonce: binding.csp_nonce.clone(),
+                serializer: binding.serializer.clone(),
+                timeout: binding.timeout,
+                total_bytes_in: Cell::new(0),
+                total_bytes_out: Cell::new(0),
+                total_elapsed_sanitization: Cell::new(0.0),
+                total_elapsed: Cell::new(0.0),
+            }),
+            Cell::new(None),
+            roots,
+        ))
+    }
+
+    #[allow(clippy::let_unit_value)]
+    fn scan_parse_args(args: &[Value]) -> Result<RewriterValues, magnus::Error> {
+        let args = scan_args::scan_args(args)?;
+        let _: () = args.required;
+        let _: () = args.optional;
+        let _: () = args.splat;
+        let _: () = args.trailing;
+        let _: () = args.block;
+
+        let kwargs = scan_args::get_kwargs::<
+            _,
+            (),
+            (
+                Option<Option<Obj<SelmaSanitizer>>>,
+                Option<RArray>,
+                Option<Value>,
+                Option<bool>,
+                Option<RHash>,
+                Option<bool>,
+                Option<Symbol>,
+                Option<Value>,
+                Option<RHash>,
+            ),
+            RHash,
+        >(
+            args.keywords,
+            &[],
+            &[
+                "sanitizer",
+                "handlers",
+                "handler_sanitizer",
+                "typographer",
+                "extract",
+                "profile",
+                "malformed",
+                "on_removal",
+                "scoped_sanitizers",
+            ],
+        )?;
+        let (
+            rb_sanitizer,
+            rb_handlers,
+            rb_handler_sanitizer,
+            rb_typographer,
+            rb_extract,
+            rb_profile,
+            rb_malformed,
+            rb_on_removal,
+            rb_scoped_sanitizers,
+        ) = kwargs.optional;
+        let options = RewriterOptions::from_hash(kwargs.splat)?;
+
+        Ok((
+            rb_sanitizer,
+            rb_handlers,
+            rb_handler_sanitizer,
+            rb_typographer,
+            rb_extract,
+            rb_profile,
+            rb_malformed,
+            rb_on_removal,
+            rb_scoped_sanitizers,
+            options,
+        ))
+    }
+
+    /// Perform HTML rewrite sequence.
+    fn rewrite(&self, html: String) -> Result<String, magnus::Error> {
+        // handlers get a context even if nobody's going to look at it afterwards
+        let context = RHash::new();
+        let rewritten_html = self.perform_rewrite(html, context);
+        std::hint::black_box(context);
+
+        rewritten_html
+    }
+
+    /// @yard
+    /// @def rewrite_with_context(html, context = {})
+    /// Rewrites `html` like `#rewrite`, with `context` available to every handler
+    /// (as `#context`) for as long as the rewrite lasts. Handlers can use it to
+    /// keep track of things, like how many links there were, instead of keeping
+    /// that state around themselves.
+    /// @param html [String] The HTML to rewrite
+    /// @param context [Hash] The hash handlers share
+    /// @return [Array(String, Hash)] The rewritten HTML, and the context
+    #[allow(clippy::let_unit_value)]
+    fn rewrite_with_context(&self, args: &[Value]) -> Result<RArray, magnus::Error> {
+        let args = scan_args::scan_args(args)?;
+        let (html,): (String,) = args.required;
+        let (context,): (Option<RHash>,) = args.optional;
+        let _: () = args.splat;
+        let _: () = args.trailing;
+        let _: () = args.block;
+        let _: () = args.keywords;
+
+        let context = context.unwrap_or_else(RHash::new);
+        let rewritten_html = self.perform_rewrite(html, context)?;
+
+        let result = RArray::new();
+        result.push(rewritten_html)?;
+        result.push(context)?;
+
+        Ok(result)
+    }
+
+    /// @yard
+    /// @def halt!(raise: false)
+    /// Stops the rewrite this rewriter is in the middle of, from within one of
+    /// its handlers. No more handlers are called; `#rewrite` returns the HTML
+    /// which has been written out so far, or, with `raise: true`, raises a
+    /// `Selma::HaltedError` instead.
+    /// @param raise [Boolean] Whether `#rewrite` should raise rather than return
+    #[allow(clippy::let_unit_value)]
+    fn halt(&self, args: &[Value]) -> Result<(), magnus::Error> {
+        let args = scan_args::scan_args(args)?;
+        let _: () = args.required;
+        let _: () = args.optional;
+        let _: () = args.splat;
+        let _: () = args.trailing;
+        let _: () = args.block;
+
+        let kwargs =
+            scan_args::get_kwargs::<_, (), (Option<bool>,), ()>(args.keywords, &[], &["raise"])?;
+        let (raise,) = kwargs.optional;
+
+        if self.0.try_borrow_mut().is_ok() {
+            return Err(invalid_state_error(
+                "cannot call `halt!` unless this rewriter is rewriting",
+            ));
+        }
+
+        self.1.set(Some(if raise.unwrap_or(false) {
+            Halt::Raise
+        } else {
+            Halt::ReturnOutput
+        }));
+
+        // unwinds the handler, and with it the rewrite
+        Err(halted_error("the rewrite was halted by a handler"))
+    }
+
+    fn perform_rewrite(&self, html: String, context: RHash) -> Result<String, magnus::Error> {
+        // the rewriter stays borrowed until this call returns (whether or not it
+        // succeeds), so a handler calling back into `#rewrite` is caught here, and
+        // every call after that starts over from a clean slate
+        let binding = match self.0.try_borrow_mut() {
+            Ok(binding) => binding,
+            Err(_) => {
+                return Err(invalid_state_error(
+                    "cannot call `rewrite` while this rewriter is already rewriting",
+                ))
+            }
+        };
+
+        let start = Instant::now();
+        self.1.set(None);
+
+        // `#stats` only ever describes the latest run
+        binding.removals.reset();
+        binding.total_bytes_in.set(html.len());
+        binding.total_bytes_out.set(0);
+        binding.total_elapsed_sanitization.set(0.0);
+        binding.total_elapsed.set(0.0);
+        binding.extracted.replace(Extracted::default());
+        binding.parser_stats.reset();
+        for handler in binding.handlers.iter() {
+            handler.reset_stats();
+        }
+
+        Self::call_lifecycle(&binding, Self::SELMA_BEFORE_REWRITE)?;
+        let deadline = Deadline::new(start, binding.timeout);
+        let rewritten_html =
+            self.perform_rewrite_between_callbacks(&binding, html, context, start, deadline);
 
-    #[allow(clippy::let_unit_value)]
-    fn scan_parse_args(args: &[Value]) -> Result<RewriterValues, magnus::Error> {
-        let args = scan_args::scan_args(args)?;
-        let _: () = args.required;
-        let _: () = args.optional;
-        let _: () = args.splat;
-        let _: () = args.trailing;
-        let _: () = args.block;
+        // handlers hear that the document is done even if it failed, so they
+        // can clean up after it, but the rewrite's own error comes first
+        let after_rewrite = Self::call_lifecycle(&binding, Self::SELMA_AFTER_REWRITE);
+        let rewritten_html = rewritten_html?;
+        after_rewrite?;
 
-        let kwargs = scan_args::get_kwargs::<
-            _,
-            (),
-            (Option<Option<Obj<SelmaSanitizer>>>, Option<RArray>),
-            (),
-        >(args.keywords, &[], &["sanitizer", "handlers"])?;
-        let (rb_sanitizer, rb_handlers) = kwargs.optional;
+        Ok(rewritten_html)
+    }
+
+    /// Calls the `before_rewrite` or `after_rewrite` of every handler which
+    /// defines it, in the order they were given.
+    fn call_lifecycle(binding: &Rewriter, callback: &str) -> Result<(), magnus::Error> {
+        let ruby = Ruby::get().unwrap();
+        for handler in binding.handlers.iter() {
+            let rb_handler = ruby.get_inner(handler.rb_handler);
+            if rb_handler.respond_to(callback, true)? {
+                rb_handler.funcall::<_, _, Value>(callback, ())?;
+            }
+        }
 
-        Ok((rb_sanitizer, rb_handlers))
+        Ok(())
     }
 
-    /// Perform HTML rewrite sequence.
-    fn rewrite(&self, html: String) -> Result<String, magnus::Error> {
-        let sanitized_html = match &self.0.borrow().sanitizer {
+    fn perform_rewrite_between_callbacks(
+        &self,
+        binding: &Rewriter,
+        html: String,
+        context: RHash,
+        start: Instant,
+        deadline: Deadline,
+    ) -> Result<String, magnus::Error> {
+        let sanitized_html = match &binding.sanitizer {
             None => Ok(html),
             Some(sanitizer) => {
-                let sanitized_html = match Self::perform_sanitization(sanitizer, &html) {
-                    Ok(sanitized_html) => sanitized_html,
-                    Err(err) => return Err(err),
-                };
+                let sanitized_html = Self::perform_sanitization(
+                    sanitizer,
+                    &binding.scoped_sanitizers,
+                    &html,
+                    &binding.removals,
+                    binding.malformed,
+                    deadline,
+                );
+                binding
+                    .total_elapsed_sanitization
+                    .set(start.elapsed().as_secs_f64());
+
+                if let Some(on_removal) = binding.on_removal {
+                    binding
+                        .removals
+                        .report(Ruby::get().unwrap().get_inner(on_removal))?;
+                }
 
-                String::from_utf8(sanitized_html)
+                String::from_utf8(sanitized_html?)
+            }
+        };
+        let sanitized_html = match sanitized_html {
+            Ok(sanitized_html) => sanitized_html,
+            Err(err) => {
+                return Err(rewriting_error(format!(
+                    "Failed to sanitize HTML: output is not valid UTF-8 ({err})"
+                )))
             }
         };
-        let binding = self.0.borrow_mut();
-        let handlers = &binding.handlers;
 
-        match Self::perform_handler_rewrite(self, handlers, sanitized_html.unwrap()) {
-            Ok(rewritten_html) => Ok(String::from_utf8(rewritten_html).unwrap()),
+        let rewritten_html =
+            self.perform_handler_rewrite(binding, context, sanitized_html, deadline);
+        binding.total_elapsed.set(start.elapsed().as_secs_f64());
+
+        match rewritten_html {
+            Ok(rewritten_html) => match String::from_utf8(rewritten_html) {
+                Ok(rewritten_html) => {
+                    // once any subtrees the handlers captured have been put back
+                    let rewritten_html = binding.serializer.serialize(rewritten_html);
+                    binding.total_bytes_out.set(rewritten_html.len());
+                    Ok(rewritten_html)
+                }
+                Err(err) => Err(rewriting_error(format!(
+                    "Failed to rewrite HTML: output is not valid UTF-8 ({err})"
+                ))),
+            },
             Err(err) => Err(err),
         }
     }
 
-    fn perform_sanitization(
-        sanitizer: &SelmaSanitizer,
-        html: &String,
-    ) -> Result<Vec<u8>, magnus::Error> {
-        let mut first_pass_html = vec![];
-        {
-            let mut document_content_handlers: Vec<DocumentContentHandlers> = vec![];
-            if !sanitizer.get_allow_doctype() {
-                document_content_handlers.push(doctype!(|d| {
-                    sanitizer.remove_doctype(d);
-                    Ok(())
-                }));
-            }
-            if !sanitizer.get_allow_comments() {
-                document_content_handlers.push(doc_comments!(|c| {
-                    sanitizer.remove_comment(c);
-                    Ok(())
-                }));
+    /// @yard
+    /// @def last_run_stats
+    /// Reports the same things as `#stats`, along with what the parser went
+    /// through while the handlers ran: how many `bytes_parsed`, and how many
+    /// `selectors` it matched every element against. With `profile: true`, it
+    /// also counts the `elements`, `text_chunks` and `comments` it came across.
+    /// (lol_html doesn't say how much memory it used.)
+    /// @return [Hash]
+    fn last_run_stats(&self) -> Result<RHash, magnus::Error> {
+        let rb_stats = self.stats()?;
+        let binding = self.0.borrow();
+        let parser_stats = &binding.parser_stats;
+
+        let rb_parser_stats = RHash::new();
+        rb_parser_stats.aset(Symbol::new("bytes_parsed"), parser_stats.bytes_parsed.get())?;
+        rb_parser_stats.aset(Symbol::new("selectors"), parser_stats.selectors.get())?;
+        if binding.profile {
+            rb_parser_stats.aset(Symbol::new("elements"), parser_stats.elements.get())?;
+            rb_parser_stats.aset(Symbol::new("text_chunks"), parser_stats.text_chunks.get())?;
+            rb_parser_stats.aset(Symbol::new("comments"), parser_stats.comments.get())?;
+        }
+        rb_stats.aset(Symbol::new("parser"), rb_parser_stats)?;
+
+        Ok(rb_stats)
+    }
+
+    /// @yard
+    /// @def extracted
+    /// Returns what the `extract:` option asked for, from the most recent
+    /// `#rewrite`. With `word_count:`, that's the number of `words` and
+    /// (non-whitespace) `characters` in the document's visible text, and its
+    /// `reading_time` in minutes; with `word_count: { sections: true }`, it's
+    /// also broken down into `sections`, each starting at a heading. With
+    /// `text_sample:`, it's the `text_sample`: up to that many bytes of the
+    /// visible text, with its whitespace collapsed. `first_image: true` gives the
+    /// `src` of the first image the sanitizer kept, or `nil`, and `excerpt:` about
+    /// that many characters of paragraph text, ending with `…` if there's more.
+    /// @return [Hash]
+    fn extracted(&self) -> Result<RHash, magnus::Error> {
+        match self.0.try_borrow() {
+            Ok(binding) => binding.extracted.borrow().to_hash(),
+            Err(_) => Err(invalid_state_error(
+                "cannot call `extracted` while this rewriter is rewriting",
+            )),
+        }
+    }
+
+    /// @yard
+    /// @def explain(html)
+    /// Reports which elements each handler's selectors match in `html`, without
+    /// calling any of the handlers, for tests which would rather count matches
+    /// than compare whole documents. Each match is a Hash with the `handler`,
+    /// the `selector` and which of them it was (`match: :element` or
+    /// `:text_within`), the `element`'s name, its start `tag`, and the byte
+    /// `offset` of that tag. The matching happens after sanitization, like in
+    /// `#rewrite`, so the tags and offsets are those of the sanitized HTML.
+    /// @param html [String] The HTML to match the selectors against
+    /// @return [Array<Hash>] The matches, in document order
+    fn explain(&self, html: String) -> Result<RArray, magnus::Error> {
+        let binding = match self.0.try_borrow() {
+            Ok(binding) => binding,
+            Err(_) => {
+                return Err(invalid_state_error(
+                    "cannot call `explain` while this rewriter is rewriting",
+                ))
             }
-            let mut rewriter = HtmlRewriter::new(
-                Settings {
-                    document_content_handlers,
-                    element_content_handlers: vec![element!("*", |el| {
-                        sanitizer.try_remove_element(el);
-                        if el.removed() {
-                            return Ok(());
-                        }
-                        match sanitizer.sanitize_attributes(el) {
-                            Ok(_) => Ok(()),
-                            Err(err) => Err(err.to_string().into()),
-                        }
-                    })],
-                    // TODO: allow for MemorySettings to be defined
-                    ..Settings::default()
-                },
-                |c: &[u8]| first_pass_html.extend_from_slice(c),
-            );
+        };
 
-            let result = rewriter.write(html.as_bytes());
-            if result.is_err() {
-                return Err(magnus::Error::new(
-                    exception::runtime_error(),
-                    format!("Failed to sanitize HTML: {}", result.unwrap_err()),
-                ));
+        let html = match &binding.sanitizer {
+            None => html,
+            Some(sanitizer) => {
+                let sanitized = Self::perform_sanitization(
+                    sanitizer,
+                    &binding.scoped_sanitizers,
+                    &html,
+                    &Removals::default(),
+                    binding.malformed,
+                    Deadline::default(),
+                )?;
+                String::from_utf8(sanitized).map_err(|err| {
+                    rewriting_error(format!(
+                        "Failed to sanitize HTML: output is not valid UTF-8 ({err})"
+                    ))
+                })?
             }
-        }
+        };
 
-        let mut output = vec![];
-        {
-            let mut element_content_handlers: Vec<(Cow<Selector>, ElementContentHandlers)> = vec![];
-            if sanitizer.get_escape_tagfilter() {
-                element_content_handlers.push(element!(Tag::ESCAPEWORTHY_TAGS_CSS, |el| {
-                    let should_remove = sanitizer.allow_element(el);
-                    if should_remove {
-                        sanitizer.force_remove_element(el);
-                    }
+        let ruby = Ruby::get().unwrap();
+        // how far into `html` the parser is; it's given a byte at a time, so
+        // an element's handlers run just as the `>` ending its start tag is read
+        let position = Cell::new(0);
+        let matches: RefCell<Vec<Match>> = RefCell::new(vec![]);
 
+        let mut element_content_handlers: Vec<(Cow<Selector>, ElementContentHandlers)> = vec![];
+        for (handler_index, handler) in binding.handlers.iter().enumerate() {
+            let selector = ruby.get_inner(handler.rb_selector);
+
+            for (kind, css) in [
+                ("element", selector.match_element()),
+                ("text_within", selector.match_text_within()),
+            ] {
+                let css = match css {
+                    Some(css) => css,
+                    None => continue,
+                };
+                let position = &position;
+                let matches = &matches;
+                let closure_css = css.clone();
+
+                element_content_handlers.push(element!(css, move |el| {
+                    matches.borrow_mut().push(Match {
+                        handler_index,
+                        kind,
+                        selector: closure_css.clone(),
+                        tag_name: el.tag_name().to_lowercase(),
+                        end: position.get(),
+                    });
                     Ok(())
                 }));
             }
+        }
 
+        {
             let mut rewriter = HtmlRewriter::new(
                 Settings {
                     element_content_handlers,
+                    strict: binding.malformed.strict(),
                     ..Settings::default()
                 },
-                |c: &[u8]| output.extend_from_slice(c),
+                |_: &[u8]| {},
             );
+            for (index, byte) in html.bytes().enumerate() {
+                position.set(index + 1);
+                if let Err(err) = binding.malformed.tolerate(rewriter.write(&[byte])) {
+                    return Err(rewriting_error(format!("Failed to rewrite HTML: {err}")));
+                }
+            }
+        }
 
-            let result = rewriter.write(first_pass_html.as_slice());
-            if result.is_err() {
-                return Err(magnus::Error::new(
-                    exception::runtime_error(),
-                    format!("Failed to sanitize HTML: {}", result.unwrap_err()),
-                ));
+        let rb_matches = RArray::new();
+        for found in matches.into_inner() {
+            let start = Self::start_tag_offset(&html, found.end, &found.tag_name);
+
+            let rb_match = RHash::new();
+            rb_match.aset(
+                Symbol::new("handler"),
+                ruby.get_inner(binding.handlers[found.handler_index].rb_handler),
+            )?;
+            rb_match.aset(Symbol::new("selector"), found.selector)?;
+            rb_match.aset(Symbol::new("match"), Symbol::new(found.kind))?;
+            rb_match.aset(Symbol::new("tag"), &html[start..found.end])?;
+            rb_match.aset(Symbol::new("element"), found.tag_name)?;
+            rb_match.aset(Symbol::new("offset"), start)?;
+            rb_matches.push(rb_match)?;
+        }
+
+        Ok(rb_matches)
+    }
+
+    /// Where the start tag ending at `end` begins; the last `<name` before it
+    /// is the one, unless an attribute value holds another.
+    fn start_tag_offset(html: &str, end: usize, tag_name: &str) -> usize {
+        let opening = format!("<{tag_name}");
+
+        html.as_bytes()[..end]
+            .windows(opening.len())
+            .rposition(|window| window.eq_ignore_ascii_case(opening.as_bytes()))
+            .unwrap_or(0)
+    }
+
+    /// @yard
+    /// @def stats
+    /// Reports what happened during the most recent `#rewrite`: how many elements
+    /// the sanitizer removed, and how many elements and text chunks each handler
+    /// was called on (in the order the handlers were given). Times are in seconds;
+    /// `native_elapsed` is everything that wasn't spent in a Ruby handler.
+    /// @return [Hash]
+    fn stats(&self) -> Result<RHash, magnus::Error> {
+        let binding = match self.0.try_borrow() {
+            Ok(binding) => binding,
+            Err(_) => {
+                return Err(invalid_state_error(
+                    "cannot call `stats` while this rewriter is rewriting",
+                ))
             }
+        };
+        let ruby = Ruby::get().unwrap();
+
+        let mut handlers_elapsed = 0.0;
+        let rb_handlers = RArray::new();
+        for handler in binding.handlers.iter() {
+            let rb_handler_stats = RHash::new();
+            rb_handler_stats.aset(Symbol::new("handler"), ruby.get_inner(handler.rb_handler))?;
+            rb_handler_stats.aset(Symbol::new("selector"), ruby.get_inner(handler.rb_selector))?;
+            rb_handler_stats.aset(
+                Symbol::new("elements_matched"),
+                handler.total_element_handler_calls.get(),
+            )?;
+            rb_handler_stats.aset(
+                Symbol::new("text_chunks_matched"),
+                handler.total_text_handler_calls.get(),
+            )?;
+            rb_handler_stats.aset(
+                Symbol::new("element_handlers_elapsed"),
+                handler.total_elapsed_element_handlers.get(),
+            )?;
+            rb_handler_stats.aset(
+                Symbol::new("text_handlers_elapsed"),
+                handler.total_elapsed_text_handlers.get(),
+            )?;
+            rb_handlers.push(rb_handler_stats)?;
+
+            handlers_elapsed += handler.total_elapsed_element_handlers.get()
+                + handler.total_elapsed_text_handlers.get();
         }
 
-        Ok(output)
+        let rb_stats = RHash::new();
+        rb_stats.aset(
+            Symbol::new("elements_removed"),
+            binding.removals.count.get(),
+        )?;
+        rb_stats.aset(Symbol::new("bytes_in"), binding.total_bytes_in.get())?;
+        rb_stats.aset(Symbol::new("bytes_out"), binding.total_bytes_out.get())?;
+        rb_stats.aset(Symbol::new("elapsed"), binding.total_elapsed.get())?;
+        rb_stats.aset(
+            Symbol::new("sanitization_elapsed"),
+            binding.total_elapsed_sanitization.get(),
+        )?;
+        rb_stats.aset(
+            Symbol::new("native_elapsed"),
+            (binding.total_elapsed.get() - handlers_elapsed).max(0.0),
+        )?;
+        rb_stats.aset(Symbol::new("handlers"), rb_handlers)?;
+
+        Ok(rb_stats)
+    }
+
+    /// Runs the sanitizer (and those scoped to parts of the document) over
+    /// `html`, recording whatever it takes out in `removals`.
+    fn perform_sanitization(
+        sanitizer: &SelmaSanitizer,
+        scoped_sanitizers: &[(String, SelmaSanitizer)],
+        html: &str,
+        removals: &Removals,
+        malformed: Malformed,
+        deadline: Deadline,
+    ) -> Result<Vec<u8>, magnus::Error> {
+        let engine = sanitizer.engine();
+        let scoped_engines: Vec<_> = scoped_sanitizers
+            .iter()
+            .map(|(selector, sanitizer)| (selector.as_str(), sanitizer.engine()))
+            .collect();
+        let scoped_engines: Vec<(&str, &Sanitizer)> = scoped_engines
+            .iter()
+            .map(|(selector, engine)| (*selector, &**engine))
+            .collect();
+
+        sanitize::sanitize(
+            &engine,
+            &scoped_engines,
+            html,
+            SanitizeContext {
+                on_removal: &|element, reason| removals.record(element, reason),
+                malformed,
+                deadline,
+                nesting: 0,
+            },
+        )
+        .map_err(sanitize_error)
+    }
+
+    /// Sanitizes HTML on its own, outside of any `#rewrite`. This is what the
+    /// HTML which handlers inject goes through, given a `handler_sanitizer:`.
+    pub(crate) fn sanitize_fragment(
+        sanitizer: &SelmaSanitizer,
+        html: String,
+    ) -> Result<String, magnus::Error> {
+        sanitizer.engine().sanitize(&html).map_err(sanitize_error)
     }
 
     pub fn perform_handler_rewrite(
         &self,
-        handlers: &[Handler],
+        rewriter: &Rewriter,
+        context: RHash,
         html: String,
+        deadline: Deadline,
     ) -> Result<Vec<u8>, magnus::Error> {
+        let handlers = &rewriter.handlers;
+        let handler_sanitizer = rewriter.handler_sanitizer.as_ref();
+        let subtree_markers = SubtreeMarkers::new();
+        let skipped_subtrees = SkippedSubtrees::default();
+        let text_replacements =
+            TextReplacements::new(&rewriter.text_replacements, rewriter.typographer);
+        let extractor = Rc::new(Extractor::new(&rewriter.extraction));
+
         // TODO: this should ideally be done ahead of time, not on every `#rewrite` call
         let mut element_content_handlers: Vec<(Cow<Selector>, ElementContentHandlers)> = vec![];
 
-        handlers.iter().for_each(|handler| {
-            let element_stack: Rc<RefCell<Vec<String>>> = Rc::new(RefCell::new(vec![]));
+        // lol_html can only carry errors as strings, so the first exception a Ruby
+        // handler raises is kept here, to be re-raised untouched once the rewrite aborts
+        let handler_error: Rc<RefCell<Option<magnus::Error>>> = Rc::new(RefCell::new(None));
 
-            let ruby = Ruby::get().unwrap();
+        let text_captures = Rc::new(TextCaptures::new(handler_error.clone()));
 
+        // holds on to every handler's element contexts, and the blocks given to
+        // `text_content`, so that they're reachable from the stack (and thus safe
+        // from the GC) until the rewrite is over
+        let gc_anchors = RArray::new();
+        gc_anchors.push(text_captures.blocks)?;
+
+        // the names of the elements which are currently open, outermost first; this
+        // is shared by every handler, and is what `#ancestors` reports
+        let element_stack: Rc<RefCell<Vec<String>>> = Rc::new(RefCell::new(vec![]));
+
+        let ruby = Ruby::get().unwrap();
+
+        for (handler_index, handler) in handlers.iter().enumerate() {
+            let rb_handler = ruby.get_inner(handler.rb_handler);
             let selector = ruby.get_inner(handler.rb_selector);
 
-            // TODO: test final raise by simulating errors
+            // what `handle_element` returned for each matched element that's still
+            // open, innermost last; handlers which match both elements and text get
+            // the innermost one passed along to `handle_text_chunk`
+            let element_contexts = RArray::new();
+            gc_anchors.push(element_contexts)?;
+
+            let pass_element_context = selector.match_element().is_some()
+                && selector.match_text_within().is_some()
+                && Self::takes_element_context(rb_handler)?;
+
             if selector.match_element().is_some() {
                 let closure_element_stack = element_stack.clone();
+                let closure_handler_error = handler_error.clone();
+                let subtree_markers = &subtree_markers;
+                let closure_text_captures = text_captures.clone();
+                let skipped_subtrees = &skipped_subtrees;
 
                 element_content_handlers.push(element!(
                     selector.match_element().unwrap(),
                     move |el| {
-                        match Self::process_element_handlers(
-                            ruby.get_inner(handler.rb_handler),
+                        if skipped_subtrees.skipping() {
+                            return Ok(());
+                        }
+
+                        if let Err(err) = deadline.check() {
+                            return Err(Self::stash_handler_error(
+                                &closure_handler_error,
+                                sanitize_error(err),
+                            ));
+                        }
+
+                        let calls = &handler.total_element_handler_calls;
+                        calls.set(calls.get() + 1);
+                        let start = Instant::now();
+
+                        // copied, so that nothing stays borrowed while Ruby runs
+                        let ancestors = closure_element_stack.borrow().clone();
+                        let result = Self::process_element_handlers(
+                            rb_handler,
                             el,
-                            &closure_element_stack.borrow(),
-                        ) {
+                            &ancestors,
+                            handler_sanitizer,
+                            context,
+                            subtree_markers,
+                            &closure_text_captures,
+                            skipped_subtrees,
+                            &closure_handler_error,
+                        )
+                        .and_then(|element_context| {
+                            if element_context.equal(Symbol::new(Self::SELMA_SKIP_CHILDREN))? {
+                                skipped_subtrees.skip(el);
+                            }
+
+                            if pass_element_context {
+                                Self::push_element_context(el, element_contexts, element_context)
+                            } else {
+                                Ok(())
+                            }
+                        });
+
+                        let elapsed = &handler.total_elapsed_element_handlers;
+                        elapsed.set(elapsed.get() + start.elapsed().as_secs_f64());
+
+                        match result {
                             Ok(_) => Ok(()),
-                            Err(err) => Err(err.to_string().into()),
+                            Err(err) => Err(Self::stash_handler_error(&closure_handler_error, err)),
                         }
                     }
                 ));
@@ -288,132 +1584,413 @@ impl SelmaRewriter {
 
             if selector.match_text_within().is_some() {
                 let closure_element_stack = element_stack.clone();
+                let closure_handler_error = handler_error.clone();
+                let skipped_subtrees = &skipped_subtrees;
 
                 element_content_handlers.push(text!(
                     selector.match_text_within().unwrap(),
                     move |text| {
-                        let element_stack = closure_element_stack.as_ref().borrow();
+                        // seems that sometimes lol-html returns blank text / EOLs?
+                        if text.as_str().is_empty() || skipped_subtrees.skipping() {
+                            return Ok(());
+                        }
+
+                        // copied, so that nothing stays borrowed while Ruby runs
+                        let element_stack = closure_element_stack.borrow().clone();
                         if selector.ignore_text_within().is_some() {
-                            // check if current tag is a tag we should be ignoring text within
-                            let head_tag_name = element_stack.last().unwrap().to_string();
-                            if selector
-                                .ignore_text_within()
-                                .unwrap()
-                                .iter()
-                                .any(|f| f == &head_tag_name)
-                            {
-                                return Ok(());
+                            // check if current tag is a tag we should be ignoring text within;
+                            // top-level text has no enclosing tag
+                            if let Some(head_tag_name) = element_stack.last() {
+                                if selector
+                                    .ignore_text_within()
+                                    .unwrap()
+                                    .iter()
+                                    .any(|f| f == head_tag_name)
+                                {
+                                    return Ok(());
+                                }
                             }
                         }
 
-                        let ruby = Ruby::get().unwrap();
-                        match Self::process_text_handlers(ruby.get_inner(handler.rb_handler), text)
-                        {
+                        if let Err(err) = deadline.check() {
+                            return Err(Self::stash_handler_error(
+                                &closure_handler_error,
+                                sanitize_error(err),
+                            ));
+                        }
+
+                        let calls = &handler.total_text_handler_calls;
+                        calls.set(calls.get() + 1);
+
+                        let element_context = if pass_element_context {
+                            // `nil` for text outside of any matched element
+                            match element_contexts.entry::<Value>(-1) {
+                                Ok(element_context) => Some(element_context),
+                                Err(err) => {
+                                    return Err(Self::stash_handler_error(
+                                        &closure_handler_error,
+                                        err,
+                                    ))
+                                }
+                            }
+                        } else {
+                            None
+                        };
+
+                        let start = Instant::now();
+                        let result = Self::process_text_handlers(
+                            rb_handler,
+                            text,
+                            &element_stack,
+                            handler_sanitizer,
+                            context,
+                            element_context,
+                        );
+
+                        let elapsed = &handler.total_elapsed_text_handlers;
+                        elapsed.set(elapsed.get() + start.elapsed().as_secs_f64());
+
+                        match result {
                             Ok(_) => Ok(()),
-                            Err(err) => Err(err.to_string().into()),
+                            Err(err) => Err(Self::stash_handler_error(&closure_handler_error, err)),
                         }
                     }
                 ));
             }
 
-            // we need to check *every* element we iterate over, to create a stack of elements
+            if let Some(css) = selector.relocate() {
+                let subtree_markers = &subtree_markers;
+
+                element_content_handlers.push(element!(css, move |el| {
+                    if !el.removed() {
+                        subtree_markers.mark_relocated(el, handler_index);
+                    }
+                    Ok(())
+                }));
+            }
+        }
+
+        if let Some(nonce) = &rewriter.csp_nonce {
+            // after the Ruby handlers, so that none of them can leave a `<script>`
+            // or `<style>` with some other nonce
+            element_content_handlers.push(element!("script, style", move |el| {
+                if !el.removed() {
+                    el.set_attribute("nonce", nonce)?;
+                }
+                Ok(())
+            }));
+        }
+
+        let mut document_content_handlers: Vec<DocumentContentHandlers> = vec![];
+
+        for (index, css) in text_replacements.within_selectors() {
+            let text_replacements = &text_replacements;
+
+            element_content_handlers.push(element!(css, move |el| {
+                text_replacements.enter_within(index, el);
+                Ok(())
+            }));
+        }
+
+        if !rewriter.extraction.is_empty() {
+            let closure_element_stack = element_stack.clone();
+            let extractor = &extractor;
+
+            // before any text is replaced, to count what the document said
+            document_content_handlers.push(doc_text!(move |text| {
+                extractor.push_text(text, &closure_element_stack.borrow());
+                Ok(())
+            }));
+        }
+
+        if !text_replacements.is_empty() {
+            let closure_element_stack = element_stack.clone();
+            let text_replacements = &text_replacements;
+
+            // after the Ruby handlers, since document handlers come last
+            document_content_handlers.push(doc_text!(move |text| {
+                text_replacements.replace_chunk(text, &closure_element_stack.borrow());
+                Ok(())
+            }));
+        }
+
+        if !handlers.is_empty() || !text_replacements.is_empty() || !rewriter.extraction.is_empty()
+        {
+            // we need to check *every* element we iterate over, to create a stack of elements;
+            // this comes after the handlers, so that an element isn't its own ancestor
+            let text_replacements = &text_replacements;
+            let extractor = &extractor;
             element_content_handlers.push(element!("*", move |el| {
                 let tag_name = el.tag_name().to_lowercase();
+                text_replacements.enter_element(&tag_name);
+                extractor.enter_element(el, &tag_name);
 
                 // no need to track self-closing tags
                 if Tag::tag_from_tag_name(&tag_name).self_closing {
                     return Ok(());
                 };
 
+                // if there's no end tag to pop this element off the stack, don't push it either
+                let end_tag_handlers = match el.end_tag_handlers() {
+                    Some(end_tag_handlers) => end_tag_handlers,
+                    None => return Ok(()),
+                };
+
                 element_stack.as_ref().borrow_mut().push(tag_name);
 
                 let closure_element_stack = element_stack.clone();
 
-                el.end_tag_handlers()
-                    .unwrap()
-                    .push(Box::new(move |_end_tag| {
-                        let mut stack = closure_element_stack.as_ref().borrow_mut();
-                        stack.pop();
-                        Ok(())
-                    }));
+                end_tag_handlers.push(Box::new(move |_end_tag| {
+                    let mut stack = closure_element_stack.as_ref().borrow_mut();
+                    stack.pop();
+                    Ok(())
+                }));
+
+                Ok(())
+            }));
+        }
+
+        // every bit of text goes to the `text_content` of each element it's within
+        document_content_handlers.push(doc_text!(|text| {
+            text_captures.push_text(text.as_str());
+            Ok(())
+        }));
 
+        let parser_stats = &rewriter.parser_stats;
+        parser_stats.bytes_parsed.set(html.len());
+        parser_stats.selectors.set(element_content_handlers.len());
+
+        if rewriter.profile {
+            element_content_handlers.push(element!("*", |_el| {
+                ParserStats::increment(&parser_stats.elements);
+                Ok(())
+            }));
+            document_content_handlers.push(doc_text!(|text| {
+                if !text.as_str().is_empty() {
+                    ParserStats::increment(&parser_stats.text_chunks);
+                }
+                Ok(())
+            }));
+            document_content_handlers.push(doc_comments!(|_comment| {
+                ParserStats::increment(&parser_stats.comments);
                 Ok(())
             }));
-        });
+        }
 
+        let extracted = &rewriter.extracted;
+        let malformed = rewriter.malformed;
         let mut output = vec![];
         {
             let mut rewriter = HtmlRewriter::new(
                 Settings {
                     element_content_handlers,
+                    document_content_handlers,
+                    strict: malformed.strict(),
                     ..Settings::default()
                 },
                 |c: &[u8]| output.extend_from_slice(c),
             );
-            match rewriter.write(html.as_bytes()) {
+            let mut written = Ok(());
+            for chunk in deadline.chunks(html.as_bytes()) {
+                deadline.check().map_err(sanitize_error)?;
+                written = malformed.tolerate(rewriter.write(chunk));
+                if written.is_err() {
+                    break;
+                }
+            }
+            extracted.replace(extractor.finish());
+
+            match written {
                 Ok(_) => {}
+                // errors raised by the Ruby handlers themselves
+                Err(RewritingError::ContentHandlerError(err)) => {
+                    let handler_error = handler_error.borrow_mut().take();
+
+                    // a handler called `halt!`, and wants what's been written so far
+                    if self.1.get() == Some(Halt::ReturnOutput) {
+                        std::hint::black_box(gc_anchors);
+                        return Ok(subtree_markers.strip(output));
+                    }
+
+                    return Err(match handler_error {
+                        Some(handler_error) => handler_error,
+                        None => rewriting_error(format!("Failed to rewrite HTML: {err}")),
+                    });
+                }
                 Err(err) => {
-                    return Err(magnus::Error::new(
-                        exception::runtime_error(),
-                        format!("{err:?}"),
-                    ));
+                    return Err(rewriting_error(format!("Failed to rewrite HTML: {err}")));
                 }
             }
         }
+        std::hint::black_box(gc_anchors);
+
+        if subtree_markers.marked.get() {
+            return Self::extract_subtrees(handlers, handler_sanitizer, &subtree_markers, output);
+        }
+
         Ok(output)
     }
 
+    /// Replaces the subtrees handlers captured, and moves what each handler's
+    /// `relocate:` selector matched to the end of the document, in whatever form
+    /// `handle_relocated` returns it.
+    fn extract_subtrees(
+        handlers: &[Handler],
+        handler_sanitizer: Option<&SelmaSanitizer>,
+        subtree_markers: &SubtreeMarkers,
+        output: Vec<u8>,
+    ) -> Result<Vec<u8>, magnus::Error> {
+        let output = match String::from_utf8(output) {
+            Ok(output) => output,
+            Err(err) => {
+                return Err(rewriting_error(format!(
+                    "Failed to rewrite HTML: output is not valid UTF-8 ({err})"
+                )))
+            }
+        };
+        let (mut output, relocated) =
+            subtree_markers.extract(&output, handlers.len(), handler_sanitizer)?;
+
+        let ruby = Ruby::get().unwrap();
+        for (handler, relocated) in handlers.iter().zip(relocated) {
+            if relocated.is_empty() {
+                continue;
+            }
+
+            let rb_handler = ruby.get_inner(handler.rb_handler);
+            let rb_relocated = RArray::from_vec(relocated);
+            let html: Option<String> =
+                rb_handler.funcall(Self::SELMA_HANDLE_RELOCATED, (rb_relocated,))?;
+
+            if let Some(html) = html {
+                match handler_sanitizer {
+                    Some(sanitizer) => output.push_str(&Self::sanitize_fragment(sanitizer, html)?),
+                    None => output.push_str(&html),
+                }
+            }
+        }
+
+        Ok(output.into_bytes())
+    }
+
+    #[allow(clippy::too_many_arguments)]
     fn process_element_handlers(
         rb_handler: Value,
         element: &mut Element,
         ancestors: &[String],
-    ) -> Result<(), magnus::Error> {
+        handler_sanitizer: Option<&SelmaSanitizer>,
+        context: RHash,
+        subtree_markers: &SubtreeMarkers,
+        text_captures: &Rc<TextCaptures>,
+        skipped_subtrees: &SkippedSubtrees,
+        handler_error: &Rc<RefCell<Option<magnus::Error>>>,
+    ) -> Result<Value, magnus::Error> {
         // if `on_end_tag` function is defined, call it
-        if rb_handler.respond_to(Self::SELMA_ON_END_TAG, true).unwrap() {
-            // TODO: error here is an "EndTagError"
-            element
-                .end_tag_handlers()
-                .unwrap()
-                .push(Box::new(move |end_tag| {
-                    let rb_end_tag = SelmaHTMLEndTag::new(end_tag);
+        if rb_handler.respond_to(Self::SELMA_ON_END_TAG, true)? {
+            // elements which can't have an end tag (like `<br>`) never call `on_end_tag`
+            if let Some(end_tag_handlers) = element.end_tag_handlers() {
+                let handler_error = handler_error.clone();
+
+                end_tag_handlers.push(Box::new(move |end_tag| {
+                    let rb_end_tag = SelmaHTMLEndTag::new(end_tag, context);
 
                     match rb_handler.funcall::<_, _, Value>(Self::SELMA_ON_END_TAG, (rb_end_tag,)) {
                         Ok(_) => Ok(()),
-                        Err(err) => Err(err.to_string().into()),
+                        Err(err) => Err(Self::stash_handler_error(&handler_error, err)),
                     }
                 }));
+            }
         }
 
-        let rb_element = SelmaHTMLElement::new(element, ancestors);
-        let rb_result =
-            rb_handler.funcall::<_, _, Value>(Self::SELMA_HANDLE_ELEMENT, (rb_element,));
-        match rb_result {
-            Ok(_) => Ok(()),
-            Err(err) => Err(err),
+        let rb_element = SelmaHTMLElement::new(
+            element,
+            ancestors,
+            handler_sanitizer,
+            context,
+            subtree_markers,
+            text_captures,
+            skipped_subtrees,
+        );
+        rb_handler.funcall::<_, _, Value>(Self::SELMA_HANDLE_ELEMENT, (rb_element,))
+    }
+
+    /// Whether `handle_text_chunk` can be called with a second argument, the
+    /// value `handle_element` returned for the enclosing element.
+    fn takes_element_context(rb_handler: Value) -> Result<bool, magnus::Error> {
+        // leave it to the actual call to complain about a missing method
+        if !rb_handler.respond_to(Self::SELMA_HANDLE_TEXT_CHUNK, true)? {
+            return Ok(false);
         }
+
+        let arity: i64 = rb_handler
+            .funcall::<_, _, Value>("method", (Symbol::new(Self::SELMA_HANDLE_TEXT_CHUNK),))?
+            .funcall("arity", ())?;
+
+        // two required arguments, or one or two followed by optional ones
+        Ok(matches!(arity, 2 | -2 | -3))
     }
 
-    fn process_text_handlers(
-        rb_handler: Value,
-        text_chunk: &mut TextChunk,
+    fn push_element_context(
+        element: &mut Element,
+        element_contexts: RArray,
+        element_context: Value,
     ) -> Result<(), magnus::Error> {
-        // prevents missing `handle_text_chunk` function
-        let content = text_chunk.as_str();
-
-        // seems that sometimes lol-html returns blank text / EOLs?
-        if content.is_empty() {
+        // a removed element takes its text along with it
+        if element.removed() {
             return Ok(());
         }
 
-        let rb_text_chunk = SelmaHTMLTextChunk::new(text_chunk);
-        match rb_handler.funcall::<_, _, Value>(Self::SELMA_HANDLE_TEXT_CHUNK, (rb_text_chunk,)) {
+        // elements without an end tag (like `<br>`) have no text to pass the context to
+        if let Some(end_tag_handlers) = element.end_tag_handlers() {
+            element_contexts.push(element_context)?;
+
+            end_tag_handlers.push(Box::new(move |_end_tag| {
+                match element_contexts.pop::<Value>() {
+                    Ok(_) => Ok(()),
+                    Err(err) => Err(err.to_string().into()),
+                }
+            }));
+        }
+
+        Ok(())
+    }
+
+    fn process_text_handlers(
+        rb_handler: Value,
+        text_chunk: &mut TextChunk,
+        ancestors: &[String],
+        handler_sanitizer: Option<&SelmaSanitizer>,
+        context: RHash,
+        element_context: Option<Value>,
+    ) -> Result<(), magnus::Error> {
+        let rb_text_chunk =
+            SelmaHTMLTextChunk::new(text_chunk, ancestors, handler_sanitizer, context);
+        let rb_result = match element_context {
+            None => {
+                rb_handler.funcall::<_, _, Value>(Self::SELMA_HANDLE_TEXT_CHUNK, (rb_text_chunk,))
+            }
+            Some(element_context) => rb_handler.funcall::<_, _, Value>(
+                Self::SELMA_HANDLE_TEXT_CHUNK,
+                (rb_text_chunk, element_context),
+            ),
+        };
+        match rb_result {
             Ok(_) => Ok(()),
-            Err(err) => Err(magnus::Error::new(
-                exception::runtime_error(),
-                format!("{err:?}"),
-            )),
+            Err(err) => Err(err),
         }
     }
+
+    /// Holds on to the (first) error a Ruby handler raised, and hands lol_html
+    /// a description of it to abort the rewrite with.
+    fn stash_handler_error(
+        handler_error: &RefCell<Option<magnus::Error>>,
+        err: magnus::Error,
+    ) -> Box<dyn std::error::Error + Send + Sync> {
+        let message = err.to_string();
+        handler_error.borrow_mut().get_or_insert(err);
+
+        message.into()
+    }
 }
 
 pub fn init(m_selma: RModule) -> Result<(), magnus::Error> {
@@ -425,6 +2002,33 @@ pub fn init(m_selma: RModule) -> Result<(), magnus::Error> {
     c_rewriter
         .define_method("rewrite", method!(SelmaRewriter::rewrite, 1))
         .expect("cannot define method `rewrite`");
+    c_rewriter
+        .define_method(
+            "rewrite_with_context",
+            method!(SelmaRewriter::rewrite_with_context, -1),
+        )
+        .expect("cannot define method `rewrite_with_context`");
+    c_rewriter
+        .define_method("halt!", method!(SelmaRewriter::halt, -1))
+        .expect("cannot define method `halt!`");
+    c_rewriter
+        .define_method("with", method!(SelmaRewriter::with, -1))
+        .expect("cannot define method `with`");
+
+    crate::text_replace::init(c_rewriter)
+        .expect("cannot define Selma::Rewriter::TextReplace class");
+    c_rewriter
+        .define_method("stats", method!(SelmaRewriter::stats, 0))
+        .expect("cannot define method `stats`");
+    c_rewriter
+        .define_method("last_run_stats", method!(SelmaRewriter::last_run_stats, 0))
+        .expect("cannot define method `last_run_stats`");
+    c_rewriter
+        .define_method("extracted", method!(SelmaRewriter::extracted, 0))
+        .expect("cannot define method `extracted`");
+    c_rewriter
+        .define_method("explain", method!(SelmaRewriter::explain, 1))
+        .expect("cannot define method `explain`");
 
     Ok(())
 }
@@ -1,46 +1,38 @@
-use std::{borrow::BorrowMut, collections::HashMap};
+use std::cell::Ref;
 
-use lol_html::{
-    errors::AttributeNameError,
-    html_content::{Comment, ContentType, Doctype, Element, EndTag},
-};
 use magnus::{
-    class, function, method, scan_args,
-    value::{Opaque, ReprValue},
-    Module, Object, RArray, RHash, RModule, Ruby, Value,
+    class, exception, function, method, scan_args,
+    typed_data::Obj,
+    value::{Lazy, Opaque, ReprValue},
+    Module, Object, RArray, RHash, RModule, Ruby, Symbol, Value,
+};
+use selma_core::css::CssPolicy;
+use selma_core::sanitizer::{
+    AttributeLimit, AttributeOverflow, AttributeValueLimit, AttributeValueOverflow, ClassLimit,
+    ClassOverflow, ControlCharacters, DuplicateAttributes, EmbedFallback, ImagePolicy,
+    IntegrityPolicy, NoscriptPolicy, SandboxMode, Sanitizer, ShadowRoots, SourcePolicy,
+    UnicodeFilter,
 };
 
-#[derive(Clone, Debug, Default)]
-struct ElementSanitizer {
-    allowed_attrs: Vec<String>,
-    required_attrs: Vec<String>,
-    allowed_classes: Vec<String>,
-    protocol_sanitizers: HashMap<String, Vec<String>>,
-}
+use crate::{config::ConfigWarning, errors::invalid_state_error, profiles::Profile};
 
+/// A `Sanitizer`, along with the config it was set up from, and whether it can
+/// still be changed from Ruby.
 #[derive(Clone)]
-pub struct Sanitizer {
-    flags: [u8; crate::tags::Tag::TAG_COUNT],
-    allowed_attrs: Vec<String>,
-    allowed_classes: Vec<String>,
-    element_sanitizers: HashMap<String, ElementSanitizer>,
-
-    pub escape_tagfilter: bool,
-    pub allow_comments: bool,
-    pub allow_doctype: bool,
+pub struct ConfiguredSanitizer {
+    engine: Sanitizer,
+    immutable: bool,
+    /// Whether `Selma::Sanitizer#setup` has applied the config yet
+    set_up: bool,
     config: Opaque<RHash>,
+    warnings: Vec<ConfigWarning>,
 }
 
 #[derive(Clone)]
 #[magnus::wrap(class = "Selma::Sanitizer")]
-pub struct SelmaSanitizer(std::cell::RefCell<Sanitizer>);
+pub struct SelmaSanitizer(std::cell::RefCell<ConfiguredSanitizer>);
 
 impl SelmaSanitizer {
-    const SELMA_SANITIZER_ALLOW: u8 = (1 << 0);
-    // const SELMA_SANITIZER_ESCAPE_TAGFILTER: u8 = (1 << 1);
-    const SELMA_SANITIZER_REMOVE_CONTENTS: u8 = (1 << 2);
-    const SELMA_SANITIZER_WRAP_WHITESPACE: u8 = (1 << 3);
-
     pub fn new(arguments: &[Value]) -> Result<Self, magnus::Error> {
         let args = scan_args::scan_args::<(), (Option<RHash>,), (), (), (), ()>(arguments)?;
         let (opt_config,): (Option<RHash>,) = args.optional;
@@ -51,26 +43,144 @@ impl SelmaSanitizer {
             None => magnus::eval::<RHash>(r#"Selma::Sanitizer::Config::DEFAULT"#).unwrap(),
         };
 
-        let mut element_sanitizers = HashMap::new();
-        crate::tags::Tag::html_tags().iter().for_each(|html_tag| {
-            let es = ElementSanitizer::default();
-            element_sanitizers.insert(
-                crate::tags::Tag::element_name_from_enum(html_tag).to_string(),
-                es,
-            );
-        });
+        let warnings = crate::config::validate(config)?;
+        for warning in &warnings {
+            // `Kernel#warn` goes through `Warning.warn`, unless `$VERBOSE` is nil
+            magnus::module::kernel().funcall::<_, _, Value>(
+                "warn",
+                (format!(
+                    "Selma::Sanitizer: {}: {}",
+                    warning.path, warning.message
+                ),),
+            )?;
+        }
+
+        let sanitizer = Self::with_config(config);
+        sanitizer.0.borrow_mut().warnings = warnings;
 
-        Ok(Self(std::cell::RefCell::new(Sanitizer {
-            flags: [0; crate::tags::Tag::TAG_COUNT],
-            allowed_attrs: vec![],
-            allowed_classes: vec![],
-            element_sanitizers,
+        Ok(sanitizer)
+    }
 
-            escape_tagfilter: true,
-            allow_comments: false,
-            allow_doctype: true,
+    fn with_config(config: RHash) -> Self {
+        Self(std::cell::RefCell::new(ConfiguredSanitizer {
+            engine: Sanitizer::new(),
+            immutable: false,
+            set_up: false,
             config: config.into(),
-        })))
+            warnings: vec![],
+        }))
+    }
+
+    /// Builds a sanitizer from one of the native profiles. The result is
+    /// already set up, and can't be reconfigured from Ruby.
+    fn from_profile(profile: &Profile) -> Result<Self, magnus::Error> {
+        let config = magnus::eval::<RHash>(&format!(
+            "Selma::Sanitizer::Config::{}",
+            profile.config_name
+        ))?;
+        let sanitizer = Self::with_config(config);
+
+        {
+            let mut binding = sanitizer.0.borrow_mut();
+            binding.engine = Sanitizer::from_profile(profile);
+            binding.immutable = true;
+        }
+
+        Ok(sanitizer)
+    }
+
+    pub fn basic() -> Obj<Self> {
+        static BASIC: Lazy<Obj<SelmaSanitizer>> = Lazy::new(|_ruby| {
+            Obj::wrap(SelmaSanitizer::from_profile(&crate::profiles::BASIC).unwrap())
+        });
+
+        Ruby::get().unwrap().get_inner(&BASIC)
+    }
+
+    pub fn relaxed() -> Obj<Self> {
+        static RELAXED: Lazy<Obj<SelmaSanitizer>> = Lazy::new(|_ruby| {
+            Obj::wrap(SelmaSanitizer::from_profile(&crate::profiles::RELAXED).unwrap())
+        });
+
+        Ruby::get().unwrap().get_inner(&RELAXED)
+    }
+
+    pub fn restricted() -> Obj<Self> {
+        static RESTRICTED: Lazy<Obj<SelmaSanitizer>> = Lazy::new(|_ruby| {
+            Obj::wrap(SelmaSanitizer::from_profile(&crate::profiles::RESTRICTED).unwrap())
+        });
+
+        Ruby::get().unwrap().get_inner(&RESTRICTED)
+    }
+
+    pub fn github() -> Obj<Self> {
+        static GITHUB: Lazy<Obj<SelmaSanitizer>> = Lazy::new(|_ruby| {
+            Obj::wrap(SelmaSanitizer::from_profile(&crate::profiles::GITHUB).unwrap())
+        });
+
+        Ruby::get().unwrap().get_inner(&GITHUB)
+    }
+
+    /// Whether or not this sanitizer is one of the natively built profiles.
+    pub fn is_immutable(&self) -> bool {
+        self.0.borrow().immutable
+    }
+
+    /// What does the sanitizing, as configured so far.
+    pub fn engine(&self) -> Ref<'_, Sanitizer> {
+        Ref::map(self.0.borrow(), |sanitizer| &sanitizer.engine)
+    }
+
+    fn is_set_up(&self) -> bool {
+        self.0.borrow().set_up
+    }
+
+    fn mark_set_up(&self) {
+        self.0.borrow_mut().set_up = true;
+    }
+
+    /// A copy of everything this sanitizer allows, which can be changed
+    /// without changing this one, even if this is a built-in profile. The
+    /// config isn't read again.
+    fn derive(&self) -> Self {
+        let mut sanitizer = self.0.borrow().clone();
+        sanitizer.immutable = false;
+        sanitizer.set_up = true;
+
+        Self(std::cell::RefCell::new(sanitizer))
+    }
+
+    fn check_mutable(&self) -> Result<(), magnus::Error> {
+        if self.is_immutable() {
+            return Err(magnus::Error::new(
+                exception::frozen_error(),
+                "can't modify a built-in Selma::Sanitizer profile",
+            ));
+        }
+
+        // only possible from Ruby code which something sanitizing with this
+        // very sanitizer calls into, which would otherwise panic
+        if self.0.try_borrow_mut().is_err() {
+            return Err(invalid_state_error(
+                "cannot change a Selma::Sanitizer while it's sanitizing",
+            ));
+        }
+
+        Ok(())
+    }
+
+    /// What's in the config that can't have any effect, as `{path:, message:}`
+    /// hashes.
+    fn get_warnings(&self) -> Result<RArray, magnus::Error> {
+        let warnings = RArray::new();
+        for warning in &self.0.borrow().warnings {
+            let rb_warning = RHash::new();
+            rb_warning.aset(Symbol::new("path"), warning.path.as_str())?;
+            rb_warning.aset(Symbol::new("message"), warning.message.as_str())?;
+            warnings.push(rb_warning)?;
+        }
+
+        Ok(warnings)
     }
 
     fn get_config(&self) -> Result<RHash, magnus::Error> {
@@ -81,468 +191,651 @@ impl SelmaSanitizer {
     }
 
     /// Toggle a sanitizer option on or off.
-    fn set_flag(&self, tag_name: String, flag: u8, set: bool) {
-        let tag = crate::tags::Tag::tag_from_tag_name(tag_name.as_str());
-        if set {
-            self.0.borrow_mut().flags[tag.index] |= flag;
-        } else {
-            self.0.borrow_mut().flags[tag.index] &= !flag;
-        }
+    fn set_flag(&self, tag_name: String, flag: u8, set: bool) -> Result<(), magnus::Error> {
+        self.check_mutable()?;
+
+        self.0.borrow_mut().engine.set_flag(&tag_name, flag, set);
+        Ok(())
     }
 
     /// Toggles all sanitization options on or off.
-    fn set_all_flags(&self, flag: u8, set: bool) {
-        if set {
-            crate::tags::Tag::html_tags()
-                .iter()
-                .enumerate()
-                .for_each(|(iter, _)| {
-                    self.0.borrow_mut().flags[iter] |= flag;
-                });
-        } else {
-            crate::tags::Tag::html_tags()
-                .iter()
-                .enumerate()
-                .for_each(|(iter, _)| {
-                    self.0.borrow_mut().flags[iter] &= flag;
-                });
-        }
-    }
+    fn set_all_flags(&self, flag: u8, set: bool) -> Result<(), magnus::Error> {
+        self.check_mutable()?;
 
-    /// Whether or not to keep dangerous HTML tags.
-    fn set_escape_tagfilter(&self, allow: bool) -> bool {
-        self.0.borrow_mut().escape_tagfilter = allow;
-        allow
+        self.0.borrow_mut().engine.set_all_flags(flag, set);
+        Ok(())
     }
 
-    pub fn escape_tagfilter(&self, e: &mut Element) -> bool {
-        if self.0.borrow().escape_tagfilter {
-            let tag = crate::tags::Tag::tag_from_element(e);
-            if crate::tags::Tag::is_tag_escapeworthy(tag) {
-                e.remove();
-                return true;
-            }
-        }
+    /// Whether or not to keep dangerous HTML tags.
+    fn set_escape_tagfilter(&self, allow: bool) -> Result<bool, magnus::Error> {
+        self.check_mutable()?;
 
-        false
+        self.0.borrow_mut().engine.escape_tagfilter = allow;
+        Ok(allow)
     }
 
     pub fn get_escape_tagfilter(&self) -> bool {
-        self.0.borrow().escape_tagfilter
+        self.0.borrow().engine.escape_tagfilter
     }
 
     /// Whether or not to keep HTML comments.
-    fn set_allow_comments(&self, allow: bool) -> bool {
-        self.0.borrow_mut().allow_comments = allow;
-        allow
+    fn set_allow_comments(&self, allow: bool) -> Result<bool, magnus::Error> {
+        self.check_mutable()?;
+
+        self.0.borrow_mut().engine.allow_comments = allow;
+        Ok(allow)
     }
 
     pub fn get_allow_comments(&self) -> bool {
-        self.0.borrow().allow_comments
+        self.0.borrow().engine.allow_comments
+    }
+
+    /// Whether or not to keep IE conditional comments (`<!--[if IE]>...<![endif]-->`),
+    /// when comments are kept at all. Their contents are markup to IE, so
+    /// they're dropped as a whole by default.
+    fn set_allow_conditional_comments(&self, allow: bool) -> Result<bool, magnus::Error> {
+        self.check_mutable()?;
+
+        self.0.borrow_mut().engine.allow_conditional_comments = allow;
+        Ok(allow)
+    }
+
+    pub fn get_allow_conditional_comments(&self) -> bool {
+        self.0.borrow().engine.allow_conditional_comments
     }
 
-    pub fn remove_comment(&self, c: &mut Comment) {
-        c.remove();
+    /// Whether or not to keep CDATA sections (`<![CDATA[...]]>`). Outside of
+    /// SVG and MathML these are parsed as comments, so when they're kept, it's
+    /// only when comments are kept, too.
+    fn set_allow_cdata(&self, allow: bool) -> Result<bool, magnus::Error> {
+        self.check_mutable()?;
+
+        self.0.borrow_mut().engine.allow_cdata = allow;
+        Ok(allow)
+    }
+
+    pub fn get_allow_cdata(&self) -> bool {
+        self.0.borrow().engine.allow_cdata
     }
 
     /// Whether or not to keep HTML doctype.
-    fn set_allow_doctype(&self, allow: bool) -> bool {
-        self.0.borrow_mut().allow_doctype = allow;
-        allow
+    fn set_allow_doctype(&self, allow: bool) -> Result<bool, magnus::Error> {
+        self.check_mutable()?;
+
+        self.0.borrow_mut().engine.allow_doctype = allow;
+        Ok(allow)
     }
 
     /// Whether or not to keep HTML doctype.
     pub fn get_allow_doctype(&self) -> bool {
-        self.0.borrow().allow_doctype
+        self.0.borrow().engine.allow_doctype
     }
 
-    pub fn remove_doctype(&self, d: &mut Doctype) {
-        d.remove();
-    }
+    /// Whether or not to rewrite the entities in text, so that the same text
+    /// always comes out the same way.
+    fn set_normalize_entities(&self, normalize: bool) -> Result<bool, magnus::Error> {
+        self.check_mutable()?;
 
-    fn set_allowed_attribute(&self, eln: Value, attr_name: String, allow: bool) -> bool {
-        let mut binding = self.0.borrow_mut();
+        self.0.borrow_mut().engine.normalize_entities = normalize;
+        Ok(normalize)
+    }
 
-        let element_name = eln.to_r_string().unwrap().to_string().unwrap();
-        if element_name == "all" {
-            let allowed_attrs = &mut binding.allowed_attrs;
-            Self::set_allowed(allowed_attrs, &attr_name, allow);
-        } else {
-            let element_sanitizers = &mut binding.element_sanitizers;
-            let element_sanitizer = Self::get_element_sanitizer(element_sanitizers, &element_name);
+    pub fn get_normalize_entities(&self) -> bool {
+        self.0.borrow().engine.normalize_entities
+    }
 
-            element_sanitizer.allowed_attrs.push(attr_name);
-        }
+    /// How to treat `<noscript>` elements: `:remove`, `:unwrap`, `:escape`, or
+    /// `nil` to treat them like any other element.
+    fn set_noscript(&self, policy: Option<Symbol>) -> Result<Option<Symbol>, magnus::Error> {
+        self.check_mutable()?;
+
+        let noscript = match policy {
+            None => None,
+            Some(policy) => match policy.name()?.as_ref() {
+                "remove" => Some(NoscriptPolicy::Remove),
+                "unwrap" => Some(NoscriptPolicy::Unwrap),
+                "escape" => Some(NoscriptPolicy::Escape),
+                name => {
+                    return Err(magnus::Error::new(
+                        exception::arg_error(),
+                        format!("unknown noscript policy `:{name}`"),
+                    ))
+                }
+            },
+        };
 
-        allow
+        self.0.borrow_mut().engine.noscript = noscript;
+        Ok(policy)
     }
 
-    fn set_allowed_class(&self, element_name: String, class_name: String, allow: bool) -> bool {
-        let mut binding = self.0.borrow_mut();
-        if element_name == "all" {
-            let allowed_classes = &mut binding.allowed_classes;
-            Self::set_allowed(allowed_classes, &class_name, allow);
-        } else {
-            let element_sanitizers = &mut binding.element_sanitizers;
-            let element_sanitizer = Self::get_element_sanitizer(element_sanitizers, &element_name);
+    /// What to do with declarative shadow roots: `:remove`, `:flatten` or
+    /// `:sanitize`. By default (`nil`), they're treated like any other `<template>`.
+    fn set_shadow_roots(&self, policy: Option<Symbol>) -> Result<Option<Symbol>, magnus::Error> {
+        self.check_mutable()?;
+
+        let shadow_roots = match policy {
+            None => None,
+            Some(policy) => match policy.name()?.as_ref() {
+                "remove" => Some(ShadowRoots::Remove),
+                "flatten" => Some(ShadowRoots::Flatten),
+                "sanitize" => Some(ShadowRoots::Sanitize),
+                name => {
+                    return Err(magnus::Error::new(
+                        exception::arg_error(),
+                        format!("unknown shadow root policy `:{name}`"),
+                    ))
+                }
+            },
+        };
 
-            let allowed_classes = element_sanitizer.allowed_classes.borrow_mut();
-            Self::set_allowed(allowed_classes, &class_name, allow)
-        }
-        allow
+        self.0.borrow_mut().engine.shadow_roots = shadow_roots;
+        Ok(policy)
     }
 
-    fn set_allowed_protocols(&self, element_name: String, attr_name: String, allow_list: RArray) {
-        let mut binding = self.0.borrow_mut();
-
-        let element_sanitizers = &mut binding.element_sanitizers;
-        let element_sanitizer = Self::get_element_sanitizer(element_sanitizers, &element_name);
+    /// What to do with the `integrity` and `crossorigin` of `<script>` and
+    /// `<link>` elements: `:strip` or `:require`. By default (`nil`), they're
+    /// sanitized like any other attributes.
+    fn set_integrity(&self, policy: Option<Symbol>) -> Result<Option<Symbol>, magnus::Error> {
+        self.check_mutable()?;
+
+        let integrity = match policy {
+            None => None,
+            Some(policy) => match policy.name()?.as_ref() {
+                "strip" => Some(IntegrityPolicy::Strip),
+                "require" => Some(IntegrityPolicy::Require),
+                name => {
+                    return Err(magnus::Error::new(
+                        exception::arg_error(),
+                        format!("unknown integrity policy `:{name}`"),
+                    ))
+                }
+            },
+        };
 
-        let protocol_sanitizers = &mut element_sanitizer.protocol_sanitizers.borrow_mut();
+        self.0.borrow_mut().engine.integrity = integrity;
+        Ok(policy)
+    }
 
-        for opt_allowed_protocol in allow_list.each() {
-            let allowed_protocol = opt_allowed_protocol.unwrap();
-            let protocol_list = protocol_sanitizers.get_mut(&attr_name);
-            if allowed_protocol.is_kind_of(class::string()) {
-                match protocol_list {
-                    None => {
-                        protocol_sanitizers
-                            .insert(attr_name.to_string(), vec![allowed_protocol.to_string()]);
-                    }
-                    Some(protocol_list) => protocol_list.push(allowed_protocol.to_string()),
+    /// What to do with bidi overrides, zero-width characters and the like,
+    /// in text and attribute values. By default (`nil`), they're kept.
+    fn set_unicode_filter(&self, filter: Option<Symbol>) -> Result<Option<Symbol>, magnus::Error> {
+        self.check_mutable()?;
+
+        let unicode_filter = match filter {
+            None => None,
+            Some(filter) => match filter.name()?.as_ref() {
+                "strip" => Some(UnicodeFilter::Strip),
+                "escape" => Some(UnicodeFilter::Escape),
+                name => {
+                    return Err(magnus::Error::new(
+                        exception::arg_error(),
+                        format!("unknown unicode filter `:{name}`"),
+                    ))
                 }
-            } else if allowed_protocol.is_kind_of(class::symbol())
-                && allowed_protocol.inspect() == ":relative"
-            {
-                match protocol_list {
-                    None => {
-                        protocol_sanitizers.insert(
-                            attr_name.to_string(),
-                            vec!["#".to_string(), "/".to_string()],
-                        );
-                    }
-                    Some(protocol_list) => {
-                        protocol_list.push("#".to_string());
-                        protocol_list.push("/".to_string());
-                    }
+            },
+        };
+
+        self.0.borrow_mut().engine.unicode_filter = unicode_filter;
+        Ok(filter)
+    }
+
+    /// What to do with control characters in the HTML: `:strip` or `:reject`.
+    /// By default (`nil`), they're left for the parser to deal with.
+    fn set_control_characters(
+        &self,
+        policy: Option<Symbol>,
+    ) -> Result<Option<Symbol>, magnus::Error> {
+        self.check_mutable()?;
+
+        let control_characters = match policy {
+            None => None,
+            Some(policy) => match policy.name()?.as_ref() {
+                "strip" => Some(ControlCharacters::Strip),
+                "reject" => Some(ControlCharacters::Reject),
+                name => {
+                    return Err(magnus::Error::new(
+                        exception::arg_error(),
+                        format!("unknown control characters policy `:{name}`"),
+                    ))
                 }
-            }
-        }
+            },
+        };
+
+        self.0.borrow_mut().engine.control_characters = control_characters;
+        Ok(policy)
     }
 
-    fn set_allowed(set: &mut Vec<String>, attr_name: &String, allow: bool) {
-        if allow {
-            set.push(attr_name.to_string());
-        } else if set.contains(attr_name) {
-            set.swap_remove(set.iter().position(|x| x == attr_name).unwrap());
-        }
+    /// Denies event handler (or other) attributes, and attributes using dangerous
+    /// protocols, after the allowlist has been applied.
+    fn set_paranoid(
+        &self,
+        attribute_prefixes: Vec<String>,
+        protocols: Vec<String>,
+    ) -> Result<(), magnus::Error> {
+        self.check_mutable()?;
+
+        self.0
+            .borrow_mut()
+            .engine
+            .set_paranoid(attribute_prefixes, protocols);
+        Ok(())
     }
 
-    pub fn sanitize_attributes(&self, element: &mut Element) -> Result<(), AttributeNameError> {
-        let tag = crate::tags::Tag::tag_from_element(element);
-        let tag_name = &element.tag_name();
-        let element_sanitizer = {
-            let mut binding = self.0.borrow_mut();
-            let element_sanitizers = &mut binding.element_sanitizers;
-            Self::get_element_sanitizer(element_sanitizers, tag_name).clone()
-        };
+    pub fn is_paranoid(&self) -> bool {
+        self.0.borrow().engine.paranoid.is_some()
+    }
 
-        let binding = self.0.borrow();
+    /// Whether or not links can only point somewhere else in the same
+    /// document: any `href` on an `<a>` which isn't a fragment is removed.
+    fn set_fragment_links(&self, fragments_only: bool) -> Result<bool, magnus::Error> {
+        self.check_mutable()?;
 
-        // FIXME: This is a hack to get around the fact that we can't borrow
-        let attribute_map: HashMap<String, String> = element
-            .attributes()
-            .iter()
-            .map(|a| (a.name(), a.value()))
-            .collect();
-
-        for (attr_name, attr_val) in attribute_map.iter() {
-            // you can actually embed <!-- ... --> inside
-            // an HTML tag to pass malicious data. If this is
-            // encountered, remove the entire element to be safe.
-            if attr_name.starts_with("<!--") {
-                Self::force_remove_element(self, element);
-                return Ok(());
-            }
+        self.0.borrow_mut().engine.fragment_links = fragments_only;
+        Ok(fragments_only)
+    }
 
-            // first, trim leading spaces and unescape any encodings
-            let trimmed = attr_val.trim_start();
-            let x = escapist::unescape_html(trimmed.as_bytes());
-            let unescaped_attr_val = String::from_utf8_lossy(&x).to_string();
-
-            let should_keep_attrubute = match Self::should_keep_attribute(
-                &binding,
-                element,
-                &element_sanitizer,
-                attr_name,
-                &unescaped_attr_val,
-            ) {
-                Ok(should_keep) => should_keep,
-                Err(e) => {
-                    return Err(e);
-                }
-            };
-
-            if !should_keep_attrubute {
-                element.remove_attribute(attr_name);
-            } else {
-                // Prevent the use of `<meta>` elements that set a charset other than UTF-8,
-                // since output is always UTF-8.
-                if crate::tags::Tag::is_meta(tag) {
-                    if attr_name == "charset" && unescaped_attr_val != "utf-8" {
-                        match element.set_attribute(attr_name, "utf-8") {
-                            Ok(_) => {}
-                            Err(err) => {
-                                return Err(err);
-                            }
-                        }
-                    }
-                } else if !unescaped_attr_val.is_empty() {
-                    let mut buf = String::new();
-                    // ...then, escape any special characters, for security
-                    if attr_name == "href" {
-                        escapist::escape_href(&mut buf, unescaped_attr_val.as_str()).unwrap();
-                    } else {
-                        escapist::escape_html(&mut buf, unescaped_attr_val.as_str()).unwrap();
-                    };
-
-                    match element.set_attribute(attr_name, &buf) {
-                        Ok(_) => {}
-                        Err(err) => {
-                            return Err(err);
-                        }
-                    }
-                }
-            }
-        }
+    pub fn get_fragment_links(&self) -> bool {
+        self.0.borrow().engine.fragment_links
+    }
 
-        let required = &element_sanitizer.required_attrs;
-        if required.contains(&"*".to_string()) {
-            return Ok(());
-        }
-        for attr in element.attributes().iter() {
-            let attr_name = &attr.name();
-            if required.contains(attr_name) {
-                return Ok(());
-            }
-        }
+    /// Whether or not to keep forms looking like forms, without letting them work:
+    /// `<form>` is unwrapped, controls are disabled, and anything saying where
+    /// or how to submit is removed.
+    fn set_neutralize_forms(&self, neutralize: bool) -> Result<bool, magnus::Error> {
+        self.check_mutable()?;
 
-        Ok(())
+        self.0.borrow_mut().engine.neutralize_forms = neutralize;
+        Ok(neutralize)
     }
 
-    fn should_keep_attribute(
-        binding: &Sanitizer,
-        element: &mut Element,
-        element_sanitizer: &ElementSanitizer,
-        attr_name: &String,
-        attr_val: &String,
-    ) -> Result<bool, AttributeNameError> {
-        let mut allowed: bool = false;
-        let element_allowed_attrs = element_sanitizer.allowed_attrs.contains(attr_name);
-        let sanitizer_allowed_attrs = binding.allowed_attrs.contains(attr_name);
+    pub fn get_neutralize_forms(&self) -> bool {
+        self.0.borrow().engine.neutralize_forms
+    }
 
-        if element_allowed_attrs {
-            allowed = true;
-        }
+    /// Whether or not elements removed within a `<pre>`, `<textarea>` or
+    /// `<code>` are unwrapped without the whitespace `whitespace_elements`
+    /// would otherwise put around them, since it's significant there.
+    fn set_preserve_preformatted_whitespace(&self, preserve: bool) -> Result<bool, magnus::Error> {
+        self.check_mutable()?;
 
-        if !allowed && sanitizer_allowed_attrs {
-            allowed = true;
-        }
+        self.0.borrow_mut().engine.preserve_preformatted_whitespace = preserve;
+        Ok(preserve)
+    }
 
-        if !allowed {
-            return Ok(false);
-        }
+    pub fn get_preserve_preformatted_whitespace(&self) -> bool {
+        self.0.borrow().engine.preserve_preformatted_whitespace
+    }
 
-        let protocol_sanitizer_values = element_sanitizer.protocol_sanitizers.get(attr_name);
-        match protocol_sanitizer_values {
-            None => {
-                // has a protocol, but no sanitization list
-                if !attr_val.is_empty() && Self::has_protocol(attr_val) {
-                    return Ok(false);
+    /// What to do with an element which has the same attribute more than
+    /// once: `:drop` all but the first (the default), or `:remove` the element.
+    fn set_duplicate_attributes(
+        &self,
+        policy: Option<Symbol>,
+    ) -> Result<Option<Symbol>, magnus::Error> {
+        self.check_mutable()?;
+
+        let duplicate_attributes = match policy {
+            None => DuplicateAttributes::Drop,
+            Some(policy) => match policy.name()?.as_ref() {
+                "drop" => DuplicateAttributes::Drop,
+                "remove" => DuplicateAttributes::Remove,
+                name => {
+                    return Err(magnus::Error::new(
+                        exception::arg_error(),
+                        format!("unknown duplicate attributes policy `:{name}`"),
+                    ))
                 }
+            },
+        };
+
+        self.0.borrow_mut().engine.duplicate_attributes = duplicate_attributes;
+        Ok(policy)
+    }
+
+    /// Limits how many attributes an element can have (up to `count`), and
+    /// what happens to one with more: `:drop` or `:remove`.
+    fn set_attribute_limit(&self, count: u32, overflow: Symbol) -> Result<(), magnus::Error> {
+        self.check_mutable()?;
+
+        let overflow = match overflow.name()?.as_ref() {
+            "drop" => AttributeOverflow::Drop,
+            "remove" => AttributeOverflow::Remove,
+            name => {
+                return Err(magnus::Error::new(
+                    exception::arg_error(),
+                    format!("unknown attribute overflow `:{name}`"),
+                ))
             }
-            Some(protocol_sanitizer_values) => {
-                if !attr_val.is_empty()
-                    && !Self::has_allowed_protocol(protocol_sanitizer_values, attr_val)
-                {
-                    return Ok(false);
-                }
+        };
+
+        self.0.borrow_mut().engine.attribute_limit = Some(AttributeLimit {
+            count: count as usize,
+            overflow,
+        });
+        Ok(())
+    }
+
+    /// Limits how long (in bytes, once unescaped) an attribute's value can be,
+    /// and what happens to one that's longer: `:drop` or `:truncate`.
+    fn set_attribute_value_limit(&self, bytes: u32, overflow: Symbol) -> Result<(), magnus::Error> {
+        self.check_mutable()?;
+
+        let overflow = match overflow.name()?.as_ref() {
+            "drop" => AttributeValueOverflow::Drop,
+            "truncate" => AttributeValueOverflow::Truncate,
+            name => {
+                return Err(magnus::Error::new(
+                    exception::arg_error(),
+                    format!("unknown attribute value overflow `:{name}`"),
+                ))
             }
-        }
+        };
 
-        if attr_name == "class" {
-            return Self::sanitize_class_attribute(
-                binding,
-                element,
-                element_sanitizer,
-                attr_name,
-                attr_val,
-            );
-        }
+        self.0.borrow_mut().engine.attribute_value_limit = Some(AttributeValueLimit {
+            bytes: bytes as usize,
+            overflow,
+        });
+        Ok(())
+    }
 
-        Ok(true)
+    /// Limits how many classes a `class` attribute can have (up to `tokens`),
+    /// and how long each can be (up to `token_bytes`), and what happens to the
+    /// rest: `:drop` or `:remove`.
+    fn set_class_limit(
+        &self,
+        tokens: Option<u32>,
+        token_bytes: Option<u32>,
+        overflow: Symbol,
+    ) -> Result<(), magnus::Error> {
+        self.check_mutable()?;
+
+        let overflow = match overflow.name()?.as_ref() {
+            "drop" => ClassOverflow::Drop,
+            "remove" => ClassOverflow::Remove,
+            name => {
+                return Err(magnus::Error::new(
+                    exception::arg_error(),
+                    format!("unknown class overflow `:{name}`"),
+                ))
+            }
+        };
+
+        self.0.borrow_mut().engine.class_limit = Some(ClassLimit {
+            tokens: tokens.map(|tokens| tokens as usize),
+            token_bytes: token_bytes.map(|bytes| bytes as usize),
+            overflow,
+        });
+        Ok(())
     }
 
-    fn has_protocol(attr_val: &str) -> bool {
-        attr_val.contains("://")
+    /// Leaves the subtree of any element whose `attribute` is `token` unsanitized,
+    /// and takes the attribute away.
+    fn set_passthrough(&self, attribute: String, token: String) -> Result<(), magnus::Error> {
+        self.check_mutable()?;
+
+        self.0.borrow_mut().engine.set_passthrough(attribute, token);
+        Ok(())
     }
 
-    fn has_allowed_protocol(protocols_allowed: &[String], attr_val: &String) -> bool {
-        // FIXME: is there a more idiomatic way to do this?
-        let mut pos: usize = 0;
-        let mut chars = attr_val.chars();
-        let len = attr_val.len();
+    fn set_image_policy(
+        &self,
+        lazy: bool,
+        max_width: Option<u32>,
+        max_height: Option<u32>,
+        require_src: bool,
+    ) -> Result<(), magnus::Error> {
+        self.check_mutable()?;
+
+        self.0.borrow_mut().engine.images = Some(ImagePolicy {
+            lazy,
+            max_width,
+            max_height,
+            require_src,
+        });
+        Ok(())
+    }
 
-        for (i, c) in attr_val.chars().enumerate() {
-            if c != ':' && c != '/' && c != '#' && pos + 1 < len {
-                pos = i + 1;
-            } else {
-                break;
-            }
-        }
+    /// Checks the `media`, `sizes` and `type` of `<source>` elements, keeping
+    /// them (whether or not `attributes` allows them) when they're well-formed,
+    /// and the `type`s are among `types`.
+    fn set_source_policy(&self, types: Vec<String>) -> Result<(), magnus::Error> {
+        self.check_mutable()?;
 
-        let char = chars.nth(pos).unwrap();
+        self.0.borrow_mut().engine.sources = Some(SourcePolicy { types });
+        Ok(())
+    }
 
-        if char == '/' {
-            return protocols_allowed.contains(&"/".to_string());
-        }
+    /// Puts a placeholder, linking to what was embedded, in place of any
+    /// `<iframe>`, `<embed>` or `<object>` which is removed.
+    fn set_embed_fallback(
+        &self,
+        text: String,
+        template: Option<String>,
+    ) -> Result<(), magnus::Error> {
+        self.check_mutable()?;
 
-        if char == '#' {
-            return protocols_allowed.contains(&"#".to_string());
-        }
+        self.0.borrow_mut().engine.embed_fallback = Some(EmbedFallback { text, template });
+        Ok(())
+    }
 
-        // Allow protocol name to be case-insensitive
-        let protocol = attr_val[0..pos].to_lowercase();
+    /// Restricts kept `<iframe>`s: their `sandbox` is always set (see
+    /// `SandboxMode`), and their `referrerpolicy` and `allow` are checked
+    /// against `referrer_policies` and `allow`, when they're given.
+    fn set_iframe_policy(
+        &self,
+        sandbox: Vec<String>,
+        sandbox_mode: Symbol,
+        referrer_policies: Option<Vec<String>>,
+        allow: Option<Vec<String>>,
+    ) -> Result<(), magnus::Error> {
+        self.check_mutable()?;
+
+        let sandbox_mode = match sandbox_mode.name()?.as_ref() {
+            "replace" => SandboxMode::Replace,
+            "merge" => SandboxMode::Merge,
+            name => {
+                return Err(magnus::Error::new(
+                    exception::arg_error(),
+                    format!("unknown sandbox mode `:{name}`"),
+                ))
+            }
+        };
 
-        protocols_allowed.contains(&protocol.to_lowercase())
+        self.0.borrow_mut().engine.set_iframe_policy(
+            sandbox,
+            sandbox_mode,
+            referrer_policies,
+            allow,
+        );
+        Ok(())
     }
 
-    fn sanitize_class_attribute(
-        binding: &Sanitizer,
-        element: &mut Element,
-        element_sanitizer: &ElementSanitizer,
-        attr_name: &str,
-        attr_val: &str,
-    ) -> Result<bool, lol_html::errors::AttributeNameError> {
-        let allowed_global = &binding.allowed_classes;
+    /// Allows `<audio>` and `<video>` (along with their `<source>`s and
+    /// `<track>`s, and the attributes they need), with only `track_kinds` of
+    /// `<track>`, and without `autoplay` unless they're `muted`.
+    fn set_media_policy(&self, track_kinds: Vec<String>) -> Result<(), magnus::Error> {
+        self.check_mutable()?;
 
-        let mut valid_classes: Vec<String> = vec![];
+        self.0.borrow_mut().engine.set_media_policy(track_kinds);
+        Ok(())
+    }
 
-        let allowed_local = &element_sanitizer.allowed_classes;
+    /// Whether or not to turn AMP's media elements (`<amp-img>`, etc.) into their
+    /// HTML counterparts, before they're sanitized like any other element.
+    fn set_normalize_amp(&self, normalize: bool) -> Result<bool, magnus::Error> {
+        self.check_mutable()?;
 
-        // No class filters, so everything goes through
-        if allowed_global.is_empty() && allowed_local.is_empty() {
-            return Ok(true);
-        }
+        self.0.borrow_mut().engine.normalize_amp = normalize;
+        Ok(normalize)
+    }
 
-        let attr_value = attr_val.trim_start();
-        attr_value
-            .split_whitespace()
-            .map(|s| s.to_string())
-            .for_each(|class| {
-                if allowed_global.contains(&class) || allowed_local.contains(&class) {
-                    valid_classes.push(class);
-                }
-            });
+    /// Keeps only the declarations in `style` attributes which set one of
+    /// `properties`, with a value that can't load or run anything.
+    fn set_css_policy(&self, properties: Vec<String>) -> Result<(), magnus::Error> {
+        self.check_mutable()?;
 
-        if valid_classes.is_empty() {
-            return Ok(false);
-        }
+        self.0.borrow_mut().engine.css = Some(CssPolicy::new(properties));
+        Ok(())
+    }
 
-        match element.set_attribute(attr_name, valid_classes.join(" ").as_str()) {
-            Ok(_) => Ok(true),
-            Err(err) => Err(err),
-        }
+    /// Whether or not to remove the `src`, `srcset`, `background` and `poster`
+    /// attributes which would fetch something from another host.
+    fn set_strip_remote_content(&self, strip: bool) -> Result<bool, magnus::Error> {
+        self.check_mutable()?;
+
+        self.0.borrow_mut().engine.strip_remote_content = strip;
+        Ok(strip)
     }
 
-    pub fn allow_element(&self, element: &mut Element) -> bool {
-        let tag = crate::tags::Tag::tag_from_element(element);
-        let flags: u8 = self.0.borrow().flags[tag.index];
+    /// Which hosts (and their subdomains) images are considered trackers from,
+    /// or `None` to keep tracking pixels.
+    fn set_strip_tracking_pixels(&self, hosts: Option<Vec<String>>) -> Result<(), magnus::Error> {
+        self.check_mutable()?;
 
-        (flags & Self::SELMA_SANITIZER_ALLOW) == 0
+        self.0.borrow_mut().engine.set_strip_tracking_pixels(hosts);
+        Ok(())
     }
 
-    pub fn try_remove_element(&self, element: &mut Element) -> bool {
-        let tag = crate::tags::Tag::tag_from_element(element);
-        let flags: u8 = self.0.borrow().flags[tag.index];
+    fn set_table_policy(
+        &self,
+        wrap_rows: bool,
+        max_span: Option<u32>,
+        wrapper_class: Option<String>,
+    ) -> Result<(), magnus::Error> {
+        self.check_mutable()?;
+
+        self.0
+            .borrow_mut()
+            .engine
+            .set_table_policy(wrap_rows, max_span, wrapper_class);
+        Ok(())
+    }
 
-        let should_remove = !element.removed() && self.allow_element(element);
+    /// The prefix to apply to `id` and `name` attributes, and to fragment links.
+    fn set_name_prefix(
+        &self,
+        name_prefix: Option<String>,
+    ) -> Result<Option<String>, magnus::Error> {
+        self.check_mutable()?;
 
-        if should_remove {
-            if crate::tags::Tag::has_text_content(tag) {
-                Self::remove_element(
-                    element,
-                    tag.self_closing,
-                    Self::SELMA_SANITIZER_REMOVE_CONTENTS,
-                );
-            } else {
-                Self::remove_element(element, tag.self_closing, flags);
-            }
+        self.0.borrow_mut().engine.set_name_prefix(name_prefix);
+        Ok(self.get_name_prefix())
+    }
 
-            Self::check_if_end_tag_needs_removal(element);
-        } else {
-            // anything in <iframe> must be removed, if it's kept
-            if crate::tags::Tag::is_iframe(tag) {
-                if self.0.borrow().flags[tag.index] != 0 {
-                    element.set_inner_content(" ", ContentType::Text);
-                } else {
-                    element.set_inner_content("", ContentType::Text);
-                }
-            }
-        }
+    pub fn get_name_prefix(&self) -> Option<String> {
+        self.0.borrow().engine.name_prefix.clone()
+    }
+
+    fn set_allowed_attribute(
+        &self,
+        eln: Value,
+        attr_name: String,
+        allow: bool,
+    ) -> Result<bool, magnus::Error> {
+        self.check_mutable()?;
 
-        should_remove
+        let element_name = eln.to_r_string().unwrap().to_string().unwrap();
+        self.0
+            .borrow_mut()
+            .engine
+            .allow_attribute(&element_name, &attr_name, allow);
+        Ok(allow)
     }
 
-    fn remove_element(element: &mut Element, self_closing: bool, flags: u8) {
-        let wrap_whitespace = (flags & Self::SELMA_SANITIZER_WRAP_WHITESPACE) != 0;
-        let remove_contents = (flags & Self::SELMA_SANITIZER_REMOVE_CONTENTS) != 0;
+    fn set_allowed_class(
+        &self,
+        element_name: String,
+        class_name: String,
+        allow: bool,
+    ) -> Result<bool, magnus::Error> {
+        self.check_mutable()?;
+
+        self.0
+            .borrow_mut()
+            .engine
+            .allow_class(&element_name, &class_name, allow);
+        Ok(allow)
+    }
 
-        if remove_contents {
-            element.remove();
-        } else {
-            if wrap_whitespace {
-                if self_closing {
-                    element.after(" ", ContentType::Text);
-                } else {
-                    element.before(" ", ContentType::Text);
-                    element.after(" ", ContentType::Text);
-                }
+    /// Allows (or, with `allow` false, stops allowing) each of the protocols
+    /// in an element's attribute.
+    fn set_allowed_protocols(
+        &self,
+        element_name: String,
+        attr_name: String,
+        allow_list: RArray,
+        allow: bool,
+    ) -> Result<(), magnus::Error> {
+        self.check_mutable()?;
+
+        let mut protocols = vec![];
+        for opt_allowed_protocol in allow_list.each() {
+            let allowed_protocol = opt_allowed_protocol.unwrap();
+            if allowed_protocol.is_kind_of(class::string()) {
+                protocols.push(allowed_protocol.to_string());
+            } else if let Some(symbol) = Symbol::from_value(allowed_protocol) {
+                protocols.extend(Sanitizer::relative_protocols(&symbol.name()?));
             }
-            element.remove_and_keep_content();
         }
-    }
 
-    pub fn force_remove_element(&self, element: &mut Element) {
-        let tag = crate::tags::Tag::tag_from_element(element);
-        let self_closing = tag.self_closing;
-        Self::remove_element(element, self_closing, Self::SELMA_SANITIZER_REMOVE_CONTENTS);
-        Self::check_if_end_tag_needs_removal(element);
+        let engine = &mut self.0.borrow_mut().engine;
+        if allow {
+            engine.add_allowed_protocols(&element_name, &attr_name, protocols);
+        } else {
+            engine.remove_allowed_protocols(&element_name, &attr_name, &protocols);
+        }
+
+        Ok(())
     }
 
-    fn check_if_end_tag_needs_removal(element: &mut Element) {
-        if element.removed() && !crate::tags::Tag::tag_from_element(element).self_closing {
-            element
-                .end_tag_handlers()
-                .unwrap()
-                .push(Box::new(move |end| {
-                    Self::remove_end_tag(end);
-                    Ok(())
-                }));
-        }
+    /// Forces an attribute (and its value) onto every kept instance of an element.
+    fn set_added_attribute(
+        &self,
+        element_name: String,
+        attr_name: String,
+        attr_value: String,
+    ) -> Result<(), magnus::Error> {
+        self.check_mutable()?;
+
+        self.0
+            .borrow_mut()
+            .engine
+            .add_attribute(&element_name, attr_name, attr_value);
+        Ok(())
     }
 
-    fn remove_end_tag(end_tag: &mut EndTag) {
-        end_tag.remove();
+    /// Strips an attribute from an element, even if it's otherwise allowed.
+    fn set_removed_attribute(
+        &self,
+        element_name: String,
+        attr_name: String,
+        remove: bool,
+    ) -> Result<bool, magnus::Error> {
+        self.check_mutable()?;
+
+        self.0
+            .borrow_mut()
+            .engine
+            .remove_attribute(&element_name, &attr_name, remove);
+        Ok(remove)
     }
 
-    fn get_element_sanitizer<'a>(
-        element_sanitizers: &'a mut HashMap<String, ElementSanitizer>,
-        element_name: &str,
-    ) -> &'a mut ElementSanitizer {
-        element_sanitizers
-            .entry(element_name.to_string())
-            .or_insert_with(ElementSanitizer::default)
+    /// Treats an attribute of an element as a boolean one, like the standard
+    /// ones (`controls`, `disabled`, `open`, ...), so that its value is dropped
+    /// rather than checked.
+    fn set_boolean_attribute(
+        &self,
+        element_name: String,
+        attr_name: String,
+        boolean: bool,
+    ) -> Result<bool, magnus::Error> {
+        self.check_mutable()?;
+
+        self.0
+            .borrow_mut()
+            .engine
+            .set_boolean_attribute(&element_name, &attr_name, boolean);
+        Ok(boolean)
     }
 }
 
@@ -552,7 +845,16 @@ pub fn init(m_selma: RModule) -> Result<(), magnus::Error> {
         .expect("cannot define class Selma::Sanitizer");
 
     c_sanitizer.define_singleton_method("new", function!(SelmaSanitizer::new, -1))?;
+    c_sanitizer.define_singleton_method("basic", function!(SelmaSanitizer::basic, 0))?;
+    c_sanitizer.define_singleton_method("relaxed", function!(SelmaSanitizer::relaxed, 0))?;
+    c_sanitizer.define_singleton_method("restricted", function!(SelmaSanitizer::restricted, 0))?;
+    c_sanitizer.define_singleton_method("github", function!(SelmaSanitizer::github, 0))?;
     c_sanitizer.define_method("config", method!(SelmaSanitizer::get_config, 0))?;
+    c_sanitizer.define_method("warnings", method!(SelmaSanitizer::get_warnings, 0))?;
+    c_sanitizer.define_method("immutable?", method!(SelmaSanitizer::is_immutable, 0))?;
+    c_sanitizer.define_method("set_up?", method!(SelmaSanitizer::is_set_up, 0))?;
+    c_sanitizer.define_method("mark_set_up", method!(SelmaSanitizer::mark_set_up, 0))?;
+    c_sanitizer.define_method("derive", method!(SelmaSanitizer::derive, 0))?;
 
     c_sanitizer.define_method("set_flag", method!(SelmaSanitizer::set_flag, 3))?;
     c_sanitizer.define_method("set_all_flags", method!(SelmaSanitizer::set_all_flags, 2))?;
@@ -575,6 +877,30 @@ pub fn init(m_selma: RModule) -> Result<(), magnus::Error> {
         method!(SelmaSanitizer::get_allow_comments, 0),
     )?;
 
+    c_sanitizer.define_method(
+        "set_allow_conditional_comments",
+        method!(SelmaSanitizer::set_allow_conditional_comments, 1),
+    )?;
+    c_sanitizer.define_method(
+        "allow_conditional_comments",
+        method!(SelmaSanitizer::get_allow_conditional_comments, 0),
+    )?;
+
+    c_sanitizer.define_method(
+        "set_allow_cdata",
+        method!(SelmaSanitizer::set_allow_cdata, 1),
+    )?;
+    c_sanitizer.define_method("allow_cdata", method!(SelmaSanitizer::get_allow_cdata, 0))?;
+
+    c_sanitizer.define_method(
+        "set_normalize_entities",
+        method!(SelmaSanitizer::set_normalize_entities, 1),
+    )?;
+    c_sanitizer.define_method(
+        "normalize_entities",
+        method!(SelmaSanitizer::get_normalize_entities, 0),
+    )?;
+
     c_sanitizer.define_method(
         "set_allow_doctype",
         method!(SelmaSanitizer::set_allow_doctype, 1),
@@ -584,6 +910,110 @@ pub fn init(m_selma: RModule) -> Result<(), magnus::Error> {
         method!(SelmaSanitizer::get_allow_doctype, 0),
     )?;
 
+    c_sanitizer.define_method(
+        "set_name_prefix",
+        method!(SelmaSanitizer::set_name_prefix, 1),
+    )?;
+    c_sanitizer.define_method("name_prefix", method!(SelmaSanitizer::get_name_prefix, 0))?;
+
+    c_sanitizer.define_method("set_noscript", method!(SelmaSanitizer::set_noscript, 1))?;
+    c_sanitizer.define_method("set_integrity", method!(SelmaSanitizer::set_integrity, 1))?;
+    c_sanitizer.define_method(
+        "set_duplicate_attributes",
+        method!(SelmaSanitizer::set_duplicate_attributes, 1),
+    )?;
+    c_sanitizer.define_method(
+        "set_shadow_roots",
+        method!(SelmaSanitizer::set_shadow_roots, 1),
+    )?;
+    c_sanitizer.define_method(
+        "set_fragment_links",
+        method!(SelmaSanitizer::set_fragment_links, 1),
+    )?;
+    c_sanitizer.define_method(
+        "fragment_links",
+        method!(SelmaSanitizer::get_fragment_links, 0),
+    )?;
+    c_sanitizer.define_method(
+        "set_preserve_preformatted_whitespace",
+        method!(SelmaSanitizer::set_preserve_preformatted_whitespace, 1),
+    )?;
+    c_sanitizer.define_method(
+        "preserve_preformatted_whitespace",
+        method!(SelmaSanitizer::get_preserve_preformatted_whitespace, 0),
+    )?;
+    c_sanitizer.define_method(
+        "set_neutralize_forms",
+        method!(SelmaSanitizer::set_neutralize_forms, 1),
+    )?;
+    c_sanitizer.define_method(
+        "neutralize_forms",
+        method!(SelmaSanitizer::get_neutralize_forms, 0),
+    )?;
+    c_sanitizer.define_method(
+        "set_image_policy",
+        method!(SelmaSanitizer::set_image_policy, 4),
+    )?;
+    c_sanitizer.define_method(
+        "set_embed_fallback",
+        method!(SelmaSanitizer::set_embed_fallback, 2),
+    )?;
+    c_sanitizer.define_method(
+        "set_iframe_policy",
+        method!(SelmaSanitizer::set_iframe_policy, 4),
+    )?;
+    c_sanitizer.define_method(
+        "set_media_policy",
+        method!(SelmaSanitizer::set_media_policy, 1),
+    )?;
+    c_sanitizer.define_method(
+        "set_source_policy",
+        method!(SelmaSanitizer::set_source_policy, 1),
+    )?;
+    c_sanitizer.define_method(
+        "set_normalize_amp",
+        method!(SelmaSanitizer::set_normalize_amp, 1),
+    )?;
+    c_sanitizer.define_method("set_css_policy", method!(SelmaSanitizer::set_css_policy, 1))?;
+    c_sanitizer.define_method(
+        "set_strip_remote_content",
+        method!(SelmaSanitizer::set_strip_remote_content, 1),
+    )?;
+    c_sanitizer.define_method(
+        "set_strip_tracking_pixels",
+        method!(SelmaSanitizer::set_strip_tracking_pixels, 1),
+    )?;
+    c_sanitizer.define_method(
+        "set_table_policy",
+        method!(SelmaSanitizer::set_table_policy, 3),
+    )?;
+    c_sanitizer.define_method("set_paranoid", method!(SelmaSanitizer::set_paranoid, 2))?;
+    c_sanitizer.define_method("paranoid?", method!(SelmaSanitizer::is_paranoid, 0))?;
+    c_sanitizer.define_method(
+        "set_unicode_filter",
+        method!(SelmaSanitizer::set_unicode_filter, 1),
+    )?;
+    c_sanitizer.define_method(
+        "set_attribute_limit",
+        method!(SelmaSanitizer::set_attribute_limit, 2),
+    )?;
+    c_sanitizer.define_method(
+        "set_attribute_value_limit",
+        method!(SelmaSanitizer::set_attribute_value_limit, 2),
+    )?;
+    c_sanitizer.define_method(
+        "set_passthrough",
+        method!(SelmaSanitizer::set_passthrough, 2),
+    )?;
+    c_sanitizer.define_method(
+        "set_class_limit",
+        method!(SelmaSanitizer::set_class_limit, 3),
+    )?;
+    c_sanitizer.define_method(
+        "set_control_characters",
+        method!(SelmaSanitizer::set_control_characters, 1),
+    )?;
+
     c_sanitizer.define_method(
         "set_allowed_attribute",
         method!(SelmaSanitizer::set_allowed_attribute, 3),
@@ -596,7 +1026,22 @@ pub fn init(m_selma: RModule) -> Result<(), magnus::Error> {
 
     c_sanitizer.define_method(
         "set_allowed_protocols",
-        method!(SelmaSanitizer::set_allowed_protocols, 3),
+        method!(SelmaSanitizer::set_allowed_protocols, 4),
+    )?;
+
+    c_sanitizer.define_method(
+        "set_added_attribute",
+        method!(SelmaSanitizer::set_added_attribute, 3),
+    )?;
+
+    c_sanitizer.define_method(
+        "set_removed_attribute",
+        method!(SelmaSanitizer::set_removed_attribute, 3),
+    )?;
+
+    c_sanitizer.define_method(
+        "set_boolean_attribute",
+        method!(SelmaSanitizer::set_boolean_attribute, 3),
     )?;
 
     Ok(())
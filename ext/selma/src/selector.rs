@@ -1,47 +1,48 @@
+use lol_html::errors::SelectorError;
 use magnus::{exception, function, scan_args, Error, Module, Object, RModule, Value};
 
+use crate::errors::selector_error;
+
 #[derive(Clone, Debug)]
 #[magnus::wrap(class = "Selma::Selector")]
 pub struct SelmaSelector {
     match_element: Option<String>,
     match_text_within: Option<String>,
     ignore_text_within: Option<Vec<String>>,
+    relocate: Option<String>,
 }
 
-type SelectorMatches = (Option<String>, Option<String>, Option<Vec<String>>);
+type SelectorMatches = (
+    Option<String>,
+    Option<String>,
+    Option<Vec<String>>,
+    Option<String>,
+);
 
 impl SelmaSelector {
     fn new(args: &[Value]) -> Result<Self, Error> {
-        let (match_element, match_text_within, rb_ignore_text_within) =
+        let (match_element, match_text_within, rb_ignore_text_within, relocate) =
             Self::scan_parse_args(args)?;
 
-        if match_element.is_none() && match_text_within.is_none() {
+        if match_element.is_none() && match_text_within.is_none() && relocate.is_none() {
             return Err(Error::new(
                 exception::arg_error(),
-                "Neither `match_element` nor `match_text_within` option given",
+                "Neither `match_element`, `match_text_within` nor `relocate` option given",
             ));
         }
 
         // FIXME: not excited about this double parse work (`element!` does it too),
         // but at least we can bail ASAP if the CSS is invalid
-        if match_element.is_some() {
-            let css = match_element.as_ref().unwrap();
-            if css.parse::<lol_html::Selector>().is_err() {
-                return Err(Error::new(
-                    exception::arg_error(),
-                    format!("Could not parse `match_element` (`{css:?}`) as valid CSS"),
-                ));
-            }
+        if let Some(css) = match_element.as_ref() {
+            Self::parse_css("match_element", css)?;
         }
 
-        if match_text_within.is_some() {
-            let css = match_text_within.as_ref().unwrap();
-            if css.parse::<lol_html::Selector>().is_err() {
-                return Err(Error::new(
-                    exception::arg_error(),
-                    format!("Could not parse `match_text_within` (`{css:?}`) as valid CSS",),
-                ));
-            }
+        if let Some(css) = match_text_within.as_ref() {
+            Self::parse_css("match_text_within", css)?;
+        }
+
+        if let Some(css) = relocate.as_ref() {
+            Self::parse_css("relocate", css)?;
         }
 
         let ignore_text_within = match rb_ignore_text_within {
@@ -61,6 +62,7 @@ impl SelmaSelector {
             match_element,
             match_text_within,
             ignore_text_within,
+            relocate,
         })
     }
 
@@ -76,16 +78,70 @@ impl SelmaSelector {
         let kw = scan_args::get_kwargs::<
             _,
             (),
-            (Option<String>, Option<String>, Option<Vec<String>>),
+            (
+                Option<String>,
+                Option<String>,
+                Option<Vec<String>>,
+                Option<String>,
+            ),
             (),
         >(
             args.keywords,
             &[],
-            &["match_element", "match_text_within", "ignore_text_within"],
+            &[
+                "match_element",
+                "match_text_within",
+                "ignore_text_within",
+                "relocate",
+            ],
         )?;
-        let (match_element, match_text_within, rb_ignore_text_within) = kw.optional;
+        let (match_element, match_text_within, rb_ignore_text_within, relocate) = kw.optional;
+
+        Ok((
+            match_element,
+            match_text_within,
+            rb_ignore_text_within,
+            relocate,
+        ))
+    }
+
+    /// @yard
+    /// @def valid?(css)
+    /// @param css [String] A CSS selector
+    /// @return [Boolean] Whether or not `css` can be used by a `Selma::Selector`
+    fn is_valid(css: String) -> bool {
+        css.parse::<lol_html::Selector>().is_ok()
+    }
+
+    pub(crate) fn parse_css(option: &str, css: &str) -> Result<(), Error> {
+        let reason = match css.parse::<lol_html::Selector>() {
+            Ok(_) => return Ok(()),
+            Err(reason) => reason,
+        };
+        let position = Self::error_position(css, reason);
+
+        Err(selector_error(
+            format!(
+                "Could not parse `{option}` (`{css:?}`) as valid CSS: {} (at position {position})",
+                reason.to_string().trim_end_matches('.')
+            ),
+            position,
+            reason.to_string(),
+        ))
+    }
 
-        Ok((match_element, match_text_within, rb_ignore_text_within))
+    // lol_html doesn't tell us where a selector went wrong, so find the shortest
+    // prefix of it which already fails for the same reason; the character which
+    // ends that prefix is the culprit
+    fn error_position(css: &str, reason: SelectorError) -> usize {
+        css.char_indices()
+            .position(|(index, c)| {
+                css[..index + c.len_utf8()]
+                    .parse::<lol_html::Selector>()
+                    .err()
+                    == Some(reason)
+            })
+            .unwrap_or_else(|| css.chars().count())
     }
 
     pub fn match_element(&self) -> Option<String> {
@@ -99,6 +155,10 @@ impl SelmaSelector {
     pub fn ignore_text_within(&self) -> Option<Vec<String>> {
         self.ignore_text_within.clone()
     }
+
+    pub fn relocate(&self) -> Option<String> {
+        self.relocate.clone()
+    }
 }
 
 pub fn init(m_selma: RModule) -> Result<(), Error> {
@@ -107,6 +167,7 @@ pub fn init(m_selma: RModule) -> Result<(), Error> {
         .expect("cannot define class Selma::Selector");
 
     c_selector.define_singleton_method("new", function!(SelmaSelector::new, -1))?;
+    c_selector.define_singleton_method("valid?", function!(SelmaSelector::is_valid, 1))?;
 
     Ok(())
 }
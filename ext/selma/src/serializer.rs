@@ -0,0 +1,885 @@
+use magnus::{
+    class, exception, r_hash::ForEach, value::ReprValue, Error, Integer, RHash, RString, Symbol,
+    Value,
+};
+
+use std::borrow::Cow;
+
+use crate::tags::Tag;
+use selma_core::tokens::{StartTag, Token, Tokens};
+
+/// How a rewriter's output is written out once the sanitizer and handlers are
+/// done with it, as asked for by its `minify:`, `pretty:`, `xhtml:` or `canonical:`
+/// options. This works
+/// on the serialized HTML, token by token (see `selma_core::tokens`), since
+/// lol_html writes tags out the way it found them.
+#[derive(Clone, Debug, Default)]
+pub struct Serializer {
+    minify: Option<Minify>,
+    pretty: Option<Pretty>,
+    xhtml: bool,
+    canonical: Option<Canonical>,
+}
+
+/// What `minify:` takes out; everything, unless told otherwise.
+#[derive(Clone, Debug)]
+struct Minify {
+    /// collapses runs of whitespace outside of `<pre>` and `<textarea>`, and
+    /// drops whatever's next to a block's tags
+    whitespace: bool,
+    comments: bool,
+    /// drops the quotes around attribute values which don't need them, and the
+    /// values of attributes which are empty
+    quotes: bool,
+}
+
+/// How `pretty:` indents the blocks it puts on lines of their own.
+#[derive(Clone, Debug)]
+struct Pretty {
+    indent: String,
+}
+
+/// How `canonical:` writes out HTML which means the same thing the same way
+/// every time: names in lowercase, attributes in order and double-quoted, and
+/// character references decoded and escaped again only where they have to be.
+#[derive(Clone, Debug)]
+struct Canonical {
+    /// collapses runs of whitespace outside of `<pre>` and `<textarea>`; line
+    /// endings are `\n`s either way
+    whitespace: bool,
+}
+
+impl Serializer {
+    pub fn new(
+        rb_minify: Option<Value>,
+        rb_pretty: Option<Value>,
+        xhtml: bool,
+        rb_canonical: Option<Value>,
+    ) -> Result<Self, Error> {
+        let minify = Minify::new(rb_minify)?;
+        let pretty = Pretty::new(rb_pretty)?;
+        let canonical = Canonical::new(rb_canonical)?;
+
+        if minify.is_some() && pretty.is_some() {
+            return Err(argument_error(
+                "`minify:` and `pretty:` can't be used together".to_string(),
+            ));
+        }
+        if minify.is_some() && xhtml {
+            return Err(argument_error(
+                "`minify:` and `xhtml:` can't be used together".to_string(),
+            ));
+        }
+        if minify.is_some() && canonical.is_some() {
+            return Err(argument_error(
+                "`minify:` and `canonical:` can't be used together".to_string(),
+            ));
+        }
+
+        Ok(Self {
+            minify,
+            pretty,
+            xhtml,
+            canonical,
+        })
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.minify.is_none() && self.pretty.is_none() && !self.xhtml && self.canonical.is_none()
+    }
+
+    pub fn serialize(&self, html: String) -> String {
+        if let Some(minify) = &self.minify {
+            return minify.serialize(&html);
+        }
+        if let Some(pretty) = &self.pretty {
+            return pretty.serialize(&html, self.writer());
+        }
+        if let Some(canonical) = &self.canonical {
+            return canonical.serialize(&html, self.writer());
+        }
+        if !self.xhtml {
+            return html;
+        }
+
+        let mut output = String::with_capacity(html.len());
+        let mut writer = self.writer();
+        for token in Tokens::new(&html) {
+            writer.token(&token, &mut output);
+        }
+
+        output
+    }
+
+    fn writer(&self) -> Writer {
+        Writer::new(self.xhtml, self.canonical.is_some())
+    }
+}
+
+impl Minify {
+    fn new(value: Option<Value>) -> Result<Option<Self>, Error> {
+        let value = match value {
+            Some(value) if value.to_bool() => value,
+            _ => return Ok(None),
+        };
+
+        let mut minify = Self {
+            whitespace: true,
+            comments: true,
+            quotes: true,
+        };
+        if value.is_kind_of(class::true_class()) {
+            return Ok(Some(minify));
+        }
+
+        let rb_minify = match RHash::from_value(value) {
+            Some(rb_minify) => rb_minify,
+            None => {
+                return Err(argument_error(format!(
+                    "minify: expected true or a Hash, got {}",
+                    value.inspect()
+                )))
+            }
+        };
+
+        rb_minify.foreach(|key: Value, value: Value| {
+            let name = Symbol::from_value(key).and_then(|sym| sym.name().ok());
+            let enabled = value.to_bool();
+            match name.as_deref() {
+                Some("whitespace") => minify.whitespace = enabled,
+                Some("comments") => minify.comments = enabled,
+                Some("quotes") => minify.quotes = enabled,
+                _ => {
+                    return Err(argument_error(format!(
+                        "minify[{}]: unknown key",
+                        key.inspect()
+                    )))
+                }
+            }
+            Ok(ForEach::Continue)
+        })?;
+
+        Ok(Some(minify))
+    }
+
+    fn serialize(&self, html: &str) -> String {
+        let mut output = String::with_capacity(html.len());
+        let mut whitespace = Whitespace::default();
+
+        for token in Tokens::new(html) {
+            match token {
+                Token::Text(text) if self.whitespace && whitespace.preformatted == 0 => {
+                    whitespace.collapse(text, &mut output)
+                }
+                Token::Text(text) | Token::RawText(text) => {
+                    whitespace.flush(false, &mut output);
+                    output.push_str(text);
+                }
+                // the whitespace on either side of a comment still counts
+                Token::Comment(_) if self.comments => {}
+                Token::Comment(raw) => {
+                    whitespace.flush(false, &mut output);
+                    output.push_str(raw);
+                }
+                Token::Other(raw) => {
+                    whitespace.flush(Token::is_doctype(raw), &mut output);
+                    output.push_str(raw);
+                }
+                Token::StartTag(tag) => {
+                    let block = tag.is_block();
+                    whitespace.flush(block, &mut output);
+                    if tag.is_preformatted() && !tag.self_closing {
+                        whitespace.preformatted += 1;
+                    }
+
+                    if self.quotes {
+                        Self::write_start_tag(&tag, &mut output);
+                    } else {
+                        output.push_str(tag.raw);
+                    }
+                }
+                Token::EndTag { name, raw } => {
+                    let name = name.to_ascii_lowercase();
+                    whitespace.flush(Tag::is_block(Tag::tag_from_tag_name(&name)), &mut output);
+                    if StartTag::PREFORMATTED.contains(&name.as_str()) {
+                        whitespace.preformatted = whitespace.preformatted.saturating_sub(1);
+                    }
+
+                    output.push_str(raw);
+                }
+            }
+        }
+        whitespace.flush(false, &mut output);
+
+        output
+    }
+
+    fn write_start_tag(tag: &StartTag, output: &mut String) {
+        output.push('<');
+        output.push_str(tag.name);
+
+        let mut unquoted = false;
+        for attribute in tag.attributes.iter() {
+            output.push(' ');
+            output.push_str(attribute.name);
+            unquoted = false;
+
+            match attribute.value {
+                None | Some(("", _)) => {}
+                Some((value, _)) if Self::can_unquote(value) => {
+                    output.push('=');
+                    output.push_str(value);
+                    unquoted = true;
+                }
+                Some((value, quote)) => {
+                    let quote = quote.unwrap_or(if value.contains('"') { '\'' } else { '"' });
+                    output.push('=');
+                    output.push(quote);
+                    if quote == '"' {
+                        output.push_str(&value.replace('"', "&quot;"));
+                    } else {
+                        output.push_str(value);
+                    }
+                    output.push(quote);
+                }
+            }
+        }
+
+        if tag.self_closing {
+            // `<circle r=4/>` would be a radius of `4/`
+            if unquoted {
+                output.push(' ');
+            }
+            output.push('/');
+        }
+        output.push('>');
+    }
+
+    /// Whether a value means the same thing without its quotes.
+    fn can_unquote(value: &str) -> bool {
+        !value.is_empty()
+            && !value.bytes().any(|b| {
+                b.is_ascii_whitespace() || matches!(b, b'"' | b'\'' | b'=' | b'<' | b'>' | b'`')
+            })
+    }
+}
+
+impl Pretty {
+    const INDENT: usize = 2;
+
+    /// Elements which go on lines of their own, besides blocks, since the
+    /// whitespace around them isn't rendered either.
+    const OWN_LINE: &'static [&'static str] =
+        &["base", "head", "link", "meta", "script", "style", "title"];
+
+    fn new(value: Option<Value>) -> Result<Option<Self>, Error> {
+        let value = match value {
+            Some(value) if value.to_bool() => value,
+            _ => return Ok(None),
+        };
+
+        let mut pretty = Self {
+            indent: " ".repeat(Self::INDENT),
+        };
+        if value.is_kind_of(class::true_class()) {
+            return Ok(Some(pretty));
+        }
+
+        let rb_pretty = match RHash::from_value(value) {
+            Some(rb_pretty) => rb_pretty,
+            None => {
+                return Err(argument_error(format!(
+                    "pretty: expected true or a Hash, got {}",
+                    value.inspect()
+                )))
+            }
+        };
+
+        rb_pretty.foreach(|key: Value, value: Value| {
+            let name = Symbol::from_value(key).and_then(|sym| sym.name().ok());
+            match name.as_deref() {
+                Some("indent") => pretty.indent = Self::indent(value)?,
+                _ => {
+                    return Err(argument_error(format!(
+                        "pretty[{}]: unknown key",
+                        key.inspect()
+                    )))
+                }
+            }
+            Ok(ForEach::Continue)
+        })?;
+
+        Ok(Some(pretty))
+    }
+
+    /// A number of spaces, or a string of spaces and tabs (like `"\t"`).
+    fn indent(value: Value) -> Result<String, Error> {
+        if let Some(spaces) = Integer::from_value(value).and_then(|i| i.to_usize().ok()) {
+            return Ok(" ".repeat(spaces));
+        }
+        if let Some(indent) = RString::from_value(value).and_then(|s| s.to_string().ok()) {
+            if indent.chars().all(|c| c == ' ' || c == '\t') {
+                return Ok(indent);
+            }
+        }
+
+        Err(argument_error(format!(
+            "pretty[:indent]: expected a non-negative Integer or a String of spaces and tabs, got {}",
+            value.inspect()
+        )))
+    }
+
+    fn is_own_line(name: &str) -> bool {
+        Tag::is_block(Tag::tag_from_tag_name(name)) || Self::OWN_LINE.contains(&name)
+    }
+
+    fn is_void(tag: &StartTag, name: &str) -> bool {
+        tag.self_closing || Tag::tag_from_tag_name(name).self_closing
+    }
+
+    /// Works out which of the elements which go on their own lines hold others
+    /// which do, too, and so get their contents indented on lines of their own,
+    /// by the index of their start tags.
+    fn containers(tokens: &[Token]) -> Vec<bool> {
+        let mut containers = vec![false; tokens.len()];
+        let mut open: Vec<(usize, String)> = vec![];
+        let mut preformatted = 0;
+
+        for (index, token) in tokens.iter().enumerate() {
+            match token {
+                Token::StartTag(tag) => {
+                    let name = tag.name.to_ascii_lowercase();
+                    if preformatted > 0 {
+                        if tag.is_preformatted() {
+                            preformatted += 1;
+                        }
+                        continue;
+                    }
+                    if !Self::is_own_line(&name) {
+                        continue;
+                    }
+
+                    if let Some((parent, _)) = open.last() {
+                        containers[*parent] = true;
+                    }
+                    if !Self::is_void(tag, &name) {
+                        if tag.is_preformatted() {
+                            preformatted += 1;
+                        }
+                        open.push((index, name));
+                    }
+                }
+                Token::EndTag { name, .. } => {
+                    let name = name.to_ascii_lowercase();
+                    if StartTag::PREFORMATTED.contains(&name.as_str()) {
+                        preformatted -= usize::from(preformatted > 0);
+                    }
+                    if preformatted > 0 {
+                        continue;
+                    }
+                    if let Some(position) = open.iter().rposition(|(_, open)| *open == name) {
+                        open.truncate(position);
+                    }
+                }
+                Token::Other(raw) if Token::is_doctype(raw) => {
+                    if let Some((parent, _)) = open.last() {
+                        containers[*parent] = true;
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        containers
+    }
+
+    fn serialize(&self, html: &str, mut writer: Writer) -> String {
+        let tokens: Vec<Token> = Tokens::new(html).collect();
+        let containers = Self::containers(&tokens);
+
+        let mut output = String::with_capacity(html.len() * 2);
+        let mut lines = Lines {
+            indent: &self.indent,
+            depth: 0,
+            line_break: false,
+            whitespace: Whitespace::default(),
+        };
+        // the elements on lines of their own which are open, and whether each
+        // is a container
+        let mut open: Vec<(String, bool)> = vec![];
+
+        for (index, token) in tokens.iter().enumerate() {
+            if lines.whitespace.preformatted > 0 {
+                match token {
+                    Token::StartTag(tag) if tag.is_preformatted() && !tag.self_closing => {
+                        lines.whitespace.preformatted += 1;
+                    }
+                    Token::EndTag { name, .. }
+                        if StartTag::PREFORMATTED.contains(&name.to_ascii_lowercase().as_str()) =>
+                    {
+                        lines.whitespace.preformatted -= 1;
+                    }
+                    _ => {}
+                }
+                if lines.whitespace.preformatted > 0 {
+                    writer.token(token, &mut output);
+                    continue;
+                }
+            }
+
+            match token {
+                Token::Text(text) => lines.text(&writer.text(text), &mut output),
+                Token::RawText(text) => output.push_str(text),
+                Token::Other(raw) if Token::is_doctype(raw) => {
+                    lines.block(&mut output);
+                    writer.token(token, &mut output);
+                    lines.after_block(true);
+                }
+                Token::Comment(raw) | Token::Other(raw) => {
+                    lines.inline(&mut output);
+                    output.push_str(raw);
+                }
+                Token::StartTag(tag) => {
+                    let name = tag.name.to_ascii_lowercase();
+                    if !Self::is_own_line(&name) {
+                        lines.inline(&mut output);
+                        writer.start_tag(tag, &mut output);
+                        continue;
+                    }
+
+                    lines.block(&mut output);
+                    writer.start_tag(tag, &mut output);
+                    if Self::is_void(tag, &name) {
+                        lines.after_block(true);
+                        continue;
+                    }
+
+                    let container = containers[index];
+                    if container {
+                        lines.depth += 1;
+                    }
+                    lines.after_block(container);
+                    if tag.is_preformatted() {
+                        lines.whitespace.preformatted += 1;
+                    }
+                    open.push((name, container));
+                }
+                Token::EndTag { name, raw } => {
+                    let lowercase_name = name.to_ascii_lowercase();
+                    let position = open.iter().rposition(|(open, _)| *open == lowercase_name);
+                    let position = match position {
+                        Some(position) if Self::is_own_line(&lowercase_name) => position,
+                        _ => {
+                            lines.inline(&mut output);
+                            writer.end_tag(name, raw, &mut output);
+                            continue;
+                        }
+                    };
+
+                    // anything left open within it is closed along with it
+                    let closed = open.split_off(position);
+                    lines.depth -= closed.iter().filter(|(_, container)| *container).count();
+
+                    // an element without any others on their own lines ends on
+                    // the same line as it starts
+                    if closed[0].1 {
+                        lines.block(&mut output);
+                    } else {
+                        lines.whitespace.pending = false;
+                    }
+                    writer.end_tag(name, raw, &mut output);
+                    lines.after_block(true);
+                }
+            }
+        }
+
+        output
+    }
+}
+
+impl Canonical {
+    fn new(value: Option<Value>) -> Result<Option<Self>, Error> {
+        let value = match value {
+            Some(value) if value.to_bool() => value,
+            _ => return Ok(None),
+        };
+
+        let mut canonical = Self { whitespace: true };
+        if value.is_kind_of(class::true_class()) {
+            return Ok(Some(canonical));
+        }
+
+        let rb_canonical = match RHash::from_value(value) {
+            Some(rb_canonical) => rb_canonical,
+            None => {
+                return Err(argument_error(format!(
+                    "canonical: expected true or a Hash, got {}",
+                    value.inspect()
+                )))
+            }
+        };
+
+        rb_canonical.foreach(|key: Value, value: Value| {
+            let name = Symbol::from_value(key).and_then(|sym| sym.name().ok());
+            match name.as_deref() {
+                Some("whitespace") => canonical.whitespace = value.to_bool(),
+                _ => {
+                    return Err(argument_error(format!(
+                        "canonical[{}]: unknown key",
+                        key.inspect()
+                    )))
+                }
+            }
+            Ok(ForEach::Continue)
+        })?;
+
+        Ok(Some(canonical))
+    }
+
+    fn serialize(&self, html: &str, mut writer: Writer) -> String {
+        let mut output = String::with_capacity(html.len());
+        let mut preformatted: usize = 0;
+
+        for token in Tokens::new(html) {
+            match &token {
+                Token::Text(text) if self.whitespace && preformatted == 0 => {
+                    output.push_str(&writer.text(&Self::collapse(text)));
+                    continue;
+                }
+                Token::StartTag(tag) if tag.is_preformatted() && !tag.self_closing => {
+                    preformatted += 1;
+                }
+                Token::EndTag { name, .. }
+                    if StartTag::PREFORMATTED.contains(&name.to_ascii_lowercase().as_str()) =>
+                {
+                    preformatted = preformatted.saturating_sub(1);
+                }
+                _ => {}
+            }
+
+            writer.token(&token, &mut output);
+        }
+
+        output
+    }
+
+    /// Turns each run of whitespace into a single space. Unlike `minify:`,
+    /// this keeps the whitespace next to blocks' tags, since CSS can make that
+    /// matter.
+    fn collapse(text: &str) -> Cow<'_, str> {
+        let mut whitespace = false;
+        let needed = text.bytes().any(|b| {
+            let run = whitespace && b.is_ascii_whitespace();
+            whitespace = b.is_ascii_whitespace();
+            run || (whitespace && b != b' ')
+        });
+        if !needed {
+            return Cow::Borrowed(text);
+        }
+
+        let mut collapsed = String::with_capacity(text.len());
+        let mut pending = false;
+        for c in text.chars() {
+            if c.is_ascii_whitespace() {
+                pending = true;
+                continue;
+            }
+            if pending {
+                collapsed.push(' ');
+                pending = false;
+            }
+            collapsed.push(c);
+        }
+        if pending {
+            collapsed.push(' ');
+        }
+
+        Cow::Owned(collapsed)
+    }
+}
+
+/// Where `pretty:` is up to: how deep it's indenting, and whether what's next
+/// goes on a new line.
+struct Lines<'i> {
+    indent: &'i str,
+    depth: usize,
+    line_break: bool,
+    whitespace: Whitespace,
+}
+
+impl Lines<'_> {
+    fn new_line(&mut self, output: &mut String) {
+        self.line_break = false;
+        if output.is_empty() {
+            return;
+        }
+
+        output.push('\n');
+        for _ in 0..self.depth {
+            output.push_str(self.indent);
+        }
+    }
+
+    fn text(&mut self, text: &str, output: &mut String) {
+        for c in text.chars() {
+            if c.is_ascii_whitespace() {
+                self.whitespace.pending = true;
+                continue;
+            }
+
+            self.inline(output);
+            output.push(c);
+        }
+    }
+
+    /// Before anything that's written out as it comes, like text or `<em>`.
+    fn inline(&mut self, output: &mut String) {
+        if self.line_break {
+            self.whitespace.pending = false;
+            self.new_line(output);
+        }
+        self.whitespace.flush(false, output);
+    }
+
+    /// Before a tag which goes on a line of its own.
+    fn block(&mut self, output: &mut String) {
+        self.whitespace.pending = false;
+        self.new_line(output);
+    }
+
+    /// After a tag which goes on a line of its own; with `line_break`, what
+    /// comes next does too.
+    fn after_block(&mut self, line_break: bool) {
+        self.line_break = line_break;
+        self.whitespace.after_block = true;
+    }
+}
+
+/// Keeps track of the whitespace being collapsed, which can't be written out
+/// until it's clear what's after it.
+#[derive(Default)]
+struct Whitespace {
+    pending: bool,
+    /// whether the last thing written was a block's tag, or a doctype
+    after_block: bool,
+    /// how many `<pre>`s (or the like) are open
+    preformatted: usize,
+}
+
+impl Whitespace {
+    fn collapse(&mut self, text: &str, output: &mut String) {
+        for c in text.chars() {
+            if c.is_ascii_whitespace() {
+                self.pending = true;
+                continue;
+            }
+
+            self.flush(false, output);
+            output.push(c);
+        }
+    }
+
+    /// Writes out a single space for the whitespace before something, unless
+    /// that something is, or comes right after, a block's tag.
+    fn flush(&mut self, block: bool, output: &mut String) {
+        if self.pending && !block && !self.after_block {
+            output.push(' ');
+        }
+        self.pending = false;
+        self.after_block = block;
+    }
+}
+
+/// Writes tokens out as they are, or, for `xhtml:`, the way an XML parser
+/// needs them: with lowercase names (outside of SVG and MathML, where case
+/// matters), every attribute value quoted, void elements closed (`<br />`), and
+/// stray `<`s and `&`s escaped. `canonical:` writes tags the same way, but
+/// with their attributes sorted, and decodes text and attribute values to
+/// escape them again.
+struct Writer {
+    xhtml: bool,
+    canonical: bool,
+    /// how many `<svg>`s and `<math>`s are open
+    foreign: usize,
+}
+
+impl Writer {
+    const FOREIGN: &'static [&'static str] = &["svg", "math"];
+
+    fn new(xhtml: bool, canonical: bool) -> Self {
+        Self {
+            xhtml,
+            canonical,
+            foreign: 0,
+        }
+    }
+
+    fn token(&mut self, token: &Token, output: &mut String) {
+        match token {
+            Token::Text(text) => output.push_str(&self.text(text)),
+            Token::StartTag(tag) => self.start_tag(tag, output),
+            Token::EndTag { name, raw } => self.end_tag(name, raw, output),
+            Token::Other(raw) if self.canonical && Token::is_html_doctype(raw) => {
+                output.push_str("<!DOCTYPE html>")
+            }
+            token => output.push_str(token.raw()),
+        }
+    }
+
+    fn text<'t>(&self, text: &'t str) -> Cow<'t, str> {
+        if self.canonical {
+            return Cow::Owned(self.normalize(text, false));
+        }
+        if !self.xhtml || !text.contains(['<', '&']) {
+            return Cow::Borrowed(text);
+        }
+
+        Cow::Owned(Self::escape(text, false))
+    }
+
+    fn start_tag(&mut self, tag: &StartTag, output: &mut String) {
+        if !self.xhtml && !self.canonical {
+            output.push_str(tag.raw);
+            return;
+        }
+
+        let lowercase_name = tag.name.to_ascii_lowercase();
+        let foreign = self.foreign > 0;
+        // `<svg viewBox>` is foreign, too
+        let foreign_root = Self::FOREIGN.contains(&lowercase_name.as_str());
+        if foreign_root && !tag.self_closing {
+            self.foreign += 1;
+        }
+
+        let mut attributes: Vec<(Cow<str>, Option<&str>)> = tag
+            .attributes
+            .iter()
+            .map(|attribute| {
+                let name = if foreign || foreign_root {
+                    Cow::Borrowed(attribute.name)
+                } else {
+                    Cow::Owned(attribute.name.to_ascii_lowercase())
+                };
+                (name, attribute.value.map(|(value, _)| value))
+            })
+            .collect();
+        if self.canonical {
+            attributes.sort_by(|(a, _), (b, _)| a.cmp(b));
+        }
+
+        output.push('<');
+        output.push_str(if foreign { tag.name } else { &lowercase_name });
+        for (name, value) in attributes.iter() {
+            output.push(' ');
+            output.push_str(name);
+            output.push_str("=\"");
+            match value {
+                Some(value) if self.canonical => output.push_str(&self.normalize(value, true)),
+                Some(value) => output.push_str(&Self::escape(value, true)),
+                // `disabled` is `disabled="disabled"`
+                None if self.xhtml => output.push_str(name),
+                // and `disabled=""`, as a browser would write it
+                None => {}
+            }
+            output.push('"');
+        }
+
+        let void = !foreign && Tag::tag_from_tag_name(&lowercase_name).self_closing;
+        output.push_str(match (self.xhtml, foreign) {
+            (true, _) if tag.self_closing || void => " />",
+            // only foreign elements can close themselves in HTML
+            (false, true) if tag.self_closing => " />",
+            _ => ">",
+        });
+    }
+
+    fn end_tag(&mut self, name: &str, raw: &str, output: &mut String) {
+        if !self.xhtml && !self.canonical {
+            output.push_str(raw);
+            return;
+        }
+
+        let lowercase_name = name.to_ascii_lowercase();
+        if Self::FOREIGN.contains(&lowercase_name.as_str()) {
+            self.foreign = self.foreign.saturating_sub(1);
+        }
+
+        output.push_str("</");
+        output.push_str(if self.foreign > 0 {
+            name
+        } else {
+            &lowercase_name
+        });
+        output.push('>');
+    }
+
+    /// Escapes the `<`s, and the `&`s which don't start a character reference,
+    /// along with any `"`s in an attribute value.
+    fn escape(text: &str, attribute: bool) -> String {
+        let mut escaped = String::with_capacity(text.len());
+
+        for (index, c) in text.char_indices() {
+            match c {
+                '<' => escaped.push_str("&lt;"),
+                '"' if attribute => escaped.push_str("&quot;"),
+                '&' if !Self::is_reference(&text[index + 1..]) => escaped.push_str("&amp;"),
+                c => escaped.push(c),
+            }
+        }
+
+        escaped
+    }
+
+    /// Decodes every character reference, and then escapes what a browser
+    /// would when it serializes HTML: `&`s, non-breaking spaces, `<`s and `>`s,
+    /// and `"`s in attribute values. Line endings become `\n`s.
+    fn normalize(&self, text: &str, attribute: bool) -> String {
+        let unescaped = escapist::unescape_html(text.as_bytes());
+        let unescaped = String::from_utf8_lossy(&unescaped);
+        let mut normalized = String::with_capacity(unescaped.len());
+
+        let mut chars = unescaped.chars().peekable();
+        while let Some(c) = chars.next() {
+            match c {
+                '&' => normalized.push_str("&amp;"),
+                // XML doesn't know `&nbsp;`
+                '\u{a0}' if !self.xhtml => normalized.push_str("&nbsp;"),
+                '"' if attribute => normalized.push_str("&quot;"),
+                '<' => normalized.push_str("&lt;"),
+                '>' => normalized.push_str("&gt;"),
+                '\r' => {
+                    chars.next_if_eq(&'\n');
+                    normalized.push('\n');
+                }
+                c => normalized.push(c),
+            }
+        }
+
+        normalized
+    }
+
+    /// Whether what's after a `&` makes it a character reference, like `&amp;`
+    /// or `&#x27;`.
+    fn is_reference(rest: &str) -> bool {
+        let end = match rest.find(';') {
+            Some(end) => end,
+            None => return false,
+        };
+        let reference = &rest[..end];
+
+        match reference.strip_prefix('#') {
+            Some(number) => match number.strip_prefix(['x', 'X']) {
+                Some(hex) => !hex.is_empty() && hex.bytes().all(|b| b.is_ascii_hexdigit()),
+                None => !number.is_empty() && number.bytes().all(|b| b.is_ascii_digit()),
+            },
+            None => {
+                reference.starts_with(|c: char| c.is_ascii_alphabetic())
+                    && reference.bytes().all(|b| b.is_ascii_alphanumeric())
+            }
+        }
+    }
+}
+
+fn argument_error(message: String) -> Error {
+    Error::new(exception::arg_error(), message)
+}
@@ -0,0 +1,259 @@
+use lol_html::html_content::{ContentType, Element, TextChunk, TextType};
+use magnus::{
+    exception, function, scan_args, value::ReprValue, Error, Module, Object, RClass, Value,
+};
+use regex::{Regex, RegexBuilder};
+use std::{
+    borrow::Cow,
+    cell::{Cell, RefCell},
+    rc::Rc,
+};
+
+use crate::{selector::SelmaSelector, typographer::Typographer};
+
+/// A handler which finds and replaces text without calling into Ruby.
+#[derive(Clone, Debug)]
+#[magnus::wrap(class = "Selma::Rewriter::TextReplace")]
+pub struct SelmaTextReplace {
+    pattern: Regex,
+    replacement: String,
+    within: Option<String>,
+    except: Vec<String>,
+}
+
+type TextReplaceValues = (Value, String, Option<String>, Option<Vec<String>>);
+
+impl SelmaTextReplace {
+    // `Regexp#options` bits
+    const IGNORECASE: i64 = 1;
+    const EXTENDED: i64 = 2;
+    const MULTILINE: i64 = 4;
+
+    /// @yard
+    /// @def new(pattern:, replacement:, within: nil, except: [])
+    /// @param pattern [Regexp, String] What to find; note that it's matched by Rust's `regex` crate, not Ruby
+    /// @param replacement [String] What to replace it with, where `$1` or `${name}` are the pattern's groups
+    /// @param within [String] A CSS selector for the elements whose text is replaced; by default, that's all of it
+    /// @param except [Array<String>] The names of elements whose text is left alone
+    /// @return [Selma::Rewriter::TextReplace]
+    fn new(args: &[Value]) -> Result<Self, Error> {
+        let (rb_pattern, replacement, within, except) = Self::scan_parse_args(args)?;
+
+        let pattern = Self::compile(rb_pattern)?;
+
+        if let Some(css) = within.as_ref() {
+            SelmaSelector::parse_css("within", css)?;
+        }
+
+        let except = except
+            .unwrap_or_default()
+            .iter()
+            .map(|tag_name| tag_name.to_lowercase())
+            .collect();
+
+        Ok(Self {
+            pattern,
+            replacement,
+            within,
+            except,
+        })
+    }
+
+    #[allow(clippy::let_unit_value)]
+    fn scan_parse_args(args: &[Value]) -> Result<TextReplaceValues, Error> {
+        let args = scan_args::scan_args(args)?;
+        let _: () = args.required;
+        let _: () = args.optional;
+        let _: () = args.splat;
+        let _: () = args.trailing;
+        let _: () = args.block;
+
+        let kw =
+            scan_args::get_kwargs::<_, (Value, String), (Option<String>, Option<Vec<String>>), ()>(
+                args.keywords,
+                &["pattern", "replacement"],
+                &["within", "except"],
+            )?;
+        let (rb_pattern, replacement) = kw.required;
+        let (within, except) = kw.optional;
+
+        Ok((rb_pattern, replacement, within, except))
+    }
+
+    /// Builds the regex from a `Regexp`'s source (keeping its `i`, `x`, and `m`
+    /// flags) or a string. `^` and `$` match at line breaks, like in Ruby.
+    fn compile(rb_pattern: Value) -> Result<Regex, Error> {
+        let (source, options) = if rb_pattern.respond_to("source", false)? {
+            let source: String = rb_pattern.funcall("source", ())?;
+            let options: i64 = rb_pattern.funcall("options", ())?;
+            (source, options)
+        } else {
+            (rb_pattern.try_convert::<String>()?, 0)
+        };
+
+        RegexBuilder::new(&source)
+            .multi_line(true)
+            .case_insensitive(options & Self::IGNORECASE != 0)
+            .ignore_whitespace(options & Self::EXTENDED != 0)
+            .dot_matches_new_line(options & Self::MULTILINE != 0)
+            .build()
+            .map_err(|err| {
+                Error::new(
+                    exception::arg_error(),
+                    format!("Could not compile `pattern` ({source:?}): {err}"),
+                )
+            })
+    }
+
+    fn replace<'t>(&self, text: &'t str) -> Cow<'t, str> {
+        self.pattern.replace_all(text, self.replacement.as_str())
+    }
+}
+
+/// All of a rewriter's `TextReplace`s, which are run together, in order, on the
+/// text of each text node, followed by its typographer, if it has one. Text nodes are put back together from the chunks
+/// lol_html splits them into, so that matches can span those chunks.
+pub struct TextReplacements<'r> {
+    replacements: &'r [SelmaTextReplace],
+    /// how many of the open elements match each replacement's `within:`
+    within_depths: Vec<Rc<Cell<usize>>>,
+    typographer: Option<Typographer>,
+    /// the text of the current text node, as it appears in the document
+    buffer: RefCell<String>,
+}
+
+impl<'r> TextReplacements<'r> {
+    pub fn new(replacements: &'r [SelmaTextReplace], typographer: bool) -> Self {
+        Self {
+            replacements,
+            within_depths: replacements.iter().map(|_| Rc::new(Cell::new(0))).collect(),
+            typographer: typographer.then(Typographer::new),
+            buffer: RefCell::new(String::new()),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.replacements.is_empty() && self.typographer.is_none()
+    }
+
+    pub fn enter_element(&self, tag_name: &str) {
+        if let Some(typographer) = &self.typographer {
+            typographer.enter_element(tag_name);
+        }
+    }
+
+    /// The `within:` selectors, along with the index of their replacement.
+    pub fn within_selectors(&self) -> Vec<(usize, String)> {
+        self.replacements
+            .iter()
+            .enumerate()
+            .filter_map(|(index, replacement)| Some((index, replacement.within.clone()?)))
+            .collect()
+    }
+
+    /// Marks the text within `element` as being within the selector of the
+    /// replacement at `index`, until its end tag.
+    pub fn enter_within(&self, index: usize, element: &mut Element) {
+        if let Some(end_tag_handlers) = element.end_tag_handlers() {
+            let depth = self.within_depths[index].clone();
+            depth.set(depth.get() + 1);
+
+            end_tag_handlers.push(Box::new(move |_end_tag| {
+                depth.set(depth.get() - 1);
+                Ok(())
+            }));
+        }
+    }
+
+    fn applies(&self, index: usize, ancestors: &[String]) -> bool {
+        let replacement = &self.replacements[index];
+
+        (replacement.within.is_none() || self.within_depths[index].get() > 0)
+            && !ancestors
+                .iter()
+                .any(|ancestor| replacement.except.contains(ancestor))
+    }
+
+    pub fn replace_chunk(&self, chunk: &mut TextChunk, ancestors: &[String]) {
+        // the text of `<script>`s and the like isn't escaped, and is best left alone
+        if !matches!(chunk.text_type(), TextType::Data | TextType::RCData) {
+            return;
+        }
+
+        let mut buffer = self.buffer.borrow_mut();
+
+        // what another handler has already replaced is theirs, so the text
+        // before it is all there's going to be
+        if chunk.removed() {
+            self.flush(chunk, &mut buffer, ancestors);
+            return;
+        }
+
+        // every chunk of a text node is within the same elements
+        let smarten = self
+            .typographer
+            .as_ref()
+            .is_some_and(|typographer| !typographer.is_excepted(ancestors));
+        if !smarten && !(0..self.replacements.len()).any(|index| self.applies(index, ancestors)) {
+            if let Some(typographer) = &self.typographer {
+                typographer.skip(chunk.as_str());
+            }
+            return;
+        }
+
+        buffer.push_str(chunk.as_str());
+        chunk.remove();
+
+        if chunk.last_in_text_node() {
+            self.flush(chunk, &mut buffer, ancestors);
+        }
+    }
+
+    fn flush(&self, chunk: &mut TextChunk, buffer: &mut String, ancestors: &[String]) {
+        if buffer.is_empty() {
+            return;
+        }
+
+        let mut text =
+            String::from_utf8_lossy(&escapist::unescape_html(buffer.as_bytes())).into_owned();
+        let mut replaced = false;
+        for (index, replacement) in self.replacements.iter().enumerate() {
+            if !self.applies(index, ancestors) {
+                continue;
+            }
+
+            if let Cow::Owned(replaced_text) = replacement.replace(&text) {
+                text = replaced_text;
+                replaced = true;
+            }
+        }
+
+        if let Some(typographer) = &self.typographer {
+            if !typographer.is_excepted(ancestors) {
+                let smartened = typographer.smarten(&text);
+                if smartened != text {
+                    text = smartened;
+                    replaced = true;
+                }
+            }
+        }
+
+        // text without any matches is put back exactly as it was
+        if replaced {
+            chunk.before(&text, ContentType::Text);
+        } else {
+            chunk.before(buffer, ContentType::Html);
+        }
+        buffer.clear();
+    }
+}
+
+pub fn init(c_rewriter: RClass) -> Result<(), Error> {
+    let c_text_replace = c_rewriter
+        .define_class("TextReplace", magnus::class::object())
+        .expect("cannot define class Selma::Rewriter::TextReplace");
+
+    c_text_replace.define_singleton_method("new", function!(SelmaTextReplace::new, -1))?;
+
+    Ok(())
+}
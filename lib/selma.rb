@@ -11,3 +11,8 @@ require_relative "selma/sanitizer"
 require_relative "selma/html"
 require_relative "selma/rewriter"
 require_relative "selma/selector"
+require_relative "selma/handler"
+require_relative "selma/handlers"
+require_relative "selma/configuration"
+require_relative "selma/registry"
+require_relative "selma/nokogiri"
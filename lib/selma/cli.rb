@@ -0,0 +1,89 @@
+# frozen_string_literal: true
+
+require "optparse"
+require "selma"
+
+module Selma
+  # The `selma` executable: sanitizes HTML from files (or stdin) with a
+  # sanitizer config from a YAML or JSON file, or one of the built-in
+  # profiles, for batch migrations and trying configs out outside an app.
+  #
+  #   selma --config sanitizer.yml post.html > clean.html
+  #   selma --profile relaxed --in-place posts/*.html
+  #   curl https://example.com | selma --profile basic --explain
+  class CLI
+    PROFILES = ["basic", "relaxed", "restricted", "github"].freeze
+
+    def self.start(argv, input: $stdin, output: $stdout, error: $stderr)
+      new(input: input, output: output, error: error).run(argv)
+    end
+
+    def initialize(input:, output:, error:)
+      @input = input
+      @output = output
+      @error = error
+      @in_place = false
+      @explain = false
+    end
+
+    # Returns the exit status: 0 on success, 1 if a file couldn't be
+    # sanitized, 2 for bad arguments.
+    def run(argv)
+      files = parser.parse(argv)
+      sanitizer = @sanitizer || Selma::Sanitizer.new
+
+      if @in_place && files.empty?
+        @error.puts("selma: --in-place needs at least one file")
+        return 2
+      end
+
+      (files.empty? ? [nil] : files).each do |file|
+        html = (file.nil? || file == "-") ? @input.read : File.read(file, encoding: "UTF-8")
+        sanitized = rewriter(sanitizer, file).rewrite(html)
+
+        if @in_place
+          File.write(file, sanitized)
+        else
+          @output.write(sanitized)
+        end
+      end
+
+      0
+    rescue OptionParser::ParseError, Selma::ConfigError, ArgumentError, TypeError => e
+      @error.puts("selma: #{e.message}")
+      2
+    rescue Selma::Error, SystemCallError, RuntimeError => e
+      @error.puts("selma: #{e.message}")
+      1
+    end
+
+    private
+
+    def parser
+      OptionParser.new do |opts|
+        opts.banner = "Usage: selma [options] [FILE...]"
+
+        opts.on("-c", "--config FILE", "Sanitize with the config in FILE (YAML, or JSON if it ends in .json)") do |path|
+          @sanitizer = Selma::Sanitizer.load_file(path)
+        end
+
+        opts.on("-p", "--profile NAME", PROFILES, "Sanitize with a built-in profile (#{PROFILES.join(", ")})") do |name|
+          @sanitizer = Selma::Sanitizer.public_send(name)
+        end
+
+        opts.on("-i", "--in-place", "Overwrite each FILE with its sanitized HTML") { @in_place = true }
+
+        opts.on("-e", "--explain", "Print each removed element, and why, to stderr") { @explain = true }
+      end
+    end
+
+    def rewriter(sanitizer, file)
+      return Selma::Rewriter.new(sanitizer: sanitizer) unless @explain
+
+      on_removal = ->(tag_name, reason, _attributes) do
+        @error.puts("#{file || "-"}: removed <#{tag_name}> (#{reason})")
+      end
+      Selma::Rewriter.new(sanitizer: sanitizer, on_removal: on_removal)
+    end
+  end
+end
@@ -0,0 +1,75 @@
+# frozen_string_literal: true
+
+module Selma
+  # What `Selma.rewrite` builds its rewriter from, as set in `Selma.configure`.
+  # `options` are any other keyword arguments for `Selma::Rewriter.new`, like
+  # `minify: true`.
+  class Configuration
+    attr_accessor :sanitizer, :handlers, :options
+
+    def initialize
+      @sanitizer = Selma::Sanitizer.new
+      @handlers = []
+      @options = {}
+    end
+
+    def initialize_copy(other)
+      super
+      @handlers = other.handlers.dup
+      @options = other.options.dup
+    end
+
+    def build_rewriter
+      Selma::Rewriter.new(sanitizer: sanitizer, handlers: handlers.to_a, **options)
+    end
+  end
+
+  CONFIGURATION_LOCK = Mutex.new
+  private_constant :CONFIGURATION_LOCK
+
+  class << self
+    # Changes what `Selma.rewrite` rewrites with. The block is given a copy of
+    # the current `Selma::Configuration`, which replaces it once the block
+    # returns; the rewriter is then rebuilt the next time it's needed.
+    #
+    #   Selma.configure do |config|
+    #     config.sanitizer = Selma::Sanitizer.relaxed
+    #     config.handlers = [Selma::Handlers::VideoEmbeds.new]
+    #     config.options = { minify: true }
+    #   end
+    def configure
+      CONFIGURATION_LOCK.synchronize do
+        configuration = (@configuration || Configuration.new).dup
+        yield configuration
+        @configuration = configuration
+        @rewriter = nil
+        @generation = (@generation || 0) + 1
+      end
+    end
+
+    # Rewrites `html` with the rewriter `Selma.configure` describes (or the
+    # default sanitizer, if it was never called). The rewriter's built once
+    # for the whole process, and each thread rewrites with its own copy of it
+    # (see `Selma::Rewriter#with`), since a rewriter can only rewrite one
+    # document at a time. The copies share the handlers, so handlers which keep
+    # state need to be thread-safe.
+    #
+    # With `policy:`, `html` is rewritten with the rewriter registered under
+    # that name (see `Selma::Registry`) instead.
+    def rewrite(html, policy: nil)
+      return Registry.rewriter(policy).rewrite(html) unless policy.nil?
+
+      generation, rewriter = Thread.current[:selma_rewriter]
+      unless rewriter && generation == @generation
+        generation, base = CONFIGURATION_LOCK.synchronize do
+          @rewriter ||= (@configuration || Configuration.new).build_rewriter
+          [@generation, @rewriter]
+        end
+        rewriter = base.with
+        Thread.current[:selma_rewriter] = [generation, rewriter]
+      end
+
+      rewriter.rewrite(html)
+    end
+  end
+end
@@ -0,0 +1,124 @@
+# frozen_string_literal: true
+
+require "selma"
+
+module Selma
+  # For tests (and migrations from the Sanitize gem), not for sanitizing:
+  # `require "selma/differential"` and sanitize the same HTML with Selma and
+  # Sanitize, using a Selma config and its closest Sanitize equivalent, to
+  # find where their output differs.
+  #
+  #   Selma::Differential.differences(posts.map(&:body), config).each do |result|
+  #     puts "#{result.html}\n  selma:    #{result.selma}\n  sanitize: #{result.sanitize}"
+  #   end
+  #
+  # Both outputs are parsed and serialized again by Nokogiri before they're
+  # compared, so that differences in how they're written out (like `<br/>`
+  # and `<br>`) don't count. Sanitize isn't a dependency of Selma; it has to
+  # be installed separately.
+  module Differential
+    # The keys Sanitize understands the same way Selma does.
+    SHARED_KEYS = [
+      :add_attributes,
+      :allow_comments,
+      :allow_doctype,
+      :attributes,
+      :elements,
+      :protocols,
+      :remove_contents,
+      :whitespace_elements,
+    ].freeze
+
+    # The HTML, what each sanitized it to, and the keys in the Selma config
+    # which Sanitize doesn't have an equivalent for (so may explain why they
+    # differ).
+    Result = Struct.new(:html, :selma, :sanitize, :untranslated_keys, keyword_init: true) do
+      def same?
+        selma == sanitize
+      end
+    end
+
+    class << self
+      def available?
+        require "sanitize"
+        true
+      rescue LoadError
+        false
+      end
+
+      def compare(html, config = Selma::Sanitizer::Config::DEFAULT)
+        comparer(config).call(html)
+      end
+
+      # The results for each of `htmls` which don't match.
+      def differences(htmls, config = Selma::Sanitizer::Config::DEFAULT)
+        compare = comparer(config)
+        htmls.map { |html| compare.call(html) }.reject(&:same?)
+      end
+
+      # The Sanitize config closest to a Selma one, and the Selma keys which
+      # couldn't be translated.
+      def sanitize_config(config)
+        untranslated = config.keys.reject do |key|
+          SHARED_KEYS.include?(key) || config[key] == Selma::Sanitizer::Config::DEFAULT[key]
+        end
+
+        translated = config.slice(*SHARED_KEYS).to_h do |key, value|
+          case key
+          when :whitespace_elements
+            [key, value.to_a.to_h { |element| [element, { before: " ", after: " " }] }]
+          when :protocols
+            protocols, approximated = sanitize_protocols(value)
+            untranslated << key if approximated
+            [key, protocols]
+          else
+            [key, value]
+          end
+        end
+
+        [translated, untranslated]
+      end
+
+      private
+
+      def comparer(config)
+        raise LoadError, "Selma::Differential needs the sanitize gem" unless available?
+
+        rewriter = Selma::Rewriter.new(sanitizer: Selma::Sanitizer.new(config))
+        sanitizer_config, untranslated = sanitize_config(config)
+        sanitizer = Sanitize.new(sanitizer_config)
+
+        lambda do |html|
+          Result.new(
+            html: html,
+            selma: normalize(rewriter.rewrite(html)),
+            sanitize: normalize(sanitizer.fragment(html)),
+            untranslated_keys: untranslated,
+          )
+        end
+      end
+
+      # Sanitize only has `:relative`, for every kind of relative URL.
+      def sanitize_protocols(protocols)
+        approximated = false
+
+        translated = protocols.to_h do |element, attributes|
+          [element, attributes.to_h do |attribute, allowed|
+            [attribute, Array(allowed).map do |protocol|
+              next protocol unless protocol.is_a?(Symbol) && protocol != :relative
+
+              approximated = true
+              :relative
+            end.uniq]
+          end]
+        end
+
+        [translated, approximated]
+      end
+
+      def normalize(html)
+        Nokogiri::HTML5.fragment(html).to_html
+      end
+    end
+  end
+end
@@ -0,0 +1,25 @@
+# frozen_string_literal: true
+
+module Selma
+  # What `Selma::Rewriter` expects of a handler: a `#selector`, and the
+  # `#handle_element` or `#handle_text_chunk` its selector calls for. Including
+  # it is optional, but it documents the interface, and gives a handler no-op
+  # lifecycle callbacks to override:
+  #
+  # - `#before_rewrite` is called before each document is rewritten, which is
+  #   the place to reset whatever the handler keeps track of per document.
+  # - `#after_rewrite` is called once it's been rewritten, even if rewriting
+  #   it failed.
+  #
+  # The rewriter calls these on any handler which defines them, in the order
+  # the handlers were given, whether or not it includes this module.
+  module Handler
+    def selector
+      raise NotImplementedError, "#{self.class} must define #selector"
+    end
+
+    def before_rewrite; end
+
+    def after_rewrite; end
+  end
+end
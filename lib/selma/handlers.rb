@@ -0,0 +1,12 @@
+# frozen_string_literal: true
+
+module Selma
+  # Ready-made handlers for `Selma::Rewriter`, for common rewrites.
+  module Handlers
+  end
+end
+
+require_relative "handlers/responsive_images"
+require_relative "handlers/content_ids"
+require_relative "handlers/embeds"
+require_relative "handlers/video_embeds"
@@ -0,0 +1,33 @@
+# frozen_string_literal: true
+
+module Selma
+  module Handlers
+    # Rewrites `<img src="cid:...">` (which points at an email's attachment) to
+    # wherever that attachment is served from. The callback is given the
+    # content ID and returns the URL to use, or `nil` to drop the image.
+    #
+    #   handler = Selma::Handlers::ContentIds.new { |cid| attachment_url(message, cid) }
+    class ContentIds
+      SELECTOR = Selma::Selector.new(match_element: %(img[src^="cid:"]))
+
+      def initialize(callback = nil, &block)
+        @callback = callback || block
+        raise ArgumentError, "a callback (or block) is required" unless @callback.respond_to?(:call)
+      end
+
+      def selector
+        SELECTOR
+      end
+
+      def handle_element(element)
+        url = @callback.call(element["src"].delete_prefix("cid:"))
+
+        if url.nil?
+          element.remove
+        else
+          element["src"] = url.to_s
+        end
+      end
+    end
+  end
+end
@@ -0,0 +1,38 @@
+# frozen_string_literal: true
+
+module Selma
+  module Handlers
+    # Expands placeholders for embedded content (like oEmbed or Open Graph
+    # previews) into whatever HTML the callback returns for them. The callback
+    # is given the placeholder's attributes, and the HTML it returns replaces
+    # the whole placeholder, once it's been through its own sanitizer. That
+    # sanitizer usually needs to allow more (like `<iframe>`) than the one the
+    # rest of the document goes through; returning `nil` leaves the placeholder
+    # as it is.
+    #
+    #   sanitizer = Selma::Sanitizer.new(elements: ["iframe"], attributes: { "iframe" => ["src"] })
+    #   handler = Selma::Handlers::Embeds.new(sanitizer: sanitizer) do |attributes|
+    #     oembed(attributes["data-oembed-url"])&.fetch("html")
+    #   end
+    class Embeds
+      DEFAULT_SELECTOR = "div[data-oembed-url]"
+
+      attr_reader :selector
+
+      def initialize(callback = nil, selector: DEFAULT_SELECTOR, sanitizer: Selma::Sanitizer.new, &block)
+        @callback = callback || block
+        raise ArgumentError, "a callback (or block) is required" unless @callback.respond_to?(:call)
+
+        @selector = Selma::Selector.new(match_element: selector)
+        @rewriter = Selma::Rewriter.new(sanitizer: sanitizer)
+      end
+
+      def handle_element(element)
+        html = @callback.call(element.attributes)
+        return if html.nil?
+
+        element.replace(@rewriter.rewrite(html.to_s), as: :html)
+      end
+    end
+  end
+end
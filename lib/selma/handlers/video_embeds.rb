@@ -0,0 +1,117 @@
+# frozen_string_literal: true
+
+require "cgi"
+require "uri"
+
+module Selma
+  module Handlers
+    # Normalizes YouTube and Vimeo embeds to their privacy-enhanced players
+    # (`www.youtube-nocookie.com`, and Vimeo's with `dnt=1`), keeping only the
+    # query parameters in `YOUTUBE_PARAMS` and `VIMEO_PARAMS`. Every other
+    # `<iframe>` is removed. With `links: true` (the default), links to a
+    # video's page (like `https://youtu.be/...`) are replaced by an embed of
+    # the video, `width:` by `height:` in size.
+    #
+    # The sanitizer needs to keep the `<iframe>`s (and their `src`) for the
+    # handler to see them.
+    #
+    #   Selma::Rewriter.new(sanitizer: sanitizer, handlers: [Selma::Handlers::VideoEmbeds.new]).rewrite(html)
+    class VideoEmbeds
+      SELECTOR = Selma::Selector.new(match_element: "iframe, a[href]")
+
+      YOUTUBE_HOSTS = ["youtube.com", "www.youtube.com", "m.youtube.com", "youtube-nocookie.com", "www.youtube-nocookie.com"].freeze
+      YOUTUBE_PARAMS = ["cc_load_policy", "controls", "end", "hl", "loop", "playlist", "rel", "start"].freeze
+      YOUTUBE_ID = /\A[A-Za-z0-9_-]{11}\z/
+
+      VIMEO_HOSTS = ["vimeo.com", "www.vimeo.com", "player.vimeo.com"].freeze
+      VIMEO_PARAMS = ["autopause", "byline", "color", "loop", "muted", "portrait", "title"].freeze
+      VIMEO_ID = /\A[0-9]+\z/
+
+      PARAM_VALUE = /\A[A-Za-z0-9_,-]+\z/
+
+      def initialize(links: true, width: 560, height: 315)
+        @links = links
+        @width = width
+        @height = height
+      end
+
+      def selector
+        SELECTOR
+      end
+
+      def handle_element(element)
+        if element.tag_name == "iframe"
+          src = embed_url(element["src"], embedded: true)
+
+          if src.nil?
+            element.remove
+          else
+            element["src"] = src
+          end
+        elsif @links && (src = embed_url(element["href"], embedded: false))
+          element.replace(
+            %(<iframe src="#{CGI.escapeHTML(src)}" width="#{@width.to_i}" height="#{@height.to_i}" allowfullscreen></iframe>),
+            as: :html,
+          )
+        end
+      end
+
+      private
+
+      # The privacy-enhanced embed URL for a YouTube or Vimeo URL: an embed's,
+      # if it's `embedded`, otherwise a video page's.
+      def embed_url(url, embedded:)
+        uri = URI.parse(url.to_s.strip)
+        return unless uri.is_a?(URI::HTTP) && uri.host
+
+        host = uri.host.downcase
+        params = URI.decode_www_form(uri.query.to_s)
+        path = uri.path.split("/").reject(&:empty?)
+
+        if YOUTUBE_HOSTS.include?(host) || host == "youtu.be"
+          id = youtube_id(host, path, params, embedded)
+          return unless id&.match?(YOUTUBE_ID)
+
+          # a video page's `t` is where the embed `start`s
+          start = params.assoc("t")&.last
+          params << ["start", seconds(start).to_s] if start && seconds(start) && !params.assoc("start")
+
+          "https://www.youtube-nocookie.com/embed/#{id}#{query(params, YOUTUBE_PARAMS)}"
+        elsif VIMEO_HOSTS.include?(host)
+          id = embedded ? (path[1] if host == "player.vimeo.com" && path[0] == "video") : path[0]
+          return unless id&.match?(VIMEO_ID)
+
+          "https://player.vimeo.com/video/#{id}#{query(params, VIMEO_PARAMS, [["dnt", "1"]])}"
+        end
+      rescue URI::InvalidURIError
+        nil
+      end
+
+      def youtube_id(host, path, params, embedded)
+        if embedded
+          path[1] if host != "youtu.be" && path[0] == "embed"
+        elsif host == "youtu.be"
+          path[0]
+        elsif path == ["watch"]
+          params.assoc("v")&.last
+        elsif path[0] == "shorts"
+          path[1]
+        end
+      end
+
+      # "90", "90s" or "1m30s" in seconds.
+      def seconds(time)
+        match = /\A(?:(\d+)h)?(?:(\d+)m)?(?:(\d+)s?)?\z/.match(time)
+        return if match.nil? || match[0].empty?
+
+        (match[1].to_i * 3600) + (match[2].to_i * 60) + match[3].to_i
+      end
+
+      def query(params, allowed, extra = [])
+        kept = params.select { |name, value| allowed.include?(name) && value.match?(PARAM_VALUE) }.uniq(&:first)
+        kept += extra
+        kept.empty? ? "" : "?#{URI.encode_www_form(kept)}"
+      end
+    end
+  end
+end
@@ -0,0 +1,26 @@
+# frozen_string_literal: true
+
+module Selma
+  class << self
+    # Sanitizes a Nokogiri document, fragment, node or node set (or anything
+    # else with a Nokogiri-like `to_html`), for pipelines which already have a
+    # parsed tree. It's written out as UTF-8, which is what the rewriter reads,
+    # and rewritten in a single pass. With `as: :html` (the default), that HTML
+    # is returned; with `as: :node`, it's parsed again, into a document of the
+    # same class as a document, or a fragment of its document otherwise.
+    #
+    # Nokogiri isn't a dependency of Selma; this only needs the node.
+    def sanitize_node(node, sanitizer: Selma::Sanitizer.new, as: :html)
+      raise ArgumentError, "as: must be :html or :node, got #{as.inspect}" unless [:html, :node].include?(as)
+
+      html = Selma::Rewriter.new(sanitizer: sanitizer).rewrite(node.to_html(encoding: "UTF-8"))
+      return html if as == :html
+
+      if node.respond_to?(:root) && node.class.respond_to?(:parse)
+        node.class.parse(html)
+      else
+        node.document.fragment(html)
+      end
+    end
+  end
+end
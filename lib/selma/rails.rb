@@ -0,0 +1,114 @@
+# frozen_string_literal: true
+
+require "selma"
+
+module Selma
+  # Lets Rails apps sanitize with Selma in place of rails-html-sanitizer. This
+  # isn't loaded by `require "selma"`; `require "selma/rails"` (say, in an
+  # initializer) adds a `sanitize_with_selma` helper to every view, and the
+  # sanitizers here can be swapped in for Rails' own:
+  #
+  #   config.action_view.sanitizer_vendor = Selma::Rails::Vendor
+  #   ActionText::ContentHelper.sanitizer = Selma::Rails::SafeListSanitizer.new
+  module Rails
+    # The attributes rails-html-sanitizer allows by default, on any element.
+    ALLOWED_ATTRIBUTES = Set.new(["abbr", "alt", "cite", "class", "datetime", "height", "href", "lang", "name", "src", "title", "width", "xml:lang"]).freeze
+
+    # Sanitizes like rails-html-sanitizer's `SafeListSanitizer`: `tags:` and
+    # `attributes:` (or `allowed_tags` and `allowed_attributes`) are the
+    # elements, and the attributes on any of them, that are kept, with URLs
+    # checked against the protocols `Selma::Sanitizer::Config::RELAXED` allows.
+    # Loofah scrubbers aren't supported.
+    class SafeListSanitizer
+      class << self
+        attr_accessor :allowed_tags, :allowed_attributes
+      end
+
+      self.allowed_tags = Set.new(Selma::Sanitizer::Config::RELAXED[:elements]).freeze
+      self.allowed_attributes = ALLOWED_ATTRIBUTES
+
+      def sanitize(html, options = {})
+        return if html.nil?
+        raise ArgumentError, "Selma::Rails::SafeListSanitizer doesn't support Loofah scrubbers" if options[:scrubber]
+
+        tags = options.fetch(:tags, self.class.allowed_tags)
+        attributes = options.fetch(:attributes, self.class.allowed_attributes)
+
+        Rails.rewriter([:safe_list, tags.to_a.sort, attributes.to_a.sort]) do
+          Selma::Rewriter.new(sanitizer: Selma::Sanitizer.new({
+            elements: tags.to_a,
+            attributes: { all: attributes.to_a },
+            protocols: Selma::Sanitizer::Config::RELAXED[:protocols],
+          }))
+        end.rewrite(html.to_s)
+      end
+
+      def sanitize_css(_style_string)
+        raise NotImplementedError, "Selma doesn't sanitize CSS"
+      end
+    end
+
+    # Strips every element, like rails-html-sanitizer's `FullSanitizer`,
+    # leaving the text (without the contents of `<script>` and the like).
+    class FullSanitizer
+      def sanitize(html, _options = {})
+        return if html.nil?
+
+        Rails.rewriter([:full]) { Selma::Rewriter.new(sanitizer: Selma::Sanitizer.new) }.rewrite(html.to_s)
+      end
+    end
+
+    # Unwraps every `<a>`, like rails-html-sanitizer's `LinkSanitizer`. Like it,
+    # this doesn't sanitize anything else.
+    class LinkSanitizer
+      def sanitize(html, _options = {})
+        return if html.nil?
+
+        Rails.rewriter([:link]) do
+          Selma::Rewriter.new(sanitizer: nil) { on("a") { |element| element.remove_and_keep_content } }
+        end.rewrite(html.to_s)
+      end
+    end
+
+    # What `config.action_view.sanitizer_vendor` expects.
+    module Vendor
+      class << self
+        def safe_list_sanitizer
+          SafeListSanitizer
+        end
+
+        def full_sanitizer
+          FullSanitizer
+        end
+
+        def link_sanitizer
+          LinkSanitizer
+        end
+
+        alias_method :white_list_sanitizer, :safe_list_sanitizer
+      end
+    end
+
+    module Helper
+      # Sanitizes `html` with `sanitizer:` (a `Selma::Sanitizer`, or a config
+      # for one), or `Selma::Sanitizer.relaxed`, and marks the result as safe
+      # to render.
+      def sanitize_with_selma(html, sanitizer: nil)
+        sanitizer = Selma::Sanitizer.new(sanitizer) if sanitizer.is_a?(Hash)
+        rewritten = Selma::Rewriter.new(sanitizer: sanitizer || Selma::Sanitizer.relaxed).rewrite(html.to_s)
+        rewritten.respond_to?(:html_safe) ? rewritten.html_safe : rewritten
+      end
+    end
+
+    class << self
+      # Rewriters are built once per thread for each kind of sanitizing, since
+      # a rewriter can only rewrite one document at a time.
+      def rewriter(key)
+        rewriters = (Thread.current[:selma_rails_rewriters] ||= {})
+        rewriters[key] ||= yield
+      end
+    end
+  end
+end
+
+ActiveSupport.on_load(:action_view) { include Selma::Rails::Helper } if defined?(ActiveSupport)
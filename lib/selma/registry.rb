@@ -0,0 +1,63 @@
+# frozen_string_literal: true
+
+module Selma
+  # Named policies, for apps which sanitize with a handful of different
+  # rewriters (one per tenant, say, or per kind of content). Each is built
+  # once, when it's registered, and `Selma.rewrite(html, policy: name)`
+  # rewrites with a per-thread copy of it, as `Selma.rewrite` does with the
+  # configured rewriter.
+  #
+  #   Selma::Registry.register(:comments, Selma::Sanitizer.basic)
+  #   Selma::Registry.register(:posts, Selma::Sanitizer.relaxed, handlers: [Selma::Handlers::VideoEmbeds.new], minify: true)
+  #
+  #   Selma.rewrite(html, policy: :comments)
+  #
+  # The policies themselves are kept natively; with the GVL held throughout,
+  # registering, replacing and looking one up are atomic without a lock.
+  module Registry
+    class << self
+      # Registers (or replaces) the policy `name`: a rewriter with `sanitizer`
+      # (a `Selma::Sanitizer`, or a config for one), `handlers:`, and any other
+      # options for `Selma::Rewriter.new`.
+      def register(name, sanitizer, handlers: [], **options)
+        sanitizer = Selma::Sanitizer.new(sanitizer) if sanitizer.is_a?(Hash)
+        handlers = handlers.to_a
+        rewriter = Selma::Rewriter.new(sanitizer: sanitizer, handlers: handlers, **options)
+
+        store(name.to_sym, rewriter, sanitizer, handlers)
+        name.to_sym
+      end
+
+      def unregister(name)
+        delete(name.to_sym)
+      end
+
+      def registered?(name)
+        !fetch(name.to_sym).nil?
+      end
+
+      def names
+        native_names
+      end
+
+      # This thread's copy of the rewriter for the policy `name`. Raises an
+      # `ArgumentError` if there's no such policy.
+      def rewriter(name)
+        name = name.to_sym
+        entry = fetch(name)
+        raise ArgumentError, "no Selma policy is registered as #{name.inspect}" if entry.nil?
+
+        current, registered = entry
+        rewriters = (Thread.current[:selma_policy_rewriters] ||= {})
+        generation, rewriter = rewriters[name]
+        return rewriter if rewriter && generation == current
+
+        rewriter = registered.with
+        rewriters[name] = [current, rewriter]
+        rewriter
+      end
+
+      private :store, :fetch, :delete, :native_names
+    end
+  end
+end
@@ -1,6 +1,124 @@
 # frozen_string_literal: true
 
 module Selma
+  class << self
+    # An object which is told about every `Selma::Rewriter#rewrite`. This can be
+    # anything which responds to `instrument(name, payload) { ... }` (like
+    # `ActiveSupport::Notifications`), or a callable taking `(name, payload)`.
+    # The payload is the rewriter's `#stats`. By default, nothing is reported.
+    attr_accessor :instrumenter
+
+    # A callable which is given the Markdown handlers add with `as: :markdown`,
+    # and returns the HTML it renders to, like
+    # `->(markdown) { Commonmarker.to_html(markdown) }`. That HTML is then run
+    # through the rewriter's `handler_sanitizer:`.
+    attr_accessor :markdown_renderer
+  end
+
   class Rewriter
+    EVENT_NAME = "rewrite.selma"
+
+    module Instrumentation
+      def rewrite(html)
+        instrument { super }
+      end
+
+      def rewrite_with_context(html, context = {})
+        instrument { super }
+      end
+
+      private
+
+      def instrument
+        instrumenter = Selma.instrumenter
+        return yield unless instrumenter
+
+        if instrumenter.respond_to?(:instrument)
+          payload = {}
+          instrumenter.instrument(EVENT_NAME, payload) do
+            yield.tap { payload.merge!(stats) }
+          end
+        else
+          yield.tap { instrumenter.call(EVENT_NAME, stats) }
+        end
+      end
+    end
+
+    prepend Instrumentation
+
+    # A handler for the elements matching `css`, which calls the block given to
+    # `Builder#on` with each of them.
+    class ElementBlock
+      attr_reader :selector
+
+      def initialize(css, block)
+        @selector = Selma::Selector.new(match_element: css)
+        @block = block
+      end
+
+      def handle_element(element)
+        @block.call(element)
+      end
+    end
+
+    # A handler for the text within the elements matching `css`, which calls
+    # the block given to `Builder#on_text` with each text chunk.
+    class TextBlock
+      attr_reader :selector
+
+      def initialize(css, block)
+        @selector = Selma::Selector.new(match_text_within: css)
+        @block = block
+      end
+
+      def handle_text_chunk(text)
+        @block.call(text)
+      end
+    end
+
+    # Collects the handlers registered by the block given to
+    # `Selma::Rewriter.new`, which is either given the builder or, if it
+    # doesn't take an argument, evaluated within it:
+    #
+    #   Selma::Rewriter.new(sanitizer: sanitizer) do
+    #     on("a[href]") { |element| element["rel"] = "nofollow" }
+    #     on_text("p") { |text| text.replace(text.to_s.upcase, as: :text) }
+    #   end
+    class Builder
+      attr_reader :handlers
+
+      def initialize
+        @handlers = []
+      end
+
+      def on(css, &block)
+        raise ArgumentError, "a block is required" unless block
+
+        @handlers << ElementBlock.new(css, block)
+        self
+      end
+
+      def on_text(css, &block)
+        raise ArgumentError, "a block is required" unless block
+
+        @handlers << TextBlock.new(css, block)
+        self
+      end
+    end
+
+    module Construction
+      def new(*args, **kwargs, &block)
+        return super unless block
+
+        builder = Builder.new
+        block.arity == 1 ? yield(builder) : builder.instance_eval(&block)
+
+        # the block's handlers run after any given as `handlers:`, and the block
+        # itself isn't passed on
+        super(*args, **kwargs.merge(handlers: kwargs.fetch(:handlers, []).to_a + builder.handlers), &nil)
+      end
+    end
+
+    singleton_class.prepend(Construction)
   end
 end
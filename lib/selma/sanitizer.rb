@@ -8,16 +8,91 @@ module Selma
     ESCAPE_TAGFILTER = (1 << 1)
     REMOVE_CONTENTS = (1 << 2)
     WRAP_WHITESPACE = (1 << 3)
+    VOID = (1 << 4)
+    RAW_TEXT = (1 << 5)
+    ESCAPE_CONTENTS = (1 << 6)
+
+    class << self
+      # A sanitizer configured by a YAML file (or JSON, if its name ends in
+      # `.json`), validated like any other config. Keys become symbols, as do
+      # strings starting with a colon (`":relative"`), so that a file can say
+      # anything a config hash can. Raises `Selma::ConfigError`, naming the
+      # file, if it can't be parsed or isn't a valid config.
+      def load_file(path)
+        new(load_config(path))
+      rescue Selma::ConfigError => e
+        raise Selma::ConfigError, "#{path}: #{e.message}"
+      end
+
+      private
+
+      def load_config(path)
+        text = File.read(path, encoding: "UTF-8")
+        config = File.extname(path).casecmp?(".json") ? parse_json(text) : parse_yaml(text)
+        raise Selma::ConfigError, "expected a mapping of options, got #{config.inspect}" unless config.is_a?(Hash)
+
+        symbolize_config(config)
+      end
+
+      def parse_json(text)
+        require "json"
+        JSON.parse(text)
+      rescue JSON::ParserError => e
+        raise Selma::ConfigError, e.message
+      end
+
+      def parse_yaml(text)
+        require "yaml"
+        YAML.safe_load(text)
+      rescue Psych::Exception => e
+        raise Selma::ConfigError, e.message
+      end
+
+      def symbolize_config(value)
+        case value
+        when Hash then value.to_h { |key, item| [key.to_sym, symbolize_config(item)] }
+        when Array then value.map { |item| symbolize_config(item) }
+        when /\A:[^:]/ then value[1..].to_sym
+        else value
+        end
+      end
+    end
+
+    # `new` is in Rust, and validates the config; this applies it, once, so that
+    # what's allowed can be changed afterwards (by `allow_attribute`,
+    # `disallow_attribute`, and so on) without the config being reapplied over
+    # the top when a rewriter sets the sanitizer up
+    module Construction
+      def new(*args)
+        super.tap(&:setup)
+      end
+    end
+    singleton_class.prepend(Construction)
 
-    # initialize is in Rust, this just helps manage config setup in Ruby
-    # TODO: could this just become initialize?
     def setup
+      # the built-in profiles (`Selma::Sanitizer.basic`, etc.) are configured natively
+      return if immutable? || set_up?
+
+      mark_set_up
+
       allow_element(config[:elements] || [])
 
       (config[:attributes] || {}).each do |element, attrs|
         allow_attribute(element, attrs)
       end
 
+      (config[:add_attributes] || {}).each do |element, attrs|
+        add_attribute(element, attrs)
+      end
+
+      (config[:remove_attributes] || {}).each do |element, attrs|
+        remove_attribute(element, attrs)
+      end
+
+      (config[:boolean_attributes] || {}).each do |element, attrs|
+        boolean_attribute(element, attrs)
+      end
+
       (config[:protocols] || {}).each do |element, protocols|
         protocols.each do |attribute, pr|
           allow_protocol(element, attribute, pr)
@@ -26,17 +101,118 @@ module Selma
 
       remove_contents(config[:remove_contents]) if config.include?(:remove_contents)
 
+      escape_contents(config[:escape_contents]) if config.include?(:escape_contents)
+
       wrap_with_whitespace(config[:whitespace_elements]) if config.include?(:whitespace_elements)
 
+      void_element(config[:void_elements]) if config.include?(:void_elements)
+
+      raw_text_element(config[:raw_text_elements]) if config.include?(:raw_text_elements)
+
       set_escape_tagfilter(config.fetch(:escape_tagfilter, true))
       set_allow_comments(config.fetch(:allow_comments, false))
+      set_allow_conditional_comments(config.fetch(:allow_conditional_comments, false))
+      set_allow_cdata(config.fetch(:allow_cdata, false))
       set_allow_doctype(config.fetch(:allow_doctype, true))
+      set_normalize_entities(config.fetch(:normalize_entities, false))
+      set_name_prefix(config.fetch(:name_prefix, nil))
+      set_noscript(config.fetch(:noscript, nil))
+      set_shadow_roots(config.fetch(:shadow_roots, nil))
+      set_integrity(config.fetch(:integrity, nil))
+      set_duplicate_attributes(config.fetch(:duplicate_attributes, nil))
+      set_neutralize_forms(config.fetch(:neutralize_forms, false))
+      set_fragment_links(config.fetch(:fragment_links, false))
+      set_preserve_preformatted_whitespace(config.fetch(:preserve_preformatted_whitespace, false))
+      set_normalize_amp(config.fetch(:normalize_amp, false))
+
+      if (tables = config[:tables])
+        set_table_policy(tables.fetch(:wrap_rows, false), tables[:max_span], tables[:wrapper_class])
+      end
+
+      if (css = config[:css])
+        css = {} if css == true
+        set_css_policy(css.fetch(:properties, Config::CSS_PROPERTIES).to_a)
+      end
+
+      set_strip_remote_content(config.fetch(:strip_remote_content, false))
+
+      trackers = config.fetch(:strip_tracking_pixels, false)
+      set_strip_tracking_pixels(trackers == true ? Config::TRACKER_HOSTS : trackers.to_a) if trackers
+
+      if (images = config[:images])
+        set_image_policy(images.fetch(:lazy, false), images[:max_width], images[:max_height], images.fetch(:require_src, false))
+      end
+
+      if (sources = config[:sources])
+        sources = {} if sources == true
+        set_source_policy(sources.fetch(:types, Config::SOURCE_TYPES).to_a)
+      end
+
+      if (fallback = config[:embed_fallback])
+        fallback = {} if fallback == true
+        set_embed_fallback(fallback.fetch(:text, Config::EMBED_FALLBACK_TEXT), fallback[:template])
+      end
+
+      if (iframes = config[:iframes])
+        iframes = {} if iframes == true
+        set_iframe_policy(
+          iframes.fetch(:sandbox, []).to_a,
+          iframes.fetch(:sandbox_mode, :replace),
+          iframes[:referrerpolicy]&.to_a,
+          iframes[:allow]&.to_a,
+        )
+      end
+
+      if (media = config[:media])
+        media = {} if media == true
+        set_media_policy(media.fetch(:track_kinds, Config::TRACK_KINDS).to_a)
+      end
+      set_unicode_filter(config.fetch(:unicode_filter, nil))
+
+      if (max_attributes = config[:max_attributes])
+        max_attributes = { count: max_attributes } if max_attributes.is_a?(Integer)
+        set_attribute_limit(max_attributes.fetch(:count, Config::MAX_ATTRIBUTES), max_attributes.fetch(:overflow, :remove))
+      end
+
+      if (max_value_bytes = config[:max_attribute_value_bytes])
+        max_value_bytes = { bytes: max_value_bytes } if max_value_bytes.is_a?(Integer)
+        set_attribute_value_limit(max_value_bytes.fetch(:bytes, Config::MAX_ATTRIBUTE_VALUE_BYTES), max_value_bytes.fetch(:overflow, :drop))
+      end
+
+      if (passthrough = config[:passthrough])
+        set_passthrough(passthrough[:attribute].to_s, passthrough[:token])
+      end
+
+      if (max_classes = config[:max_classes])
+        max_classes = { tokens: max_classes } if max_classes.is_a?(Integer)
+        set_class_limit(max_classes[:tokens], max_classes[:token_bytes], max_classes.fetch(:overflow, :drop))
+      end
+      set_control_characters(config.fetch(:control_characters, nil))
+
+      paranoid = config.fetch(:paranoid, false)
+      if paranoid
+        paranoid = Config::PARANOID.merge(paranoid == true ? {} : paranoid)
+        set_paranoid(paranoid[:attribute_prefixes].to_a, paranoid[:protocols].to_a)
+      end
     end
 
     def elements
       config[:elements]
     end
 
+    # A copy of this sanitizer, changed by the block (which is given the copy),
+    # leaving this one as it was. Nothing's parsed or validated again, so it's
+    # a cheap way to derive, say, a policy for each tenant from a shared one.
+    # The copy can be changed even if this is a built-in profile, but its
+    # `config` is still this one's.
+    #
+    #   with_tables = Selma::Sanitizer.relaxed.dup_with { |s| s.allow_element(["table", "tr", "td"]) }
+    def dup_with
+      derived = derive
+      yield derived if block_given?
+      derived
+    end
+
     def allow_element(elements)
       elements.flatten.each { |e| set_flag(e, ALLOW, true) }
     end
@@ -49,6 +225,18 @@ module Selma
       attrs.flatten.each { |attr| set_allowed_attribute(element, attr, true) }
     end
 
+    def add_attribute(element, attrs)
+      attrs.each { |attr, value| set_added_attribute(element, attr, value) }
+    end
+
+    def remove_attribute(element, attrs)
+      attrs.flatten.each { |attr| set_removed_attribute(element, attr, true) }
+    end
+
+    def boolean_attribute(element, attrs)
+      attrs.flatten.each { |attr| set_boolean_attribute(element, attr, true) }
+    end
+
     def require_any_attributes(element, attrs)
       if attr.empty?
         set_required_attribute(element, "*", true)
@@ -65,9 +253,18 @@ module Selma
       klass.flatten.each { |k| set_allowed_class(element, k, true) }
     end
 
+    def disallow_class(element, *klass)
+      klass.flatten.each { |k| set_allowed_class(element, k, false) }
+    end
+
     def allow_protocol(element, attr, protos)
       protos = [protos] unless protos.is_a?(Array)
-      set_allowed_protocols(element, attr, protos)
+      set_allowed_protocols(element.to_s, attr, protos, true)
+    end
+
+    def disallow_protocol(element, attr, protos)
+      protos = [protos] unless protos.is_a?(Array)
+      set_allowed_protocols(element.to_s, attr, protos, false)
     end
 
     def remove_contents(elements)
@@ -78,8 +275,20 @@ module Selma
       end
     end
 
+    def escape_contents(elements)
+      elements.flatten.each { |e| set_flag(e, ESCAPE_CONTENTS, true) }
+    end
+
     def wrap_with_whitespace(elements)
       elements.flatten.each { |e| set_flag(e, WRAP_WHITESPACE, true) }
     end
+
+    def void_element(elements)
+      elements.flatten.each { |e| set_flag(e, VOID, true) }
+    end
+
+    def raw_text_element(elements)
+      elements.flatten.each { |e| set_flag(e, RAW_TEXT, true) }
+    end
   end
 end
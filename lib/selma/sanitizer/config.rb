@@ -57,6 +57,125 @@ module Selma
           !(value == true || value == false || value.nil? || value.is_a?(Method) || value.is_a?(Numeric) || value.is_a?(Symbol))
         end
       end
+
+      # What `paranoid: true` denies, whatever else the config allows: attributes
+      # whose names start with any of `attribute_prefixes` (event handlers), and
+      # attributes whose values use any of `protocols`. Passing a Hash as
+      # `paranoid:` overrides either list.
+      PARANOID = freeze_config(
+        attribute_prefixes: ["on"],
+        protocols: ["javascript", "vbscript"],
+      )
+
+      # How many attributes `max_attributes:` lets an element keep, unless it
+      # says otherwise, which is far more than anything legitimate needs.
+      MAX_ATTRIBUTES = 256
+
+      # How long (in bytes) `max_attribute_value_bytes:` lets an attribute's
+      # value be, unless it says otherwise.
+      MAX_ATTRIBUTE_VALUE_BYTES = 65_536
+
+      # The hosts (along with their subdomains) whose images
+      # `strip_tracking_pixels: true` removes, on top of any 1x1 images.
+      TRACKER_HOSTS = freeze_config([
+        "bananatag.com",
+        "doubleclick.net",
+        "getnotify.com",
+        "google-analytics.com",
+        "mailtrack.io",
+        "mixpanel.com",
+        "pixel.wp.com",
+        "yesware.com",
+      ])
+
+      # The MIME types `sources: true` lets a `<source>`'s `type` be: the image
+      # formats browsers pick between in a `<picture>`, and the common video and
+      # audio ones.
+      SOURCE_TYPES = freeze_config([
+        "image/avif",
+        "image/gif",
+        "image/jpeg",
+        "image/png",
+        "image/webp",
+        "video/mp4",
+        "video/ogg",
+        "video/webm",
+        "audio/mpeg",
+        "audio/ogg",
+        "audio/wav",
+        "audio/webm",
+      ])
+
+      # The CSS properties `css: true` keeps in `style` attributes: colors,
+      # fonts, text and box layout, but nothing which positions an element over
+      # the rest of the page.
+      CSS_PROPERTIES = freeze_config([
+        "background-color",
+        "border",
+        "border-bottom",
+        "border-collapse",
+        "border-color",
+        "border-left",
+        "border-radius",
+        "border-right",
+        "border-spacing",
+        "border-style",
+        "border-top",
+        "border-width",
+        "color",
+        "display",
+        "font",
+        "font-family",
+        "font-size",
+        "font-style",
+        "font-weight",
+        "height",
+        "letter-spacing",
+        "line-height",
+        "list-style-type",
+        "margin",
+        "margin-bottom",
+        "margin-left",
+        "margin-right",
+        "margin-top",
+        "max-width",
+        "min-width",
+        "padding",
+        "padding-bottom",
+        "padding-left",
+        "padding-right",
+        "padding-top",
+        "table-layout",
+        "text-align",
+        "text-decoration",
+        "text-indent",
+        "text-transform",
+        "vertical-align",
+        "white-space",
+        "width",
+        "word-break",
+      ])
+
+      # The kinds of `<track>` which `media: true` keeps: the ones shown to
+      # people, rather than `metadata` for scripts.
+      TRACK_KINDS = freeze_config([
+        "captions",
+        "chapters",
+        "descriptions",
+        "subtitles",
+      ])
+
+      # The text of the link `embed_fallback:` puts in place of a removed embed.
+      EMBED_FALLBACK_TEXT = "View embedded content"
+
+      # Keeps `<audio>` and `<video>` (with their `<source>`s and `<track>`s),
+      # for merging into another config, as in
+      # `Config.merge(Config::RELAXED, Config::MEDIA)`.
+      MEDIA = freeze_config(
+        media: {
+          track_kinds: TRACK_KINDS,
+        },
+      )
     end
   end
 end
@@ -65,3 +184,5 @@ require "selma/sanitizer/config/basic"
 require "selma/sanitizer/config/default"
 require "selma/sanitizer/config/relaxed"
 require "selma/sanitizer/config/restricted"
+require "selma/sanitizer/config/github"
+require "selma/sanitizer/config/email"
@@ -13,24 +13,235 @@ module Selma
         # comments.
         allow_comments: false,
 
+        # Whether or not to allow IE conditional comments
+        # (`<!--[if IE]>...<![endif]-->`) when comments are allowed. These are
+        # removed as a whole unless this is true, since their contents are
+        # markup to IE, and pass through the sanitizer untouched.
+        allow_conditional_comments: false,
+
+        # Whether or not to allow CDATA sections (`<![CDATA[...]]>`). Outside of
+        # SVG and MathML these are treated like comments (and are only allowed
+        # when comments are); within them, they're removed unless this is true.
+        allow_cdata: false,
+
         # Whether or not to allow well-formed HTML doctype declarations such as
         # "<!DOCTYPE html>" when sanitizing a document.
         allow_doctype: false,
 
+        # HTML attributes to add to specific elements, regardless of whether they
+        # were present in the original HTML. Values for token list attributes,
+        # like `rel` and `class`, are merged with any existing tokens.
+        add_attributes: {},
+
         # HTML attributes to allow in specific elements. By default, no attributes
         # are allowed. Use the symbol :data to indicate that arbitrary HTML5
         # data-* attributes should be allowed.
         attributes: {},
 
+        # HTML attributes to remove from specific elements, even if they would
+        # otherwise be allowed (for example, by an `:all` attribute rule).
+        remove_attributes: {},
+
+        # HTML attributes of specific elements to treat as boolean ones, like
+        # `controls` or `open`, which are on whatever their value is: when one
+        # is allowed, it's kept without its value, which isn't checked. The
+        # standard boolean attributes are always treated this way.
+        boolean_attributes: {},
+
         # HTML elements to allow. By default, no elements are allowed (which means
         # that all HTML will be stripped).
         elements: [],
 
+        # A prefix to add to the values of `id` and `name` attributes, as well as
+        # to the targets of fragment links (`href="#foo"`), like GitHub's
+        # `user-content-`. This prevents user content from clobbering the ids of
+        # the surrounding page. By default, no prefix is added.
+        name_prefix: nil,
+
+        # Whether or not to decode the entities in text, and re-encode only `&`,
+        # `<` and `>`, so that `&eacute;`, `&#233;` and `é` all come out as `é`.
+        normalize_entities: false,
+
         # URL handling protocols to allow in specific attributes. By default, no
         # protocols are allowed. Use :relative in place of a protocol if you want
-        # to allow relative URLs sans protocol.
+        # to allow relative URLs sans protocol, or just some kinds of them:
+        # :fragment (`#top`), :root_relative (`/top`), or :protocol_relative
+        # (`//example.com/top`). :relative only allows path-relative URLs with
+        # a `/` or `#` in them (like `docs/top.html`); all of them (like
+        # `top.html`) need :path_relative. Protocols for `:all` elements
+        # apply to every element which doesn't list its own for the attribute.
         protocols: {},
 
+        # Whether or not to remove event handler attributes (`on*`), and
+        # attributes with `javascript:` or `vbscript:` values, even when the
+        # rest of the config allows them. A Hash overrides the lists of
+        # attribute prefixes and protocols that are denied (see
+        # `Selma::Sanitizer::Config::PARANOID`).
+        paranoid: false,
+
+        # Whether or not to remove tracking pixels: images which are 1x1 (or
+        # smaller), or which come from a known tracker. `true` uses the hosts in
+        # `Selma::Sanitizer::Config::TRACKER_HOSTS`; an Array of hosts replaces
+        # them.
+        strip_tracking_pixels: false,
+
+        # Whether or not to remove the `src`, `srcset`, `background` and
+        # `poster` attributes whose URLs are on another host (`http:`, `https:`
+        # or protocol-relative ones), which would be fetched as soon as the page
+        # is shown. Relative URLs, and ones like `cid:` and `data:`, are kept.
+        strip_remote_content: false,
+
+        # How to sanitize the CSS in `style` attributes (which are only kept at
+        # all if `attributes` allows them). A declaration is kept if its
+        # property is one of `properties` (`Config::CSS_PROPERTIES` for `true`)
+        # and its value can't load or run anything: no `url()`, `expression()`,
+        # escapes or comments. By default (`nil`), `style` attributes are kept
+        # as they are.
+        css: nil,
+
+        # How to normalize the tables which are kept. `wrap_rows: true` wraps
+        # `<tr>`s directly within a `<table>` in a `<tbody>`; `colspan` and
+        # `rowspan` values above `max_span` are removed; and `wrapper_class`
+        # wraps each table in a `<div>` with that class (for scrolling wide
+        # tables). By default (`nil`), tables are left as they are.
+        tables: nil,
+
+        # How to normalize the `<img>` elements which are kept. `lazy: true`
+        # sets `loading="lazy"` and `decoding="async"`; `max_width` and
+        # `max_height` shrink the `width` and `height` attributes to fit (keeping
+        # their ratio); and `require_src: true` drops images left without a
+        # `src`. By default (`nil`), images are left as they are.
+        images: nil,
+
+        # Whether or not to check the `media`, `sizes` and `type` attributes of
+        # `<source>` elements, and keep the ones which pass whether or not
+        # `attributes` allows them: `media` needs to be a list of media queries
+        # and `sizes` a list of source sizes (without any CSS functions), and
+        # `type` one of `types` (`Config::SOURCE_TYPES` for `true`). By default
+        # (`nil`), they're sanitized like any other attributes.
+        sources: nil,
+
+        # Whether or not to keep `<audio>` and `<video>` elements, along with
+        # their `<source>`s and `<track>`s and the attributes they need. Their
+        # `src` and `poster` URLs have to be `https:` or relative (unless
+        # `protocols` says otherwise), `autoplay` is removed unless they're
+        # `muted`, and `<track>`s are removed unless their `kind` is one of
+        # `track_kinds` (`Config::TRACK_KINDS` for `true`). By default (`nil`),
+        # they're sanitized like any other elements.
+        media: nil,
+
+        # How to restrict the `<iframe>` elements which are kept. Their `sandbox`
+        # is set to the `sandbox` tokens (none, the strictest, by default), or,
+        # with `sandbox_mode: :merge`, to the ones they already have which are
+        # among them. `referrerpolicy` has to be one of the `referrerpolicy`
+        # values, or it's set to the first one; and `allow` keeps only the
+        # listed features. By default (`nil`), they're sanitized like any other
+        # elements.
+        iframes: nil,
+
+        # What to put in place of the `<iframe>`, `<embed>` and `<object>`
+        # elements which are removed: `true` for a link to what they embedded
+        # (when it's an `http:` or `https:` URL), whose `text` defaults to
+        # `Config::EMBED_FALLBACK_TEXT`, or a `template` of HTML in which
+        # `%{link}`, `%{url}` and `%{text}` are replaced. By default (`nil`),
+        # nothing is.
+        embed_fallback: nil,
+
+        # Whether or not to keep form controls looking like they do, but unable
+        # to submit anything: `<form>` elements are unwrapped, `input`, `button`,
+        # `select` and `textarea` are disabled, and `action`, `formaction`,
+        # `method` and `formmethod` are removed.
+        neutralize_forms: false,
+
+        # Whether or not links can only point somewhere in the same document,
+        # like a table of contents: an `href` on an `<a>` which isn't a
+        # fragment (`#heading-id`) is removed, whatever protocols are allowed.
+        fragment_links: false,
+
+        # Whether or not elements removed within a `<pre>`, `<textarea>` or
+        # `<code>` (which is kept) are unwrapped without the whitespace that
+        # `whitespace_elements` would put around them, so that nothing is added
+        # where whitespace is significant.
+        preserve_preformatted_whitespace: false,
+
+        # What to do with declarative shadow roots (`<template
+        # shadowrootmode="open">`). `:remove` drops them, contents and all;
+        # `:flatten` keeps their contents in the light DOM; and `:sanitize`
+        # keeps them (and their `shadowroot*` attributes) even if `template`
+        # isn't allowed, with their contents sanitized like everything else. By
+        # default (`nil`), they're treated like any other `<template>`.
+        shadow_roots: nil,
+
+        # What to do with the `integrity` and `crossorigin` attributes of the
+        # `<script>` and `<link>` elements which are allowed. `:strip` removes
+        # them; `:require` removes the elements without a well-formed
+        # `integrity` (which needs to be allowed), and gives the rest
+        # `crossorigin="anonymous"` if they don't say otherwise. By default
+        # (`nil`), they're sanitized like any other attributes.
+        integrity: nil,
+
+        # What to do with an element which has the same attribute more than
+        # once, like `<a href="/safe" href="javascript:...">`, where browsers
+        # only see the first. `:drop` (the default) drops the rest before
+        # anything's sanitized, and `:remove` removes the element, since such
+        # tricks are mostly used to get past filters.
+        duplicate_attributes: :drop,
+
+        # Whether or not to turn AMP's media elements into the HTML elements
+        # they stand in for: `<amp-img>` and `<amp-anim>` become `<img>`,
+        # `<amp-video>`, `<amp-audio>` and `<amp-iframe>` become `<video>`,
+        # `<audio>` and `<iframe>`. This happens before anything's sanitized, so
+        # the renamed elements (and their attributes) still need to be allowed.
+        normalize_amp: false,
+
+        # What to do with bidi overrides (like U+202E), zero-width spaces and
+        # other invisible characters in text and attribute values, which can
+        # make content read differently than it's stored. `:strip` removes them,
+        # and `:escape` replaces them with a visible stand-in, like `[U+202E]`.
+        # By default (`nil`), they're kept.
+        unicode_filter: nil,
+
+        # How many attributes an element may have, as a defense against inputs
+        # with thousands of them, which are slow to sanitize. Either an Integer,
+        # or a Hash like `{ count: 100, overflow: :drop }`, where `:drop` keeps
+        # the first `count` attributes and `:remove` (the default) removes the
+        # element, along with everything in it. By default (`nil`), there's no
+        # limit.
+        max_attributes: nil,
+
+        # How long (in bytes, once unescaped) an attribute's value may be, since
+        # megabyte-long `title`s or `data-*` values bloat storage, and slow down
+        # everything that reads them. Either an Integer, or a Hash like
+        # `{ bytes: 4096, overflow: :truncate }`, where `:drop` (the default)
+        # removes the attribute, and `:truncate` cuts its value short. By
+        # default (`nil`), there's no limit.
+        max_attribute_value_bytes: nil,
+
+        # How many classes a `class` attribute may have, and how long (in bytes)
+        # each one may be, so that thousands of them don't take ages to check
+        # against the allowed classes. Either an Integer (the number of
+        # classes), or a Hash like `{ tokens: 50, token_bytes: 64, overflow:
+        # :remove }`, where `:drop` (the default) drops the classes which are
+        # too long, along with any after the first `tokens`, and `:remove`
+        # removes the whole attribute. By default (`nil`), there's no limit.
+        max_classes: nil,
+
+        # Subtrees which trusted code (like a server-rendered widget) has put
+        # in otherwise untrusted HTML, and which are left just as they are: a
+        # Hash like `{ attribute: "data-selma-raw", token: "..." }` leaves any
+        # element whose `attribute` is `token` unsanitized, along with
+        # everything in it, and removes the attribute. The token should be a
+        # secret, since the untrusted HTML could have the attribute, too. By
+        # default (`nil`), everything is sanitized.
+        passthrough: nil,
+
+        # What to do with NUL bytes and the other C0 control characters (except
+        # for tabs, newlines, form feeds and carriage returns) in the HTML, which
+        # can trip up whatever reads it next. `:strip` removes them before it's
+        # parsed, and `:reject` raises a `Selma::RewritingError` instead. By
+        # default (`nil`), they're left to the parser.
+        control_characters: nil,
+
         # An Array of element names whose contents will be removed. The contents
         # of all other filtered elements will be left behind.
         remove_contents: [
@@ -46,6 +257,12 @@ module Selma
           "xmp",
         ],
 
+        # Raw text elements (`noembed`, `noframes`, `plaintext`, `script`,
+        # `style` and `xmp`) whose contents, when they're removed, are kept as
+        # escaped text rather than dropped, whatever `remove_contents` says. By
+        # default, their contents are dropped.
+        escape_contents: [],
+
         # Elements which, when removed, should have their contents surrounded by
         # whitespace.
         whitespace_elements: [
@@ -76,6 +293,15 @@ module Selma
           "section",
           "ul",
         ],
+
+        # Custom elements which, like `<br>`, have no contents or end tag, so
+        # that only the whitespace after them is kept when they're removed (if
+        # they're `whitespace_elements`).
+        void_elements: [],
+
+        # Custom elements whose contents are text rather than markup, like
+        # `<script>`'s, and so are removed along with them.
+        raw_text_elements: [],
       )
     end
   end
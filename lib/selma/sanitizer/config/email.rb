@@ -0,0 +1,58 @@
+# frozen_string_literal: true
+
+module Selma
+  class Sanitizer
+    module Config
+      # For displaying untrusted email: table layouts and the presentational
+      # attributes email clients still rely on are kept, as are inline styles
+      # (with only the declarations in `CSS_PROPERTIES`, and nothing which
+      # loads or runs anything), but forms can't be submitted, tracking pixels
+      # are removed, and event handlers are denied outright.
+      #
+      # `cid:` images (which point at attachments) are allowed, and can be
+      # rewritten with `Selma::Handlers::ContentIds`. Remote images are kept;
+      # merge in `strip_remote_content: true` to only show attachments until
+      # the reader asks for the rest.
+      EMAIL = freeze_config(
+        elements: RELAXED[:elements].to_a - ["style", "title"] + [
+          "button",
+          "center",
+          "font",
+          "form",
+          "input",
+          "label",
+          "option",
+          "select",
+          "textarea",
+        ],
+
+        allow_doctype: true,
+
+        css: { properties: CSS_PROPERTIES },
+
+        attributes: RELAXED[:attributes].to_h.except("style").merge(
+          :all => RELAXED[:attributes][:all].to_a - ["tabindex"],
+          "body" => ["bgcolor"],
+          "font" => ["color", "face", "size"],
+          "input" => ["checked", "name", "type", "value"],
+          "option" => ["selected", "value"],
+          "td" => RELAXED[:attributes]["td"].to_a + ["bgcolor", "height", "nowrap"],
+          "th" => RELAXED[:attributes]["th"].to_a + ["bgcolor", "height", "nowrap"],
+          "tr" => ["align", "bgcolor", "valign"],
+        ),
+
+        protocols: RELAXED[:protocols].to_h.merge(
+          "a" => { "href" => ["http", "https", "mailto"] },
+          "img" => { "src" => ["http", "https", "cid"] },
+        ),
+
+        neutralize_forms: true,
+        paranoid: true,
+        strip_remote_content: false,
+        strip_tracking_pixels: true,
+        tables: { wrap_rows: true, max_span: 100 },
+        unicode_filter: :strip,
+      )
+    end
+  end
+end
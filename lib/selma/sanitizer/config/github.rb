@@ -0,0 +1,175 @@
+# frozen_string_literal: true
+
+module Selma
+  class Sanitizer
+    module Config
+      GITHUB = freeze_config(
+        elements: [
+          "a",
+          "abbr",
+          "b",
+          "bdo",
+          "blockquote",
+          "br",
+          "caption",
+          "cite",
+          "code",
+          "dd",
+          "del",
+          "details",
+          "dfn",
+          "div",
+          "dl",
+          "dt",
+          "em",
+          "figcaption",
+          "figure",
+          "h1",
+          "h2",
+          "h3",
+          "h4",
+          "h5",
+          "h6",
+          "hr",
+          "i",
+          "img",
+          "ins",
+          "kbd",
+          "li",
+          "mark",
+          "ol",
+          "p",
+          "pre",
+          "q",
+          "rp",
+          "rt",
+          "ruby",
+          "s",
+          "samp",
+          "small",
+          "span",
+          "strike",
+          "strong",
+          "sub",
+          "summary",
+          "sup",
+          "table",
+          "tbody",
+          "td",
+          "tfoot",
+          "th",
+          "thead",
+          "time",
+          "tr",
+          "tt",
+          "ul",
+          "var",
+          "wbr",
+        ],
+
+        allow_doctype: false,
+
+        attributes: {
+          all: [
+            "abbr",
+            "accept",
+            "accept-charset",
+            "accesskey",
+            "align",
+            "alt",
+            "aria-describedby",
+            "aria-hidden",
+            "aria-label",
+            "aria-labelledby",
+            "axis",
+            "border",
+            "cellpadding",
+            "cellspacing",
+            "char",
+            "charoff",
+            "charset",
+            "checked",
+            "clear",
+            "color",
+            "cols",
+            "colspan",
+            "compact",
+            "coords",
+            "datetime",
+            "dir",
+            "disabled",
+            "enctype",
+            "for",
+            "frame",
+            "headers",
+            "height",
+            "hreflang",
+            "hspace",
+            "ismap",
+            "itemprop",
+            "label",
+            "lang",
+            "maxlength",
+            "media",
+            "multiple",
+            "name",
+            "nohref",
+            "noshade",
+            "nowrap",
+            "open",
+            "progress",
+            "prompt",
+            "readonly",
+            "rel",
+            "rev",
+            "role",
+            "rows",
+            "rowspan",
+            "rules",
+            "scope",
+            "selected",
+            "shape",
+            "size",
+            "span",
+            "start",
+            "summary",
+            "tabindex",
+            "target",
+            "title",
+            "type",
+            "usemap",
+            "valign",
+            "value",
+            "vspace",
+            "width",
+          ],
+          "a" => ["href"],
+          "blockquote" => ["cite"],
+          "del" => ["cite"],
+          "div" => ["itemscope", "itemtype"],
+          "img" => ["src", "longdesc"],
+          "ins" => ["cite"],
+          "q" => ["cite"],
+        },
+
+        protocols: {
+          "a" => { "href" => ["http", "https", "mailto", :relative] },
+          "blockquote" => { "cite" => ["http", "https", :relative] },
+          "del" => { "cite" => ["http", "https", :relative] },
+          "img" => {
+            "longdesc" => ["http", "https", :relative],
+            "src" => ["http", "https", :relative],
+          },
+          "ins" => { "cite" => ["http", "https", :relative] },
+          "q" => { "cite" => ["http", "https", :relative] },
+        },
+
+        remove_contents: ["iframe", "math", "noembed", "noframes", "noscript", "plaintext", "script", "style", "svg", "xmp"],
+
+        whitespace_elements: DEFAULT[:whitespace_elements],
+
+        name_prefix: "user-content-",
+      )
+    end
+  end
+end
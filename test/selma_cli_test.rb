@@ -0,0 +1,98 @@
+# frozen_string_literal: true
+
+require "test_helper"
+require "selma/cli"
+require "json"
+require "stringio"
+require "tmpdir"
+
+class SelmaCLITest < Minitest::Test
+  def selma(*argv, input: "")
+    output = StringIO.new
+    error = StringIO.new
+    status = Selma::CLI.start(argv, input: StringIO.new(input), output: output, error: error)
+
+    [status, output.string, error.string]
+  end
+
+  def test_it_sanitizes_stdin_with_a_profile
+    status, output, = selma("--profile", "basic", input: %(<b onclick="x()">Hi</b><script>alert(1)</script>))
+
+    assert_equal(0, status)
+    assert_equal("<b>Hi</b>", output)
+  end
+
+  def test_it_sanitizes_with_the_default_sanitizer
+    _, output, = selma(input: "<b>Hi</b>")
+
+    assert_equal("Hi", output)
+  end
+
+  def test_it_reads_a_yaml_config
+    Dir.mktmpdir do |dir|
+      config = File.join(dir, "sanitizer.yml")
+      File.write(config, <<~YAML)
+        elements: [a]
+        attributes:
+          a: [href]
+        protocols:
+          a:
+            href: [https, ":relative"]
+      YAML
+
+      _, output, = selma("--config", config, input: %(<a href="/x">x</a><a href="javascript:y()">y</a>))
+
+      assert_equal(%(<a href="/x">x</a><a>y</a>), output)
+    end
+  end
+
+  def test_it_reads_a_json_config
+    Dir.mktmpdir do |dir|
+      config = File.join(dir, "sanitizer.json")
+      File.write(config, JSON.generate({ elements: ["i"] }))
+
+      _, output, = selma("-c", config, input: "<i>x</i><b>y</b>")
+
+      assert_equal("<i>x</i>y", output)
+    end
+  end
+
+  def test_it_sanitizes_files_in_place
+    Dir.mktmpdir do |dir|
+      file = File.join(dir, "post.html")
+      File.write(file, "<b>Hi</b><blink>there</blink>")
+
+      status, output, = selma("--profile", "basic", "--in-place", file)
+
+      assert_equal(0, status)
+      assert_empty(output)
+      assert_equal("<b>Hi</b>there", File.read(file))
+    end
+  end
+
+  def test_it_explains_removals
+    _, _, error = selma("--profile", "basic", "--explain", input: "<blink>Hi</blink>")
+
+    assert_equal("-: removed <blink> (not_allowed)\n", error)
+  end
+
+  def test_it_reports_bad_configs
+    Dir.mktmpdir do |dir|
+      config = File.join(dir, "sanitizer.yml")
+      File.write(config, "elements: nope\n")
+
+      status, output, error = selma("--config", config, input: "<b>Hi</b>")
+
+      assert_equal(2, status)
+      assert_empty(output)
+      assert_match(/\Aselma: .*elements/, error)
+    end
+  end
+
+  def test_it_rejects_unknown_profiles
+    status, _, error = selma("--profile", "lax")
+
+    assert_equal(2, status)
+    assert_match(/\Aselma: invalid argument/, error)
+  end
+end
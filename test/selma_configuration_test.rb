@@ -0,0 +1,68 @@
+# frozen_string_literal: true
+
+require "test_helper"
+
+class SelmaConfigurationTest < Minitest::Test
+  class Shout
+    SELECTOR = Selma::Selector.new(match_text_within: "p")
+
+    def selector
+      SELECTOR
+    end
+
+    def handle_text_chunk(text)
+      text.replace(text.to_s.upcase, as: :text)
+    end
+  end
+
+  def teardown
+    Selma.configure do |config|
+      config.sanitizer = Selma::Sanitizer.new
+      config.handlers = []
+      config.options = {}
+    end
+  end
+
+  def test_it_rewrites_with_the_default_sanitizer
+    assert_equal("hi", Selma.rewrite(%(<b onclick="x()">hi</b><script>alert(1)</script>)))
+  end
+
+  def test_it_rewrites_with_the_configured_rewriter
+    Selma.configure do |config|
+      config.sanitizer = Selma::Sanitizer.new({ elements: ["p"] })
+      config.handlers = [Shout.new]
+    end
+
+    assert_equal("<p>HI</p>there", Selma.rewrite("<p>hi</p><b>there</b>"))
+  end
+
+  def test_it_rebuilds_the_rewriter_once_reconfigured
+    Selma.configure { |config| config.sanitizer = Selma::Sanitizer.new({ elements: ["p"] }) }
+
+    assert_equal("<p>hi</p>", Selma.rewrite("<p>hi</p>"))
+
+    Selma.configure { |config| config.handlers = [Shout.new] }
+
+    assert_equal("<p>HI</p>", Selma.rewrite("<p>hi</p>"))
+  end
+
+  def test_it_passes_options_through
+    Selma.configure do |config|
+      config.sanitizer = Selma::Sanitizer.new({ elements: ["p"] })
+      config.options = { minify: true }
+    end
+
+    assert_equal("<p>a b</p>", Selma.rewrite("<p>a    b</p>"))
+  end
+
+  def test_it_rewrites_from_many_threads
+    Selma.configure do |config|
+      config.sanitizer = Selma::Sanitizer.new({ elements: ["p"] })
+      config.handlers = [Shout.new]
+    end
+
+    results = Array.new(8) { |i| Thread.new { Array.new(20) { Selma.rewrite("<p>#{i}a</p>") }.uniq } }.map(&:value)
+
+    assert_equal(Array.new(8) { |i| ["<p>#{i}A</p>"] }, results)
+  end
+end
@@ -0,0 +1,42 @@
+# frozen_string_literal: true
+
+require "test_helper"
+require "selma/differential"
+
+class SelmaDifferentialTest < Minitest::Test
+  def setup
+    skip("the sanitize gem isn't installed") unless Selma::Differential.available?
+  end
+
+  def test_matching_output_is_the_same
+    config = { elements: ["a", "b"], attributes: { "a" => ["href"] }, protocols: { "a" => { "href" => ["https"] } } }
+    result = Selma::Differential.compare(%(<b onclick="x()">Hi</b> <a href="javascript:y()">there</a><br/>), config)
+
+    assert_predicate(result, :same?)
+    assert_equal(%(<b>Hi</b> <a>there</a>), result.selma)
+    assert_empty(result.untranslated_keys)
+  end
+
+  def test_it_reports_only_differences
+    config = { elements: ["span"], attributes: { "span" => ["id"] }, name_prefix: "user-content-" }
+    differences = Selma::Differential.differences([%(<span>a</span>), %(<span id="x">b</span>)], config)
+
+    assert_equal(1, differences.length)
+    assert_equal(%(<span id="user-content-x">b</span>), differences.first.selma)
+    assert_equal(%(<span id="x">b</span>), differences.first.sanitize)
+    assert_equal([:name_prefix], differences.first.untranslated_keys)
+  end
+
+  def test_it_translates_configs
+    config, untranslated = Selma::Differential.sanitize_config({
+      elements: ["p"],
+      whitespace_elements: ["p"],
+      protocols: { "a" => { "href" => ["https", :fragment, :relative] } },
+      allow_comments: false,
+    })
+
+    assert_equal({ "p" => { before: " ", after: " " } }, config[:whitespace_elements])
+    assert_equal({ "a" => { "href" => ["https", :relative] } }, config[:protocols])
+    assert_equal([:protocols], untranslated)
+  end
+end
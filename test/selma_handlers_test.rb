@@ -0,0 +1,172 @@
+# frozen_string_literal: true
+
+require "test_helper"
+
+module Selma
+  class HandlersTest < Minitest::Test
+    describe "ResponsiveImages" do
+      def setup
+        @sanitizer = Selma::Sanitizer.new({
+          elements: ["img", "p"],
+          attributes: { "img" => ["src", "alt"] },
+          protocols: { "img" => { "src" => ["https"] } },
+        })
+      end
+
+      def test_it_sets_what_the_callback_returns
+        images = Selma::Handlers::ResponsiveImages.new do |src|
+          { src: "https://cdn.example/960/#{src}", "srcset" => "https://cdn.example/480/#{src} 480w", sizes: "100vw" }
+        end
+        html = %(<p><img src="a.png" alt="a"></p>)
+
+        assert_equal(
+          %(<p><img src="https://cdn.example/960/a.png" alt="a" srcset="https://cdn.example/480/a.png 480w" sizes="100vw"></p>),
+          Selma::Rewriter.new(sanitizer: nil, handlers: [images]).rewrite(html),
+        )
+      end
+
+      def test_it_calls_the_callback_once_per_image
+        srcs = []
+        images = Selma::Handlers::ResponsiveImages.new(->(src) { srcs << src && nil })
+        html = %(<img src="https://example.com/a.png"><img alt="no src"><img src="https://example.com/b.png">)
+
+        Selma::Rewriter.new(sanitizer: @sanitizer, handlers: [images]).rewrite(html)
+
+        assert_equal(["https://example.com/a.png", "https://example.com/b.png"], srcs)
+      end
+
+      def test_it_ignores_other_attributes
+        images = Selma::Handlers::ResponsiveImages.new { |_src| { onerror: "alert(1)", sizes: "50vw" } }
+
+        assert_equal(
+          %(<img src="https://example.com/a.png" sizes="50vw">),
+          Selma::Rewriter.new(sanitizer: @sanitizer, handlers: [images]).rewrite(%(<img src="https://example.com/a.png">)),
+        )
+      end
+
+      def test_it_needs_a_callback
+        assert_raises(ArgumentError) { Selma::Handlers::ResponsiveImages.new }
+      end
+    end
+
+    describe "ContentIds" do
+      def setup
+        @sanitizer = Selma::Sanitizer.new(Selma::Sanitizer::Config::EMAIL)
+      end
+
+      def test_it_rewrites_cid_images
+        attachments = Selma::Handlers::ContentIds.new { |cid| "https://mail.example/attachments/#{cid}" }
+        html = %(<p><img src="cid:logo@example.com" alt="logo"><img src="https://example.com/a.png"></p>)
+
+        assert_equal(
+          %(<p><img src="https://mail.example/attachments/logo@example.com" alt="logo"><img src="https://example.com/a.png"></p>),
+          Selma::Rewriter.new(sanitizer: @sanitizer, handlers: [attachments]).rewrite(html),
+        )
+      end
+
+      def test_it_drops_images_it_cannot_find
+        attachments = Selma::Handlers::ContentIds.new(->(_cid) {})
+
+        assert_equal(
+          "<p>hi</p>",
+          Selma::Rewriter.new(sanitizer: @sanitizer, handlers: [attachments]).rewrite(%(<p>hi<img src="cid:missing"></p>)),
+        )
+      end
+    end
+
+    describe "Embeds" do
+      def setup
+        @sanitizer = Selma::Sanitizer.new({
+          elements: ["div", "p", "a"],
+          attributes: { "div" => ["data-oembed-url"], "a" => ["href"] },
+          protocols: { "a" => { "href" => ["https"] } },
+        })
+        @embed_sanitizer = Selma::Sanitizer.new({
+          elements: ["iframe"],
+          attributes: { "iframe" => ["src", "width"] },
+          protocols: { "iframe" => { "src" => ["https"] } },
+          remove_contents: ["script"],
+        })
+      end
+
+      def test_it_replaces_placeholders_with_sanitized_embeds
+        embeds = Selma::Handlers::Embeds.new(sanitizer: @embed_sanitizer) do |attributes|
+          %(<iframe src="#{attributes["data-oembed-url"]}/embed" width="560" onload="alert(1)"></iframe><script>alert(2)</script>)
+        end
+        html = %(<p>Watch:</p><div data-oembed-url="https://video.example/v/1"><a href="https://video.example/v/1">a video</a></div>)
+
+        assert_equal(
+          %(<p>Watch:</p><iframe src="https://video.example/v/1/embed" width="560"></iframe>),
+          Selma::Rewriter.new(sanitizer: @sanitizer, handlers: [embeds]).rewrite(html),
+        )
+      end
+
+      def test_it_leaves_placeholders_without_an_embed
+        embeds = Selma::Handlers::Embeds.new(->(_attributes) {}, sanitizer: @embed_sanitizer)
+        html = %(<div data-oembed-url="https://video.example/v/1"><a href="https://video.example/v/1">a video</a></div>)
+
+        assert_equal(html, Selma::Rewriter.new(sanitizer: @sanitizer, handlers: [embeds]).rewrite(html))
+      end
+
+      def test_it_matches_the_given_selector
+        embeds = Selma::Handlers::Embeds.new(selector: "p.embed", sanitizer: @embed_sanitizer) { |_attributes| "embedded" }
+
+        assert_equal(
+          %(embedded<p>not embedded</p>),
+          Selma::Rewriter.new(sanitizer: nil, handlers: [embeds]).rewrite(%(<p class="embed">x</p><p>not embedded</p>)),
+        )
+      end
+    end
+
+    describe "VideoEmbeds" do
+      def setup
+        @sanitizer = Selma::Sanitizer.new({
+          elements: ["p", "a", "iframe"],
+          attributes: { "a" => ["href"], "iframe" => ["src", "width", "height"] },
+          protocols: { "a" => { "href" => ["https"] }, "iframe" => { "src" => ["https"] } },
+        })
+        @embeds = Selma::Handlers::VideoEmbeds.new
+      end
+
+      def test_it_normalizes_youtube_embeds
+        html = %(<iframe src="https://www.youtube.com/embed/dQw4w9WgXcQ?start=30&autoplay=1" width="560"></iframe>)
+
+        assert_equal(
+          %(<iframe src="https://www.youtube-nocookie.com/embed/dQw4w9WgXcQ?start=30" width="560"></iframe>),
+          Selma::Rewriter.new(sanitizer: @sanitizer, handlers: [@embeds]).rewrite(html),
+        )
+      end
+
+      def test_it_normalizes_vimeo_embeds
+        html = %(<iframe src="https://player.vimeo.com/video/76979871?api=1"></iframe>)
+
+        assert_equal(
+          %(<iframe src="https://player.vimeo.com/video/76979871?dnt=1"></iframe>),
+          Selma::Rewriter.new(sanitizer: @sanitizer, handlers: [@embeds]).rewrite(html),
+        )
+      end
+
+      def test_it_removes_other_iframes
+        html = %(<p>a</p><iframe src="https://evil.example/embed/dQw4w9WgXcQ"></iframe><iframe src="https://www.youtube.com/embed/nope"></iframe>)
+
+        assert_equal("<p>a</p>", Selma::Rewriter.new(sanitizer: @sanitizer, handlers: [@embeds]).rewrite(html))
+      end
+
+      def test_it_embeds_links_to_videos
+        html = %(<p><a href="https://youtu.be/dQw4w9WgXcQ?t=1m30s">https://youtu.be/dQw4w9WgXcQ</a> <a href="https://example.com">a link</a></p>)
+
+        assert_equal(
+          %(<p><iframe src="https://www.youtube-nocookie.com/embed/dQw4w9WgXcQ?start=90" width="560" height="315" allowfullscreen></iframe> <a href="https://example.com">a link</a></p>),
+          Selma::Rewriter.new(sanitizer: @sanitizer, handlers: [@embeds]).rewrite(html),
+        )
+      end
+
+      def test_it_leaves_links_unless_asked_to_embed_them
+        embeds = Selma::Handlers::VideoEmbeds.new(links: false)
+        html = %(<p><a href="https://vimeo.com/76979871">a video</a></p>)
+
+        assert_equal(html, Selma::Rewriter.new(sanitizer: @sanitizer, handlers: [embeds]).rewrite(html))
+      end
+    end
+  end
+end
@@ -0,0 +1,55 @@
+# frozen_string_literal: true
+
+require "test_helper"
+
+class SelmaHTMLMarkdownTest < Minitest::Test
+  def to_markdown(html, sanitizer: nil)
+    Selma::HTML.to_markdown(html, sanitizer: sanitizer)
+  end
+
+  def test_it_converts_headings_and_paragraphs
+    html = %(<h1>Title</h1><p>Some <em>very</em> <strong>bold </strong>text &amp; <del>not</del> more.</p>)
+
+    assert_equal("# Title\n\nSome *very* **bold** text & ~~not~~ more.", to_markdown(html))
+  end
+
+  def test_it_converts_links_and_images
+    html = %(<p><a href="https://example.com" title="Example">a link</a> and <img src="cat.png" alt="A cat"></p>)
+
+    assert_equal(%([a link](https://example.com "Example") and ![A cat](cat.png)), to_markdown(html))
+  end
+
+  def test_it_converts_lists
+    html = %(<ul><li>one</li><li>two<ul><li>nested</li></ul></li></ul><ol start="3"><li>three</li><li>four</li></ol>)
+
+    assert_equal("- one\n- two\n  - nested\n\n3. three\n4. four", to_markdown(html))
+  end
+
+  def test_it_converts_blockquotes_and_line_breaks
+    html = %(<blockquote><p>quoted</p><p>one<br>two</p></blockquote>)
+
+    assert_equal("> quoted\n>\n> one\\\n> two", to_markdown(html))
+  end
+
+  def test_it_converts_code
+    html = %(<p>Use <code>a`b</code></p><pre><code class="language-ruby">puts 1\n```\n</code></pre>)
+
+    assert_equal("Use ``a`b``\n\n````ruby\nputs 1\n```\n````", to_markdown(html))
+  end
+
+  def test_it_escapes_what_would_be_syntax
+    assert_equal("\\# not \\*a\\* \\[heading\\]\n\n1\\. not a list", to_markdown(%(<p># not *a* [heading]</p><p>1. not a list</p>)))
+  end
+
+  def test_it_reduces_other_elements_to_their_content
+    html = %(<div><span>one</span></div><script>alert(1)</script><table><tr><td>a</td><td>b</td></tr></table>)
+
+    assert_equal("one\n\na b", to_markdown(html))
+  end
+
+  def test_it_sanitizes_first
+    html = %(<p>Hi <a href="javascript:alert(1)">there</a><iframe src="x"></iframe></p>)
+
+    assert_equal("Hi there", Selma::HTML.to_markdown(html))
+  end
+end
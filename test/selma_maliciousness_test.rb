@@ -44,7 +44,7 @@ class SelmaMaliciousnessTest < Minitest::Test
 
   def test_that_it_does_hate_missing_match_text_within
     frag = "<strong>Wow!</strong>"
-    assert_raises(RuntimeError) do
+    assert_raises(NoMethodError) do
       Selma::Rewriter.new(sanitizer: nil, handlers: [NoHandleText.new]).rewrite(frag)
     end
   end
@@ -150,7 +150,7 @@ class SelmaMaliciousnessTest < Minitest::Test
 
   def test_that_it_raises_on_handle_text_returning_non_string
     frag = "<time>Wow!</time>"
-    assert_raises(RuntimeError) do
+    assert_raises(NoMethodError) do
       Selma::Rewriter.new(sanitizer: nil, handlers: [GarbageTextOptions.new]).rewrite(frag)
     end
   end
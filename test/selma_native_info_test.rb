@@ -0,0 +1,32 @@
+# frozen_string_literal: true
+
+require "test_helper"
+
+class SelmaNativeInfoTest < Minitest::Test
+  def test_it_describes_the_extension
+    assert_match(/\A\d+\.\d+\.\d+\z/, Selma::NATIVE_INFO[:lol_html_version])
+    assert_match(/\A\d+\.\d+\.\d+\z/, Selma::NATIVE_INFO[:selma_core_version])
+    assert_equal(["UTF-8"], Selma::NATIVE_INFO[:encodings])
+    refute(Selma::NATIVE_INFO[:memory_limits][:configurable])
+    assert_kind_of(Integer, Selma::NATIVE_INFO[:memory_limits][:max_allowed_memory_usage])
+  end
+
+  def test_it_is_deeply_frozen
+    verify_deeply_frozen(Selma::NATIVE_INFO)
+  end
+
+  def test_the_selectors_it_lists_are_supported
+    selectors = Selma::NATIVE_INFO[:selectors]
+
+    selectors[:pseudo_classes].each do |pseudo_class|
+      argument = { "not" => "(.x)", "nth-child" => "(2)", "nth-of-type" => "(2)" }.fetch(pseudo_class, "")
+      Selma::Selector.new(match_element: "li:#{pseudo_class}#{argument}")
+    end
+
+    selectors[:attribute_operators].each do |operator|
+      Selma::Selector.new(match_element: %(a[href#{operator}"x"]))
+    end
+
+    assert_raises(Selma::SelectorError) { Selma::Selector.new(match_element: "h1 + p") }
+  end
+end
@@ -0,0 +1,42 @@
+# frozen_string_literal: true
+
+require "test_helper"
+require "nokogiri"
+
+class SelmaNokogiriTest < Minitest::Test
+  def setup
+    @sanitizer = Selma::Sanitizer.new({ elements: ["p", "b"] })
+  end
+
+  def test_it_sanitizes_a_fragment_into_html
+    fragment = Nokogiri::HTML5.fragment(%(<p onclick="x()">Hi <b>there</b></p><script>alert(1)</script>))
+
+    assert_equal("<p>Hi <b>there</b></p>", Selma.sanitize_node(fragment, sanitizer: @sanitizer))
+  end
+
+  def test_it_sanitizes_a_node
+    fragment = Nokogiri::HTML5.fragment(%(<div><p>Hi <i>there</i></p></div><p>not this</p>))
+
+    assert_equal("<p>Hi there</p>", Selma.sanitize_node(fragment.at_css("p"), sanitizer: @sanitizer))
+  end
+
+  def test_it_reparses_a_fragment
+    fragment = Nokogiri::HTML5.fragment(%(<p>Hi <i>there</i></p>))
+    sanitized = Selma.sanitize_node(fragment, sanitizer: @sanitizer, as: :node)
+
+    assert_kind_of(Nokogiri::HTML5::DocumentFragment, sanitized)
+    assert_equal("<p>Hi there</p>", sanitized.to_html)
+  end
+
+  def test_it_reparses_a_document
+    document = Nokogiri::HTML5(%(<html><body><p>Hi <i>there</i></p></body></html>))
+    sanitized = Selma.sanitize_node(document, sanitizer: @sanitizer, as: :node)
+
+    assert_kind_of(Nokogiri::HTML5::Document, sanitized)
+    assert_equal("<p>Hi there</p>", sanitized.at_css("body").inner_html)
+  end
+
+  def test_it_rejects_other_results
+    assert_raises(ArgumentError) { Selma.sanitize_node(Nokogiri::HTML5.fragment("<p>x</p>"), as: :text) }
+  end
+end
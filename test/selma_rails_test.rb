@@ -0,0 +1,65 @@
+# frozen_string_literal: true
+
+require "test_helper"
+require "selma/rails"
+
+class SelmaRailsTest < Minitest::Test
+  include Selma::Rails::Helper
+
+  def test_safe_list_sanitizer_keeps_the_allowed_tags_and_attributes
+    html = %(<p class="a" onclick="x()">Hi <a href="https://example.com" target="_blank">there</a><script>alert(1)</script></p>)
+
+    assert_equal(
+      %(<p class="a">Hi <a href="https://example.com">there</a></p>),
+      Selma::Rails::SafeListSanitizer.new.sanitize(html),
+    )
+  end
+
+  def test_safe_list_sanitizer_takes_tags_and_attributes
+    html = %(<span title="t"><b class="b">bold</b> <i>italic</i></span>)
+
+    assert_equal(
+      %(<b class="b">bold</b> italic),
+      Selma::Rails::SafeListSanitizer.new.sanitize(html, tags: Set.new(["b"]), attributes: ["class"]),
+    )
+  end
+
+  def test_safe_list_sanitizer_checks_protocols
+    assert_equal("<a>x</a>", Selma::Rails::SafeListSanitizer.new.sanitize(%(<a href="javascript:alert(1)">x</a>)))
+  end
+
+  def test_safe_list_sanitizer_handles_nil
+    assert_nil(Selma::Rails::SafeListSanitizer.new.sanitize(nil))
+  end
+
+  def test_safe_list_sanitizer_has_allowlists_for_action_text
+    assert_includes(Selma::Rails::SafeListSanitizer.allowed_tags, "p")
+    assert_includes(Selma::Rails::SafeListSanitizer.allowed_attributes, "href")
+  end
+
+  def test_safe_list_sanitizer_rejects_scrubbers
+    assert_raises(ArgumentError) { Selma::Rails::SafeListSanitizer.new.sanitize("<p>x</p>", scrubber: Object.new) }
+  end
+
+  def test_full_sanitizer_strips_every_tag
+    assert_equal("Hi there", Selma::Rails::FullSanitizer.new.sanitize(%(Hi <b>there</b><script>alert(1)</script>)))
+  end
+
+  def test_link_sanitizer_unwraps_links
+    assert_equal("<p>Hi there</p>", Selma::Rails::LinkSanitizer.new.sanitize(%(<p>Hi <a href="https://example.com">there</a></p>)))
+  end
+
+  def test_vendor_provides_the_sanitizers
+    assert_equal(Selma::Rails::SafeListSanitizer, Selma::Rails::Vendor.safe_list_sanitizer)
+    assert_equal(Selma::Rails::FullSanitizer, Selma::Rails::Vendor.full_sanitizer)
+    assert_equal(Selma::Rails::LinkSanitizer, Selma::Rails::Vendor.link_sanitizer)
+  end
+
+  def test_helper_sanitizes_with_the_relaxed_profile
+    assert_equal("<p>Hi</p>", sanitize_with_selma(%(<p onclick="x()">Hi</p>)))
+  end
+
+  def test_helper_takes_a_sanitizer_config
+    assert_equal("Hi", sanitize_with_selma("<b>Hi</b>", sanitizer: { elements: [] }))
+  end
+end
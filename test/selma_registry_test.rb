@@ -0,0 +1,68 @@
+# frozen_string_literal: true
+
+require "test_helper"
+
+class SelmaRegistryTest < Minitest::Test
+  # nothing but the registry holds on to it, or to its selector
+  class MarkPosts
+    def selector
+      Selma::Selector.new(match_element: "p")
+    end
+
+    def handle_element(element)
+      element["class"] = "post"
+    end
+  end
+
+  def teardown
+    Selma::Registry.names.each { |name| Selma::Registry.unregister(name) }
+  end
+
+  def test_it_rewrites_with_a_registered_policy
+    Selma::Registry.register(:comments, Selma::Sanitizer.restricted)
+    Selma::Registry.register(:posts, { elements: ["a", "b"] }, minify: true)
+    html = %(<b>a</b>   <a href="/x">i</a>)
+
+    assert_equal("<b>a</b>   i", Selma.rewrite(html, policy: :comments))
+    assert_equal("<b>a</b> <a>i</a>", Selma.rewrite(html, policy: "posts"))
+    assert_equal("a   i", Selma.rewrite(html))
+  end
+
+  def test_it_replaces_policies
+    Selma::Registry.register(:comments, { elements: ["b"] })
+
+    assert_equal("<b>a</b>", Selma.rewrite("<b>a</b>", policy: :comments))
+
+    Selma::Registry.register(:comments, { elements: [] })
+
+    assert_equal("a", Selma.rewrite("<b>a</b>", policy: :comments))
+  end
+
+  def test_it_raises_for_unknown_policies
+    Selma::Registry.register(:comments, Selma::Sanitizer.basic)
+    Selma::Registry.unregister(:comments)
+
+    refute(Selma::Registry.registered?(:comments))
+    assert_raises(ArgumentError) { Selma.rewrite("<b>a</b>", policy: :comments) }
+  end
+
+  def test_it_raises_for_invalid_configs_when_registering
+    assert_raises(Selma::ConfigError) { Selma::Registry.register(:comments, { elemnts: ["b"] }) }
+    refute(Selma::Registry.registered?(:comments))
+  end
+
+  def test_its_policies_outlive_a_gc
+    Selma::Registry.register(:posts, { elements: ["p"], attributes: { "p" => ["class"] } }, handlers: [MarkPosts.new])
+    GC.start(full_mark: true, immediate_sweep: true)
+
+    assert_equal(%(<p class="post">a</p>), Selma.rewrite("<p>a</p>", policy: :posts))
+  end
+
+  def test_it_rewrites_from_many_threads
+    Selma::Registry.register(:comments, { elements: ["b"] })
+
+    results = Array.new(8) { |i| Thread.new { Array.new(20) { Selma.rewrite("<b>#{i}</b><i>x</i>", policy: :comments) }.uniq } }.map(&:value)
+
+    assert_equal(Array.new(8) { |i| ["<b>#{i}</b>x"] }, results)
+  end
+end
@@ -0,0 +1,68 @@
+# frozen_string_literal: true
+
+require "test_helper"
+
+class SelmaRewriterBlocksTest < Minitest::Test
+  class Record
+    SELECTOR = Selma::Selector.new(match_element: "p")
+
+    def initialize(calls)
+      @calls = calls
+    end
+
+    def selector
+      SELECTOR
+    end
+
+    def handle_element(_element)
+      @calls << :handler
+    end
+  end
+
+  def test_it_registers_element_handlers
+    rewriter = Selma::Rewriter.new(sanitizer: nil) do
+      on("a[href]") { |element| element["rel"] = "nofollow" }
+    end
+
+    assert_equal(
+      %(<a href="https://example.com" rel="nofollow">a</a><a>b</a>),
+      rewriter.rewrite(%(<a href="https://example.com">a</a><a>b</a>)),
+    )
+  end
+
+  def test_it_registers_text_handlers
+    rewriter = Selma::Rewriter.new(sanitizer: nil) do
+      on_text("p") { |text| text.replace(text.to_s.sub("cat", "dog"), as: :text) }
+    end
+
+    assert_equal("<p>a dog</p><div>a cat</div>", rewriter.rewrite("<p>a cat</p><div>a cat</div>"))
+  end
+
+  def test_it_yields_the_builder_to_a_block_with_an_argument
+    count = 0
+    rewriter = Selma::Rewriter.new(sanitizer: nil) do |r|
+      r.on("p") { |_element| count += 1 }
+    end
+    rewriter.rewrite("<p>a</p><p>b</p>")
+
+    assert_equal(2, count)
+  end
+
+  def test_it_runs_block_handlers_after_the_given_ones
+    calls = []
+    rewriter = Selma::Rewriter.new(sanitizer: nil, handlers: [Record.new(calls)]) do
+      on("p") { |_element| calls << :block }
+    end
+    rewriter.rewrite("<p>hi</p>")
+
+    assert_equal([:handler, :block], calls)
+  end
+
+  def test_it_requires_a_block_for_each_handler
+    assert_raises(ArgumentError) { Selma::Rewriter.new(sanitizer: nil) { on("p") } }
+  end
+
+  def test_it_rejects_invalid_css
+    assert_raises(Selma::SelectorError) { Selma::Rewriter.new(sanitizer: nil) { on("p[") { |_element| } } }
+  end
+end
@@ -0,0 +1,65 @@
+# frozen_string_literal: true
+
+require "test_helper"
+
+class SelmaRewriterCanonicalTest < Minitest::Test
+  def canonical(html, canonical: true, **options)
+    Selma::Rewriter.new(sanitizer: nil, canonical: canonical, **options).rewrite(html)
+  end
+
+  def test_it_sorts_and_quotes_attributes
+    html = %(<DIV title='a "b"' class=x ID="y" hidden>Hi</DIV>)
+
+    assert_equal(%(<div class="x" hidden="" id="y" title="a &quot;b&quot;">Hi</div>), canonical(html))
+  end
+
+  def test_it_normalizes_character_references
+    html = %(<p title="&#60;&lt;">Tom &#x26; Jerry&#160;&gt; &#65;</p>)
+
+    assert_equal(%(<p title="&lt;&lt;">Tom &amp; Jerry&nbsp;&gt; A</p>), canonical(html))
+  end
+
+  def test_it_writes_the_same_html_the_same_way
+    one = %(<p class="a" id="b">Fish &amp; chips</p>)
+    two = %(<P id=b CLASS='a'>Fish &#38;\r\n  chips</P>)
+
+    assert_equal(canonical(one), canonical(two))
+  end
+
+  def test_it_collapses_whitespace_outside_of_pre
+    html = %(<div>\r\n  one\t two\r\n</div><pre>  keep\r\n  this</pre>)
+
+    assert_equal(%(<div> one two </div><pre>  keep\n  this</pre>), canonical(html))
+  end
+
+  def test_it_can_leave_whitespace_alone
+    html = %(<div>\r\n  one\t two</div>)
+
+    assert_equal(%(<div>\n  one\t two</div>), canonical(html, canonical: { whitespace: false }))
+  end
+
+  def test_it_normalizes_the_doctype_and_void_elements
+    html = %(<!doctype html><p>One<br/>two<img src=a.png ></p><svg viewBox="0 0 1 1"><circle r="4"/></svg>)
+
+    assert_equal(
+      %(<!DOCTYPE html><p>One<br>two<img src="a.png"></p><svg viewBox="0 0 1 1"><circle r="4" /></svg>),
+      canonical(html),
+    )
+  end
+
+  def test_it_can_be_xhtml
+    assert_equal(%(<input checked="checked" type="checkbox" />), canonical(%(<input type=checkbox checked>), xhtml: true))
+  end
+
+  def test_it_cannot_be_minified
+    error = assert_raises(ArgumentError) { Selma::Rewriter.new(canonical: true, minify: true) }
+
+    assert_equal("`minify:` and `canonical:` can't be used together", error.message)
+  end
+
+  def test_it_rejects_unknown_keys
+    error = assert_raises(ArgumentError) { Selma::Rewriter.new(canonical: { quotes: true }) }
+
+    assert_equal("canonical[:quotes]: unknown key", error.message)
+  end
+end
@@ -0,0 +1,80 @@
+# frozen_string_literal: true
+
+require "test_helper"
+
+class SelmaRewriterContextTest < Minitest::Test
+  class CountLinks
+    SELECTOR = Selma::Selector.new(match_element: "a[href]")
+
+    def selector
+      SELECTOR
+    end
+
+    def handle_element(element)
+      element.context[:link_count] += 1
+    end
+  end
+
+  class CountWords
+    SELECTOR = Selma::Selector.new(match_element: "p", match_text_within: "p")
+
+    def selector
+      SELECTOR
+    end
+
+    def handle_element(element)
+      element.context[:paragraphs] += 1
+    end
+
+    def handle_text_chunk(text)
+      text.context[:words] += text.to_s.split.size
+    end
+
+    def on_end_tag(end_tag)
+      end_tag.context[:last_closed] = end_tag.tag_name
+    end
+  end
+
+  def test_that_handlers_share_the_context
+    frag = %(<p>One <a href="/a">two</a></p><p>Three <a href="/b">four</a> five</p>)
+    rewriter = Selma::Rewriter.new(sanitizer: nil, handlers: [CountLinks.new, CountWords.new])
+    modified_doc, context = rewriter.rewrite_with_context(frag, Hash.new(0))
+
+    assert_equal(frag, modified_doc)
+    assert_equal({ link_count: 2, paragraphs: 2, words: 5, last_closed: "p" }, context)
+  end
+
+  def test_that_each_rewrite_gets_its_own_context
+    rewriter = Selma::Rewriter.new(sanitizer: nil, handlers: [CountLinks.new])
+
+    _, first = rewriter.rewrite_with_context(%(<a href="/a">A</a>), Hash.new(0))
+    _, second = rewriter.rewrite_with_context(%(<a href="/a">A</a><a href="/b">B</a>), Hash.new(0))
+
+    assert_equal({ link_count: 1 }, first)
+    assert_equal({ link_count: 2 }, second)
+  end
+
+  class RememberElement
+    SELECTOR = Selma::Selector.new(match_element: "strong")
+
+    attr_reader :element
+
+    def selector
+      SELECTOR
+    end
+
+    def handle_element(element)
+      element.context[:seen] = true
+      @element = element
+    end
+  end
+
+  def test_that_the_context_defaults_to_an_empty_hash
+    handler = RememberElement.new
+    modified_doc, context = Selma::Rewriter.new(sanitizer: nil, handlers: [handler]).rewrite_with_context("<strong>Wow!</strong>")
+
+    assert_equal("<strong>Wow!</strong>", modified_doc)
+    assert_equal({ seen: true }, context)
+    assert_raises(RuntimeError) { handler.element.context }
+  end
+end
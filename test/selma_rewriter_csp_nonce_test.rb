@@ -0,0 +1,48 @@
+# frozen_string_literal: true
+
+require "test_helper"
+
+class SelmaRewriterCspNonceTest < Minitest::Test
+  def setup
+    @sanitizer = Selma::Sanitizer.new({
+      elements: ["p", "script", "style"],
+      attributes: { "script" => ["src", "nonce"] },
+    })
+  end
+
+  def test_it_gives_scripts_and_styles_the_nonce
+    html = %(<style>p { color: red; }</style><p>Hi</p><script src="/app.js"></script>)
+    rewriter = Selma::Rewriter.new(sanitizer: @sanitizer, csp_nonce: "rAnd0m+/==")
+
+    assert_equal(
+      %(<style nonce="rAnd0m+/==">p { color: red; }</style><p>Hi</p><script src="/app.js" nonce="rAnd0m+/=="></script>),
+      rewriter.rewrite(html),
+    )
+  end
+
+  def test_it_replaces_any_other_nonce
+    html = %(<script src="/app.js" nonce="guessed"></script>)
+    rewriter = Selma::Rewriter.new(sanitizer: @sanitizer, csp_nonce: "rAnd0m")
+
+    assert_equal(%(<script src="/app.js" nonce="rAnd0m"></script>), rewriter.rewrite(html))
+  end
+
+  def test_it_works_without_a_sanitizer
+    rewriter = Selma::Rewriter.new(sanitizer: nil, csp_nonce: "rAnd0m")
+
+    assert_equal(%(<script nonce="rAnd0m">go()</script>), rewriter.rewrite("<script>go()</script>"))
+  end
+
+  def test_it_rejects_nonces_which_are_not_base64
+    error = assert_raises(ArgumentError) { Selma::Rewriter.new(csp_nonce: %(x" onload="alert(1))) }
+
+    assert_match(/`csp_nonce:` must be a base64 value/, error.message)
+    assert_raises(ArgumentError) { Selma::Rewriter.new(csp_nonce: "") }
+  end
+
+  def test_it_still_rejects_unknown_keywords
+    error = assert_raises(ArgumentError) { Selma::Rewriter.new(csp_nonces: "rAnd0m") }
+
+    assert_equal("unknown keyword: :csp_nonces", error.message)
+  end
+end
@@ -0,0 +1,278 @@
+# frozen_string_literal: true
+
+require "test_helper"
+
+class SelmaRewriterErrorsTest < Minitest::Test
+  def test_rewriting_errors_are_selma_errors
+    assert_operator(Selma::RewritingError, :<, Selma::Error)
+  end
+
+  def test_that_it_raises_a_rewriting_error_with_context
+    sanitizer = Selma::Sanitizer.new({
+      elements: ["a"],
+      add_attributes: { "a" => { "data=foo" => "bar" } },
+    })
+
+    error = assert_raises(Selma::RewritingError) do
+      Selma::Rewriter.new(sanitizer: sanitizer).rewrite("<a>Wow!</a>")
+    end
+
+    assert_equal("Failed to sanitize HTML: could not sanitize the attributes of <a>: `=` character is forbidden in the attribute name", error.message)
+  end
+
+  def test_that_it_removes_self_closing_foreign_elements
+    sanitizer = Selma::Sanitizer.new({ elements: ["svg"] })
+    frag = '<svg><circle cx="50" cy="50" r="40" /></svg>'
+
+    assert_equal("<svg></svg>", Selma::Rewriter.new(sanitizer: sanitizer).rewrite(frag))
+  end
+
+  def test_that_it_does_not_choke_on_non_ascii_urls
+    sanitizer = Selma::Sanitizer.new({
+      elements: ["a"],
+      attributes: { "a" => ["href"] },
+      protocols: { "a" => { "href" => ["https"] } },
+    })
+
+    assert_equal("<a>Wow!</a>", Selma::Rewriter.new(sanitizer: sanitizer).rewrite('<a href="éé">Wow!</a>'))
+  end
+
+  class VoidEndTag
+    SELECTOR = Selma::Selector.new(match_element: "br")
+
+    attr_reader :end_tags
+
+    def initialize
+      @end_tags = []
+    end
+
+    def selector
+      SELECTOR
+    end
+
+    def handle_element(element)
+      element["class"] = "breaky"
+    end
+
+    def on_end_tag(end_tag)
+      @end_tags << end_tag.tag_name
+    end
+  end
+
+  def test_that_it_skips_on_end_tag_for_void_elements
+    handler = VoidEndTag.new
+    frag = "<p>Wow!<br>Such</p>"
+    modified_doc = Selma::Rewriter.new(sanitizer: nil, handlers: [handler]).rewrite(frag)
+
+    assert_equal('<p>Wow!<br class="breaky">Such</p>', modified_doc)
+    assert_empty(handler.end_tags)
+  end
+
+  class HandlerError < StandardError; end
+
+  class RaisingHandler
+    SELECTOR = Selma::Selector.new(match_element: "strong", match_text_within: "em")
+
+    def selector
+      SELECTOR
+    end
+
+    def handle_element(element)
+      raise HandlerError, "bad element"
+    end
+
+    def handle_text_chunk(text)
+      raise HandlerError, "bad text"
+    end
+  end
+
+  def test_that_it_passes_handler_errors_through
+    rewriter = Selma::Rewriter.new(sanitizer: nil, handlers: [RaisingHandler.new])
+
+    error = assert_raises(HandlerError) { rewriter.rewrite("<strong>Wow!</strong>") }
+    assert_equal("bad element", error.message)
+    assert(error.backtrace.any? { |line| line.include?(__FILE__) && line.include?("handle_element") })
+
+    error = assert_raises(HandlerError) { rewriter.rewrite("<em>Wow!</em>") }
+    assert_equal("bad text", error.message)
+  end
+
+  class RaisingEndTag
+    SELECTOR = Selma::Selector.new(match_element: "strong")
+
+    def selector
+      SELECTOR
+    end
+
+    def handle_element(element); end
+
+    def on_end_tag(end_tag)
+      raise HandlerError, "bad end tag"
+    end
+  end
+
+  def test_that_it_passes_end_tag_errors_through
+    rewriter = Selma::Rewriter.new(sanitizer: nil, handlers: [RaisingEndTag.new])
+
+    error = assert_raises(HandlerError) { rewriter.rewrite("<strong>Wow!</strong>") }
+    assert_equal("bad end tag", error.message)
+  end
+
+  def test_that_it_can_rewrite_after_a_handler_error
+    rewriter = Selma::Rewriter.new(sanitizer: nil, handlers: [RaisingHandler.new])
+
+    assert_raises(HandlerError) { rewriter.rewrite("<strong>Wow!</strong>") }
+    assert_equal("<p>Wow!</p>", rewriter.rewrite("<p>Wow!</p>"))
+  end
+
+  def test_invalid_state_errors_are_selma_errors
+    assert_operator(Selma::InvalidStateError, :<, Selma::Error)
+  end
+
+  class Reentrant
+    SELECTOR = Selma::Selector.new(match_element: "strong")
+
+    attr_accessor :rewriter
+
+    def selector
+      SELECTOR
+    end
+
+    def handle_element(element)
+      rewriter.rewrite("<p>Again!</p>")
+    end
+  end
+
+  def test_that_it_raises_on_reentrant_rewrites
+    handler = Reentrant.new
+    rewriter = Selma::Rewriter.new(sanitizer: nil, handlers: [handler])
+    handler.rewriter = rewriter
+
+    error = assert_raises(Selma::InvalidStateError) { rewriter.rewrite("<strong>Wow!</strong>") }
+    assert_match(/already rewriting/, error.message)
+
+    # the failed call leaves nothing behind
+    assert_equal("<p>Wow!</p>", rewriter.rewrite("<p>Wow!</p>"))
+  end
+
+  def test_that_handlers_can_use_other_rewriters
+    handler = Reentrant.new
+    handler.rewriter = Selma::Rewriter.new(sanitizer: nil, handlers: [RaisingEndTag.new])
+    rewriter = Selma::Rewriter.new(sanitizer: nil, handlers: [handler])
+
+    assert_equal("<strong>Wow!</strong>", rewriter.rewrite("<strong>Wow!</strong>"))
+  end
+
+  # content which, when it's turned into a String, changes what it's being
+  # added to
+  class MeddlingContent
+    def initialize(target, content)
+      @target = target
+      @content = content
+    end
+
+    def to_str
+      if @target.is_a?(Selma::HTML::Element)
+        @target["data-meddled"] = "yes"
+      else
+        @target.before("[", as: :text)
+      end
+      @content
+    end
+  end
+
+  class AppendMeddling
+    SELECTOR = Selma::Selector.new(match_element: "strong", match_text_within: "em")
+
+    def selector
+      SELECTOR
+    end
+
+    def handle_element(element)
+      element.append(MeddlingContent.new(element, "<b>!</b>"), as: :html)
+    end
+
+    def handle_text_chunk(text)
+      text.replace(MeddlingContent.new(text, text.to_s.upcase), as: :text)
+    end
+  end
+
+  def test_that_content_can_call_back_into_what_it_is_added_to
+    rewriter = Selma::Rewriter.new(sanitizer: nil, handlers: [AppendMeddling.new])
+
+    assert_equal(
+      %(<strong data-meddled="yes">Wow<b>!</b></strong><em>[WOW</em>),
+      rewriter.rewrite("<strong>Wow</strong><em>wow</em>"),
+    )
+  end
+
+  class RenderMeddling
+    SELECTOR = Selma::Selector.new(match_element: "div")
+
+    attr_reader :element
+
+    def selector
+      SELECTOR
+    end
+
+    def handle_element(element)
+      @element = element
+      element.set_inner_content("hi", as: :markdown)
+    end
+  end
+
+  def test_that_a_markdown_renderer_can_call_back_into_the_element
+    handler = RenderMeddling.new
+    Selma.markdown_renderer = lambda do |markdown|
+      handler.element["data-rendered"] = handler.element["class"]
+      "<p>#{markdown}</p>"
+    end
+    rewriter = Selma::Rewriter.new(sanitizer: nil, handlers: [handler], handler_sanitizer: Selma::Sanitizer.new({ elements: ["p"] }))
+
+    assert_equal(%(<div class="c" data-rendered="c"><p>hi</p></div>), rewriter.rewrite(%(<div class="c"></div>)))
+  ensure
+    Selma.markdown_renderer = nil
+  end
+
+  class SanitizerMeddling
+    SELECTOR = Selma::Selector.new(match_element: "b")
+
+    attr_reader :seen
+
+    def initialize(sanitizer)
+      @sanitizer = sanitizer
+      @seen = []
+    end
+
+    def selector
+      SELECTOR
+    end
+
+    def handle_element(element)
+      @seen << @sanitizer.config[:elements]
+      @sanitizer.allow_element(["i"])
+    end
+  end
+
+  def test_that_handlers_can_use_the_sanitizer_during_a_rewrite
+    sanitizer = Selma::Sanitizer.new({ elements: ["b"] })
+    handler = SanitizerMeddling.new(sanitizer)
+    rewriter = Selma::Rewriter.new(sanitizer: sanitizer, handlers: [handler])
+
+    # the rewrite goes on with the sanitizer as it was when the rewriter was built
+    assert_equal("<b>a</b>b<b>c</b>", rewriter.rewrite("<b>a</b><i>b</i><b>c</b>"))
+    assert_equal([["b"], ["b"]], handler.seen)
+    assert_equal("<b>a</b><i>b</i>", Selma::Rewriter.new(sanitizer: sanitizer).rewrite("<b>a</b><i>b</i>"))
+  end
+
+  def test_that_it_can_rewrite_after_a_sanitizer_error
+    sanitizer = Selma::Sanitizer.new({
+      elements: ["a"],
+      add_attributes: { "a" => { "data=foo" => "bar" } },
+    })
+    rewriter = Selma::Rewriter.new(sanitizer: sanitizer)
+
+    assert_raises(Selma::RewritingError) { rewriter.rewrite("<a>Wow!</a>") }
+    assert_equal("Wow!", rewriter.rewrite("<p>Wow!</p>"))
+  end
+end
@@ -0,0 +1,75 @@
+# frozen_string_literal: true
+
+require "test_helper"
+
+class SelmaRewriterExplainTest < Minitest::Test
+  class MatchLinks
+    SELECTOR = Selma::Selector.new(match_element: "a[href]", match_text_within: "p")
+
+    attr_reader :calls
+
+    def initialize
+      @calls = 0
+    end
+
+    def selector
+      SELECTOR
+    end
+
+    def handle_element(element)
+      @calls += 1
+    end
+
+    def handle_text_chunk(text)
+      @calls += 1
+    end
+  end
+
+  class MatchImages
+    SELECTOR = Selma::Selector.new(match_element: "img")
+
+    def selector
+      SELECTOR
+    end
+
+    def handle_element(element); end
+  end
+
+  def test_it_reports_each_match_in_document_order
+    links = MatchLinks.new
+    images = MatchImages.new
+    rewriter = Selma::Rewriter.new(sanitizer: nil, handlers: [links, images])
+    html = %(<p>Go <a href="/a">here</a></p><img src="x.png"><a name="b">not a link</a>)
+
+    assert_equal([
+      { handler: links, selector: "p", match: :text_within, tag: "<p>", element: "p", offset: 0 },
+      { handler: links, selector: "a[href]", match: :element, tag: %(<a href="/a">), element: "a", offset: 6 },
+      { handler: images, selector: "img", match: :element, tag: %(<img src="x.png">), element: "img", offset: 31 },
+    ], rewriter.explain(html))
+  end
+
+  def test_it_does_not_call_the_handlers
+    links = MatchLinks.new
+    rewriter = Selma::Rewriter.new(sanitizer: nil, handlers: [links])
+    rewriter.explain(%(<p><a href="/a">here</a></p>))
+
+    assert_equal(0, links.calls)
+    assert_equal(0, rewriter.stats[:handlers].first[:elements_matched])
+  end
+
+  def test_it_reports_offsets_in_bytes
+    rewriter = Selma::Rewriter.new(sanitizer: nil, handlers: [MatchImages.new])
+    html = %(<p>héllo <img src='a>b.png' alt="é"></p>)
+    found = rewriter.explain(html).first
+
+    assert_equal(10, found[:offset])
+    assert_equal(found[:tag], html.byteslice(found[:offset], found[:tag].bytesize))
+  end
+
+  def test_it_matches_after_sanitizing
+    rewriter = Selma::Rewriter.new(handlers: [MatchLinks.new])
+    found = rewriter.explain(%(<p><a href="/a" onclick="x()">here</a></p>))
+
+    assert_equal([%(<a href="/a">)], found.select { |m| m[:match] == :element }.map { |m| m[:tag] })
+  end
+end
@@ -0,0 +1,127 @@
+# frozen_string_literal: true
+
+require "test_helper"
+
+class SelmaRewriterExtractTest < Minitest::Test
+  def extract(frag, extract)
+    rewriter = Selma::Rewriter.new(sanitizer: nil, extract: extract)
+    modified_doc = rewriter.rewrite(frag)
+
+    [modified_doc, rewriter.extracted]
+  end
+
+  def test_that_it_counts_words_and_characters
+    frag = "<p>Hello, <em>wonder</em>ful world &amp; all</p><p>again</p>"
+    modified_doc, extracted = extract(frag, { word_count: true })
+
+    assert_equal(frag, modified_doc)
+    assert_equal({ word_count: { words: 6, characters: 29, reading_time: 1 } }, extracted)
+  end
+
+  def test_that_it_only_counts_visible_text
+    frag = "<title>Title</title><style>p { color: red }</style><p>One<br>two</p><script>let x = 1</script>"
+    _, extracted = extract(frag, { word_count: true })
+
+    assert_equal(2, extracted[:word_count][:words])
+  end
+
+  def test_that_it_works_out_the_reading_time
+    frag = "<p>#{"word " * 450}</p>"
+    _, extracted = extract(frag, { word_count: { words_per_minute: 150 } })
+
+    assert_equal(450, extracted[:word_count][:words])
+    assert_equal(3, extracted[:word_count][:reading_time])
+  end
+
+  def test_that_it_counts_sections
+    frag = "<p>Intro text</p><h2>Set <code>up</code></h2><p>Install the gem</p><h3>Usage</h3><p>Call it</p>"
+    _, extracted = extract(frag, { word_count: { sections: true } })
+
+    assert_equal(10, extracted[:word_count][:words])
+    assert_equal(
+      [
+        { heading: nil, words: 2, characters: 9 },
+        { heading: "Set up", words: 3, characters: 13 },
+        { heading: "Usage", words: 2, characters: 6 },
+      ],
+      extracted[:word_count][:sections],
+    )
+  end
+
+  def test_that_it_samples_text
+    frag = "<h1>Bonjour</h1><p>tout <em>le</em>   monde,\n et au revoir</p><script>alert(1)</script>"
+    _, extracted = extract(frag, { text_sample: 1024 })
+
+    assert_equal({ text_sample: "Bonjour tout le monde, et au revoir" }, extracted)
+  end
+
+  def test_that_the_text_sample_is_limited_in_bytes
+    _, extracted = extract("<p>Déjà vu</p>", { text_sample: 4 })
+
+    assert_equal("Déj", extracted[:text_sample])
+
+    _, extracted = extract("<p>Déjà vu</p>", { text_sample: 5 })
+
+    assert_equal("Déj", extracted[:text_sample])
+  end
+
+  def test_that_it_finds_the_first_image_the_sanitizer_kept
+    sanitizer = Selma::Sanitizer.new({
+      elements: ["img", "p"],
+      attributes: { "img" => ["src"] },
+      protocols: { "img" => { "src" => ["https"] } },
+    })
+    frag = %(<img src="javascript:alert(1)"><p><img src=""><img src="https://example.com/a.png"><img src="https://example.com/b.png"></p>)
+    rewriter = Selma::Rewriter.new(sanitizer: sanitizer, extract: { first_image: true })
+    rewriter.rewrite(frag)
+
+    assert_equal({ first_image: "https://example.com/a.png" }, rewriter.extracted)
+  end
+
+  def test_that_the_first_image_is_nil_without_one
+    _, extracted = extract("<p>No images</p>", { first_image: true })
+
+    assert_equal({ first_image: nil }, extracted)
+  end
+
+  def test_that_it_excerpts_paragraphs
+    frag = "<h1>Title</h1><p>The first   <em>paragraph</em>.</p><blockquote>Not this</blockquote><p>And the second one</p>"
+    _, extracted = extract(frag, { excerpt: 100 })
+
+    assert_equal({ excerpt: "The first paragraph. And the second one" }, extracted)
+  end
+
+  def test_that_a_long_excerpt_ends_at_a_word
+    frag = "<p>The first paragraph, and the rest</p>"
+
+    assert_equal("The first paragraph, and the…", extract(frag, { excerpt: 28 }).last[:excerpt])
+    assert_equal("The first paragraph, and…", extract(frag, { excerpt: 26 }).last[:excerpt])
+    assert_equal("The first paragraph, and…", extract(frag, { excerpt: 24 }).last[:excerpt])
+    assert_equal("The first paragraph, and the rest", extract(frag, { excerpt: 33 }).last[:excerpt])
+  end
+
+  def test_that_each_rewrite_is_counted_on_its_own
+    rewriter = Selma::Rewriter.new(sanitizer: nil, extract: { word_count: true })
+
+    rewriter.rewrite("<p>one two three</p>")
+    rewriter.rewrite("<p>four</p>")
+
+    assert_equal(1, rewriter.extracted[:word_count][:words])
+  end
+
+  def test_that_nothing_is_extracted_by_default
+    rewriter = Selma::Rewriter.new
+    rewriter.rewrite("<p>Hello</p>")
+
+    assert_empty(rewriter.extracted)
+  end
+
+  def test_that_it_raises_on_unknown_options
+    assert_raises(ArgumentError) { Selma::Rewriter.new(extract: { words: true }) }
+    assert_raises(ArgumentError) { Selma::Rewriter.new(extract: { word_count: { sections: true, per_minute: 5 } }) }
+    assert_raises(ArgumentError) { Selma::Rewriter.new(extract: { word_count: { words_per_minute: 0 } }) }
+    assert_raises(ArgumentError) { Selma::Rewriter.new(extract: { word_count: "yes" }) }
+    assert_raises(ArgumentError) { Selma::Rewriter.new(extract: { text_sample: -1 }) }
+    assert_raises(ArgumentError) { Selma::Rewriter.new(extract: { excerpt: "short" }) }
+  end
+end
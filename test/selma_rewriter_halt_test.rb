@@ -0,0 +1,63 @@
+# frozen_string_literal: true
+
+require "test_helper"
+
+class SelmaRewriterHaltTest < Minitest::Test
+  class HaltOnSpam
+    SELECTOR = Selma::Selector.new(match_element: "p")
+
+    attr_accessor :rewriter
+    attr_reader :seen
+
+    def initialize(raise: false)
+      @raise = raise
+      @seen = []
+    end
+
+    def selector
+      SELECTOR
+    end
+
+    def handle_element(element)
+      rewriter.halt!(raise: @raise) if element["class"] == "spam"
+      @seen << element["class"]
+    end
+  end
+
+  def build_rewriter(handler)
+    handler.rewriter = Selma::Rewriter.new(sanitizer: nil, handlers: [handler])
+  end
+
+  def test_that_it_returns_the_output_so_far
+    handler = HaltOnSpam.new
+    frag = %(<p class="ham">Hello</p><p class="spam">Buy now</p><p class="eggs">Bye</p>)
+    modified_doc = build_rewriter(handler).rewrite(frag)
+
+    assert(modified_doc.start_with?(%(<p class="ham">Hello</p>)))
+    refute_includes(modified_doc, "Buy now")
+    refute_includes(modified_doc, "Bye")
+    assert_equal(["ham"], handler.seen)
+  end
+
+  def test_that_it_raises_if_asked
+    handler = HaltOnSpam.new(raise: true)
+    frag = %(<p class="ham">Hello</p><p class="spam">Buy now</p><p class="eggs">Bye</p>)
+
+    assert_raises(Selma::HaltedError) { build_rewriter(handler).rewrite(frag) }
+    assert_equal(["ham"], handler.seen)
+  end
+
+  def test_that_the_next_rewrite_starts_over
+    handler = HaltOnSpam.new
+    rewriter = build_rewriter(handler)
+    rewriter.rewrite(%(<p class="spam">Buy now</p>))
+
+    assert_equal(%(<p class="ham">Hello</p>), rewriter.rewrite(%(<p class="ham">Hello</p>)))
+  end
+
+  def test_that_it_cannot_halt_outside_of_a_rewrite
+    rewriter = build_rewriter(HaltOnSpam.new)
+
+    assert_raises(Selma::InvalidStateError) { rewriter.halt! }
+  end
+end
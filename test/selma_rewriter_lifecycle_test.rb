@@ -0,0 +1,114 @@
+# frozen_string_literal: true
+
+require "test_helper"
+
+class SelmaRewriterLifecycleTest < Minitest::Test
+  class NumberHeadings
+    include Selma::Handler
+
+    SELECTOR = Selma::Selector.new(match_element: "h2")
+
+    attr_reader :calls
+
+    def initialize
+      @calls = []
+    end
+
+    def selector
+      SELECTOR
+    end
+
+    def before_rewrite
+      @calls << :before
+      @count = 0
+    end
+
+    def handle_element(element)
+      @count += 1
+      element["id"] = "section-#{@count}"
+    end
+
+    def after_rewrite
+      @calls << :after
+    end
+  end
+
+  class Failing
+    SELECTOR = Selma::Selector.new(match_element: "p")
+
+    attr_reader :after_called
+
+    def selector
+      SELECTOR
+    end
+
+    def handle_element(_element)
+      raise "nope"
+    end
+
+    def after_rewrite
+      @after_called = true
+    end
+  end
+
+  class Plain
+    include Selma::Handler
+
+    SELECTOR = Selma::Selector.new(match_element: "p")
+
+    def selector
+      SELECTOR
+    end
+
+    def handle_element(element)
+      element["class"] = "plain"
+    end
+  end
+
+  # nothing but the rewriter holds on to its selector
+  class FreshSelector
+    def selector
+      Selma::Selector.new(match_element: "p")
+    end
+
+    def handle_element(element)
+      element["class"] = "kept"
+    end
+  end
+
+  def test_it_resets_state_before_each_document
+    handler = NumberHeadings.new
+    rewriter = Selma::Rewriter.new(sanitizer: nil, handlers: [handler])
+
+    assert_equal(%(<h2 id="section-1">a</h2>), rewriter.rewrite("<h2>a</h2>"))
+    assert_equal(%(<h2 id="section-1">b</h2>), rewriter.rewrite("<h2>b</h2>"))
+    assert_equal([:before, :after, :before, :after], handler.calls)
+  end
+
+  def test_it_calls_after_rewrite_when_the_rewrite_fails
+    handler = Failing.new
+    rewriter = Selma::Rewriter.new(sanitizer: nil, handlers: [handler])
+
+    assert_raises(RuntimeError) { rewriter.rewrite("<p>a</p>") }
+    assert(handler.after_called)
+  end
+
+  def test_the_callbacks_are_optional
+    rewriter = Selma::Rewriter.new(sanitizer: nil, handlers: [Plain.new])
+
+    assert_equal(%(<p class="plain">a</p>), rewriter.rewrite("<p>a</p>"))
+  end
+
+  def test_its_handlers_outlive_a_gc
+    rewriter = Selma::Rewriter.new(sanitizer: nil, handlers: [FreshSelector.new])
+    GC.start(full_mark: true, immediate_sweep: true)
+
+    assert_equal(%(<p class="kept">a</p>), rewriter.rewrite("<p>a</p>"))
+  end
+
+  def test_the_module_requires_a_selector
+    handler = Class.new { include Selma::Handler }.new
+
+    assert_raises(NotImplementedError) { handler.selector }
+  end
+end
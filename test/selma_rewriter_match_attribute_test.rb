@@ -49,4 +49,45 @@ class SelmaRewriterMatchAttributeTest < Minitest::Test
     frag = "<article><div class='a b c 1 2 3' data-foo='baz'>Wow!</div></article>"
     Selma::Rewriter.new(sanitizer: nil, handlers: [GetAttrs.new]).rewrite(frag)
   end
+
+  class ClassList
+    SELECTOR = Selma::Selector.new(match_element: "a")
+
+    def selector
+      SELECTOR
+    end
+
+    def handle_element(element)
+      element.add_class("link external link")
+      element.remove_class("old")
+      element.append_attribute_token("rel", "noopener nofollow")
+    end
+  end
+
+  def test_that_it_merges_token_lists
+    frag = %(<a class="link  old" rel="nofollow">One</a><a>Two</a>)
+    modified_doc = Selma::Rewriter.new(sanitizer: nil, handlers: [ClassList.new]).rewrite(frag)
+
+    assert_equal(%(<a class="link external" rel="nofollow noopener">One</a><a class="link external" rel="noopener nofollow">Two</a>), modified_doc)
+  end
+
+  class RemoveLastClass
+    SELECTOR = Selma::Selector.new(match_element: "span")
+
+    def selector
+      SELECTOR
+    end
+
+    def handle_element(element)
+      element.remove_class("only")
+      element.remove_attribute_token("rel", "missing")
+    end
+  end
+
+  def test_that_it_removes_emptied_token_lists
+    frag = %(<span class=" only ">Wow!</span>)
+    modified_doc = Selma::Rewriter.new(sanitizer: nil, handlers: [RemoveLastClass.new]).rewrite(frag)
+
+    assert_equal("<span>Wow!</span>", modified_doc)
+  end
 end
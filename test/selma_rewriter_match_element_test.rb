@@ -217,6 +217,212 @@ class SelmaRewriterMatchElementTest < Minitest::Test
     assert_equal(%(<strong>Gee!</strong>), modified_doc)
   end
 
+  class ReplaceElement
+    SELECTOR = Selma::Selector.new(match_element: "strong")
+
+    def selector
+      SELECTOR
+    end
+
+    def handle_element(element)
+      element.replace("<em>Gee!</em>", as: :html)
+    end
+  end
+
+  def test_that_it_replaces
+    frag = "<p><strong>Wow!</strong></p>"
+    modified_doc = Selma::Rewriter.new(sanitizer: nil, handlers: [ReplaceElement.new]).rewrite(frag)
+
+    assert_equal(%(<p><em>Gee!</em></p>), modified_doc)
+  end
+
+  class InjectHTML
+    SELECTOR = Selma::Selector.new(match_element: "p")
+
+    def selector
+      SELECTOR
+    end
+
+    def handle_element(element)
+      element.append(%(<img src="x" onerror="alert(1)"><strong onclick="alert(2)">ok</strong>), as: :html)
+    end
+  end
+
+  def test_that_it_sanitizes_injected_html
+    frag = "<p>hi</p>"
+    handler_sanitizer = Selma::Sanitizer.new({ elements: ["strong"] })
+    modified_doc = Selma::Rewriter.new(sanitizer: nil, handlers: [InjectHTML.new], handler_sanitizer: handler_sanitizer).rewrite(frag)
+
+    assert_equal(%(<p>hi<strong>ok</strong></p>), modified_doc)
+  end
+
+  def test_that_it_sanitizes_injected_html_like_the_document
+    frag = "<p>hi</p>"
+    sanitizer = Selma::Sanitizer.new({ elements: ["p", "strong"] })
+    modified_doc = Selma::Rewriter.new(sanitizer: sanitizer, handlers: [InjectHTML.new], handler_sanitizer: true).rewrite(frag)
+
+    assert_equal(%(<p>hi<strong>ok</strong></p>), modified_doc)
+  end
+
+  def test_that_it_trusts_injected_html_by_default
+    frag = "<p>hi</p>"
+    modified_doc = Selma::Rewriter.new(sanitizer: nil, handlers: [InjectHTML.new]).rewrite(frag)
+
+    assert_equal(%(<p>hi<img src="x" onerror="alert(1)"><strong onclick="alert(2)">ok</strong></p>), modified_doc)
+  end
+
+  def test_that_it_needs_a_sanitizer_to_reuse_for_injected_html
+    assert_raises(ArgumentError) do
+      Selma::Rewriter.new(sanitizer: nil, handlers: [InjectHTML.new], handler_sanitizer: true)
+    end
+  end
+
+  class InjectMarkdown
+    SELECTOR = Selma::Selector.new(match_element: "div[data-preview]")
+
+    def selector
+      SELECTOR
+    end
+
+    def handle_element(element)
+      element.set_inner_content("**#{element["data-preview"]}** <script>alert(1)</script>", as: :markdown)
+    end
+  end
+
+  def test_that_it_renders_and_sanitizes_injected_markdown
+    Selma.markdown_renderer = ->(markdown) { "<p>#{markdown.gsub(/\*\*(.+?)\*\*/, "<strong>\\1</strong>")}</p>" }
+    frag = %(<div data-preview="hi"></div>)
+    handler_sanitizer = Selma::Sanitizer.new({ elements: ["p", "strong"], remove_contents: ["script"] })
+    modified_doc = Selma::Rewriter.new(sanitizer: nil, handlers: [InjectMarkdown.new], handler_sanitizer: handler_sanitizer).rewrite(frag)
+
+    assert_equal(%(<div data-preview="hi"><p><strong>hi</strong> </p></div>), modified_doc)
+  ensure
+    Selma.markdown_renderer = nil
+  end
+
+  def test_that_it_needs_a_sanitizer_for_injected_markdown
+    Selma.markdown_renderer = ->(markdown) { markdown }
+    frag = %(<div data-preview="hi"></div>)
+
+    assert_raises(RuntimeError) do
+      Selma::Rewriter.new(sanitizer: nil, handlers: [InjectMarkdown.new]).rewrite(frag)
+    end
+  ensure
+    Selma.markdown_renderer = nil
+  end
+
+  def test_that_it_needs_a_renderer_for_injected_markdown
+    frag = %(<div data-preview="hi"></div>)
+    rewriter = Selma::Rewriter.new(sanitizer: nil, handlers: [InjectMarkdown.new], handler_sanitizer: Selma::Sanitizer.new)
+
+    assert_raises(RuntimeError) { rewriter.rewrite(frag) }
+  end
+
+  class CaptureSubtree
+    SELECTOR = Selma::Selector.new(match_element: "pre.mermaid")
+
+    attr_reader :captured
+
+    def initialize(max_bytes: 1024, replacement: nil)
+      @max_bytes = max_bytes
+      @replacement = replacement
+      @captured = []
+    end
+
+    def selector
+      SELECTOR
+    end
+
+    def handle_element(element)
+      element.capture_subtree(max_bytes: @max_bytes) do |html|
+        @captured << html
+        @replacement
+      end
+    end
+  end
+
+  def test_that_it_captures_subtrees
+    frag = %(<p>Before</p><pre class="mermaid">graph TD;<b>A</b>--&gt;B;</pre><p>After</p>)
+    handler = CaptureSubtree.new(replacement: %(<svg class="diagram"></svg>))
+    modified_doc = Selma::Rewriter.new(sanitizer: nil, handlers: [handler]).rewrite(frag)
+
+    assert_equal([%(<pre class="mermaid">graph TD;<b>A</b>--&gt;B;</pre>)], handler.captured)
+    assert_equal(%(<p>Before</p><svg class="diagram"></svg><p>After</p>), modified_doc)
+  end
+
+  def test_that_it_leaves_subtrees_when_nothing_is_returned
+    frag = %(<pre class="mermaid">graph TD;</pre>)
+    handler = CaptureSubtree.new
+    modified_doc = Selma::Rewriter.new(sanitizer: nil, handlers: [handler]).rewrite(frag)
+
+    assert_equal([frag], handler.captured)
+    assert_equal(frag, modified_doc)
+  end
+
+  def test_that_it_does_not_capture_subtrees_over_the_limit
+    frag = %(<pre class="mermaid">#{"A-->B;" * 20}</pre>)
+    handler = CaptureSubtree.new(max_bytes: 64, replacement: "gone")
+    modified_doc = Selma::Rewriter.new(sanitizer: nil, handlers: [handler]).rewrite(frag)
+
+    assert_empty(handler.captured)
+    assert_equal(frag, modified_doc)
+  end
+
+  class SlugHeadings
+    SELECTOR = Selma::Selector.new(match_element: "h2")
+
+    attr_reader :texts
+
+    def initialize(replace: false)
+      @replace = replace
+      @texts = []
+    end
+
+    def selector
+      SELECTOR
+    end
+
+    def handle_element(element)
+      element.text_content(replace: @replace) do |text|
+        @texts << text
+        "#{text}!" if @replace
+      end
+    end
+  end
+
+  def test_that_it_yields_text_content
+    frag = %(<h2>Hello <em>big</em> &amp; <a href="#">bold</a> world</h2><h2>Two</h2>)
+    handler = SlugHeadings.new
+    modified_doc = Selma::Rewriter.new(sanitizer: nil, handlers: [handler]).rewrite(frag)
+
+    assert_equal(["Hello big & bold world", "Two"], handler.texts)
+    assert_equal(frag, modified_doc)
+  end
+
+  def test_that_it_replaces_text_content
+    frag = %(<h2>Tom <em>&amp;</em> Jerry</h2>)
+    handler = SlugHeadings.new(replace: true)
+    modified_doc = Selma::Rewriter.new(sanitizer: nil, handlers: [handler]).rewrite(frag)
+
+    assert_equal(["Tom & Jerry"], handler.texts)
+    assert_equal(%(<h2>Tom &amp; Jerry!</h2>), modified_doc)
+  end
+
+  def test_that_it_keeps_text_content_blocks_from_the_gc
+    frag = %(<h2>One <em>two</em></h2><p>#{"filler " * 50}</p><h2>Three</h2>)
+    handler = SlugHeadings.new(replace: true)
+
+    begin
+      GC.stress = true
+      modified_doc = Selma::Rewriter.new(sanitizer: nil, handlers: [handler]).rewrite(frag)
+    ensure
+      GC.stress = false
+    end
+
+    assert_equal(["One two", "Three"], handler.texts)
+    assert_equal(%(<h2>One two!</h2><p>#{"filler " * 50}</p><h2>Three!</h2>), modified_doc)
+  end
+
   class RaiseError
     SELECTOR = Selma::Selector.new(match_element: "strong")
 
@@ -229,13 +435,13 @@ class SelmaRewriterMatchElementTest < Minitest::Test
     end
   end
 
-  # TODO: note that this error does not match, because
-  # it's difficult to pluck from magnus
   def test_that_it_can_raise_errors
     frag = "<strong>Wow!</strong>"
-    assert_raises(RuntimeError) do
+    error = assert_raises(NoMethodError) do
       Selma::Rewriter.new(sanitizer: nil, handlers: [RaiseError.new]).rewrite(frag)
     end
+
+    assert_equal("boom!", error.message)
   end
 
   class SetTagName
@@ -257,6 +463,45 @@ class SelmaRewriterMatchElementTest < Minitest::Test
     assert_equal(%(<bold>Wow!</bold>), modified_doc)
   end
 
+  class DemoteHeadings
+    SELECTOR = Selma::Selector.new(match_element: "h1, h2")
+
+    def selector
+      SELECTOR
+    end
+
+    def handle_element(element)
+      element.tag_name = "h#{element.tag_name.delete_prefix("h").to_i + 1}"
+    end
+  end
+
+  def test_that_it_renames_end_tags_too
+    frag = "<h1>Title</h1><h2>Section <em>one</em></h2>"
+    modified_doc = Selma::Rewriter.new(sanitizer: nil, handlers: [DemoteHeadings.new]).rewrite(frag)
+
+    assert_equal(%(<h2>Title</h2><h3>Section <em>one</em></h3>), modified_doc)
+  end
+
+  class SetInvalidTagName
+    SELECTOR = Selma::Selector.new(match_element: "strong")
+
+    def selector
+      SELECTOR
+    end
+
+    def handle_element(element)
+      element.tag_name = "not a name"
+    end
+  end
+
+  def test_that_it_raises_on_invalid_tag_names
+    error = assert_raises(ArgumentError) do
+      Selma::Rewriter.new(sanitizer: nil, handlers: [SetInvalidTagName.new]).rewrite("<strong>Wow!</strong>")
+    end
+
+    assert_match(/cannot rename <strong>/, error.message)
+  end
+
   class GetIsSelfClosing < Minitest::Test
     SELECTOR = Selma::Selector.new(match_element: "strong")
 
@@ -356,4 +601,143 @@ class SelmaRewriterMatchElementTest < Minitest::Test
 
     assert_equal(%(<div>Wow!</div>), modified_doc)
   end
+
+  class SkipNavLinks
+    SELECTOR = Selma::Selector.new(match_element: "a")
+
+    def selector
+      SELECTOR
+    end
+
+    def handle_element(element)
+      return if element.ancestors.intersect?(["nav", "footer"])
+
+      element["class"] = element.ancestors.join(">")
+    end
+  end
+
+  def test_that_it_exposes_ancestors
+    frag = %(<nav><a href="/">Home</a></nav><div><p><a href="/a">A</a><br><a href="/b">B</a></p></div><footer><span><a href="/c">C</a></span></footer>)
+    modified_doc = Selma::Rewriter.new(sanitizer: nil, handlers: [SkipNavLinks.new]).rewrite(frag)
+
+    assert_equal(%(<nav><a href="/">Home</a></nav><div><p><a href="/a" class="div>p">A</a><br><a href="/b" class="div>p">B</a></p></div><footer><span><a href="/c">C</a></span></footer>), modified_doc)
+  end
+
+  class AncestorsWithOtherHandlers
+    SELECTOR = Selma::Selector.new(match_element: "em")
+
+    attr_reader :ancestors
+
+    def initialize
+      @ancestors = []
+    end
+
+    def selector
+      SELECTOR
+    end
+
+    def handle_element(element)
+      @ancestors << element.ancestors
+    end
+  end
+
+  def test_that_ancestors_are_shared_between_handlers
+    handler = AncestorsWithOtherHandlers.new
+    frag = "<div><strong><em>Wow!</em></strong></div><em>Such</em>"
+    Selma::Rewriter.new(sanitizer: nil, handlers: [SkipNavLinks.new, handler]).rewrite(frag)
+
+    assert_equal([["div", "strong"], []], handler.ancestors)
+  end
+
+  class ContentCapability
+    SELECTOR = Selma::Selector.new(match_element: "*")
+
+    attr_reader :capabilities
+
+    def initialize
+      @capabilities = []
+    end
+
+    def selector
+      SELECTOR
+    end
+
+    def handle_element(element)
+      @capabilities << [element.tag_name, element.void?, element.can_have_content?]
+    end
+  end
+
+  def test_that_it_knows_which_elements_can_have_content
+    handler = ContentCapability.new
+    frag = "<p>One<br>Two</p><img src=\"x.png\"><custom-el></custom-el>"
+    Selma::Rewriter.new(sanitizer: nil, handlers: [handler]).rewrite(frag)
+
+    assert_equal([
+      ["p", false, true],
+      ["br", true, false],
+      ["img", true, false],
+      ["custom-el", false, true],
+    ], handler.capabilities)
+  end
+
+  class ReplaceWidgets
+    SELECTOR = Selma::Selector.new(match_element: "div.widget")
+
+    def initialize(skip_with:)
+      @skip_with = skip_with
+    end
+
+    def selector
+      SELECTOR
+    end
+
+    def handle_element(element)
+      element.set_inner_content("[widget]", as: :text)
+      return :skip_children if @skip_with == :symbol
+
+      element.skip!
+    end
+  end
+
+  class SeeEverything
+    SELECTOR = Selma::Selector.new(match_element: "*", match_text_within: "*")
+
+    attr_reader :seen
+
+    def initialize
+      @seen = []
+    end
+
+    def selector
+      SELECTOR
+    end
+
+    def handle_element(element)
+      @seen << element.tag_name
+    end
+
+    def handle_text_chunk(text)
+      @seen << text.to_s
+    end
+  end
+
+  def test_that_it_skips_subtrees
+    [:symbol, :method].each do |skip_with|
+      watcher = SeeEverything.new
+      frag = %(<p>Hi</p><div class="widget"><span>Inner</span></div><p>Bye</p>)
+      rewriter = Selma::Rewriter.new(sanitizer: nil, handlers: [ReplaceWidgets.new(skip_with: skip_with), watcher])
+
+      assert_equal(%(<p>Hi</p><div class="widget">[widget]</div><p>Bye</p>), rewriter.rewrite(frag))
+      assert_equal(["p", "Hi", "p", "Bye"], watcher.seen)
+    end
+  end
+
+  def test_that_handlers_only_see_what_the_sanitizer_kept
+    watcher = SeeEverything.new
+    frag = %(<p onclick="alert(1)">Hi</p><script>alert(2)</script><custom-el><b>Bold</b></custom-el>)
+    sanitizer = Selma::Sanitizer.new(Selma::Sanitizer::Config::RELAXED)
+    Selma::Rewriter.new(sanitizer: sanitizer, handlers: [watcher]).rewrite(frag)
+
+    assert_equal(["p", "Hi", "b", "Bold"], watcher.seen)
+  end
 end
@@ -0,0 +1,63 @@
+# frozen_string_literal: true
+
+require "test_helper"
+
+class SelmaRewriterMinifyTest < Minitest::Test
+  def minify(html, minify = true)
+    Selma::Rewriter.new(sanitizer: nil, minify: minify).rewrite(html)
+  end
+
+  def test_it_minifies_the_output
+    html = %(<div class="note">\n  <p>Some   <b>text</b></p> <!-- todo -->\n</div>)
+
+    assert_equal("<div class=note><p>Some <b>text</b></p></div>", minify(html))
+  end
+
+  def test_it_keeps_the_whitespace_between_inline_elements
+    html = %(<p>\n  <em>one</em>\n  <a href="#">two</a>\n</p>)
+
+    assert_equal(%(<p><em>one</em> <a href=#>two</a></p>), minify(html))
+  end
+
+  def test_it_leaves_preformatted_text_and_scripts_alone
+    html = %(<pre>  keep\n   this  </pre>\n<textarea> and  this </textarea>\n<script>if (a  <  b) {}</script>)
+
+    assert_equal(%(<pre>  keep\n   this  </pre><textarea> and  this </textarea> <script>if (a  <  b) {}</script>), minify(html))
+  end
+
+  def test_it_keeps_the_quotes_which_are_needed
+    html = %(<a title="two words" data-say='"hi"' href="/x/">x</a><input disabled value="">)
+
+    assert_equal(%(<a title="two words" data-say='"hi"' href=/x/>x</a><input disabled value>), minify(html))
+  end
+
+  def test_it_keeps_self_closing_foreign_elements_apart_from_their_attributes
+    assert_equal("<svg><circle r=4 /></svg>", minify(%(<svg><circle r="4"/></svg>)))
+  end
+
+  def test_it_only_removes_what_browsers_read_as_comments
+    assert_equal("<p>a b</p>", minify("<p>a <!-- x --!> b</p>"))
+    assert_equal("<svg><style> a </style></svg>", minify("<svg><style>\n  a  </style></svg>"))
+  end
+
+  def test_it_can_leave_some_of_it_be
+    html = %(<p class="a">  Hi  <!-- x --></p>)
+
+    assert_equal(%(<p class="a">Hi <!-- x --></p>), minify(html, { comments: false, quotes: false }))
+    assert_equal(%(<p class=a>  Hi  </p>), minify(html, { whitespace: false }))
+  end
+
+  def test_it_runs_after_the_sanitizer
+    sanitizer = Selma::Sanitizer.new({ elements: ["p"] })
+    rewriter = Selma::Rewriter.new(sanitizer: sanitizer, minify: true)
+
+    assert_equal("<p>Hi there</p>", rewriter.rewrite(%(<p>\n  Hi <span class="x">there</span>\n</p>)))
+  end
+
+  def test_it_rejects_unknown_options
+    error = assert_raises(ArgumentError) { minify("<p></p>", { spaces: true }) }
+
+    assert_equal("minify[:spaces]: unknown key", error.message)
+    assert_raises(ArgumentError) { minify("<p></p>", "yes") }
+  end
+end
@@ -0,0 +1,73 @@
+# frozen_string_literal: true
+
+require "test_helper"
+
+class SelmaRewriterPrettyTest < Minitest::Test
+  def pretty(html, pretty = true)
+    Selma::Rewriter.new(sanitizer: nil, pretty: pretty).rewrite(html)
+  end
+
+  def test_it_indents_nested_blocks
+    html = %(<ul><li>One</li><li><p>Two <em>and</em> a half</p></li></ul>)
+
+    assert_equal(<<~HTML.chomp, pretty(html))
+      <ul>
+        <li>One</li>
+        <li>
+          <p>Two <em>and</em> a half</p>
+        </li>
+      </ul>
+    HTML
+  end
+
+  def test_it_puts_the_head_on_lines_of_its_own
+    html = %(<!DOCTYPE html><html><head><meta charset="utf-8"><title>Hi</title></head><body><p>Hi</p></body></html>)
+
+    assert_equal(<<~HTML.chomp, pretty(html))
+      <!DOCTYPE html>
+      <html>
+        <head>
+          <meta charset="utf-8">
+          <title>Hi</title>
+        </head>
+        <body>
+          <p>Hi</p>
+        </body>
+      </html>
+    HTML
+  end
+
+  def test_it_replaces_the_whitespace_around_blocks
+    html = %(<div>\n      Some text\n   <p>  and   a paragraph </p>\n</div>\n\n<p>Done</p>)
+
+    assert_equal(<<~HTML.chomp, pretty(html))
+      <div>
+        Some text
+        <p>and a paragraph</p>
+      </div>
+      <p>Done</p>
+    HTML
+  end
+
+  def test_it_leaves_preformatted_text_alone
+    html = %(<div><pre>  one\n <b>two</b></pre></div>)
+
+    assert_equal(%(<div>\n  <pre>  one\n <b>two</b></pre>\n</div>), pretty(html))
+  end
+
+  def test_it_takes_an_indent
+    html = %(<div><p>Hi</p></div>)
+
+    assert_equal(%(<div>\n    <p>Hi</p>\n</div>), pretty(html, { indent: 4 }))
+    assert_equal(%(<div>\n\t<p>Hi</p>\n</div>), pretty(html, { indent: "\t" }))
+  end
+
+  def test_it_rejects_bad_options
+    assert_raises(ArgumentError) { pretty("<p></p>", { indent: "--" }) }
+    assert_raises(ArgumentError) { pretty("<p></p>", { width: 80 }) }
+
+    error = assert_raises(ArgumentError) { Selma::Rewriter.new(minify: true, pretty: true) }
+
+    assert_equal("`minify:` and `pretty:` can't be used together", error.message)
+  end
+end
@@ -0,0 +1,61 @@
+# frozen_string_literal: true
+
+require "test_helper"
+
+class SelmaRewriterRelocateTest < Minitest::Test
+  class Footnotes
+    SELECTOR = Selma::Selector.new(match_element: "aside.footnote", relocate: "aside.footnote")
+
+    attr_reader :calls
+
+    def initialize
+      @calls = 0
+    end
+
+    def selector
+      SELECTOR
+    end
+
+    def handle_element(element)
+      element["id"] = "fn-#{@calls += 1}"
+    end
+
+    def handle_relocated(footnotes)
+      %(<section class="footnotes"><ol>#{footnotes.map { |note| "<li>#{note}</li>" }.join}</ol></section>)
+    end
+  end
+
+  def test_that_it_moves_elements_to_the_end
+    frag = %(<p>One<sup>1</sup></p><aside class="footnote">First</aside><p>Two<sup>2</sup></p><aside class="footnote">Second <em>note</em></aside>)
+    modified_doc = Selma::Rewriter.new(sanitizer: nil, handlers: [Footnotes.new]).rewrite(frag)
+
+    assert_equal(
+      %(<p>One<sup>1</sup></p><p>Two<sup>2</sup></p><section class="footnotes"><ol><li><aside class="footnote" id="fn-1">First</aside></li><li><aside class="footnote" id="fn-2">Second <em>note</em></aside></li></ol></section>),
+      modified_doc,
+    )
+  end
+
+  def test_that_it_keeps_nested_elements_within_their_parent
+    frag = %(<aside class="footnote">Outer <aside class="footnote">inner</aside></aside><p>Text</p>)
+    modified_doc = Selma::Rewriter.new(sanitizer: nil, handlers: [Footnotes.new]).rewrite(frag)
+
+    assert_equal(
+      %(<p>Text</p><section class="footnotes"><ol><li><aside class="footnote" id="fn-1">Outer <aside class="footnote" id="fn-2">inner</aside></aside></li></ol></section>),
+      modified_doc,
+    )
+  end
+
+  def test_that_it_leaves_documents_without_matches_alone
+    frag = %(<p>No footnotes here</p>)
+
+    assert_equal(frag, Selma::Rewriter.new(sanitizer: nil, handlers: [Footnotes.new]).rewrite(frag))
+  end
+
+  def test_that_it_sanitizes_what_handle_relocated_returns
+    frag = %(<p>One</p><aside class="footnote">First</aside>)
+    handler_sanitizer = Selma::Sanitizer.new({ elements: ["ol", "li", "aside"] })
+    modified_doc = Selma::Rewriter.new(sanitizer: nil, handlers: [Footnotes.new], handler_sanitizer: handler_sanitizer).rewrite(frag)
+
+    assert_equal(%(<p>One</p><ol><li><aside>First</aside></li></ol>), modified_doc)
+  end
+end
@@ -0,0 +1,162 @@
+# frozen_string_literal: true
+
+require "test_helper"
+
+class SelmaRewriterStatsTest < Minitest::Test
+  class CountLinks
+    SELECTOR = Selma::Selector.new(match_element: "a", match_text_within: "p")
+
+    def selector
+      SELECTOR
+    end
+
+    def handle_element(element); end
+
+    def handle_text_chunk(text); end
+  end
+
+  class CountNothing
+    SELECTOR = Selma::Selector.new(match_element: "table")
+
+    def selector
+      SELECTOR
+    end
+
+    def handle_element(element); end
+  end
+
+  def test_that_it_starts_empty
+    handler = CountNothing.new
+    rewriter = Selma::Rewriter.new(handlers: [handler])
+
+    assert_equal({
+      elements_removed: 0,
+      bytes_in: 0,
+      bytes_out: 0,
+      elapsed: 0.0,
+      sanitization_elapsed: 0.0,
+      native_elapsed: 0.0,
+      handlers: [{
+        handler: handler,
+        selector: CountNothing::SELECTOR,
+        elements_matched: 0,
+        text_chunks_matched: 0,
+        element_handlers_elapsed: 0.0,
+        text_handlers_elapsed: 0.0,
+      }],
+    }, rewriter.stats)
+  end
+
+  def test_that_it_counts_matches_per_handler
+    links = CountLinks.new
+    nothing = CountNothing.new
+    sanitizer = Selma::Sanitizer.new({ elements: ["p", "a"] })
+    rewriter = Selma::Rewriter.new(sanitizer: sanitizer, handlers: [links, nothing])
+
+    rewriter.rewrite(%(<p>One <a>two</a> <script>three</script><b>four</b></p><a>five</a>))
+    stats = rewriter.stats
+
+    assert_equal(2, stats[:elements_removed])
+    assert_equal([links, nothing], stats[:handlers].map { |h| h[:handler] })
+    assert_equal(2, stats[:handlers][0][:elements_matched])
+    assert_operator(stats[:handlers][0][:text_chunks_matched], :>, 0)
+    assert_equal(0, stats[:handlers][1][:elements_matched])
+  end
+
+  def test_that_it_only_reports_the_latest_rewrite
+    links = CountLinks.new
+    rewriter = Selma::Rewriter.new(sanitizer: nil, handlers: [links])
+
+    rewriter.rewrite("<a>One</a><a>Two</a>")
+    rewriter.rewrite("<a>Three</a>")
+
+    assert_equal(1, rewriter.stats[:handlers][0][:elements_matched])
+  end
+
+  class SlowHandler
+    SELECTOR = Selma::Selector.new(match_element: "a")
+
+    def selector
+      SELECTOR
+    end
+
+    def handle_element(element)
+      sleep(0.01)
+    end
+  end
+
+  def test_that_it_reports_timing_and_sizes
+    rewriter = Selma::Rewriter.new(sanitizer: Selma::Sanitizer.new({ elements: ["a"] }), handlers: [SlowHandler.new])
+    output = rewriter.rewrite("<a>One</a><b>Two</b>")
+    stats = rewriter.stats
+
+    assert_equal(20, stats[:bytes_in])
+    assert_equal(output.bytesize, stats[:bytes_out])
+    assert_operator(stats[:handlers][0][:element_handlers_elapsed], :>=, 0.01)
+    assert_operator(stats[:elapsed], :>=, stats[:handlers][0][:element_handlers_elapsed])
+    assert_operator(stats[:sanitization_elapsed], :<=, stats[:elapsed])
+    assert_in_delta(stats[:elapsed] - stats[:handlers][0][:element_handlers_elapsed], stats[:native_elapsed], 0.0001)
+  end
+
+  def test_that_it_reports_parser_stats
+    rewriter = Selma::Rewriter.new(sanitizer: nil, handlers: [CountLinks.new])
+    rewriter.rewrite("<p>One <a>two</a></p><!-- three -->")
+    stats = rewriter.last_run_stats
+
+    assert_equal(1, stats[:handlers][0][:elements_matched])
+    assert_equal(35, stats[:parser][:bytes_parsed])
+    assert_operator(stats[:parser][:selectors], :>=, 2)
+    refute(stats[:parser].key?(:elements))
+  end
+
+  def test_that_it_counts_tokens_when_profiling
+    rewriter = Selma::Rewriter.new(sanitizer: nil, handlers: [CountNothing.new], profile: true)
+    rewriter.rewrite("<p>One <a>two</a></p><!-- three --><br>")
+    parser = rewriter.last_run_stats[:parser]
+
+    assert_equal(3, parser[:elements])
+    assert_equal(2, parser[:text_chunks])
+    assert_equal(1, parser[:comments])
+  end
+
+  class FakeNotifications
+    attr_reader :events
+
+    def initialize
+      @events = []
+    end
+
+    def instrument(name, payload)
+      result = yield
+      @events << [name, payload]
+      result
+    end
+  end
+
+  def test_that_it_reports_to_an_instrumenter
+    notifications = FakeNotifications.new
+    Selma.instrumenter = notifications
+    rewriter = Selma::Rewriter.new(sanitizer: nil, handlers: [CountNothing.new])
+
+    assert_equal("<p>Wow!</p>", rewriter.rewrite("<p>Wow!</p>"))
+    assert_equal(1, notifications.events.length)
+
+    name, payload = notifications.events.first
+
+    assert_equal("rewrite.selma", name)
+    assert_equal(11, payload[:bytes_out])
+  ensure
+    Selma.instrumenter = nil
+  end
+
+  def test_that_it_reports_to_a_callable
+    events = []
+    Selma.instrumenter = ->(name, payload) { events << [name, payload] }
+    Selma::Rewriter.new(sanitizer: nil, handlers: [CountNothing.new]).rewrite("<p>Wow!</p>")
+
+    assert_equal(["rewrite.selma"], events.map(&:first))
+    assert_equal(11, events.first.last[:bytes_in])
+  ensure
+    Selma.instrumenter = nil
+  end
+end
@@ -0,0 +1,62 @@
+# frozen_string_literal: true
+
+require "test_helper"
+
+class SelmaRewriterTextReplaceTest < Minitest::Test
+  def rewrite(frag, *replacements, sanitizer: nil)
+    Selma::Rewriter.new(sanitizer: sanitizer, handlers: replacements).rewrite(frag)
+  end
+
+  def test_that_it_replaces_text
+    trademarks = Selma::Rewriter::TextReplace.new(pattern: /\bSelma\b/, replacement: "Selma™", except: ["code"])
+    frag = "<p>Selma is <em>nice</em>, says Selma; <code>Selma.new</code></p>"
+
+    assert_equal("<p>Selma™ is <em>nice</em>, says Selma™; <code>Selma.new</code></p>", rewrite(frag, trademarks))
+  end
+
+  def test_that_it_only_replaces_text_within_the_selector
+    dashes = Selma::Rewriter::TextReplace.new(pattern: "--", replacement: "—", within: "p")
+    frag = "<h1>A -- B</h1><p>C -- <b>D -- E</b></p>"
+
+    assert_equal("<h1>A -- B</h1><p>C — <b>D — E</b></p>", rewrite(frag, dashes))
+  end
+
+  def test_that_it_uses_groups_and_flags
+    dimensions = Selma::Rewriter::TextReplace.new(pattern: /(\d+) X (\d+)/i, replacement: "$1×$2")
+    frag = "<p>1920 x 1080, or 1280 X 720</p>"
+
+    assert_equal("<p>1920×1080, or 1280×720</p>", rewrite(frag, dimensions))
+  end
+
+  def test_that_it_unescapes_and_escapes_text
+    ampersands = Selma::Rewriter::TextReplace.new(pattern: "&", replacement: "and")
+    tags = Selma::Rewriter::TextReplace.new(pattern: "Jerry", replacement: "<b>Jerry</b>")
+    frag = "<p>Tom &amp; Jerry</p>"
+
+    assert_equal("<p>Tom and &lt;b&gt;Jerry&lt;/b&gt;</p>", rewrite(frag, ampersands, tags))
+  end
+
+  def test_that_it_leaves_scripts_alone
+    replacement = Selma::Rewriter::TextReplace.new(pattern: "alert", replacement: "nope")
+    frag = "<script>alert(1)</script><p>alert</p>"
+
+    assert_equal("<script>alert(1)</script><p>nope</p>", rewrite(frag, replacement))
+  end
+
+  def test_that_it_runs_after_the_sanitizer
+    replacement = Selma::Rewriter::TextReplace.new(pattern: /\(c\)/, replacement: "©")
+    frag = "<p>Copyright (c) <span>2024</span></p>"
+
+    assert_equal("<p>Copyright © 2024</p>", rewrite(frag, replacement, sanitizer: Selma::Sanitizer.new({ elements: ["p"] })))
+  end
+
+  def test_that_it_raises_on_invalid_patterns
+    assert_raises(ArgumentError) do
+      Selma::Rewriter::TextReplace.new(pattern: "(unclosed", replacement: "")
+    end
+
+    assert_raises(Selma::SelectorError) do
+      Selma::Rewriter::TextReplace.new(pattern: "a", replacement: "b", within: "p[")
+    end
+  end
+end
@@ -125,6 +125,26 @@ class SelmaRewriterTextTest < Minitest::Test
     assert_equal("<div class=\"neato\"><p>Could y'all visit <a>this link and tell me what y'all think?</a> Thank y'all!</div>", modified_doc)
   end
 
+  class TextInjectHTML
+    SELECTOR = Selma::Selector.new(match_text_within: "p")
+
+    def selector
+      SELECTOR
+    end
+
+    def handle_text_chunk(text)
+      text.replace(text.to_s.sub("@you", %(<a href="javascript:alert(1)">@you</a>)), as: :html)
+    end
+  end
+
+  def test_that_it_sanitizes_injected_html
+    frag = "<p>Thanks, @you</p>"
+    handler_sanitizer = Selma::Sanitizer.new({ elements: ["a"], attributes: { "a" => ["href"] }, protocols: { "a" => { "href" => ["https"] } } })
+    modified_doc = Selma::Rewriter.new(sanitizer: nil, handlers: [TextInjectHTML.new], handler_sanitizer: handler_sanitizer).rewrite(frag)
+
+    assert_equal("<p>Thanks, <a>@you</a></p>", modified_doc)
+  end
+
   class TextMatchAndRejectElements
     SELECTOR = Selma::Selector.new(match_text_within: "*", ignore_text_within: ["code", "pre"])
 
@@ -217,4 +237,98 @@ class SelmaRewriterTextTest < Minitest::Test
 
     assert_equal(%(<span><img class="emoji" title=":flag_ar:" alt=":flag_ar:" src="emoji/1f1e6-1f1f7.png" height="20" width="20" align="absmiddle"></span>), modified_doc)
   end
+
+  class TextWithElementContext
+    SELECTOR = Selma::Selector.new(match_element: "a", match_text_within: "a, p")
+
+    def selector
+      SELECTOR
+    end
+
+    def handle_element(element)
+      element["href"]
+    end
+
+    def handle_text_chunk(text, href)
+      text.replace("#{text} (#{href || "none"})", as: :text)
+    end
+  end
+
+  def test_that_it_passes_element_context_to_text
+    frag = %(<p>Outside <a href="/one">One</a> <a href="/two">Two</a></p>)
+    modified_doc = Selma::Rewriter.new(sanitizer: nil, handlers: [TextWithElementContext.new]).rewrite(frag)
+
+    assert_equal(%(<p>Outside  (none)<a href="/one">One (/one)</a>  (none)<a href="/two">Two (/two)</a></p>), modified_doc)
+  end
+
+  class NestedElementContext
+    SELECTOR = Selma::Selector.new(match_element: "div", match_text_within: "div")
+
+    def selector
+      SELECTOR
+    end
+
+    def handle_element(element)
+      element["id"]
+    end
+
+    def handle_text_chunk(text, id)
+      text.replace("#{id}:#{text}", as: :text)
+    end
+  end
+
+  def test_that_it_passes_the_innermost_element_context
+    frag = %(<div id="outer">A<div id="inner">B</div>C</div>)
+    modified_doc = Selma::Rewriter.new(sanitizer: nil, handlers: [NestedElementContext.new]).rewrite(frag)
+
+    assert_equal(%(<div id="outer">outer:A<div id="inner">inner:B</div>outer:C</div>), modified_doc)
+  end
+
+  class NoElementContext
+    SELECTOR = Selma::Selector.new(match_element: "a", match_text_within: "a")
+
+    def selector
+      SELECTOR
+    end
+
+    def handle_element(element)
+      "ignored"
+    end
+
+    def handle_text_chunk(text)
+      text.replace(text.to_s.upcase, as: :text)
+    end
+  end
+
+  def test_that_it_does_not_pass_element_context_to_single_argument_handlers
+    frag = %(<a href="/">Wow</a>)
+    modified_doc = Selma::Rewriter.new(sanitizer: nil, handlers: [NoElementContext.new]).rewrite(frag)
+
+    assert_equal(%(<a href="/">WOW</a>), modified_doc)
+  end
+
+  class TextAncestors
+    SELECTOR = Selma::Selector.new(match_text_within: "*")
+
+    attr_reader :ancestors
+
+    def initialize
+      @ancestors = []
+    end
+
+    def selector
+      SELECTOR
+    end
+
+    def handle_text_chunk(text)
+      @ancestors << [text.to_s, text.ancestors]
+    end
+  end
+
+  def test_that_it_exposes_text_ancestors
+    handler = TextAncestors.new
+    Selma::Rewriter.new(sanitizer: nil, handlers: [handler]).rewrite("<div>One<p>Two<br>Three</p></div>")
+
+    assert_equal([["One", ["div"]], ["Two", ["div", "p"]], ["Three", ["div", "p"]]], handler.ancestors)
+  end
 end
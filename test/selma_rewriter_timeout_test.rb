@@ -0,0 +1,86 @@
+# frozen_string_literal: true
+
+require "test_helper"
+
+class SelmaRewriterTimeoutTest < Minitest::Test
+  class SlowHandler
+    SELECTOR = Selma::Selector.new(match_element: "p")
+
+    attr_reader :calls
+
+    def initialize(delay)
+      @delay = delay
+      @calls = 0
+    end
+
+    def selector
+      SELECTOR
+    end
+
+    def handle_element(element)
+      @calls += 1
+      sleep(@delay)
+    end
+  end
+
+  def test_that_a_rewrite_within_its_timeout_is_unaffected
+    rewriter = Selma::Rewriter.new(sanitizer: Selma::Sanitizer.basic, timeout: 10)
+
+    assert_equal("<p>Hello</p>", rewriter.rewrite("<p>Hello</p>"))
+  end
+
+  def test_that_slow_handlers_time_out
+    handler = SlowHandler.new(0.05)
+    rewriter = Selma::Rewriter.new(sanitizer: nil, handlers: [handler], timeout: 0.01)
+
+    error = assert_raises(Selma::TimeoutError) { rewriter.rewrite("<p>1</p><p>2</p><p>3</p>") }
+    assert_match(/timeout of 0.01 seconds/, error.message)
+    # the first handler call isn't interrupted, but no others are made after it
+    assert_equal(1, handler.calls)
+  end
+
+  def test_that_sanitizing_times_out
+    html = "<p><b>lorem</b> ipsum</p>" * 50_000
+    rewriter = Selma::Rewriter.new(sanitizer: Selma::Sanitizer.relaxed, timeout: 0.000_000_001)
+
+    assert_raises(Selma::TimeoutError) { rewriter.rewrite(html) }
+  end
+
+  def test_that_a_timeout_is_a_selma_error
+    assert_operator(Selma::TimeoutError, :<, Selma::Error)
+  end
+
+  def test_that_the_output_is_the_same_with_a_timeout
+    html = %(<p class="a">#{"lorem <b>ipsum</b> dolor " * 10_000}</p>)
+    without = Selma::Rewriter.new(sanitizer: Selma::Sanitizer.relaxed).rewrite(html)
+    with = Selma::Rewriter.new(sanitizer: Selma::Sanitizer.relaxed, timeout: 60).rewrite(html)
+
+    assert_equal(without, with)
+  end
+
+  def test_that_the_next_rewrite_starts_over
+    handler = SlowHandler.new(0.05)
+    rewriter = Selma::Rewriter.new(sanitizer: nil, handlers: [handler], timeout: 0.01)
+    assert_raises(Selma::TimeoutError) { rewriter.rewrite("<p>1</p><p>2</p>") }
+
+    assert_equal("<p>1</p>", rewriter.rewrite("<p>1</p>"))
+  end
+
+  def test_that_derived_rewriters_keep_the_timeout
+    handler = SlowHandler.new(0.05)
+    rewriter = Selma::Rewriter.new(sanitizer: nil, handlers: [handler], timeout: 0.01).with(sanitizer: nil)
+
+    assert_raises(Selma::TimeoutError) { rewriter.rewrite("<p>1</p><p>2</p>") }
+  end
+
+  def test_that_the_timeout_must_be_positive
+    [0, -1, Float::NAN, Float::INFINITY].each do |timeout|
+      assert_raises(ArgumentError) { Selma::Rewriter.new(timeout: timeout) }
+    end
+    assert_raises(TypeError) { Selma::Rewriter.new(timeout: "1") }
+  end
+
+  def test_that_a_nil_timeout_is_no_timeout
+    assert_equal("<p>Hi</p>", Selma::Rewriter.new(sanitizer: Selma::Sanitizer.basic, timeout: nil).rewrite("<p>Hi</p>"))
+  end
+end
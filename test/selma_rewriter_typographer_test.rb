@@ -0,0 +1,45 @@
+# frozen_string_literal: true
+
+require "test_helper"
+
+class SelmaRewriterTypographerTest < Minitest::Test
+  def setup
+    @rewriter = Selma::Rewriter.new(sanitizer: nil, typographer: true)
+  end
+
+  def test_that_it_smartens_punctuation
+    frag = %(<p>"Hello," she said -- it's... fine --- really.</p>)
+
+    assert_equal("<p>“Hello,” she said – it’s… fine — really.</p>", @rewriter.rewrite(frag))
+  end
+
+  def test_that_it_leaves_code_alone
+    frag = %(<p>"a" <code>"b"</code> <kbd>--</kbd></p><pre>'c' ...</pre><script>x = "y"</script>)
+
+    assert_equal(%(<p>“a” <code>"b"</code> <kbd>--</kbd></p><pre>'c' ...</pre><script>x = "y"</script>), @rewriter.rewrite(frag))
+  end
+
+  def test_that_quotes_close_across_elements
+    frag = %(<p>"<em>Hi</em>" and '<a href="/">there</a>'</p>)
+
+    assert_equal(%(<p>“<em>Hi</em>” and ‘<a href="/">there</a>’</p>), @rewriter.rewrite(frag))
+  end
+
+  def test_that_quotes_open_at_the_start_of_a_block
+    frag = %(<p>Done</p><p>"Next"</p><ul><li>a</li><li>'b'</li></ul>)
+
+    assert_equal(%(<p>Done</p><p>“Next”</p><ul><li>a</li><li>‘b’</li></ul>), @rewriter.rewrite(frag))
+  end
+
+  def test_that_it_unescapes_entities
+    frag = %(<p>&quot;Tom &amp; Jerry&quot;</p>)
+
+    assert_equal(%(<p>“Tom &amp; Jerry”</p>), @rewriter.rewrite(frag))
+  end
+
+  def test_that_it_is_off_by_default
+    frag = %(<p>"Hello" -- there</p>)
+
+    assert_equal(frag, Selma::Rewriter.new(sanitizer: nil, handlers: [Selma::Rewriter::TextReplace.new(pattern: "x", replacement: "y")]).rewrite(frag))
+  end
+end
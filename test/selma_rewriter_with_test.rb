@@ -0,0 +1,63 @@
+# frozen_string_literal: true
+
+require "test_helper"
+
+class SelmaRewriterWithTest < Minitest::Test
+  class BaseUrl
+    SELECTOR = Selma::Selector.new(match_element: "a[href^='/']")
+
+    def initialize(base_url)
+      @base_url = base_url
+    end
+
+    def selector
+      SELECTOR
+    end
+
+    def handle_element(element)
+      element["href"] = "#{@base_url}#{element["href"]}"
+    end
+  end
+
+  def setup
+    @sanitizer = Selma::Sanitizer.new({ elements: ["a", "p"], attributes: { "a" => ["href"] } })
+    @rewriter = Selma::Rewriter.new(sanitizer: @sanitizer, handlers: [BaseUrl.new("https://a.example")], minify: true)
+  end
+
+  def test_it_replaces_the_handlers
+    derived = @rewriter.with(handlers: [BaseUrl.new("https://b.example")])
+    html = %(<p><a href="/x">x</a></p>)
+
+    assert_equal(%(<p><a href="https://b.example/x">x</a></p>), derived.rewrite(html))
+    assert_equal(%(<p><a href="https://a.example/x">x</a></p>), @rewriter.rewrite(html))
+  end
+
+  def test_it_replaces_the_sanitizer
+    derived = @rewriter.with(sanitizer: Selma::Sanitizer.new({ elements: ["p"] }))
+
+    assert_equal("<p>x</p>", derived.rewrite(%(<p><a href="/x">x</a></p>)))
+  end
+
+  def test_it_keeps_everything_else
+    derived = @rewriter.with
+    html = %(<p>a    <a href="/x">x</a></p><script>alert(1)</script>)
+
+    assert_equal(@rewriter.rewrite(html), derived.rewrite(html))
+  end
+
+  def test_it_can_drop_the_sanitizer
+    derived = @rewriter.with(sanitizer: nil)
+
+    assert_equal(%(<b><a href="https://a.example/x">x</a></b>), derived.rewrite(%(<b><a href="/x">x</a></b>)))
+  end
+
+  def test_it_needs_something_to_do
+    rewriter = Selma::Rewriter.new(sanitizer: nil, handlers: [BaseUrl.new("https://a.example")])
+
+    assert_raises(ArgumentError) { rewriter.with(handlers: []) }
+  end
+
+  def test_it_rejects_unknown_keywords
+    assert_raises(ArgumentError) { @rewriter.with(typographer: true) }
+  end
+end
@@ -0,0 +1,47 @@
+# frozen_string_literal: true
+
+require "test_helper"
+
+class SelmaRewriterXhtmlTest < Minitest::Test
+  def xhtml(html, **options)
+    Selma::Rewriter.new(sanitizer: nil, xhtml: true, **options).rewrite(html)
+  end
+
+  def test_it_closes_void_elements
+    assert_equal(%(<p>One<br />two<img src="a.png" /></p>), xhtml(%(<p>One<br>two<img src="a.png"></p>)))
+  end
+
+  def test_it_lowercases_names
+    assert_equal(%(<div class="a"><b>Hi</b></div>), xhtml(%(<DIV Class="a"><B>Hi</B></DIV>)))
+  end
+
+  def test_it_keeps_the_case_of_foreign_names
+    html = %(<svg viewBox="0 0 10 10"><foreignObject><circle r="4"/></foreignObject></svg>)
+
+    assert_equal(%(<svg viewBox="0 0 10 10"><foreignObject><circle r="4" /></foreignObject></svg>), xhtml(html))
+  end
+
+  def test_it_quotes_every_attribute
+    html = %(<input type=checkbox checked title='say "hi"'>)
+
+    assert_equal(%(<input type="checkbox" checked="checked" title="say &quot;hi&quot;" />), xhtml(html))
+  end
+
+  def test_it_escapes_stray_characters
+    html = %(<p title="a&b">1 < 2 &amp; 3 & 4 &#169;</p>)
+
+    assert_equal(%(<p title="a&amp;b">1 &lt; 2 &amp; 3 &amp; 4 &#169;</p>), xhtml(html))
+  end
+
+  def test_it_leaves_scripts_alone
+    assert_equal(%(<script>if (a < b && c) {}</script>), xhtml(%(<script>if (a < b && c) {}</script>)))
+  end
+
+  def test_it_can_be_pretty
+    assert_equal(%(<div>\n  <p>One<br /></p>\n  <hr />\n</div>), xhtml(%(<DIV><p>One<br></p><hr></DIV>), pretty: true))
+  end
+
+  def test_it_cannot_be_minified
+    assert_raises(ArgumentError) { Selma::Rewriter.new(xhtml: true, minify: true) }
+  end
+end
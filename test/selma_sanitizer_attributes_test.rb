@@ -0,0 +1,783 @@
+# frozen_string_literal: true
+
+require "test_helper"
+
+module Selma
+  class SanitizerAttributesTest < Minitest::Test
+    describe "name_prefix" do
+      def setup
+        @config = {
+          elements: ["a", "h2"],
+          attributes: {
+            "a" => ["href", "name"],
+            "h2" => ["id"],
+          },
+          protocols: {
+            "a" => { "href" => ["https", :relative] },
+          },
+          name_prefix: "user-content-",
+        }
+      end
+
+      def test_it_prefixes_ids_and_names
+        sanitizer = Selma::Sanitizer.new(@config)
+        html = %(<h2 id="intro">Intro</h2><a name="top">Top</a>)
+        result = Selma::Rewriter.new(sanitizer: sanitizer).rewrite(html)
+
+        assert_equal(%(<h2 id="user-content-intro">Intro</h2><a name="user-content-top">Top</a>), result)
+      end
+
+      def test_it_prefixes_fragment_links
+        sanitizer = Selma::Sanitizer.new(@config)
+        html = %(<a href="#intro">Intro</a> <a href="https://example.com/#intro">Elsewhere</a>)
+        result = Selma::Rewriter.new(sanitizer: sanitizer).rewrite(html)
+
+        assert_equal(%(<a href="#user-content-intro">Intro</a> <a href="https://example.com/#intro">Elsewhere</a>), result)
+      end
+
+      def test_it_does_not_double_prefix
+        sanitizer = Selma::Sanitizer.new(@config)
+        html = %(<h2 id="user-content-intro">Intro</h2><a href="#user-content-intro">Intro</a>)
+        result = Selma::Rewriter.new(sanitizer: sanitizer).rewrite(html)
+
+        assert_equal(html, result)
+      end
+
+      def test_it_does_not_prefix_by_default
+        sanitizer = Selma::Sanitizer.new(@config.except(:name_prefix))
+        html = %(<h2 id="intro">Intro</h2><a href="#intro">Intro</a>)
+        result = Selma::Rewriter.new(sanitizer: sanitizer).rewrite(html)
+
+        assert_nil(sanitizer.name_prefix)
+        assert_equal(html, result)
+      end
+    end
+
+    describe "add_attributes" do
+      def setup
+        @config = {
+          elements: ["a", "img"],
+          attributes: {
+            "a" => ["href", "rel"],
+            "img" => ["src"],
+          },
+          protocols: {
+            "a" => { "href" => ["https"] },
+            "img" => { "src" => ["https"] },
+          },
+          add_attributes: {
+            "a" => { "rel" => "nofollow" },
+            "img" => { "loading" => "lazy" },
+          },
+        }
+      end
+
+      def test_it_adds_attributes
+        sanitizer = Selma::Sanitizer.new(@config)
+        html = %(<a href="https://example.com">hi</a><img src="https://example.com/a.png">)
+        result = Selma::Rewriter.new(sanitizer: sanitizer).rewrite(html)
+
+        assert_equal(%(<a href="https://example.com" rel="nofollow">hi</a><img src="https://example.com/a.png" loading="lazy">), result)
+      end
+
+      def test_it_merges_token_list_attributes
+        sanitizer = Selma::Sanitizer.new(@config)
+        html = %(<a href="https://example.com" rel="noopener nofollow">hi</a> <a rel="noopener">there</a>)
+        result = Selma::Rewriter.new(sanitizer: sanitizer).rewrite(html)
+
+        assert_equal(%(<a href="https://example.com" rel="noopener nofollow">hi</a> <a rel="noopener nofollow">there</a>), result)
+      end
+
+      def test_it_overwrites_other_attributes
+        sanitizer = Selma::Sanitizer.new(@config)
+        html = %(<img src="https://example.com/a.png" loading="eager">)
+        result = Selma::Rewriter.new(sanitizer: sanitizer).rewrite(html)
+
+        assert_equal(%(<img src="https://example.com/a.png" loading="lazy">), result)
+      end
+
+      def test_it_does_not_add_attributes_to_removed_elements
+        sanitizer = Selma::Sanitizer.new(@config.merge(elements: ["img"]))
+        html = %(<a href="https://example.com">hi</a>)
+        result = Selma::Rewriter.new(sanitizer: sanitizer).rewrite(html)
+
+        assert_equal("hi", result)
+      end
+    end
+
+    describe "remove_attributes" do
+      def setup
+        @config = {
+          elements: ["table", "tr", "td"],
+          attributes: {
+            all: ["width", "height"],
+          },
+          remove_attributes: {
+            "table" => ["width", "height"],
+          },
+        }
+      end
+
+      def test_it_removes_attributes_allowed_globally
+        sanitizer = Selma::Sanitizer.new(@config)
+        html = %(<table width="100" height="100"><tr><td width="50" height="50">hi</td></tr></table>)
+        result = Selma::Rewriter.new(sanitizer: sanitizer).rewrite(html)
+
+        assert_equal(%(<table><tr><td width="50" height="50">hi</td></tr></table>), result)
+      end
+
+      def test_it_removes_attributes_allowed_for_the_element
+        config = @config.merge(attributes: { "table" => ["width", "border"] })
+        sanitizer = Selma::Sanitizer.new(config)
+        html = %(<table width="100" border="1"><tr><td>hi</td></tr></table>)
+        result = Selma::Rewriter.new(sanitizer: sanitizer).rewrite(html)
+
+        assert_equal(%(<table border="1"><tr><td>hi</td></tr></table>), result)
+      end
+    end
+
+    describe "boolean_attributes" do
+      def setup
+        @config = {
+          elements: ["details", "summary", "video", "input", "div", "my-player"],
+          attributes: {
+            "details" => ["open"],
+            "video" => ["controls", "autoplay"],
+            "input" => ["type", "checked"],
+            "div" => ["hidden"],
+            "my-player" => ["autoplay"],
+          },
+        }
+      end
+
+      def rewrite(config, html)
+        Selma::Rewriter.new(sanitizer: Selma::Sanitizer.new(config)).rewrite(html)
+      end
+
+      def test_it_keeps_boolean_attributes_without_values
+        html = %(<details open><summary>s</summary>x</details><video controls autoplay></video>)
+
+        assert_equal(html, rewrite(@config, html))
+      end
+
+      def test_it_drops_their_values_without_checking_them
+        assert_equal(
+          %(<details open><summary>s</summary></details><video controls autoplay></video>),
+          rewrite(@config, %(<details open="open"><summary>s</summary></details><video controls="https://example.com/" autoplay="false"></video>)),
+        )
+      end
+
+      def test_it_still_removes_those_which_are_not_allowed
+        assert_equal(
+          %(<input type="checkbox" checked>),
+          rewrite(@config, %(<input type="checkbox" checked disabled>)),
+        )
+      end
+
+      def test_hidden_keeps_its_value
+        html = %(<div hidden="until-found">x</div>)
+
+        assert_equal(html, rewrite(@config, html))
+      end
+
+      def test_it_can_be_configured_per_element
+        html = %(<my-player autoplay="https://example.com/"></my-player>)
+
+        assert_equal(%(<my-player></my-player>), rewrite(@config, html))
+        assert_equal(
+          %(<my-player autoplay></my-player>),
+          rewrite(@config.merge(boolean_attributes: { "my-player" => ["autoplay"] }), html),
+        )
+      end
+    end
+
+    describe "paranoid" do
+      def setup
+        @config = {
+          elements: ["a", "div"],
+          attributes: {
+            all: ["onclick", "onmouseover", "title", "data-url"],
+            "a" => ["href"],
+          },
+          protocols: {
+            "a" => { "href" => ["https", "javascript"] },
+          },
+        }
+      end
+
+      def test_it_is_off_by_default
+        sanitizer = Selma::Sanitizer.new(@config)
+        html = %(<div onclick="alert(1)" title="hi">x</div>)
+
+        assert_equal(html, Selma::Rewriter.new(sanitizer: sanitizer).rewrite(html))
+      end
+
+      def test_it_removes_event_handlers_even_when_allowed
+        sanitizer = Selma::Sanitizer.new(@config.merge(paranoid: true))
+        html = %(<div onclick="alert(1)" ONMOUSEOVER="alert(2)" title="hi">x</div>)
+
+        assert_equal(%(<div title="hi">x</div>), Selma::Rewriter.new(sanitizer: sanitizer).rewrite(html))
+      end
+
+      def test_it_removes_javascript_values_even_when_allowed
+        sanitizer = Selma::Sanitizer.new(@config.merge(paranoid: true))
+
+        assert_equal(
+          %(<a>x</a>),
+          Selma::Rewriter.new(sanitizer: sanitizer).rewrite(%(<a href="javascript:alert(1)">x</a>)),
+        )
+        assert_equal(
+          %(<a>x</a>),
+          Selma::Rewriter.new(sanitizer: sanitizer).rewrite(%(<a href="  JaVa&#x09;Script:alert(1)">x</a>)),
+        )
+        assert_equal(
+          %(<div>x</div>),
+          Selma::Rewriter.new(sanitizer: sanitizer).rewrite(%(<div data-url="vbscript:msgbox(1)">x</div>)),
+        )
+        assert_equal(
+          %(<a href="https://example.com">x</a>),
+          Selma::Rewriter.new(sanitizer: sanitizer).rewrite(%(<a href="https://example.com">x</a>)),
+        )
+      end
+
+      def test_its_denylist_can_be_overridden
+        sanitizer = Selma::Sanitizer.new(@config.merge(paranoid: { attribute_prefixes: ["onmouse"] }))
+        html = %(<div onclick="alert(1)" onmouseover="alert(2)" data-url="vbscript:x">x</div>)
+
+        assert_equal(
+          %(<div onclick="alert(1)">x</div>),
+          Selma::Rewriter.new(sanitizer: sanitizer).rewrite(html),
+        )
+      end
+
+      def test_it_rejects_malformed_denylists
+        error = assert_raises(Selma::ConfigError) { Selma::Sanitizer.new(paranoid: { prefixes: ["on"] }) }
+
+        assert_equal("config[:paranoid][:prefixes]: unknown key", error.message)
+      end
+    end
+
+    describe "srcdoc" do
+      def setup
+        @sanitizer = Selma::Sanitizer.new({
+          elements: ["iframe", "b", "a"],
+          attributes: { "iframe" => ["srcdoc"], "a" => ["href"] },
+          protocols: { "a" => { "href" => ["https"] } },
+        })
+      end
+
+      def test_it_sanitizes_the_embedded_document
+        html = %(<iframe srcdoc="<b>hi</b><script>alert(1)</script><img src=x onerror=alert(1)>"></iframe>)
+
+        assert_equal(
+          %(<iframe srcdoc="&lt;b&gt;hi&lt;/b&gt;"></iframe>),
+          Selma::Rewriter.new(sanitizer: @sanitizer).rewrite(html),
+        )
+      end
+
+      def test_it_applies_the_rest_of_the_config_within_it
+        html = %(<iframe srcdoc="&lt;a href=&quot;https://example.com&quot; onclick=&quot;x()&quot;&gt;ok&lt;/a&gt;&lt;a href=&quot;javascript:x()&quot;&gt;no&lt;/a&gt;"></iframe>)
+
+        assert_equal(
+          %(<iframe srcdoc="&lt;a href=&quot;https://example.com&quot;&gt;ok&lt;/a&gt;&lt;a&gt;no&lt;/a&gt;"></iframe>),
+          Selma::Rewriter.new(sanitizer: @sanitizer).rewrite(html),
+        )
+      end
+
+      def test_it_sanitizes_nested_documents
+        html = %(<iframe srcdoc="<iframe srcdoc='&lt;b onclick=x()&gt;deep&lt;/b&gt;&lt;script&gt;x()&lt;/script&gt;'></iframe>"></iframe>)
+
+        assert_equal(
+          %(<iframe srcdoc="&lt;iframe srcdoc=&quot;&amp;lt;b&amp;gt;deep&amp;lt;/b&amp;gt;&quot;&gt;&lt;/iframe&gt;"></iframe>),
+          Selma::Rewriter.new(sanitizer: @sanitizer).rewrite(html),
+        )
+      end
+
+      def test_it_is_removed_from_documents_nested_too_deeply
+        html = "<b>deep</b>"
+        12.times do
+          escaped = html.gsub("&", "&amp;").gsub('"', "&quot;").gsub("<", "&lt;").gsub(">", "&gt;")
+          html = %(<iframe srcdoc="#{escaped}"></iframe>)
+        end
+
+        sanitized = Selma::Rewriter.new(sanitizer: @sanitizer).rewrite(html)
+
+        assert_equal(3, sanitized.scan("srcdoc").length)
+        refute_includes(sanitized, "deep")
+      end
+
+      def test_it_is_removed_when_not_allowed
+        sanitizer = Selma::Sanitizer.new({ elements: ["iframe"] })
+
+        assert_equal(
+          %(<iframe></iframe>),
+          Selma::Rewriter.new(sanitizer: sanitizer).rewrite(%(<iframe srcdoc="<b>hi</b>"></iframe>)),
+        )
+      end
+    end
+
+    describe "max_attributes" do
+      def many_attributes(count)
+        attributes = (1..count).map { |i| %(data-a#{i}="#{i}") }.join(" ")
+        %(<p>Before</p><b title="t" #{attributes}>Bold</b><p>After</p>)
+      end
+
+      def test_it_removes_elements_with_too_many_attributes
+        sanitizer = Selma::Sanitizer.new({ elements: ["b", "p"], attributes: { "b" => ["title"] }, max_attributes: 10 })
+
+        assert_equal(
+          "<p>Before</p><p>After</p>",
+          Selma::Rewriter.new(sanitizer: sanitizer).rewrite(many_attributes(10_000)),
+        )
+        assert_equal(
+          %(<p>Before</p><b title="t">Bold</b><p>After</p>),
+          Selma::Rewriter.new(sanitizer: sanitizer).rewrite(many_attributes(9)),
+        )
+      end
+
+      def test_it_can_drop_the_extra_attributes
+        sanitizer = Selma::Sanitizer.new({
+          elements: ["b", "p"],
+          attributes: { "b" => ["title", "data-a1", "data-a3"] },
+          max_attributes: { count: 3, overflow: :drop },
+        })
+
+        assert_equal(
+          %(<p>Before</p><b title="t" data-a1="1">Bold</b><p>After</p>),
+          Selma::Rewriter.new(sanitizer: sanitizer).rewrite(many_attributes(10_000)),
+        )
+      end
+
+      def test_it_rejects_invalid_limits
+        error = assert_raises(Selma::ConfigError) { Selma::Sanitizer.new({ max_attributes: 0 }) }
+
+        assert_equal("config[:max_attributes]: expected a positive Integer, got 0", error.message)
+
+        error = assert_raises(Selma::ConfigError) { Selma::Sanitizer.new({ max_attributes: { count: 5, overflow: :truncate } }) }
+
+        assert_equal("config[:max_attributes][:overflow]: expected :drop or :remove, got :truncate", error.message)
+      end
+    end
+
+    describe "max_attribute_value_bytes" do
+      def test_it_drops_attributes_with_long_values
+        sanitizer = Selma::Sanitizer.new({ elements: ["b"], attributes: { "b" => ["title", "data-x"] }, max_attribute_value_bytes: 8 })
+
+        assert_equal(
+          %(<b data-x="12345678">Bold</b>),
+          Selma::Rewriter.new(sanitizer: sanitizer).rewrite(%(<b title="#{"a" * 1_000_000}" data-x="12345678">Bold</b>)),
+        )
+      end
+
+      def test_it_measures_unescaped_values
+        sanitizer = Selma::Sanitizer.new({ elements: ["b"], attributes: { "b" => ["title"] }, max_attribute_value_bytes: 3 })
+
+        assert_equal(
+          %(<b title="&lt;&gt;">Bold</b>),
+          Selma::Rewriter.new(sanitizer: sanitizer).rewrite(%(<b title="&lt;&gt;">Bold</b>)),
+        )
+      end
+
+      def test_it_can_truncate_long_values
+        sanitizer = Selma::Sanitizer.new({
+          elements: ["b"],
+          attributes: { "b" => ["title"] },
+          max_attribute_value_bytes: { bytes: 4, overflow: :truncate },
+        })
+
+        assert_equal(
+          %(<b title="caf">Bold</b>),
+          Selma::Rewriter.new(sanitizer: sanitizer).rewrite(%(<b title="café au lait">Bold</b>)),
+        )
+      end
+
+      def test_it_rejects_invalid_limits
+        error = assert_raises(Selma::ConfigError) { Selma::Sanitizer.new({ max_attribute_value_bytes: { size: 5 } }) }
+
+        assert_equal("config[:max_attribute_value_bytes][:size]: unknown key", error.message)
+      end
+    end
+
+    describe "max_classes" do
+      def test_it_drops_classes_past_the_limit
+        sanitizer = Selma::Sanitizer.new({ elements: ["b"], attributes: { "b" => ["class"] }, max_classes: 2 })
+        classes = (1..100_000).map { |i| "c#{i}" }.join(" ")
+
+        assert_equal(
+          %(<b class="c1 c2">Bold</b>),
+          Selma::Rewriter.new(sanitizer: sanitizer).rewrite(%(<b class="#{classes}">Bold</b>)),
+        )
+      end
+
+      def test_it_drops_long_classes
+        sanitizer = Selma::Sanitizer.new({
+          elements: ["b"],
+          attributes: { "b" => ["class"] },
+          max_classes: { token_bytes: 4 },
+        })
+
+        assert_equal(
+          %(<b class="one four">Bold</b>),
+          Selma::Rewriter.new(sanitizer: sanitizer).rewrite(%(<b class="one three four">Bold</b>)),
+        )
+      end
+
+      def test_it_can_remove_the_attribute
+        sanitizer = Selma::Sanitizer.new({
+          elements: ["b"],
+          attributes: { "b" => ["class", "title"] },
+          max_classes: { tokens: 2, token_bytes: 8, overflow: :remove },
+        })
+        rewriter = Selma::Rewriter.new(sanitizer: sanitizer)
+
+        assert_equal(%(<b title="x">Bold</b>), rewriter.rewrite(%(<b class="a b c" title="x">Bold</b>)))
+        assert_equal(%(<b title="x">Bold</b>), rewriter.rewrite(%(<b class="a very-long-class" title="x">Bold</b>)))
+        assert_equal(%(<b class="a b">Bold</b>), rewriter.rewrite(%(<b class="a b">Bold</b>)))
+      end
+
+      def test_it_rejects_invalid_limits
+        error = assert_raises(Selma::ConfigError) { Selma::Sanitizer.new({ max_classes: { tokens: 2, overflow: :truncate } }) }
+
+        assert_equal("config[:max_classes][:overflow]: expected :drop or :remove, got :truncate", error.message)
+      end
+    end
+
+    describe "protocols for all elements" do
+      def setup
+        @config = {
+          elements: ["a", "img", "video"],
+          attributes: { "a" => ["href"], "img" => ["src"], "video" => ["src"] },
+          protocols: {
+            all: { "href" => ["https"], "src" => ["https"] },
+            "a" => { "href" => ["mailto"] },
+          },
+        }
+      end
+
+      def test_they_apply_to_every_element
+        sanitizer = Selma::Sanitizer.new(@config)
+        html = %(<img src="https://example.com/a.png"><video src="http://example.com/a.mp4"></video>)
+
+        assert_equal(
+          %(<img src="https://example.com/a.png"><video></video>),
+          Selma::Rewriter.new(sanitizer: sanitizer).rewrite(html),
+        )
+      end
+
+      def test_elements_can_override_them
+        sanitizer = Selma::Sanitizer.new(@config)
+        html = %(<a href="mailto:a@example.com">Mail</a><a href="https://example.com">Web</a>)
+
+        assert_equal(
+          %(<a href="mailto:a@example.com">Mail</a><a>Web</a>),
+          Selma::Rewriter.new(sanitizer: sanitizer).rewrite(html),
+        )
+      end
+    end
+
+    describe "fragment_links" do
+      def test_it_only_keeps_links_within_the_document
+        sanitizer = Selma::Sanitizer.new({
+          elements: ["a", "ul", "li"],
+          attributes: { "a" => ["href"] },
+          protocols: { "a" => { "href" => ["https", :relative] } },
+          fragment_links: true,
+        })
+        html = %(<ul><li><a href="#intro">Intro</a></li><li><a href="https://example.com/#intro">Elsewhere</a></li><li><a href="/intro">Up</a></li></ul>)
+
+        assert_predicate(sanitizer, :fragment_links)
+        assert_equal(
+          %(<ul><li><a href="#intro">Intro</a></li><li><a>Elsewhere</a></li><li><a>Up</a></li></ul>),
+          Selma::Rewriter.new(sanitizer: sanitizer).rewrite(html),
+        )
+      end
+
+      def test_it_still_needs_fragments_to_be_allowed
+        sanitizer = Selma::Sanitizer.new({
+          elements: ["a"],
+          attributes: { "a" => ["href"] },
+          protocols: { "a" => { "href" => ["https"] } },
+          fragment_links: true,
+        })
+
+        assert_equal(%(<a>Intro</a>), Selma::Rewriter.new(sanitizer: sanitizer).rewrite(%(<a href="#intro">Intro</a>)))
+      end
+    end
+
+    describe "attribute order" do
+      def setup
+        @config = {
+          elements: ["a", "div"],
+          attributes: { "a" => ["rel", "id", "href", "title"], "div" => ["class", "data-x", "title"] },
+          protocols: { "a" => { "href" => ["https"] } },
+          add_attributes: { "a" => { "rel" => "nofollow", "target" => "_blank" } },
+        }
+      end
+
+      def rewrite(html)
+        Selma::Rewriter.new(sanitizer: Selma::Sanitizer.new(@config)).rewrite(html)
+      end
+
+      def test_it_keeps_attributes_in_the_order_they_were_written
+        assert_equal(
+          %(<div title="t" data-x="1" class="c">x</div>),
+          rewrite(%(<div title="t" data-x="1" class="c">x</div>)),
+        )
+        assert_equal(
+          %(<div class="c" title="t" data-x="1">x</div>),
+          rewrite(%(<div class="c" title="t" data-x="1">x</div>)),
+        )
+      end
+
+      def test_it_keeps_the_order_around_removed_attributes
+        assert_equal(
+          %(<div data-x="1" title="t">x</div>),
+          rewrite(%(<div onclick="x()" data-x="1" style="color: red" title="t" id="y">x</div>)),
+        )
+      end
+
+      def test_it_keeps_rewritten_values_where_they_were
+        assert_equal(
+          %(<a title="&lt;b&gt;" href="https://example.com/a%20b" rel="nofollow" target="_blank">x</a>),
+          rewrite(%(<a title="<b>" href="https://example.com/a b">x</a>)),
+        )
+      end
+
+      def test_it_merges_added_tokens_in_place
+        assert_equal(
+          %(<a rel="noopener nofollow" id="i" target="_blank">x</a>),
+          rewrite(%(<a rel="noopener" id="i">x</a>)),
+        )
+      end
+    end
+
+    describe "href escaping" do
+      def setup
+        @config = {
+          elements: ["a"],
+          attributes: { "a" => ["href"] },
+          protocols: { "a" => { "href" => ["https", :relative] } },
+        }
+      end
+
+      def rewrite(html)
+        Selma::Rewriter.new(sanitizer: Selma::Sanitizer.new(@config)).rewrite(html)
+      end
+
+      def test_it_keeps_query_strings_intact
+        [
+          ["https://example.com/?a=1&amp;b=2", "https://example.com/?a=1&amp;b=2"],
+          ["https://example.com/?a=1&b=2", "https://example.com/?a=1&amp;b=2"],
+          ["https://example.com/?q=a%26b&amp;r=c%3Dd", "https://example.com/?q=a%26b&amp;r=c%3Dd"],
+          ["https://example.com/?q=a+b#frag", "https://example.com/?q=a+b#frag"],
+        ].each do |href, expected|
+          assert_equal(%(<a href="#{expected}">x</a>), rewrite(%(<a href="#{href}">x</a>)), href)
+        end
+      end
+
+      def test_it_does_not_double_encode_percent_escapes
+        [
+          ["https://example.com/a%20b", "https://example.com/a%20b"],
+          ["https://example.com/%E2%9C%93", "https://example.com/%E2%9C%93"],
+          ["https://example.com/100%", "https://example.com/100%"],
+          ["https://example.com/%zz", "https://example.com/%zz"],
+          ["/path%2Fwith%2Fslashes", "/path%2Fwith%2Fslashes"],
+        ].each do |href, expected|
+          assert_equal(%(<a href="#{expected}">x</a>), rewrite(%(<a href="#{href}">x</a>)), href)
+        end
+      end
+
+      def test_it_leaves_characters_browsers_encode_themselves
+        [
+          ["https://例え.jp/パス?q=ü", "https://例え.jp/パス?q=ü"],
+          ["https://example.com/a[0]|b{c}", "https://example.com/a[0]|b{c}"],
+          ["https://[::1]:8080/", "https://[::1]:8080/"],
+        ].each do |href, expected|
+          assert_equal(%(<a href="#{expected}">x</a>), rewrite(%(<a href="#{href}">x</a>)), href)
+        end
+      end
+
+      def test_it_escapes_what_cannot_appear_in_the_attribute
+        [
+          ["https://example.com/a b", "https://example.com/a%20b"],
+          ["https://example.com/&quot;onmouseover=&quot;x", "https://example.com/%22onmouseover=%22x"],
+          ["https://example.com/it's", "https://example.com/it&#x27;s"],
+          ["https://example.com/&lt;script&gt;", "https://example.com/%3Cscript%3E"],
+          ["https://example.com/a&#9;b&#10;c", "https://example.com/a%09b%0Ac"],
+        ].each do |href, expected|
+          assert_equal(%(<a href="#{expected}">x</a>), rewrite(%(<a href="#{href}">x</a>)), href)
+        end
+      end
+    end
+
+    describe "duplicate_attributes" do
+      def setup
+        @config = {
+          elements: ["a", "p"],
+          attributes: { "a" => ["href", "title"] },
+          protocols: { "a" => { "href" => ["https", :relative] } },
+        }
+      end
+
+      def test_it_keeps_the_first_of_each_attribute
+        sanitizer = Selma::Sanitizer.new(@config)
+        html = %(<a href="/safe" title="x" HREF="javascript:alert(1)">Go</a>)
+
+        assert_equal(%(<a href="/safe" title="x">Go</a>), Selma::Rewriter.new(sanitizer: sanitizer).rewrite(html))
+      end
+
+      def test_it_sanitizes_the_first_of_each_attribute
+        sanitizer = Selma::Sanitizer.new(@config)
+        html = %(<a href="javascript:alert(1)" href="/safe">Go</a>)
+
+        assert_equal(%(<a>Go</a>), Selma::Rewriter.new(sanitizer: sanitizer).rewrite(html))
+      end
+
+      def test_it_can_remove_the_element
+        sanitizer = Selma::Sanitizer.new(@config.merge(duplicate_attributes: :remove))
+        html = %(<p>Hi <a href="/safe" href="javascript:alert(1)">there</a> <a href="/ok">ok</a></p>)
+
+        assert_equal(%(<p>Hi  <a href="/ok">ok</a></p>), Selma::Rewriter.new(sanitizer: sanitizer).rewrite(html))
+      end
+
+      def test_it_rejects_unknown_policies
+        error = assert_raises(Selma::ConfigError) { Selma::Sanitizer.new(@config.merge(duplicate_attributes: :first)) }
+
+        assert_match(/config\[:duplicate_attributes\]/, error.message)
+      end
+    end
+
+    describe "css" do
+      def setup
+        @config = {
+          elements: ["p"],
+          attributes: { "p" => ["style"] },
+        }
+      end
+
+      def rewrite(config, html)
+        Selma::Rewriter.new(sanitizer: Selma::Sanitizer.new(config)).rewrite(html)
+      end
+
+      def test_it_keeps_the_allowed_declarations
+        html = %(<p style="color:red; position: fixed; font-weight: bold">x</p>)
+
+        assert_equal(%(<p style="color: red; font-weight: bold">x</p>), rewrite(@config.merge(css: true), html))
+      end
+
+      def test_it_removes_values_which_load_anything
+        html = %(<p style="background-color: url(https://evil.example/x.png)">x</p>)
+
+        assert_equal(%(<p>x</p>), rewrite(@config.merge(css: true), html))
+      end
+
+      def test_it_only_keeps_the_given_properties
+        html = %(<p style="color: red; font-weight: bold">x</p>)
+
+        assert_equal(%(<p style="color: red">x</p>), rewrite(@config.merge(css: { properties: ["color"] }), html))
+      end
+
+      def test_it_leaves_styles_as_they_are_by_default
+        html = %(<p style="color:red; position: fixed">x</p>)
+
+        assert_equal(html, rewrite(@config, html))
+      end
+
+      def test_it_rejects_malformed_properties
+        error = assert_raises(Selma::ConfigError) { Selma::Sanitizer.new(@config.merge(css: { properties: [1] })) }
+
+        assert_equal("config[:css][:properties][0]: expected a String or Symbol, got 1", error.message)
+
+        error = assert_raises(Selma::ConfigError) { Selma::Sanitizer.new(@config.merge(css: { props: ["color"] })) }
+
+        assert_equal("config[:css][:props]: unknown key", error.message)
+      end
+    end
+
+    describe "strip_remote_content" do
+      def setup
+        @config = {
+          elements: ["img"],
+          attributes: { "img" => ["src", "srcset"] },
+          protocols: { "img" => { "src" => ["https", "cid", :relative] } },
+          strip_remote_content: true,
+        }
+      end
+
+      def rewrite(config, html)
+        Selma::Rewriter.new(sanitizer: Selma::Sanitizer.new(config)).rewrite(html)
+      end
+
+      def test_it_removes_urls_on_other_hosts
+        html = %(<img src="https://example.com/a.png"><img src="//example.com/a.png"><img src="cid:logo@example.com"><img src="/a.png">)
+
+        assert_equal(%(<img><img><img src="cid:logo@example.com"><img src="/a.png">), rewrite(@config, html))
+      end
+
+      def test_it_removes_srcsets_with_any_url_on_another_host
+        html = %(<img srcset="/a.png 1x, https://example.com/b.png 2x"><img srcset="/a.png 1x, /b.png 2x">)
+
+        assert_equal(%(<img><img srcset="/a.png 1x, /b.png 2x">), rewrite(@config, html))
+      end
+
+      def test_it_keeps_remote_content_by_default
+        html = %(<img src="https://example.com/a.png">)
+
+        assert_equal(html, rewrite(@config.merge(strip_remote_content: false), html))
+      end
+    end
+
+    describe "changing what's allowed" do
+      def setup
+        @config = {
+          elements: ["a", "span"],
+          attributes: { "a" => ["href", "title", "class"], "span" => ["class"] },
+          protocols: { "a" => { "href" => ["http", "https", :relative] } },
+        }
+      end
+
+      def rewrite(sanitizer, html)
+        Selma::Rewriter.new(sanitizer: sanitizer).rewrite(html)
+      end
+
+      def test_attributes_can_be_disallowed
+        sanitizer = Selma::Sanitizer.new(@config)
+        sanitizer.disallow_attribute("a", ["title"])
+
+        assert_equal(%(<a href="/x">x</a>), rewrite(sanitizer, %(<a href="/x" title="t">x</a>)))
+      end
+
+      def test_attributes_can_be_allowed_again
+        sanitizer = Selma::Sanitizer.new(@config)
+        sanitizer.disallow_attribute("a", ["title"])
+        sanitizer.allow_attribute("a", ["title"])
+
+        assert_equal(%(<a href="/x" title="t">x</a>), rewrite(sanitizer, %(<a href="/x" title="t">x</a>)))
+      end
+
+      def test_protocols_can_be_disallowed
+        sanitizer = Selma::Sanitizer.new(@config)
+        sanitizer.disallow_protocol("a", "href", ["http", :relative])
+        html = %(<a href="http://example.com">a</a><a href="/x">b</a><a href="https://example.com">c</a>)
+
+        assert_equal(%(<a>a</a><a>b</a><a href="https://example.com">c</a>), rewrite(sanitizer, html))
+      end
+
+      def test_classes_can_be_disallowed
+        sanitizer = Selma::Sanitizer.new(@config)
+        sanitizer.allow_class("span", "note", "warning")
+        sanitizer.disallow_class("span", "warning")
+
+        assert_equal(%(<span class="note">x</span>), rewrite(sanitizer, %(<span class="note warning">x</span>)))
+      end
+
+      def test_the_config_is_not_reapplied
+        sanitizer = Selma::Sanitizer.new(@config)
+        sanitizer.disallow_attribute("a", ["title"])
+        rewrite(sanitizer, "")
+
+        assert_equal(%(<a>x</a>), rewrite(sanitizer, %(<a title="t">x</a>)))
+      end
+    end
+  end
+end
@@ -76,6 +76,72 @@ module Selma
 
           assert_equal("<script><!-- comment --></script>", Selma::Rewriter.new(sanitizer: sanitizer).rewrite("<script><!-- comment --></script>"))
         end
+
+        def test_it_removes_conditional_comments_as_a_whole
+          assert_equal(
+            "foo  bar",
+            Selma::Rewriter.new(sanitizer: @sanitizer).rewrite("foo <!--[if IE]><script>alert(1)</script><![endif]--> bar"),
+          )
+          assert_equal(
+            "foo  bar",
+            Selma::Rewriter.new(sanitizer: @sanitizer).rewrite("foo <!--[IF gte IE 4]>\n<img src=x onerror=alert(1)>\n<![ENDIF]--> bar"),
+          )
+          assert_equal(
+            "<div>hi</div>",
+            Selma::Rewriter.new(sanitizer: @sanitizer).rewrite("<![if !IE]><div>hi</div><![endif]>"),
+          )
+        end
+
+        def test_it_removes_cdata_sections
+          assert_equal(
+            "foo  bar",
+            Selma::Rewriter.new(sanitizer: @sanitizer).rewrite("foo <![CDATA[<img src=x onerror=alert(1)>]]> bar"),
+          )
+        end
+      end
+
+      context "when :allow_conditional_comments is true" do
+        def setup
+          @sanitizer = Selma::Sanitizer.new({ allow_comments: true, allow_conditional_comments: true, elements: ["div"] })
+        end
+
+        def test_it_keeps_conditional_comments
+          assert_equal(
+            "foo <!--[if IE]><p>old</p><![endif]--> bar",
+            Selma::Rewriter.new(sanitizer: @sanitizer).rewrite("foo <!--[if IE]><p>old</p><![endif]--> bar"),
+          )
+        end
+
+        def test_it_still_needs_comments_to_be_allowed
+          sanitizer = Selma::Sanitizer.new({ allow_comments: false, allow_conditional_comments: true })
+
+          assert_equal(
+            "foo  bar",
+            Selma::Rewriter.new(sanitizer: sanitizer).rewrite("foo <!--[if IE]><p>old</p><![endif]--> bar"),
+          )
+        end
+      end
+
+      context "when :allow_cdata is true" do
+        def test_it_keeps_cdata_sections
+          sanitizer = Selma::Sanitizer.new({ allow_comments: true, allow_cdata: true })
+
+          assert_equal(
+            "foo <![CDATA[x < y]]> bar",
+            Selma::Rewriter.new(sanitizer: sanitizer).rewrite("foo <![CDATA[x < y]]> bar"),
+          )
+        end
+      end
+
+      context "when :allow_cdata is false" do
+        def test_it_removes_cdata_text_within_svg
+          sanitizer = Selma::Sanitizer.new({ elements: ["svg"] })
+
+          assert_equal(
+            "<svg><![CDATA[]]></svg>",
+            Selma::Rewriter.new(sanitizer: sanitizer).rewrite("<svg><![CDATA[<img src=x onerror=alert(1)>]]></svg>"),
+          )
+        end
       end
     end
   end
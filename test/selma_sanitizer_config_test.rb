@@ -1,6 +1,8 @@
 # frozen_string_literal: true
 
 require "test_helper"
+require "json"
+require "tmpdir"
 
 module Selma
   class SanitizerConfigTest < Minitest::Test
@@ -9,6 +11,7 @@ module Selma
       verify_deeply_frozen(Selma::Sanitizer::Config::BASIC)
       verify_deeply_frozen(Selma::Sanitizer::Config::RELAXED)
       verify_deeply_frozen(Selma::Sanitizer::Config::RESTRICTED)
+      verify_deeply_frozen(Selma::Sanitizer::Config::GITHUB)
     end
 
     def test_should_deeply_freeze_and_return_a_configuration_hash
@@ -53,5 +56,186 @@ module Selma
       assert_raises(ArgumentError) { Selma::Sanitizer::Config.merge("foo", {}) }
       assert_raises(ArgumentError) { Selma::Sanitizer::Config.merge({}, "foo") }
     end
+
+    def test_built_in_configs_are_valid
+      [:DEFAULT, :BASIC, :RELAXED, :RESTRICTED, :GITHUB].each do |name|
+        Selma::Sanitizer.new(Selma::Sanitizer::Config.const_get(name))
+      end
+    end
+
+    def test_built_in_configs_have_no_warnings
+      [:DEFAULT, :BASIC, :RELAXED, :RESTRICTED, :GITHUB, :EMAIL].each do |name|
+        assert_empty(Selma::Sanitizer.new(Selma::Sanitizer::Config.const_get(name)).warnings, name)
+      end
+    end
+
+    def test_ineffective_config_is_warned_about
+      config = {
+        elements: ["a"],
+        attributes: { "a" => ["href"] },
+        add_attributes: { "span" => { "class" => "x" } },
+        protocols: { "a" => { "href" => ["https"], "ping" => ["https"] }, "img" => { "src" => ["https"] } },
+      }
+      sanitizer = nil
+      _, err = capture_io { sanitizer = Selma::Sanitizer.new(config) }
+
+      assert_equal(
+        ['config[:add_attributes]["span"]', 'config[:protocols]["a"]["ping"]', 'config[:protocols]["img"]'],
+        sanitizer.warnings.map { |warning| warning[:path] },
+      )
+      assert_equal(
+        "protocols given for `ping` on `a`, which is not in config[:attributes], so they're ignored",
+        sanitizer.warnings[1][:message],
+      )
+      assert_includes(err, %(Selma::Sanitizer: config[:protocols]["img"]: protocols given for `img`))
+    end
+
+    def test_warnings_respect_verbose
+      verbose = $VERBOSE
+      $VERBOSE = nil
+
+      assert_silent { Selma::Sanitizer.new({ elements: ["a"], protocols: { "img" => { "src" => ["https"] } } }) }
+    ensure
+      $VERBOSE = verbose
+    end
+
+    def test_merged_configs_are_valid
+      config = Selma::Sanitizer::Config.merge(Selma::Sanitizer::Config::RELAXED, elements: ["p", "img"])
+
+      Selma::Sanitizer.new(config)
+    end
+
+    def test_config_errors_are_selma_errors
+      assert_operator(Selma::ConfigError, :<, Selma::Error)
+      assert_operator(Selma::Error, :<, StandardError)
+    end
+
+    def test_unknown_keys_raise
+      error = assert_raises(Selma::ConfigError) { Selma::Sanitizer.new({ elemnts: ["a"] }) }
+
+      assert_equal("config[:elemnts]: unknown key", error.message)
+
+      error = assert_raises(Selma::ConfigError) { Selma::Sanitizer.new({ "elements" => ["a"] }) }
+
+      assert_equal('config["elements"]: unknown key', error.message)
+    end
+
+    def test_attributes_for_disallowed_elements_raise
+      config = { elements: ["p"], attributes: { "a" => ["href"] } }
+      error = assert_raises(Selma::ConfigError) { Selma::Sanitizer.new(config) }
+
+      assert_equal('config[:attributes]["a"]: attributes given for `a`, which is not in config[:elements]', error.message)
+    end
+
+    def test_attributes_for_all_elements_are_allowed
+      Selma::Sanitizer.new({ elements: ["p"], attributes: { all: ["class"], "all" => ["id"] } })
+    end
+
+    def test_invalid_element_names_raise
+      error = assert_raises(Selma::ConfigError) { Selma::Sanitizer.new({ elements: ["p", "<script>"] }) }
+
+      assert_equal("config[:elements][1]: `<script>` is not a valid element name", error.message)
+
+      error = assert_raises(Selma::ConfigError) { Selma::Sanitizer.new({ elements: [1] }) }
+
+      assert_equal("config[:elements][0]: expected a String or Symbol, got 1", error.message)
+
+      error = assert_raises(Selma::ConfigError) { Selma::Sanitizer.new({ elements: "a" }) }
+
+      assert_equal('config[:elements]: expected an Array, got "a"', error.message)
+    end
+
+    def test_custom_element_names_are_allowed
+      Selma::Sanitizer.new({ elements: ["my-element", :span] })
+    end
+
+    def test_malformed_protocols_raise
+      config = { elements: ["a"], protocols: { "a" => ["http"] } }
+      error = assert_raises(Selma::ConfigError) { Selma::Sanitizer.new(config) }
+
+      assert_equal('config[:protocols]["a"]: expected a Hash, got ["http"]', error.message)
+
+      config = { elements: ["a"], protocols: { "a" => { "href" => ["http", :absolute] } } }
+      error = assert_raises(Selma::ConfigError) { Selma::Sanitizer.new(config) }
+
+      assert_equal('config[:protocols]["a"]["href"][1]: expected a protocol String or one of :relative, :fragment, :root_relative, :path_relative, :protocol_relative, got :absolute', error.message)
+    end
+
+    def test_a_lone_protocol_is_allowed
+      Selma::Sanitizer.new({ elements: ["a"], protocols: { "a" => { "href" => :relative } } })
+    end
+
+    def test_malformed_flags_raise
+      error = assert_raises(Selma::ConfigError) { Selma::Sanitizer.new({ allow_comments: "yes" }) }
+
+      assert_equal('config[:allow_comments]: expected true or false, got "yes"', error.message)
+
+      error = assert_raises(Selma::ConfigError) { Selma::Sanitizer.new({ name_prefix: :user }) }
+
+      assert_equal("config[:name_prefix]: expected a String, got :user", error.message)
+    end
+
+    def test_escaped_contents_must_be_raw_text
+      error = assert_raises(Selma::ConfigError) { Selma::Sanitizer.new({ escape_contents: ["xmp", "svg"] }) }
+
+      assert_equal("config[:escape_contents][1]: `svg` doesn't contain raw text, so its contents can't be escaped", error.message)
+
+      sanitizer = Selma::Sanitizer.new({ elements: ["style"], escape_contents: ["style"] })
+
+      assert_equal(["config[:escape_contents][0]"], sanitizer.warnings.map { |warning| warning[:path] })
+    end
+
+    def test_configs_load_from_yaml_files
+      with_config_file("sanitizer.yml", <<~YAML) do |path|
+        elements: [a, b]
+        attributes:
+          a: [href]
+        protocols:
+          a:
+            href: [https, ":relative"]
+      YAML
+        sanitizer = Selma::Sanitizer.load_file(path)
+        html = %(<a href="/x">x</a><a href="javascript:y()">y</a><i>z</i>)
+
+        assert_equal(%(<a href="/x">x</a><a>y</a>z), Selma::Rewriter.new(sanitizer: sanitizer).rewrite(html))
+      end
+    end
+
+    def test_configs_load_from_json_files
+      with_config_file("sanitizer.json", JSON.generate({ elements: ["b"], allow_comments: true })) do |path|
+        sanitizer = Selma::Sanitizer.load_file(path)
+
+        assert_equal("<b>x</b><!-- y -->", Selma::Rewriter.new(sanitizer: sanitizer).rewrite("<b>x</b><!-- y --><i></i>"))
+      end
+    end
+
+    def test_invalid_config_files_raise
+      with_config_file("sanitizer.yml", "elements: [b]\nallow_coments: true\n") do |path|
+        error = assert_raises(Selma::ConfigError) { Selma::Sanitizer.load_file(path) }
+
+        assert(error.message.start_with?("#{path}: "), error.message)
+        assert_match(/allow_coments/, error.message)
+      end
+
+      with_config_file("sanitizer.yml", "elements: [b\n") do |path|
+        assert_raises(Selma::ConfigError) { Selma::Sanitizer.load_file(path) }
+      end
+
+      with_config_file("sanitizer.json", "[]") do |path|
+        error = assert_raises(Selma::ConfigError) { Selma::Sanitizer.load_file(path) }
+
+        assert_equal("#{path}: expected a mapping of options, got []", error.message)
+      end
+    end
+
+    private
+
+    def with_config_file(name, contents)
+      Dir.mktmpdir do |dir|
+        path = File.join(dir, name)
+        File.write(path, contents)
+        yield path
+      end
+    end
   end
 end
@@ -182,7 +182,7 @@ module Selma
           assert_equal(input, Selma::Rewriter.new(sanitizer: sanitizer).rewrite(input))
 
           sanitizer = Selma::Sanitizer.new({
-            elements: ["p"],
+            elements: ["p", "div"],
             attributes: { "div" => ["class"] },
           })
 
@@ -252,6 +252,55 @@ module Selma
           assert_equal("<a>Footnote 1</a>", Selma::Rewriter.new(sanitizer: sanitizer).rewrite(input))
         end
 
+        def test_should_allow_each_kind_of_relative_url_on_its_own
+          input = '<a href="#top">1</a><a href="/top">2</a><a href="top.html">3</a><a href="//example.com/top">4</a><a href="/\\example.com">5</a>'
+
+          sanitizer = Selma::Sanitizer.new({
+            elements: ["a"],
+            attributes: { "a" => ["href"] },
+            protocols: { "a" => { "href" => [:fragment, :root_relative] } },
+          })
+
+          assert_equal(
+            '<a href="#top">1</a><a href="/top">2</a><a>3</a><a>4</a><a>5</a>',
+            Selma::Rewriter.new(sanitizer: sanitizer).rewrite(input),
+          )
+
+          sanitizer = Selma::Sanitizer.new({
+            elements: ["a"],
+            attributes: { "a" => ["href"] },
+            protocols: { "a" => { "href" => [:path_relative, :protocol_relative] } },
+          })
+
+          assert_equal(
+            '<a>1</a><a>2</a><a href="top.html">3</a><a href="//example.com/top">4</a><a href="/\\example.com">5</a>',
+            Selma::Rewriter.new(sanitizer: sanitizer).rewrite(input),
+          )
+        end
+
+        def test_should_only_allow_bare_path_relative_urls_when_asked_to
+          input = '<a href="top.html">1</a><a href="docs/top.html">2</a><a href="top.html#a">3</a>'
+
+          sanitizer = Selma::Sanitizer.new({
+            elements: ["a"],
+            attributes: { "a" => ["href"] },
+            protocols: { "a" => { "href" => [:relative] } },
+          })
+
+          assert_equal(
+            '<a>1</a><a href="docs/top.html">2</a><a href="top.html#a">3</a>',
+            Selma::Rewriter.new(sanitizer: sanitizer).rewrite(input),
+          )
+
+          sanitizer = Selma::Sanitizer.new({
+            elements: ["a"],
+            attributes: { "a" => ["href"] },
+            protocols: { "a" => { "href" => [:relative, :path_relative] } },
+          })
+
+          assert_equal(input, Selma::Rewriter.new(sanitizer: sanitizer).rewrite(input))
+        end
+
         def test_should_remove_the_contents_of_filtered_nodes_when_remove_contents_is_true
           sanitizer = Selma::Sanitizer.new({ remove_contents: true })
 
@@ -261,6 +310,15 @@ module Selma
           )
         end
 
+        def test_should_keep_the_allowed_elements_when_remove_contents_is_false
+          sanitizer = Selma::Sanitizer.new({ elements: ["p"], remove_contents: false })
+
+          assert_equal(
+            "<p>foo bar baz</p>",
+            Selma::Rewriter.new(sanitizer: sanitizer).rewrite("<p>foo bar <span>baz</span></p>"),
+          )
+        end
+
         def test_remove_the_contents_of_specified_nodes_when_remove_contents_is_an_array_or_set_of_element_names_as_strings
           sanitizer = Selma::Sanitizer.new({ remove_contents: ["script", "span"] })
 
@@ -461,6 +519,725 @@ module Selma
           )
         end
       end
+
+      context "Noscript policy" do
+        def test_should_remove_noscript_elements
+          sanitizer = Selma::Sanitizer.new({ elements: ["noscript", "p"], noscript: :remove })
+
+          assert_equal(
+            "<p>hi</p>",
+            Selma::Rewriter.new(sanitizer: sanitizer).rewrite("<p>hi</p><noscript><p>no script</p></noscript>"),
+          )
+        end
+
+        def test_should_unwrap_and_sanitize_noscript_elements
+          sanitizer = Selma::Sanitizer.new({ elements: ["noscript", "p"], noscript: :unwrap })
+
+          assert_equal(
+            "<p>no script</p>",
+            Selma::Rewriter.new(sanitizer: sanitizer).rewrite(%(<noscript><p onclick="alert(1)">no script</p><img src=x onerror=alert(1)></noscript>)),
+          )
+        end
+
+        def test_should_escape_the_contents_of_noscript_elements
+          sanitizer = Selma::Sanitizer.new({ elements: ["noscript"], noscript: :escape })
+
+          assert_equal(
+            "<noscript>&lt;img src=x onerror=alert(1)&gt;</noscript>",
+            Selma::Rewriter.new(sanitizer: sanitizer).rewrite("<noscript><img src=x onerror=alert(1)></noscript>"),
+          )
+        end
+
+        def test_should_reject_unknown_noscript_policies
+          error = assert_raises(Selma::ConfigError) do
+            Selma::Sanitizer.new({ noscript: :keep })
+          end
+
+          assert_match(/config\[:noscript\]/, error.message)
+        end
+      end
+
+      context "Template elements" do
+        def test_should_remove_the_contents_of_disallowed_templates
+          sanitizer = Selma::Sanitizer.new({ elements: ["p", "b"] })
+
+          assert_equal(
+            "<p>before</p><p>after</p>",
+            Selma::Rewriter.new(sanitizer: sanitizer).rewrite("<p>before</p><template><b>hi</b><img src=x onerror=alert(1)></template><p>after</p>"),
+          )
+        end
+
+        def test_should_sanitize_the_contents_of_allowed_templates
+          sanitizer = Selma::Sanitizer.new({ elements: ["template", "b"] })
+
+          assert_equal(
+            "<template><b>hi</b></template>",
+            Selma::Rewriter.new(sanitizer: sanitizer).rewrite(%(<template><b onclick="alert(1)">hi</b><script>alert(1)</script><img src=x onerror=alert(1)></template>)),
+          )
+        end
+      end
+
+      context "Newer standard elements" do
+        def test_should_allow_newer_elements_which_are_allowlisted
+          sanitizer = Selma::Sanitizer.new({
+            elements: ["picture", "source", "img", "search", "slot", "dialog", "template"],
+            attributes: { "source" => ["srcset"], "img" => ["alt"] },
+          })
+
+          frag = '<search><picture><source srcset="a.webp"><img alt="a"></picture></search><slot>b</slot><dialog>c</dialog><template>d</template>'
+
+          assert_equal(frag, Selma::Rewriter.new(sanitizer: sanitizer).rewrite(frag))
+        end
+
+        def test_should_remove_newer_elements_which_are_not_allowlisted
+          sanitizer = Selma::Sanitizer.new({ elements: ["img"], whitespace_elements: ["search"] })
+
+          assert_equal(
+            ' <img> b',
+            Selma::Rewriter.new(sanitizer: sanitizer).rewrite("<search><picture><img></picture></search><slot>b</slot>"),
+          )
+        end
+      end
+
+      context "Custom elements" do
+        def test_should_only_allow_the_custom_elements_which_are_allowlisted
+          sanitizer = Selma::Sanitizer.new({ elements: ["my-element"] })
+
+          assert_equal(
+            "<my-element>hi</my-element> there",
+            Selma::Rewriter.new(sanitizer: sanitizer).rewrite("<my-element>hi</my-element> <other-element>there</other-element>"),
+          )
+        end
+
+        def test_should_allow_attributes_on_custom_elements
+          sanitizer = Selma::Sanitizer.new({
+            elements: ["my-element", "other-element"],
+            attributes: { "my-element" => ["data-foo"] },
+          })
+
+          assert_equal(
+            '<my-element data-foo="bar"></my-element><other-element></other-element>',
+            Selma::Rewriter.new(sanitizer: sanitizer).rewrite('<my-element data-foo="bar"></my-element><other-element data-foo="bar"></other-element>'),
+          )
+        end
+
+        def test_should_remove_the_contents_of_custom_elements
+          sanitizer = Selma::Sanitizer.new({ remove_contents: ["my-element"] })
+
+          assert_equal(
+            "foo  bar",
+            Selma::Rewriter.new(sanitizer: sanitizer).rewrite("foo <my-element>secret</my-element> <other-element>bar</other-element>"),
+          )
+        end
+
+        def test_should_wrap_custom_elements_with_whitespace
+          sanitizer = Selma::Sanitizer.new({ whitespace_elements: ["my-element"] })
+
+          assert_equal(
+            "foo bar baz",
+            Selma::Rewriter.new(sanitizer: sanitizer).rewrite("foo<my-element>bar</my-element>baz"),
+          )
+        end
+
+        def test_should_wrap_void_custom_elements_with_whitespace_where_they_are
+          sanitizer = Selma::Sanitizer.new({ whitespace_elements: ["my-icon"], void_elements: ["my-icon"] })
+
+          assert_equal(
+            "foo bar baz",
+            Selma::Rewriter.new(sanitizer: sanitizer).rewrite("foo<my-icon>bar<my-icon>baz"),
+          )
+        end
+
+        def test_should_remove_raw_text_custom_elements_with_their_contents
+          sanitizer = Selma::Sanitizer.new({ elements: ["b"], raw_text_elements: ["my-code"] })
+
+          assert_equal(
+            "foo <b>bar</b>",
+            Selma::Rewriter.new(sanitizer: sanitizer).rewrite("foo <my-code><b>x</b> y</my-code><b>bar</b>"),
+          )
+        end
+
+        def test_should_validate_void_and_raw_text_elements
+          error = assert_raises(Selma::ConfigError) { Selma::Sanitizer.new({ void_elements: ["my icon"] }) }
+
+          assert_match(/config\[:void_elements\]/, error.message)
+        end
+
+        def test_should_apply_remove_contents_true_to_custom_elements
+          sanitizer = Selma::Sanitizer.new({ elements: ["b"], remove_contents: true })
+
+          assert_equal(
+            "<b>hi</b>",
+            Selma::Rewriter.new(sanitizer: sanitizer).rewrite("<b>hi</b><my-element>there</my-element>"),
+          )
+        end
+
+        def test_should_match_configured_element_names_case_insensitively
+          sanitizer = Selma::Sanitizer.new({
+            elements: ["My-Element"],
+            attributes: { "MY-ELEMENT" => ["title"] },
+          })
+
+          assert_equal(
+            '<my-element title="hi">there</my-element>',
+            Selma::Rewriter.new(sanitizer: sanitizer).rewrite('<my-element title="hi">there</my-element>'),
+          )
+        end
+      end
+
+      context "Form neutralization" do
+        def setup
+          @sanitizer = Selma::Sanitizer.new({
+            elements: ["form", "input", "button", "select", "option", "label"],
+            attributes: {
+              "form" => ["action", "method"],
+              "input" => ["type", "name", "value"],
+              "button" => ["type", "formaction", "formmethod"],
+            },
+            neutralize_forms: true,
+          })
+        end
+
+        def test_should_unwrap_forms
+          assert_equal(
+            %(<label>Name</label><input type="text" name="q" disabled="">),
+            Selma::Rewriter.new(sanitizer: @sanitizer).rewrite(%(<form action="https://evil.example/steal" method="post"><label>Name</label><input type="text" name="q"></form>)),
+          )
+        end
+
+        def test_should_disable_controls_and_remove_where_they_submit_to
+          assert_equal(
+            %(<button type="submit" disabled="">Go</button><select disabled=""><option>a</option></select>),
+            Selma::Rewriter.new(sanitizer: @sanitizer).rewrite(%(<button type="submit" formaction="https://evil.example" formmethod="post">Go</button><select><option>a</option></select>)),
+          )
+        end
+
+        def test_should_leave_forms_alone_by_default
+          sanitizer = Selma::Sanitizer.new({ elements: ["form", "input"], attributes: { "form" => ["method"] } })
+
+          assert_equal(
+            %(<form method="get"><input></form>),
+            Selma::Rewriter.new(sanitizer: sanitizer).rewrite(%(<form method="get"><input></form>)),
+          )
+        end
+      end
+
+      context "Escaped contents" do
+        def setup
+          @config = { elements: ["p"], escape_contents: ["xmp", "plaintext", "style"] }
+        end
+
+        def rewrite(config, html)
+          Selma::Rewriter.new(sanitizer: Selma::Sanitizer.new(config)).rewrite(html)
+        end
+
+        def test_should_keep_the_contents_of_removed_elements_as_text
+          assert_equal(
+            "<p>a</p>&lt;b&gt;hi&lt;/b&gt; &amp;amp; bye",
+            rewrite(@config, "<p>a</p><xmp><b>hi</b> &amp; bye</xmp>"),
+          )
+          assert_equal("p &gt; b { color: red }", rewrite(@config, "<style>p > b { color: red }</style>"))
+          assert_equal("&lt;script&gt;alert(1)&lt;/script&gt;", rewrite(@config, "<plaintext><script>alert(1)</script>"))
+        end
+
+        def test_should_escape_them_even_if_their_contents_would_be_removed
+          config = Selma::Sanitizer::Config::DEFAULT.merge(escape_contents: ["xmp"])
+
+          assert_equal("&lt;img src=x onerror=alert(1)&gt;", rewrite(config, "<xmp><img src=x onerror=alert(1)></xmp>"))
+        end
+
+        def test_should_still_remove_the_contents_of_other_elements
+          assert_equal("", rewrite(@config, "<script>alert(1)</script>"))
+          assert_equal("", Selma::Rewriter.new.rewrite("<xmp><b>hi</b></xmp>"))
+        end
+
+        def test_should_leave_allowed_elements_alone
+          config = @config.merge(elements: ["style"])
+
+          assert_equal("<style>p > b { color: red }</style>", rewrite(config, "<style>p > b { color: red }</style>"))
+        end
+      end
+
+      context "Preformatted whitespace" do
+        def setup
+          @config = { elements: ["p", "pre", "code", "textarea"], whitespace_elements: ["div", "br"] }
+        end
+
+        def rewrite(config, html)
+          Selma::Rewriter.new(sanitizer: Selma::Sanitizer.new(config)).rewrite(html)
+        end
+
+        def test_should_wrap_removed_elements_in_pre_with_whitespace_by_default
+          assert_equal("<pre>a b c</pre>", rewrite(@config, "<pre>a<div>b</div>c</pre>"))
+        end
+
+        def test_should_not_wrap_removed_elements_in_preformatted_elements_when_asked_not_to
+          config = @config.merge(preserve_preformatted_whitespace: true)
+
+          assert_equal("<pre>abc</pre>", rewrite(config, "<pre>a<div>b</div>c</pre>"))
+          assert_equal("<p><code>ab</code> c d</p>", rewrite(config, "<p><code>a<br>b</code> c<br>d</p>"))
+          assert_equal("<pre><code>ab</code>c</pre>", rewrite(config, "<pre><code>a<br>b</code><br>c</pre>"))
+        end
+
+        def test_should_still_wrap_removed_elements_outside_of_them
+          config = @config.merge(preserve_preformatted_whitespace: true)
+
+          assert_equal("a b<pre>cd</pre> e f ", rewrite(config, "a<br>b<pre>c<br>d</pre><br>e<div>f</div>"))
+        end
+
+        def test_should_not_treat_removed_preformatted_elements_as_preformatted
+          config = @config.merge(elements: ["p"], preserve_preformatted_whitespace: true)
+
+          assert_equal("<p>a b</p>", rewrite(config, "<p><pre>a<br>b</pre></p>"))
+        end
+
+        def test_should_keep_the_whitespace_inside_them_when_unwrapping
+          config = @config.merge(preserve_preformatted_whitespace: true)
+          html = "<pre>  a\n  <span>b</span>\n\tc  </pre><textarea>\n  d\n</textarea>"
+
+          assert_equal("<pre>  a\n  b\n\tc  </pre><textarea>\n  d\n</textarea>", rewrite(config, html))
+        end
+      end
+
+      context "Image normalization" do
+        def setup
+          @config = {
+            elements: ["img"],
+            attributes: { "img" => ["src", "width", "height", "alt"] },
+            protocols: { "img" => { "src" => ["https"] } },
+          }
+        end
+
+        def test_should_lazy_load_images
+          sanitizer = Selma::Sanitizer.new(@config.merge(images: { lazy: true }))
+
+          assert_equal(
+            %(<img src="https://example.com/a.png" loading="lazy" decoding="async">),
+            Selma::Rewriter.new(sanitizer: sanitizer).rewrite(%(<img src="https://example.com/a.png" loading="eager">)),
+          )
+        end
+
+        def test_should_clamp_dimensions_keeping_their_ratio
+          sanitizer = Selma::Sanitizer.new(@config.merge(images: { max_width: 500, max_height: 500 }))
+
+          assert_equal(
+            %(<img src="https://example.com/a.png" width="500" height="250">),
+            Selma::Rewriter.new(sanitizer: sanitizer).rewrite(%(<img src="https://example.com/a.png" width="2000" height="1000">)),
+          )
+          assert_equal(
+            %(<img src="https://example.com/a.png" width="100" height="50">),
+            Selma::Rewriter.new(sanitizer: sanitizer).rewrite(%(<img src="https://example.com/a.png" width="100" height="50">)),
+          )
+          assert_equal(
+            %(<img src="https://example.com/a.png" height="500">),
+            Selma::Rewriter.new(sanitizer: sanitizer).rewrite(%(<img src="https://example.com/a.png" width="100%" height="9000">)),
+          )
+        end
+
+        def test_should_drop_images_without_a_src
+          sanitizer = Selma::Sanitizer.new(@config.merge(images: { require_src: true }))
+          rewriter = Selma::Rewriter.new(sanitizer: sanitizer)
+
+          assert_equal("ab", rewriter.rewrite(%(a<img alt="nothing">b)))
+          assert_equal("ab", rewriter.rewrite(%(a<img src="javascript:alert(1)">b)))
+          assert_equal(1, rewriter.stats[:elements_removed])
+        end
+
+        def test_should_reject_malformed_image_options
+          error = assert_raises(Selma::ConfigError) { Selma::Sanitizer.new(images: { max_width: -1 }) }
+
+          assert_equal("config[:images][:max_width]: expected a positive Integer, got -1", error.message)
+        end
+      end
+
+      context "Tracking pixels" do
+        def setup
+          @config = {
+            elements: ["img", "p"],
+            attributes: { "img" => ["src", "width", "height"] },
+            protocols: { "img" => { "src" => ["https"] } },
+            strip_tracking_pixels: true,
+          }
+        end
+
+        def test_should_remove_tiny_images
+          sanitizer = Selma::Sanitizer.new(@config)
+          rewriter = Selma::Rewriter.new(sanitizer: sanitizer)
+
+          assert_equal("<p>hi</p>", rewriter.rewrite(%(<p>hi<img src="https://example.com/o.gif" width="1" height="1"></p>)))
+          assert_equal("<p>hi</p>", rewriter.rewrite(%(<p>hi<img src="https://example.com/o.gif" width="0"></p>)))
+          assert_equal(
+            %(<p>hi<img src="https://example.com/a.png" width="1" height="200"></p>),
+            rewriter.rewrite(%(<p>hi<img src="https://example.com/a.png" width="1" height="200"></p>)),
+          )
+        end
+
+        def test_should_remove_images_from_trackers
+          sanitizer = Selma::Sanitizer.new(@config)
+          rewriter = Selma::Rewriter.new(sanitizer: sanitizer)
+
+          assert_equal("<p>hi</p>", rewriter.rewrite(%(<p>hi<img src="https://www.google-analytics.com/collect?v=1"></p>)))
+          assert_equal("<p>hi</p>", rewriter.rewrite(%(<p>hi<img src="https://MAILTRACK.io:443/trace"></p>)))
+          assert_equal(
+            %(<p>hi<img src="https://notmailtrack.io/a.png"></p>),
+            rewriter.rewrite(%(<p>hi<img src="https://notmailtrack.io/a.png"></p>)),
+          )
+        end
+
+        def test_should_use_the_given_hosts
+          sanitizer = Selma::Sanitizer.new(@config.merge(strip_tracking_pixels: ["tracker.example"]))
+          rewriter = Selma::Rewriter.new(sanitizer: sanitizer)
+
+          assert_equal("", rewriter.rewrite(%(<img src="https://open.tracker.example/o.gif">)))
+          assert_equal(
+            %(<img src="https://www.google-analytics.com/a.png">),
+            rewriter.rewrite(%(<img src="https://www.google-analytics.com/a.png">)),
+          )
+        end
+
+        def test_should_keep_them_by_default
+          sanitizer = Selma::Sanitizer.new(@config.merge(strip_tracking_pixels: false))
+          html = %(<img src="https://example.com/o.gif" width="1" height="1">)
+
+          assert_equal(html, Selma::Rewriter.new(sanitizer: sanitizer).rewrite(html))
+        end
+      end
+
+      context "AMP normalization" do
+        def setup
+          @sanitizer = Selma::Sanitizer.new({
+            elements: ["img", "video", "source", "p"],
+            attributes: {
+              "img" => ["src", "width", "height", "alt"],
+              "video" => ["src", "controls", "poster"],
+              "source" => ["src", "type"],
+            },
+            protocols: { "img" => { "src" => ["https"] } },
+            normalize_amp: true,
+          })
+        end
+
+        def test_should_turn_amp_images_into_images
+          assert_equal(
+            %(<p><img src="https://example.com/a.png" width="300" height="200" alt="A cat"></p>),
+            Selma::Rewriter.new(sanitizer: @sanitizer).rewrite(%(<p><amp-img src="https://example.com/a.png" width="300" height="200" layout="responsive" alt="A cat"><amp-img fallback src="https://example.com/a.jpg" width="300" height="200"></amp-img></amp-img></p>)),
+          )
+        end
+
+        def test_should_turn_amp_videos_into_videos
+          assert_equal(
+            %(<video controls poster="https://example.com/p.png"><source src="https://example.com/v.mp4" type="video/mp4"></video>),
+            Selma::Rewriter.new(sanitizer: @sanitizer).rewrite(%(<amp-video controls width="640" height="360" layout="responsive" poster="https://example.com/p.png"><source src="https://example.com/v.mp4" type="video/mp4"></amp-video>)),
+          )
+        end
+
+        def test_should_still_apply_the_allowlist
+          assert_equal(
+            "",
+            Selma::Rewriter.new(sanitizer: @sanitizer).rewrite(%(<amp-iframe src="https://example.com" width="600" height="400"></amp-iframe>)),
+          )
+        end
+
+        def test_should_leave_amp_elements_alone_by_default
+          sanitizer = Selma::Sanitizer.new({ elements: ["img"], attributes: { "img" => ["src"] } })
+
+          assert_equal(
+            "",
+            Selma::Rewriter.new(sanitizer: sanitizer).rewrite(%(<amp-img src="https://example.com/a.png"></amp-img>)),
+          )
+        end
+      end
+
+      context "Source policy" do
+        def setup
+          @config = {
+            elements: ["picture", "source", "img"],
+            attributes: { "source" => ["srcset"], "img" => ["src"] },
+            protocols: { "source" => { "srcset" => ["https"] }, "img" => { "src" => ["https"] } },
+          }
+        end
+
+        def test_should_keep_well_formed_source_attributes
+          sanitizer = Selma::Sanitizer.new(@config.merge(sources: true))
+          html = %(<picture><source srcset="https://example.com/a.avif" type="image/avif" media="(min-width: 600px)" sizes="(max-width: 900px) 100vw, 50vw"><img src="https://example.com/a.png"></picture>)
+
+          assert_equal(html, Selma::Rewriter.new(sanitizer: sanitizer).rewrite(html))
+        end
+
+        def test_should_drop_malformed_source_attributes
+          sanitizer = Selma::Sanitizer.new(@config.merge(sources: true))
+          html = %(<picture><source srcset="https://example.com/a.avif" type="text/html" media="screen; color: red" sizes="calc(100vw)"></picture>)
+
+          assert_equal(
+            %(<picture><source srcset="https://example.com/a.avif"></picture>),
+            Selma::Rewriter.new(sanitizer: sanitizer).rewrite(html),
+          )
+        end
+
+        def test_should_only_allow_the_configured_types
+          sanitizer = Selma::Sanitizer.new(@config.merge(sources: { types: ["image/webp"] }))
+          html = %(<picture><source srcset="https://example.com/a.webp" type="image/webp"><source srcset="https://example.com/a.avif" type="image/avif"></picture>)
+
+          assert_equal(
+            %(<picture><source srcset="https://example.com/a.webp" type="image/webp"><source srcset="https://example.com/a.avif"></picture>),
+            Selma::Rewriter.new(sanitizer: sanitizer).rewrite(html),
+          )
+        end
+
+        def test_should_sanitize_source_attributes_normally_by_default
+          sanitizer = Selma::Sanitizer.new(@config)
+          html = %(<picture><source srcset="https://example.com/a.avif" type="image/avif" media="(min-width: 600px)"></picture>)
+
+          assert_equal(
+            %(<picture><source srcset="https://example.com/a.avif"></picture>),
+            Selma::Rewriter.new(sanitizer: sanitizer).rewrite(html),
+          )
+        end
+
+        def test_should_reject_types_which_are_not_mime_types
+          error = assert_raises(Selma::ConfigError) { Selma::Sanitizer.new(@config.merge(sources: { types: ["webp"] })) }
+
+          assert_match(/config\[:sources\]\[:types\]\[0\]/, error.message)
+        end
+      end
+
+      context "Iframe policy" do
+        def setup
+          @config = {
+            elements: ["iframe"],
+            attributes: { "iframe" => ["src"] },
+            protocols: { "iframe" => { "src" => ["https"] } },
+          }
+        end
+
+        def test_should_force_a_sandbox
+          sanitizer = Selma::Sanitizer.new(@config.merge(iframes: true))
+          html = %(<iframe src="https://example.com" sandbox="allow-scripts allow-same-origin"></iframe><iframe src="https://example.com"></iframe>)
+
+          assert_equal(
+            %(<iframe src="https://example.com" sandbox=""></iframe><iframe src="https://example.com" sandbox=""></iframe>),
+            Selma::Rewriter.new(sanitizer: sanitizer).rewrite(html),
+          )
+        end
+
+        def test_should_replace_the_sandbox_with_the_configured_tokens
+          sanitizer = Selma::Sanitizer.new(@config.merge(iframes: { sandbox: ["allow-scripts", "allow-popups"] }))
+          html = %(<iframe src="https://example.com" sandbox="allow-same-origin"></iframe>)
+
+          assert_equal(
+            %(<iframe src="https://example.com" sandbox="allow-scripts allow-popups"></iframe>),
+            Selma::Rewriter.new(sanitizer: sanitizer).rewrite(html),
+          )
+        end
+
+        def test_should_merge_the_sandbox_with_the_configured_tokens
+          sanitizer = Selma::Sanitizer.new(@config.merge(iframes: { sandbox: ["allow-scripts", "allow-popups"], sandbox_mode: :merge }))
+          html = %(<iframe src="https://example.com" sandbox="allow-scripts allow-same-origin"></iframe><iframe src="https://example.com"></iframe>)
+
+          assert_equal(
+            %(<iframe src="https://example.com" sandbox="allow-scripts"></iframe><iframe src="https://example.com" sandbox="allow-scripts allow-popups"></iframe>),
+            Selma::Rewriter.new(sanitizer: sanitizer).rewrite(html),
+          )
+        end
+
+        def test_should_filter_referrerpolicy
+          sanitizer = Selma::Sanitizer.new(@config.merge(iframes: { referrerpolicy: ["no-referrer", "strict-origin"] }))
+          html = %(<iframe src="https://example.com" referrerpolicy="strict-origin"></iframe><iframe src="https://example.com" referrerpolicy="unsafe-url"></iframe>)
+
+          assert_equal(
+            %(<iframe src="https://example.com" referrerpolicy="strict-origin" sandbox=""></iframe><iframe src="https://example.com" referrerpolicy="no-referrer" sandbox=""></iframe>),
+            Selma::Rewriter.new(sanitizer: sanitizer).rewrite(html),
+          )
+        end
+
+        def test_should_filter_allow
+          sanitizer = Selma::Sanitizer.new(@config.merge(iframes: { allow: ["fullscreen"] }))
+          html = %(<iframe src="https://example.com" allow="camera; fullscreen 'self'"></iframe><iframe src="https://example.com" allow="camera"></iframe>)
+
+          assert_equal(
+            %(<iframe src="https://example.com" allow="fullscreen 'self'" sandbox=""></iframe><iframe src="https://example.com" sandbox=""></iframe>),
+            Selma::Rewriter.new(sanitizer: sanitizer).rewrite(html),
+          )
+        end
+
+        def test_should_leave_iframes_alone_by_default
+          sanitizer = Selma::Sanitizer.new(@config)
+          html = %(<iframe src="https://example.com" sandbox="allow-scripts"></iframe>)
+
+          assert_equal(%(<iframe src="https://example.com"></iframe>), Selma::Rewriter.new(sanitizer: sanitizer).rewrite(html))
+        end
+
+        def test_should_reject_unknown_sandbox_modes
+          error = assert_raises(Selma::ConfigError) { Selma::Sanitizer.new(@config.merge(iframes: { sandbox_mode: :union })) }
+
+          assert_match(/config\[:iframes\]\[:sandbox_mode\]/, error.message)
+        end
+      end
+
+      context "Embed fallback" do
+        def setup
+          @config = { elements: ["p"] }
+        end
+
+        def test_should_link_to_removed_embeds
+          sanitizer = Selma::Sanitizer.new(@config.merge(embed_fallback: true))
+          html = %(<p><iframe src="https://example.com/embed?a=1&amp;b=2"></iframe></p><embed src="https://example.com/a.swf"><object data="https://example.com/a.pdf"></object>)
+
+          assert_equal(
+            %(<p><a href="https://example.com/embed?a=1&amp;b=2">View embedded content</a></p><a href="https://example.com/a.swf">View embedded content</a><a href="https://example.com/a.pdf">View embedded content</a>),
+            Selma::Rewriter.new(sanitizer: sanitizer).rewrite(html),
+          )
+        end
+
+        def test_should_not_link_to_unsafe_urls
+          sanitizer = Selma::Sanitizer.new(@config.merge(embed_fallback: true))
+          html = %(<iframe src="javascript:alert(1)"></iframe><iframe></iframe>)
+
+          assert_equal(
+            "View embedded contentView embedded content",
+            Selma::Rewriter.new(sanitizer: sanitizer).rewrite(html),
+          )
+        end
+
+        def test_should_fill_in_the_template
+          template = %(<div class="embed">%{link} (%{url})</div>)
+          sanitizer = Selma::Sanitizer.new(@config.merge(embed_fallback: { text: "Watch <this>", template: template }))
+          html = %(<iframe src="https://example.com/v/1"></iframe>)
+
+          assert_equal(
+            %(<div class="embed"><a href="https://example.com/v/1">Watch &lt;this&gt;</a> (https://example.com/v/1)</div>),
+            Selma::Rewriter.new(sanitizer: sanitizer).rewrite(html),
+          )
+        end
+
+        def test_should_leave_kept_embeds_alone
+          sanitizer = Selma::Sanitizer.new(@config.merge(
+            elements: ["iframe"],
+            attributes: { "iframe" => ["src"] },
+            protocols: { "iframe" => { "src" => ["https"] } },
+            embed_fallback: true,
+          ))
+          html = %(<iframe src="https://example.com/v/1"></iframe>)
+
+          assert_equal(html, Selma::Rewriter.new(sanitizer: sanitizer).rewrite(html))
+        end
+
+        def test_should_remove_embeds_silently_by_default
+          sanitizer = Selma::Sanitizer.new(@config)
+          html = %(<p>a</p><iframe src="https://example.com/v/1"></iframe>)
+
+          assert_equal("<p>a</p>", Selma::Rewriter.new(sanitizer: sanitizer).rewrite(html))
+        end
+      end
+
+      context "Media policy" do
+        def test_should_keep_audio_and_video
+          sanitizer = Selma::Sanitizer.new(media: true)
+          html = %(<video controls poster="/poster.png" width="640"><source src="https://example.com/a.webm" type="video/webm"><track kind="captions" src="/a.vtt" srclang="en" label="English"></video><audio controls src="a.mp3"></audio>)
+
+          assert_equal(html, Selma::Rewriter.new(sanitizer: sanitizer).rewrite(html))
+        end
+
+        def test_should_strip_autoplay_unless_muted
+          sanitizer = Selma::Sanitizer.new(media: true)
+          html = %(<video autoplay src="a.mp4"></video><video autoplay muted src="a.mp4"></video>)
+
+          assert_equal(
+            %(<video src="a.mp4"></video><video autoplay muted src="a.mp4"></video>),
+            Selma::Rewriter.new(sanitizer: sanitizer).rewrite(html),
+          )
+        end
+
+        def test_should_validate_media_urls
+          sanitizer = Selma::Sanitizer.new(media: true)
+          html = %(<video src="javascript:alert(1)" poster="http://example.com/a.png"></video>)
+
+          assert_equal("<video></video>", Selma::Rewriter.new(sanitizer: sanitizer).rewrite(html))
+        end
+
+        def test_should_respect_configured_protocols
+          sanitizer = Selma::Sanitizer.new(media: true, protocols: { "video" => { "src" => ["http"] } })
+          html = %(<video src="http://example.com/a.mp4"></video>)
+
+          assert_equal(html, Selma::Rewriter.new(sanitizer: sanitizer).rewrite(html))
+        end
+
+        def test_should_remove_tracks_with_disallowed_kinds
+          removed = []
+          sanitizer = Selma::Sanitizer.new(media: { track_kinds: ["captions"] })
+          html = %(<video><track kind="captions" src="a.vtt"><track kind="metadata" src="b.vtt"><track src="c.vtt"></video>)
+          rewriter = Selma::Rewriter.new(sanitizer: sanitizer, on_removal: ->(_tag_name, reason, _attributes) { removed << reason })
+
+          assert_equal(%(<video><track kind="captions" src="a.vtt"></video>), rewriter.rewrite(html))
+          assert_equal([:track_kind, :track_kind], removed)
+        end
+
+        def test_should_compose_with_other_configs
+          config = Selma::Sanitizer::Config.merge(Selma::Sanitizer::Config::RELAXED, Selma::Sanitizer::Config::MEDIA)
+          sanitizer = Selma::Sanitizer.new(config)
+          html = %(<p>Watch:</p><video controls src="a.mp4"><track kind="subtitles" src="a.vtt"></video>)
+
+          assert_equal(html, Selma::Rewriter.new(sanitizer: sanitizer).rewrite(html))
+        end
+
+        def test_should_remove_media_by_default
+          sanitizer = Selma::Sanitizer.new
+          html = %(<video controls src="a.mp4"></video>)
+
+          assert_equal("", Selma::Rewriter.new(sanitizer: sanitizer).rewrite(html))
+        end
+
+        def test_should_reject_track_kinds_which_are_not_strings
+          error = assert_raises(Selma::ConfigError) { Selma::Sanitizer.new(media: { track_kinds: [:captions] }) }
+
+          assert_match(/config\[:media\]\[:track_kinds\]\[0\]/, error.message)
+        end
+      end
+
+      context "Table normalization" do
+        def setup
+          @config = {
+            elements: ["table", "thead", "tbody", "tr", "td", "th"],
+            attributes: { "td" => ["colspan", "rowspan"], "th" => ["colspan"] },
+          }
+        end
+
+        def test_should_wrap_stray_rows
+          sanitizer = Selma::Sanitizer.new(@config.merge(tables: { wrap_rows: true }))
+
+          assert_equal(
+            "<table><thead><tr><th>h</th></tr></thead><tbody><tr><td>a</td></tr></tbody></table>",
+            Selma::Rewriter.new(sanitizer: sanitizer).rewrite("<table><thead><tr><th>h</th></tr></thead><tr><td>a</td></tr></table>"),
+          )
+        end
+
+        def test_should_remove_oversized_spans
+          sanitizer = Selma::Sanitizer.new(@config.merge(tables: { max_span: 10 }))
+
+          assert_equal(
+            %(<table><tbody><tr><td colspan="2">a</td><td>b</td><th>c</th></tr></tbody></table>),
+            Selma::Rewriter.new(sanitizer: sanitizer).rewrite(%(<table><tbody><tr><td colspan="2">a</td><td rowspan="65534">b</td><th colspan="x">c</th></tr></tbody></table>)),
+          )
+        end
+
+        def test_should_wrap_tables
+          sanitizer = Selma::Sanitizer.new(@config.merge(tables: { wrapper_class: "table-wrapper" }))
+
+          assert_equal(
+            %(<div class="table-wrapper"><table><tbody><tr><td>a</td></tr></tbody></table></div>),
+            Selma::Rewriter.new(sanitizer: sanitizer).rewrite("<table><tbody><tr><td>a</td></tr></tbody></table>"),
+          )
+        end
+
+        def test_should_not_wrap_removed_tables
+          sanitizer = Selma::Sanitizer.new({ elements: ["td"], tables: { wrapper_class: "table-wrapper" } })
+
+          assert_equal(
+            "<td>a</td>",
+            Selma::Rewriter.new(sanitizer: sanitizer).rewrite("<table><tbody><tr><td>a</td></tr></tbody></table>"),
+          )
+        end
+      end
     end
   end
 end
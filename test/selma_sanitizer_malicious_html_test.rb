@@ -160,6 +160,31 @@ module Selma
       )
     end
 
+    def test_should_not_be_possible_to_make_a_tag_of_an_open_bracket_before_a_removed_element
+      sanitizer = Selma::Sanitizer.new({ elements: ["p"] })
+
+      assert_equal(
+        "&lt;img src=x onerror=alert(1)>",
+        Selma::Rewriter.new(sanitizer: sanitizer).rewrite(%[<<span>img src=x onerror=alert(1)>]),
+      )
+      assert_equal("1 <2 < 3", Selma::Rewriter.new(sanitizer: sanitizer).rewrite(%[1 <<b>2 < 3</b>]))
+    end
+
+    def test_should_escape_the_text_of_removed_elements_which_is_not_parsed_as_markup
+      sanitizer = Selma::Sanitizer.new({ elements: ["p"] })
+
+      ["iframe", "noembed", "noframes", "noscript", "plaintext", "textarea", "title", "xmp"].each do |tag_name|
+        assert_equal(
+          "&lt;img src=x onerror=alert(1)&gt;",
+          Selma::Rewriter.new(sanitizer: sanitizer).rewrite(%(<#{tag_name}><img src=x onerror=alert(1)>)),
+          tag_name,
+        )
+      end
+
+      # entities are decoded in `<title>` and `<textarea>`, so they're left as they are
+      assert_equal("&lt;b&gt; &amp; bye", Selma::Rewriter.new(sanitizer: sanitizer).rewrite("<title><b> &amp; bye</title>"))
+    end
+
     # https://github.com/rgrove/sanitize/security/advisories/GHSA-p4x4-rw2p-8j8m
 
     def test_prevents_a_sanitization_bypass_via_carefully_crafted_foreign_content
@@ -34,5 +34,34 @@ module Selma
 
       assert_equal("OMG HAPPY BIRTHDAY! *<:-D", Selma::Rewriter.new.rewrite("OMG HAPPY BIRTHDAY! *<:-D"))
     end
+
+    AMBIGUOUS = %(<p>Before</p><select><xmp><script>alert(1)</script></xmp></select><p>After</p>)
+
+    def test_ambiguous_markup_raises_by_default
+      assert_raises(Selma::RewritingError) do
+        Selma::Rewriter.new(sanitizer: Selma::Sanitizer.new({ elements: ["p"] })).rewrite(AMBIGUOUS)
+      end
+    end
+
+    def test_ambiguous_markup_can_be_dropped
+      rewriter = Selma::Rewriter.new(sanitizer: Selma::Sanitizer.new({ elements: ["p"] }), malformed: :drop)
+      result = rewriter.rewrite(AMBIGUOUS)
+
+      assert(result.start_with?("<p>Before</p>"))
+      refute_includes(result, "alert")
+      refute_includes(result, "After")
+    end
+
+    def test_ambiguous_markup_can_be_passed_through
+      rewriter = Selma::Rewriter.new(sanitizer: Selma::Sanitizer.new({ elements: ["p"] }), malformed: :pass_through)
+      result = rewriter.rewrite(AMBIGUOUS)
+
+      assert(result.start_with?("<p>Before</p>"))
+      assert(result.end_with?("<p>After</p>"))
+    end
+
+    def test_unknown_malformed_policies_raise
+      assert_raises(ArgumentError) { Selma::Rewriter.new(malformed: :ignore) }
+    end
   end
 end
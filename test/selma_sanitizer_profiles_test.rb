@@ -0,0 +1,80 @@
+# frozen_string_literal: true
+
+require "test_helper"
+
+module Selma
+  class SanitizerProfilesTest < Minitest::Test
+    PROFILES = {
+      basic: Selma::Sanitizer::Config::BASIC,
+      relaxed: Selma::Sanitizer::Config::RELAXED,
+      restricted: Selma::Sanitizer::Config::RESTRICTED,
+    }.freeze
+
+    def test_profiles_match_their_ruby_configs
+      PROFILES.each do |profile, config|
+        [STRINGS, PROTOCOLS].each do |fixtures|
+          fixtures.each_value do |fixture|
+            native = Selma::Rewriter.new(sanitizer: Selma::Sanitizer.send(profile)).rewrite(fixture[:html])
+            parsed = Selma::Rewriter.new(sanitizer: Selma::Sanitizer.new(config)).rewrite(fixture[:html])
+
+            assert_equal(parsed, native, "#{profile} differs for #{fixture[:html]}")
+          end
+        end
+      end
+    end
+
+    def test_github_profile_matches_its_ruby_config
+      html = '<h1><a name="intro">Hi</a></h1><a href="#intro" rel="nofollow">jump</a><img src="/a.png" onerror="x()"><script>alert(1)</script>'
+      native = Selma::Rewriter.new(sanitizer: Selma::Sanitizer.github).rewrite(html)
+      parsed = Selma::Rewriter.new(sanitizer: Selma::Sanitizer.new(Selma::Sanitizer::Config::GITHUB)).rewrite(html)
+
+      assert_equal('<h1><a name="user-content-intro">Hi</a></h1><a href="#user-content-intro" rel="nofollow">jump</a><img src="/a.png">', native)
+      assert_equal(parsed, native)
+    end
+
+    def test_email_config_keeps_layout_but_not_behavior
+      sanitizer = Selma::Sanitizer.new(Selma::Sanitizer::Config::EMAIL)
+      html = <<~HTML.chomp
+        <table width="600" bgcolor="#fff"><tr><td align="center" style="color:red" onclick="x()"><font color="red">Hi</font></td></tr></table>
+        <form action="https://evil.example"><input type="text" name="q"></form>
+        <img src="https://example.com/open.gif" width="1" height="1">
+      HTML
+
+      assert_equal(<<~HTML.chomp, Selma::Rewriter.new(sanitizer: sanitizer).rewrite(html))
+        <table width="600" bgcolor="#fff"><tbody><tr><td align="center" style="color: red"><font color="red">Hi</font></td></tr></tbody></table>
+        <input type="text" name="q" disabled="">
+
+      HTML
+    end
+
+    def test_email_config_can_strip_remote_content
+      config = Selma::Sanitizer::Config.merge(Selma::Sanitizer::Config::EMAIL, strip_remote_content: true)
+      html = %(<img src="cid:logo@example.com" alt="Logo"><img src="https://example.com/banner.png" alt="Banner">)
+
+      assert_equal(
+        %(<img src="cid:logo@example.com" alt="Logo"><img alt="Banner">),
+        Selma::Rewriter.new(sanitizer: Selma::Sanitizer.new(config)).rewrite(html),
+      )
+    end
+
+    def test_profiles_are_reused
+      assert_same(Selma::Sanitizer.basic, Selma::Sanitizer.basic)
+      assert_same(Selma::Sanitizer.github, Selma::Sanitizer.github)
+    end
+
+    def test_profiles_expose_their_config
+      assert_equal(Selma::Sanitizer::Config::RELAXED, Selma::Sanitizer.relaxed.config)
+      assert_equal("user-content-", Selma::Sanitizer.github.name_prefix)
+    end
+
+    def test_profiles_are_immutable
+      sanitizer = Selma::Sanitizer.restricted
+
+      assert_predicate(sanitizer, :immutable?)
+      assert_raises(FrozenError) { sanitizer.allow_element(["a"]) }
+      assert_raises(FrozenError) { sanitizer.set_allow_comments(true) }
+
+      refute_predicate(Selma::Sanitizer.new, :immutable?)
+    end
+  end
+end
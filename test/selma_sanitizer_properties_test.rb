@@ -0,0 +1,115 @@
+# frozen_string_literal: true
+
+require "test_helper"
+
+# Sanitizes randomly generated (but well-formed) documents with each built-in
+# profile, checking properties which should hold for any input. The documents
+# come from a fixed seed, so a failure can be reproduced; set
+# SELMA_PROPERTY_SEED to try others, and SELMA_PROPERTY_RUNS to try more.
+class SelmaSanitizerPropertiesTest < Minitest::Test
+  SEED = Integer(ENV.fetch("SELMA_PROPERTY_SEED", "20240101"))
+  RUNS = Integer(ENV.fetch("SELMA_PROPERTY_RUNS", "200"))
+
+  ELEMENTS = [
+    "a", "b", "blink", "blockquote", "code", "custom-element", "div", "em", "font", "i", "li",
+    "marquee", "p", "pre", "script", "span", "strong", "table", "td", "tr", "ul",
+  ].freeze
+  VOID_ELEMENTS = ["br", "hr", "img"].freeze
+  ATTRIBUTES = [
+    'class="c"',
+    'href="https://example.com/"',
+    'href="javascript:alert(1)"',
+    'href="/relative"',
+    'onclick="x()"',
+    'src="https://example.com/x.png"',
+    'style="color: red"',
+    'title="t"',
+  ].freeze
+  WORDS = ["lorem", "ipsum", "dolor", "sit", "amet"].freeze
+
+  TAG = %r{<(/?)([a-zA-Z][^\s/>]*)[^>]*>}
+
+  PROFILES = {
+    basic: Selma::Sanitizer::Config::BASIC,
+    restricted: Selma::Sanitizer::Config::RESTRICTED,
+    relaxed: Selma::Sanitizer::Config::RELAXED,
+    github: Selma::Sanitizer::Config::GITHUB,
+  }.freeze
+
+  def setup
+    @random = Random.new(SEED)
+  end
+
+  PROFILES.each do |name, config|
+    define_method(:"test_#{name}_output_only_has_allowed_elements") do
+      each_document(name) do |html, sanitized|
+        tags = sanitized.scan(TAG).map { |_, tag| tag.downcase }.uniq
+
+        assert_empty(tags - config[:elements].to_a, "disallowed elements left in #{sanitized.inspect}, from #{html.inspect}")
+      end
+    end
+
+    define_method(:"test_#{name}_sanitizing_is_idempotent") do
+      each_document(name) do |html, sanitized|
+        assert_equal(sanitized, rewrite(name, sanitized), "sanitizing again changed the output, from #{html.inspect}")
+      end
+    end
+
+    define_method(:"test_#{name}_output_is_balanced") do
+      each_document(name) do |html, sanitized|
+        assert(balanced?(sanitized), "unbalanced tags in #{sanitized.inspect}, from #{html.inspect}")
+      end
+    end
+  end
+
+  private
+
+  def each_document(profile)
+    RUNS.times do
+      html = document(3)
+      yield html, rewrite(profile, html)
+    end
+  end
+
+  def rewrite(profile, html)
+    Selma::Rewriter.new(sanitizer: Selma::Sanitizer.public_send(profile)).rewrite(html)
+  end
+
+  def document(depth)
+    Array.new(@random.rand(1..4)) { node(depth) }.join
+  end
+
+  def node(depth)
+    case @random.rand(depth.zero? ? 2 : 4)
+    when 0
+      WORDS.sample(@random.rand(1..3), random: @random).join(" ")
+    when 1
+      "<#{VOID_ELEMENTS.sample(random: @random)}#{attributes}>"
+    else
+      element = ELEMENTS.sample(random: @random)
+      "<#{element}#{attributes}>#{document(depth - 1)}</#{element}>"
+    end
+  end
+
+  def attributes
+    picked = ATTRIBUTES.sample(@random.rand(0..2), random: @random).uniq { |attribute| attribute[/\A[^=]+/] }
+    picked.map { |attribute| " #{attribute}" }.join
+  end
+
+  def balanced?(html)
+    open = []
+
+    html.scan(TAG).each do |closing, tag|
+      tag = tag.downcase
+      next if VOID_ELEMENTS.include?(tag)
+
+      if closing.empty?
+        open << tag
+      elsif open.pop != tag
+        return false
+      end
+    end
+
+    open.empty?
+  end
+end
@@ -4,6 +4,28 @@ require "test_helper"
 
 module Selma
   class SanitizerTest < Minitest::Test
+    # a handler which rewrites something else with another rewriter, while
+    # the document it's handling is still being rewritten
+    class RewriteWithin
+      SELECTOR = Selma::Selector.new(match_element: "p")
+
+      attr_reader :rewritten
+
+      def initialize(rewriter, html)
+        @rewriter = rewriter
+        @html = html
+        @rewritten = []
+      end
+
+      def selector
+        SELECTOR
+      end
+
+      def handle_element(element)
+        @rewritten << @rewriter.rewrite(@html)
+      end
+    end
+
     def test_it_sanitizes_by_default
       html = "<a href='https://google.com'>here is a neat site!</a>"
       rewritten = Selma::Rewriter.new.rewrite(html)
@@ -133,5 +155,391 @@ module Selma
         assert_equal("", Selma::Rewriter.new.rewrite(%(<iframe src="https://www.youtube.com/embed/dXBohfjc4WA" width="680" height="480" allowfullscreen>hackerman</iframe>)))
       end
     end
+
+    describe "entity normalization" do
+      def setup
+        @sanitizer = Selma::Sanitizer.new(elements: ["p", "textarea"], normalize_entities: true)
+      end
+
+      def test_it_decodes_named_and_numeric_entities
+        assert_equal(
+          "<p>café café café — ©</p>",
+          Selma::Rewriter.new(sanitizer: @sanitizer).rewrite("<p>caf&eacute; caf&#233; caf&#xE9; &mdash; &copy;</p>"),
+        )
+      end
+
+      def test_it_only_encodes_what_it_must
+        assert_equal(
+          "<p>1 &lt; 2 &amp;&amp; 3 &gt; 2 \"quoted\" 'too'</p>",
+          Selma::Rewriter.new(sanitizer: @sanitizer).rewrite("<p>1 &lt; 2 &amp;& 3 > 2 &quot;quoted&quot; &#39;too&#39;</p>"),
+        )
+      end
+
+      def test_it_never_turns_entities_into_markup
+        assert_equal(
+          "<p>&lt;script&gt;alert(1)&lt;/script&gt;</p>",
+          Selma::Rewriter.new(sanitizer: @sanitizer).rewrite("<p>&lt;script&gt;alert(1)&#x3C;/script&#62;</p>"),
+        )
+        assert_equal(
+          "<textarea>&lt;/textarea&gt;&lt;img&gt;</textarea>",
+          Selma::Rewriter.new(sanitizer: @sanitizer).rewrite("<textarea>&lt;/textarea&gt;&lt;img&gt;</textarea>"),
+        )
+      end
+
+      def test_it_leaves_entities_alone_by_default
+        sanitizer = Selma::Sanitizer.new(elements: ["p"])
+
+        assert_equal(
+          "<p>caf&eacute; &#233;</p>",
+          Selma::Rewriter.new(sanitizer: sanitizer).rewrite("<p>caf&eacute; &#233;</p>"),
+        )
+      end
+    end
+
+    describe "unicode filtering" do
+      def test_it_strips_deceptive_characters_from_text_and_attributes
+        sanitizer = Selma::Sanitizer.new(elements: ["a"], attributes: { "a" => ["title"] }, unicode_filter: :strip)
+
+        assert_equal(
+          %(<a title="invoice.exe">invoicegnp.exe</a>),
+          Selma::Rewriter.new(sanitizer: sanitizer).rewrite(%(<a title="in\u200Bvoice.exe">invoice\u202Egnp.exe</a>)),
+        )
+        assert_equal(
+          "admin",
+          Selma::Rewriter.new(sanitizer: sanitizer).rewrite("ad\uFEFFmin&#x200B;&rlm;"),
+        )
+        assert_equal(
+          %(<a title="">x</a>),
+          Selma::Rewriter.new(sanitizer: sanitizer).rewrite(%(<a title="&#x202E;">x</a>)),
+        )
+      end
+
+      def test_it_can_escape_deceptive_characters
+        sanitizer = Selma::Sanitizer.new(elements: ["a"], attributes: { "a" => ["title"] }, unicode_filter: :escape)
+
+        assert_equal(
+          %(<a title="a[U+2066]b">[U+202E]gnp.exe</a>),
+          Selma::Rewriter.new(sanitizer: sanitizer).rewrite(%(<a title="a\u2066b">\u202Egnp.exe</a>)),
+        )
+      end
+
+      def test_it_keeps_joiners
+        sanitizer = Selma::Sanitizer.new(unicode_filter: :strip)
+        family = "\u{1F468}\u200D\u{1F469}\u200D\u{1F467}"
+
+        assert_equal(family, Selma::Rewriter.new(sanitizer: sanitizer).rewrite(family))
+      end
+
+      def test_it_leaves_other_text_alone
+        sanitizer = Selma::Sanitizer.new(unicode_filter: :strip)
+
+        assert_equal("caf&eacute; &amp; tea", Selma::Rewriter.new(sanitizer: sanitizer).rewrite("caf&eacute; &amp; tea"))
+      end
+
+      def test_it_rejects_unknown_filters
+        error = assert_raises(Selma::ConfigError) { Selma::Sanitizer.new(unicode_filter: :yes) }
+
+        assert_equal("config[:unicode_filter]: expected :strip or :escape, got :yes", error.message)
+      end
+    end
+
+    describe "control characters" do
+      def test_it_strips_control_characters
+        sanitizer = Selma::Sanitizer.new(elements: ["a"], attributes: { "a" => ["title"] }, control_characters: :strip)
+
+        assert_equal(
+          %(<a title="ab">java\tscript\n</a>),
+          Selma::Rewriter.new(sanitizer: sanitizer).rewrite(%(<a title="a\u0000b">java\tscr\u0001ipt\u001F\n</a>)),
+        )
+      end
+
+      def test_it_strips_control_characters_before_parsing
+        sanitizer = Selma::Sanitizer.new(control_characters: :strip)
+
+        assert_equal("", Selma::Rewriter.new(sanitizer: sanitizer).rewrite("<scr\u0000ipt>alert(1)</script>"))
+      end
+
+      def test_it_rejects_control_characters
+        sanitizer = Selma::Sanitizer.new(control_characters: :reject)
+
+        error = assert_raises(Selma::RewritingError) do
+          Selma::Rewriter.new(sanitizer: sanitizer).rewrite("ok\u0000")
+        end
+
+        assert_equal("Failed to sanitize HTML: found a control character (U+0000) at byte 2", error.message)
+        assert_equal("ok\r\n", Selma::Rewriter.new(sanitizer: sanitizer).rewrite("ok\r\n"))
+      end
+
+      def test_it_rejects_unknown_policies
+        error = assert_raises(Selma::ConfigError) { Selma::Sanitizer.new(control_characters: :yes) }
+
+        assert_equal("config[:control_characters]: expected :strip or :reject, got :yes", error.message)
+      end
+    end
+
+    describe "on_removal" do
+      def test_it_reports_removed_elements
+        removed = []
+        on_removal = ->(tag_name, reason, attributes) { removed << [tag_name, reason, attributes] }
+        sanitizer = Selma::Sanitizer.new(elements: ["p"])
+        rewriter = Selma::Rewriter.new(sanitizer: sanitizer, on_removal: on_removal)
+
+        assert_equal("<p>Hi there</p>", rewriter.rewrite(%(<p>Hi <script src="x.js">alert(1)</script><b class="c">there</b></p>)))
+        assert_equal([["script", :not_allowed, { "src" => "x.js" }], ["b", :not_allowed, { "class" => "c" }]], removed)
+        assert_equal(2, rewriter.stats[:elements_removed])
+      end
+
+      def test_it_reports_why
+        removed = []
+        sanitizer = Selma::Sanitizer.new(
+          elements: ["img"],
+          attributes: { "img" => ["src", "width", "height"] },
+          protocols: { "img" => { "src" => ["https"] } },
+          strip_tracking_pixels: true,
+        )
+        rewriter = Selma::Rewriter.new(sanitizer: sanitizer, on_removal: ->(*args) { removed << args.take(2) })
+        rewriter.rewrite(%(<img src="https://example.com/a.gif" width="1" height="1"><img src="https://example.com/b.png">))
+
+        assert_equal([["img", :tracking_pixel]], removed)
+      end
+
+      def test_it_is_not_called_when_nothing_is_removed
+        called = false
+        sanitizer = Selma::Sanitizer.new(elements: ["p"])
+        Selma::Rewriter.new(sanitizer: sanitizer, on_removal: ->(*) { called = true }).rewrite("<p>Hi</p>")
+
+        refute(called)
+      end
+
+      def test_it_outlives_a_gc
+        removed = []
+        sanitizer = Selma::Sanitizer.new(elements: ["p"])
+        rewriter = Selma::Rewriter.new(sanitizer: sanitizer, on_removal: proc { |tag_name| removed << tag_name })
+        GC.start(full_mark: true, immediate_sweep: true)
+        rewriter.rewrite("<p>Hi <b>there</b></p>")
+
+        assert_equal(["b"], removed)
+      end
+    end
+
+    describe "passthrough" do
+      def setup
+        @config = { elements: ["p"], passthrough: { attribute: "data-selma-raw", token: "s3cret" } }
+      end
+
+      def test_it_leaves_trusted_subtrees_alone
+        sanitizer = Selma::Sanitizer.new(@config)
+        html = %(<p>Hi <b>there</b></p><div data-selma-raw="s3cret" class="widget"><script>load()</script><b>Bold</b></div><p><i>Bye</i></p>)
+
+        assert_equal(
+          %(<p>Hi there</p><div class="widget"><script>load()</script><b>Bold</b></div><p>Bye</p>),
+          Selma::Rewriter.new(sanitizer: sanitizer).rewrite(html),
+        )
+      end
+
+      def test_it_needs_the_token
+        sanitizer = Selma::Sanitizer.new(@config.merge(attributes: { "div" => ["data-selma-raw"] }, elements: ["p", "div"]))
+        html = %(<div data-selma-raw="guess"><script>alert(1)</script></div>)
+
+        assert_equal(%(<div data-selma-raw="guess"></div>), Selma::Rewriter.new(sanitizer: sanitizer).rewrite(html))
+      end
+
+      def test_it_raises_on_invalid_config
+        error = assert_raises(Selma::ConfigError) { Selma::Sanitizer.new(passthrough: { attribute: "data-selma-raw" }) }
+
+        assert_equal("config[:passthrough][:token]: is required", error.message)
+
+        error = assert_raises(Selma::ConfigError) { Selma::Sanitizer.new(passthrough: { attribute: "[x]", token: "t" }) }
+
+        assert_equal("config[:passthrough][:attribute]: `[x]` is not a valid attribute name", error.message)
+      end
+    end
+
+    describe "scoped_sanitizers" do
+      def test_it_sanitizes_each_scope_with_its_own_sanitizer
+        strict = Selma::Sanitizer.new(elements: ["p"])
+        relaxed = Selma::Sanitizer.new(elements: ["div", "p", "b"], attributes: { "div" => ["class"] })
+        rewriter = Selma::Rewriter.new(sanitizer: strict, scoped_sanitizers: { "div.trusted-embed" => relaxed })
+        html = %(<p><b>One</b></p><div class="trusted-embed"><p><b>Two</b></p></div><p><b>Three</b></p>)
+
+        assert_equal(
+          %(<p>One</p><div class="trusted-embed"><p><b>Two</b></p></div><p>Three</p>),
+          rewriter.rewrite(html),
+        )
+      end
+
+      def test_the_innermost_scope_wins
+        outer = Selma::Sanitizer.new(elements: ["section", "b"])
+        inner = Selma::Sanitizer.new(elements: ["aside", "i"])
+        rewriter = Selma::Rewriter.new(
+          sanitizer: Selma::Sanitizer.new,
+          scoped_sanitizers: { "section" => outer, "aside" => inner },
+        )
+        html = %(<section><b>1</b><i>2</i><aside><b>3</b><i>4</i></aside><b>5</b></section>)
+
+        assert_equal(%(<section><b>1</b>2<aside>3<i>4</i></aside><b>5</b></section>), rewriter.rewrite(html))
+      end
+
+      def test_it_raises_on_invalid_selectors
+        assert_raises(Selma::SelectorError) do
+          Selma::Rewriter.new(scoped_sanitizers: { "div[" => Selma::Sanitizer.new })
+        end
+      end
+    end
+
+    describe "shadow_roots" do
+      def setup
+        @html = %(<div><template shadowrootmode="open" shadowrootdelegatesfocus id="x"><b>Shadow</b><script>alert(1)</script></template><b>Light</b></div>)
+      end
+
+      def rewrite(policy)
+        sanitizer = Selma::Sanitizer.new(elements: ["div", "b"], shadow_roots: policy)
+        Selma::Rewriter.new(sanitizer: sanitizer).rewrite(@html)
+      end
+
+      def test_it_removes_shadow_roots
+        assert_equal(%(<div><b>Light</b></div>), rewrite(:remove))
+      end
+
+      def test_it_flattens_shadow_roots
+        assert_equal(%(<div><b>Shadow</b><b>Light</b></div>), rewrite(:flatten))
+      end
+
+      def test_it_sanitizes_shadow_roots
+        assert_equal(
+          %(<div><template shadowrootmode="open" shadowrootdelegatesfocus><b>Shadow</b></template><b>Light</b></div>),
+          rewrite(:sanitize),
+        )
+      end
+
+      def test_it_treats_them_like_any_template_by_default
+        assert_equal(%(<div><b>Light</b></div>), rewrite(nil))
+      end
+
+      def test_it_rejects_unknown_policies
+        error = assert_raises(Selma::ConfigError) { Selma::Sanitizer.new(shadow_roots: :keep) }
+
+        assert_equal("config[:shadow_roots]: expected one of :remove, :flatten or :sanitize, got :keep", error.message)
+      end
+    end
+
+    describe "integrity" do
+      def setup
+        @hash = "sha384-oqVuAfXRKap7fdgcCY5uykM6+R9GqQ8K/uxy9rx7HNQlGYl1kPzQho1wx4JwY8wC"
+      end
+
+      def rewrite(html, policy)
+        sanitizer = Selma::Sanitizer.new(
+          elements: ["div", "link"],
+          attributes: { "link" => ["href", "rel", "integrity", "crossorigin"] },
+          integrity: policy,
+        )
+        Selma::Rewriter.new(sanitizer: sanitizer).rewrite(html)
+      end
+
+      def test_it_strips_integrity_and_crossorigin
+        html = %(<div><link rel="stylesheet" href="a.css" integrity="#{@hash}" crossorigin="anonymous"></div>)
+
+        assert_equal(%(<div><link rel="stylesheet" href="a.css"></div>), rewrite(html, :strip))
+      end
+
+      def test_it_keeps_elements_with_a_valid_integrity_and_adds_crossorigin
+        html = %(<div><link rel="stylesheet" href="a.css" integrity="#{@hash}"></div>)
+
+        assert_equal(
+          %(<div><link rel="stylesheet" href="a.css" integrity="#{@hash}" crossorigin="anonymous"></div>),
+          rewrite(html, :require),
+        )
+      end
+
+      def test_it_keeps_an_existing_crossorigin
+        html = %(<div><link rel="stylesheet" href="a.css" integrity="#{@hash}" crossorigin="use-credentials"></div>)
+
+        assert_equal(html, rewrite(html, :require))
+      end
+
+      def test_it_removes_elements_without_a_valid_integrity
+        missing = %(<div><link rel="stylesheet" href="a.css"></div>)
+        malformed = %(<div><link rel="stylesheet" href="a.css" integrity="md5-ABC"></div>)
+
+        assert_equal("<div></div>", rewrite(missing, :require))
+        assert_equal("<div></div>", rewrite(malformed, :require))
+      end
+
+      def test_it_leaves_them_alone_by_default
+        html = %(<div><link rel="stylesheet" href="a.css" integrity="md5-ABC"></div>)
+
+        assert_equal(html, rewrite(html, nil))
+      end
+
+      def test_it_rejects_unknown_policies
+        error = assert_raises(Selma::ConfigError) { Selma::Sanitizer.new(integrity: :verify) }
+
+        assert_equal("config[:integrity]: expected :strip or :require, got :verify", error.message)
+      end
+    end
+
+    describe "dup_with" do
+      def rewrite(sanitizer, html)
+        Selma::Rewriter.new(sanitizer: sanitizer).rewrite(html)
+      end
+
+      def test_it_derives_a_changed_copy
+        base = Selma::Sanitizer.new({ elements: ["b"] })
+        derived = base.dup_with { |sanitizer| sanitizer.allow_element(["i"]) }
+
+        assert_equal("<b>x</b><i>y</i>", rewrite(derived, "<b>x</b><i>y</i>"))
+        assert_equal("<b>x</b>y", rewrite(base, "<b>x</b><i>y</i>"))
+      end
+
+      def test_it_keeps_runtime_changes
+        base = Selma::Sanitizer.new({ elements: ["a"], attributes: { "a" => ["title"] } })
+        base.disallow_attribute("a", ["title"])
+        derived = base.dup_with { |sanitizer| sanitizer.allow_element(["b"]) }
+
+        assert_equal("<a>x</a><b>y</b>", rewrite(derived, %(<a title="t">x</a><b>y</b>)))
+      end
+
+      def test_it_derives_from_built_in_profiles
+        derived = Selma::Sanitizer.restricted.dup_with { |sanitizer| sanitizer.disallow_element(["u"]) }
+
+        refute_predicate(derived, :immutable?)
+        assert_equal("<b>x</b>y", rewrite(derived, "<b>x</b><u>y</u>"))
+        assert_equal("<b>x</b><u>y</u>", rewrite(Selma::Sanitizer.restricted, "<b>x</b><u>y</u>"))
+      end
+    end
+
+    describe "shared between rewriters" do
+      def sanitizer
+        Selma::Sanitizer.new({
+          elements: ["p", "x-card", "b"],
+          attributes: { "x-card" => ["data-id"], "p" => ["class"] },
+        })
+      end
+
+      def test_it_can_be_used_by_a_rewriter_in_the_middle_of_another_rewrite
+        shared = sanitizer
+        inner = Selma::Rewriter.new(sanitizer: shared)
+        handler = RewriteWithin.new(inner, %(<x-card data-id="2" onclick="x()">two</x-card><i>i</i>))
+        outer = Selma::Rewriter.new(sanitizer: shared, handlers: [handler])
+
+        html = %(<x-card data-id="1" title="t">one</x-card><p class="a" id="b">p</p><x-card data-id="3">three</x-card>)
+
+        assert_equal(
+          %(<x-card data-id="1">one</x-card><p class="a">p</p><x-card data-id="3">three</x-card>),
+          outer.rewrite(html),
+        )
+        assert_equal([%(<x-card data-id="2">two</x-card>i)], handler.rewritten)
+      end
+
+      def test_elements_it_knows_nothing_about_leave_it_unchanged
+        shared = sanitizer
+        rewriter = Selma::Rewriter.new(sanitizer: shared)
+        rewriter.rewrite(%(<x-unknown data-id="1">x</x-unknown>))
+
+        assert_equal(%(<x-card data-id="1">x</x-card>), rewriter.rewrite(%(<x-card data-id="1" title="t">x</x-card>)))
+        assert_equal(%(<x-card data-id="1">x</x-card>), Selma::Rewriter.new(sanitizer: shared).rewrite(%(<x-card data-id="1" title="t">x</x-card>)))
+      end
+    end
   end
 end
@@ -12,4 +12,49 @@ class SelmaSelectorTest < Minitest::Test
       Selma::Selector.new(match_element: "")
     end
   end
+
+  def test_that_it_raises_a_selector_error
+    error = assert_raises(Selma::SelectorError) do
+      Selma::Selector.new(match_element: %(a[href=]))
+    end
+
+    assert_equal(7, error.position)
+    assert_equal("Unexpected token in the attribute selector.", error.reason)
+    assert_match(/`match_element`/, error.message)
+  end
+
+  def test_that_it_raises_a_selector_error_for_match_text_within
+    error = assert_raises(Selma::SelectorError) do
+      Selma::Selector.new(match_text_within: "div >")
+    end
+
+    assert_equal(4, error.position)
+    assert_equal("Dangling combinator in selector.", error.reason)
+    assert_match(/`match_text_within`/, error.message)
+  end
+
+  def test_that_it_raises_a_selector_error_for_relocate
+    error = assert_raises(Selma::SelectorError) do
+      Selma::Selector.new(relocate: "aside >")
+    end
+
+    assert_match(/`relocate`/, error.message)
+  end
+
+  def test_that_it_raises_a_selector_error_for_empty_css
+    error = assert_raises(Selma::SelectorError) do
+      Selma::Selector.new(match_element: "")
+    end
+
+    assert_equal(0, error.position)
+    assert_equal("The selector is empty.", error.reason)
+  end
+
+  def test_that_it_validates_css
+    assert(Selma::Selector.valid?("a[href]"))
+    assert(Selma::Selector.valid?("div > p:not(.foo)"))
+    refute(Selma::Selector.valid?("a[href=]"))
+    refute(Selma::Selector.valid?("p:hover"))
+    refute(Selma::Selector.valid?(""))
+  end
 end
@@ -0,0 +1,85 @@
+# frozen_string_literal: true
+
+require "test_helper"
+require "json"
+require "open3"
+
+class SelmaWasmTest < Minitest::Test
+  JS_DIR = File.expand_path("../ext/selma-core/js", __dir__)
+
+  # Sanitizes each `[html, config]` read from stdin with the WebAssembly build,
+  # through the same wrapper a browser would use
+  SCRIPT = <<~JS
+    import { readFileSync } from "node:fs";
+    import { load } from "./selma_core.js";
+
+    const selma = await load(readFileSync("./selma_core.wasm"));
+    const cases = JSON.parse(readFileSync(0, "utf8"));
+    console.log(JSON.stringify(cases.map(([html, config]) => selma.sanitize(html, config))));
+  JS
+
+  DOCUMENTS = [
+    %(<b onclick="x()">Hi</b><script>alert(1)</script>),
+    %(<a href="javascript:alert(1)">x</a> <a href="https://example.com/" title="y">y</a> <a href="#top">z</a>),
+    %(<p>One<br>two</p><img src="https://example.com/a.png" alt="a"><!-- comment -->),
+    %(<table><tr><td colspan="2">cell</td></tr></table><iframe src="https://example.com/"></iframe>),
+    %(<div><span class="x" id="y">text &amp; more</span></div><title>t</title>),
+  ].freeze
+
+  CONFIG = {
+    elements: ["a", "p", "img"],
+    attributes: { "a" => ["href"], "img" => ["src", "alt"] },
+    protocols: { "a" => { "href" => ["https", :relative] }, "img" => { "src" => ["https"] } },
+  }.freeze
+
+  def setup
+    skip("the WebAssembly build hasn't been made; run `rake wasm`") unless File.exist?(File.join(JS_DIR, "selma_core.wasm"))
+    skip("node isn't installed") unless system("node", "--version", out: File::NULL, err: File::NULL)
+  end
+
+  def test_it_sanitizes_with_each_profile_as_the_gem_does
+    {
+      "BASIC" => Selma::Sanitizer.basic,
+      "RESTRICTED" => Selma::Sanitizer.restricted,
+      "RELAXED" => Selma::Sanitizer.relaxed,
+      "GITHUB" => Selma::Sanitizer.github,
+    }.each do |profile, sanitizer|
+      expected = DOCUMENTS.map { |html| Selma::Rewriter.new(sanitizer: sanitizer).rewrite(html) }
+
+      assert_equal(expected, wasm_sanitize(DOCUMENTS.map { |html| [html, profile] }), profile)
+    end
+  end
+
+  def test_it_sanitizes_with_a_config_as_the_gem_does
+    sanitizer = Selma::Sanitizer.new(CONFIG)
+    expected = DOCUMENTS.map { |html| Selma::Rewriter.new(sanitizer: sanitizer).rewrite(html) }
+
+    assert_equal(expected, wasm_sanitize(DOCUMENTS.map { |html| [html, js_config(CONFIG)] }))
+  end
+
+  def test_it_refuses_config_keys_it_cannot_apply
+    cases = [["<p>Hi</p>", { elements: ["p"], remove_contents: true }]]
+    _, error, status = Open3.capture3("node", "--input-type=module", "-e", SCRIPT, stdin_data: JSON.generate(cases), chdir: JS_DIR)
+
+    refute_predicate(status, :success?)
+    assert_match("unsupported config keys: remove_contents", error)
+  end
+
+  private
+
+  def wasm_sanitize(cases)
+    output, status = Open3.capture2("node", "--input-type=module", "-e", SCRIPT, stdin_data: JSON.generate(cases), chdir: JS_DIR)
+    assert_predicate(status, :success?)
+
+    JSON.parse(output)
+  end
+
+  # relative URLs are named the way the gem's symbols are, like `":relative"`
+  def js_config(config)
+    protocols = config[:protocols].transform_values do |by_attribute|
+      by_attribute.transform_values { |protocols| protocols.map { |protocol| protocol.is_a?(Symbol) ? ":#{protocol}" : protocol } }
+    end
+
+    config.merge(protocols: protocols)
+  end
+end